@@ -0,0 +1,19 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The gRPC service is generated from its proto, with a vendored protoc so
+    // building it needs nothing installed.
+    #[cfg(feature = "grpc")]
+    {
+        let mut config = tonic_prost_build::Config::new();
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path().expect("vendored protoc"));
+        let includes = [
+            std::path::PathBuf::from("proto"),
+            protoc_bin_vendored::include_path().expect("vendored protoc includes"),
+        ];
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_with_config(config, &["proto/whoopsy.proto".into()], &includes)
+            .expect("proto/whoopsy.proto compiles");
+    }
+}
@@ -0,0 +1,326 @@
+//! A minimal web app wiring the crate together: OAuth login, a saved token, a
+//! dashboard built from [`DailySummary`] and an endpoint for WHOOP's webhooks.
+//!
+//! Register `http://localhost:3000/callback` as a redirect URI and
+//! `http://<public host>/webhook` as the webhook URL of your WHOOP app, then:
+//!
+//! ```text
+//! WHOOP_CLIENT_ID=... WHOOP_CLIENT_SECRET=... \
+//!     cargo run --example web --features web-example
+//! ```
+//!
+//! The token is saved in the same format as the CLI's token store, so
+//! `whoopsy` can reuse it. `WHOOP_REDIRECT_URI`, `WHOOP_TOKEN_FILE` and
+//! `ADDR` override the defaults.
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use whoopsy::{
+    CycleQueryParams, DailySummary, ErrorKind, OAuthConfig, RecoveryQueryParams, SleepQueryParams,
+    TokenResponse, WhoopClient, WhoopError,
+};
+
+/// Days shown on the dashboard. One page of 25 covers them.
+const DASHBOARD_DAYS: i64 = 14;
+/// Webhook events kept for the dashboard.
+const MAX_EVENTS: usize = 20;
+
+struct App {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    token_path: PathBuf,
+    /// The OAuth `state`: a single user app only needs one per run.
+    state: String,
+    events: Mutex<Vec<WebhookEvent>>,
+}
+
+impl App {
+    fn oauth_config(&self) -> OAuthConfig {
+        OAuthConfig::new(
+            self.client_id.clone(),
+            self.client_secret.clone(),
+            self.redirect_uri.clone(),
+        )
+        .with_all_scopes()
+    }
+
+    fn save_token(&self, token: &TokenResponse) -> whoopsy::Result<()> {
+        std::fs::write(&self.token_path, serde_json::to_vec_pretty(token)?)?;
+        Ok(())
+    }
+
+    /// A client for the saved token, refreshed first if it has expired.
+    /// `None` until someone has logged in.
+    async fn client(&self) -> whoopsy::Result<Option<WhoopClient>> {
+        let Ok(contents) = std::fs::read(&self.token_path) else {
+            return Ok(None);
+        };
+        let mut token: TokenResponse = serde_json::from_slice(&contents)?;
+
+        let saved_at = std::fs::metadata(&self.token_path)?.modified()?;
+        let age = SystemTime::now()
+            .duration_since(saved_at)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        // A minute of slack so the token doesn't expire mid-request.
+        let expired = token.expires_in.is_some_and(|ttl| age + 60 >= ttl);
+        if let (true, Some(refresh_token)) = (expired, token.refresh_token.clone()) {
+            token = self.oauth_config().refresh_token(refresh_token).await?;
+            self.save_token(&token)?;
+        }
+
+        Ok(Some(WhoopClient::new_with_oauth(
+            self.oauth_config(),
+            token,
+        )))
+    }
+}
+
+/// What WHOOP posts to the webhook URL.
+#[derive(Debug, Clone, Deserialize)]
+struct WebhookEvent {
+    user_id: i64,
+    /// A UUID for sleeps and workouts, the cycle id for recoveries.
+    id: serde_json::Value,
+    #[serde(rename = "type")]
+    kind: String,
+    trace_id: String,
+}
+
+#[tokio::main]
+async fn main() -> whoopsy::Result<()> {
+    let env = |name: &str| std::env::var(name).ok();
+    let (Some(client_id), Some(client_secret)) =
+        (env("WHOOP_CLIENT_ID"), env("WHOOP_CLIENT_SECRET"))
+    else {
+        eprintln!("Set WHOOP_CLIENT_ID and WHOOP_CLIENT_SECRET");
+        std::process::exit(1);
+    };
+    let addr = env("ADDR").unwrap_or_else(|| "127.0.0.1:3000".to_string());
+
+    let app = Arc::new(App {
+        client_id,
+        client_secret,
+        redirect_uri: env("WHOOP_REDIRECT_URI")
+            .unwrap_or_else(|| "http://localhost:3000/callback".to_string()),
+        token_path: env("WHOOP_TOKEN_FILE")
+            .unwrap_or_else(|| "whoopsy-token.json".to_string())
+            .into(),
+        state: uuid::Uuid::new_v4().simple().to_string(),
+        events: Mutex::new(Vec::new()),
+    });
+
+    let router = Router::new()
+        .route("/", get(dashboard))
+        .route("/login", get(login))
+        .route("/callback", get(callback))
+        .route("/webhook", post(webhook))
+        .with_state(app);
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    println!("Listening on http://{}", addr);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+async fn login(State(app): State<Arc<App>>) -> Redirect {
+    let url = format!(
+        "{}&state={}",
+        app.oauth_config().get_authorization_url(),
+        app.state
+    );
+    Redirect::to(&url)
+}
+
+#[derive(Deserialize)]
+struct Callback {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+async fn callback(State(app): State<Arc<App>>, Query(callback): Query<Callback>) -> Response {
+    if let Some(error) = callback.error {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Authorization failed: {}", error),
+        )
+            .into_response();
+    }
+    if callback.state.as_deref() != Some(app.state.as_str()) {
+        return (StatusCode::BAD_REQUEST, "State doesn't match").into_response();
+    }
+    let Some(code) = callback.code else {
+        return (StatusCode::BAD_REQUEST, "Missing code").into_response();
+    };
+
+    let token = match app.oauth_config().exchange_code(code).await {
+        Ok(token) => token,
+        Err(e) => return failure(e),
+    };
+    if let Err(e) = app.save_token(&token) {
+        return failure(e);
+    }
+    Redirect::to("/").into_response()
+}
+
+async fn dashboard(State(app): State<Arc<App>>) -> Response {
+    let client = match app.client().await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            return Html(page("<p><a href=\"/login\">Log in with WHOOP</a></p>")).into_response();
+        }
+        Err(e) => return failure(e),
+    };
+
+    match render_dashboard(&app, &client).await {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => failure(e),
+    }
+}
+
+async fn render_dashboard(app: &App, client: &WhoopClient) -> whoopsy::Result<String> {
+    let start = Some(Utc::now() - Duration::days(DASHBOARD_DAYS));
+    let limit = Some(25);
+
+    let profile = client.get_profile_basic().await?;
+    let cycles = client
+        .get_cycle_collection(Some(CycleQueryParams {
+            limit,
+            start,
+            end: None,
+            next_token: None,
+        }))
+        .await?;
+    let recoveries = client
+        .get_recovery_collection(Some(RecoveryQueryParams {
+            limit,
+            start,
+            end: None,
+            next_token: None,
+        }))
+        .await?;
+    let sleeps = client
+        .get_sleep_collection(Some(SleepQueryParams {
+            limit,
+            start,
+            end: None,
+            next_token: None,
+        }))
+        .await?;
+    let days = DailySummary::from_records(
+        &cycles.records.unwrap_or_default(),
+        &recoveries.records.unwrap_or_default(),
+        &sleeps.records.unwrap_or_default(),
+    );
+
+    let mut body = format!(
+        "<h1>{} {}</h1>\n<table>\n<tr><th>Date</th><th>Recovery</th><th>HRV</th>\
+         <th>RHR</th><th>Strain</th><th>Sleep</th><th>Sleep performance</th></tr>\n",
+        escape(&profile.first_name),
+        escape(&profile.last_name)
+    );
+    let cell = |value: Option<f32>, unit: &str| {
+        value.map_or("–".to_string(), |v| format!("{:.0}{}", v, unit))
+    };
+    for day in days.iter().rev() {
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            day.date,
+            cell(day.recovery_score, "%"),
+            cell(day.hrv_rmssd_milli, " ms"),
+            cell(day.resting_heart_rate, " bpm"),
+            day.strain.map_or("–".to_string(), |s| format!("{:.1}", s)),
+            day.sleep_milli.map_or("–".to_string(), |ms| format!(
+                "{}h {:02}m",
+                ms / 3_600_000,
+                ms % 3_600_000 / 60_000
+            )),
+            cell(day.sleep_performance_percentage, "%"),
+        );
+    }
+    body.push_str("</table>\n<h2>Recent webhook events</h2>\n<ul>\n");
+    for event in app.events.lock().unwrap().iter().rev() {
+        let _ = writeln!(
+            body,
+            "<li>{} {} (user {}, trace {})</li>",
+            escape(&event.kind),
+            escape(&event.id.to_string()),
+            event.user_id,
+            escape(&event.trace_id)
+        );
+    }
+    body.push_str("</ul>\n");
+    Ok(page(&body))
+}
+
+/// Checks WHOOP's signature, then keeps the event for the dashboard. A real
+/// app would fetch the record it names, e.g. with `get_sleep_by_id`.
+async fn webhook(State(app): State<Arc<App>>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(signature), Some(timestamp)) = (
+        header("X-WHOOP-Signature"),
+        header("X-WHOOP-Signature-Timestamp"),
+    ) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    if !verify_signature(&app.client_secret, timestamp, &body, signature) {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let Ok(event) = serde_json::from_slice::<WebhookEvent>(&body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    println!("Webhook: {} {}", event.kind, event.id);
+    let mut events = app.events.lock().unwrap();
+    events.push(event);
+    if events.len() > MAX_EVENTS {
+        events.remove(0);
+    }
+    StatusCode::NO_CONTENT
+}
+
+/// WHOOP signs `timestamp + body` with the app's client secret.
+fn verify_signature(secret: &str, timestamp: &str, body: &[u8], signature: &str) -> bool {
+    let mut mac = hmac_sha256::HMAC::new(secret.as_bytes());
+    mac.update(timestamp.as_bytes());
+    mac.update(body);
+    BASE64.encode(mac.finalize()) == signature
+}
+
+fn failure(error: WhoopError) -> Response {
+    let status = match error.kind() {
+        ErrorKind::Authentication => StatusCode::UNAUTHORIZED,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    (status, error.to_string()).into_response()
+}
+
+fn page(body: &str) -> String {
+    format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>whoopsy</title></head>\n\
+         <body>\n{}</body></html>\n",
+        body
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
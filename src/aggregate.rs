@@ -0,0 +1,634 @@
+use crate::analytics::Point;
+use crate::calendar::{Calendar, CycleDays, TravelPolicy, TravelReport};
+use crate::models::*;
+use crate::sport::{self, SportCategory};
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Totals and averages over a set of cycles, recoveries, sleeps and workouts.
+/// Pass in records already filtered to the period you care about.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Summary {
+    pub cycle_count: usize,
+    pub recovery_count: usize,
+    pub sleep_count: usize,
+    pub workout_count: usize,
+    pub average_recovery: Option<f32>,
+    pub average_hrv_milli: Option<f32>,
+    pub average_resting_heart_rate: Option<f32>,
+    pub total_strain: f32,
+    pub average_strain: Option<f32>,
+    pub total_kilojoule: f32,
+    pub average_sleep_performance: Option<f32>,
+    pub total_sleep_milli: i64,
+    /// Sleep needed but not slept, summed over every main sleep.
+    pub sleep_debt_milli: i64,
+    pub zone_durations: ZoneDurations,
+    /// Keyed by [`sport::normalize`]d name.
+    pub workouts_by_sport: BTreeMap<String, SportSummary>,
+}
+
+/// Workout totals for a single sport.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SportSummary {
+    pub count: usize,
+    pub total_duration_milli: i64,
+    pub total_strain: f32,
+    pub total_kilojoule: f32,
+}
+
+impl Summary {
+    /// Builds a summary from the given records.
+    /// Unscored records count towards the totals but not towards averages.
+    pub fn new(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        workouts: &[WorkoutV2],
+    ) -> Self {
+        let mut summary = Self {
+            cycle_count: cycles.len(),
+            recovery_count: recoveries.len(),
+            sleep_count: sleeps.len(),
+            workout_count: workouts.len(),
+            ..Default::default()
+        };
+
+        let cycle_scores: Vec<&CycleScore> =
+            cycles.iter().filter_map(|c| c.score.as_ref()).collect();
+        summary.total_strain = cycle_scores.iter().map(|s| s.strain).sum();
+        summary.total_kilojoule = cycle_scores.iter().map(|s| s.kilojoule).sum();
+        summary.average_strain = mean(cycle_scores.iter().map(|s| s.strain));
+
+        let recovery_scores: Vec<&RecoveryScore> =
+            recoveries.iter().filter_map(|r| r.score.as_ref()).collect();
+        summary.average_recovery = mean(recovery_scores.iter().map(|s| s.recovery_score));
+        summary.average_hrv_milli = mean(recovery_scores.iter().map(|s| s.hrv_rmssd_milli));
+        summary.average_resting_heart_rate =
+            mean(recovery_scores.iter().map(|s| s.resting_heart_rate));
+
+        for sleep in sleeps {
+            let Some(score) = &sleep.score else { continue };
+            let slept = score.stage_summary.total_sleep_time_milli();
+            summary.total_sleep_milli += slept;
+            if !sleep.nap {
+                summary.sleep_debt_milli += (score.sleep_needed.total_milli() - slept).max(0);
+            }
+        }
+        summary.average_sleep_performance = mean(
+            sleeps
+                .iter()
+                .filter(|s| !s.nap)
+                .filter_map(|s| s.score.as_ref()?.sleep_performance_percentage),
+        );
+
+        for workout in workouts {
+            let sport = summary
+                .workouts_by_sport
+                .entry(sport::normalize(&workout.sport_name))
+                .or_default();
+            sport.count += 1;
+            sport.total_duration_milli += (workout.end - workout.start).num_milliseconds();
+
+            if let Some(score) = &workout.score {
+                sport.total_strain += score.strain;
+                sport.total_kilojoule += score.kilojoule;
+                summary.zone_durations.add(&score.zone_durations);
+            }
+        }
+
+        summary
+    }
+}
+
+/// Time in one heart rate zone and its share of all zone time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ZoneShare {
+    pub zone: usize,
+    pub milli: i64,
+    pub percentage: f32,
+}
+
+impl ZoneShare {
+    /// Shares for zones zero to five. All zero when there's no zone time at all.
+    pub fn of(zones: &ZoneDurations) -> Vec<Self> {
+        let total = zones.total_milli().max(1) as f32;
+        zones
+            .as_array()
+            .iter()
+            .enumerate()
+            .map(|(zone, &milli)| Self {
+                zone,
+                milli,
+                percentage: milli as f32 / total * 100.0,
+            })
+            .collect()
+    }
+}
+
+/// Heart rate zone time summed over a set of workouts.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ZoneDistribution {
+    pub workout_count: usize,
+    pub zone_durations: ZoneDurations,
+    pub shares: Vec<ZoneShare>,
+}
+
+impl ZoneDistribution {
+    /// Unscored workouts count towards `workout_count` but add no zone time.
+    pub fn new<'a>(workouts: impl IntoIterator<Item = &'a WorkoutV2>) -> Self {
+        let mut distribution = Self::default();
+        for workout in workouts {
+            distribution.workout_count += 1;
+            if let Some(score) = &workout.score {
+                distribution.zone_durations.add(&score.zone_durations);
+            }
+        }
+        distribution.shares = ZoneShare::of(&distribution.zone_durations);
+        distribution
+    }
+
+    /// One distribution per week, keyed by the local Monday the week starts on.
+    pub fn by_week(workouts: &[WorkoutV2]) -> BTreeMap<NaiveDate, Self> {
+        Self::grouped(workouts, |w| {
+            Calendar::default()
+                .workout_day(w)
+                .week(Weekday::Mon)
+                .first_day()
+        })
+    }
+
+    /// Keyed by [`sport::normalize`]d name.
+    pub fn by_sport(workouts: &[WorkoutV2]) -> BTreeMap<String, Self> {
+        Self::grouped(workouts, |w| sport::normalize(&w.sport_name))
+    }
+
+    pub fn by_category(workouts: &[WorkoutV2]) -> BTreeMap<SportCategory, Self> {
+        Self::grouped(workouts, |w| sport::category(&w.sport_name))
+    }
+
+    fn grouped<K: Ord>(workouts: &[WorkoutV2], key: impl Fn(&WorkoutV2) -> K) -> BTreeMap<K, Self> {
+        let mut groups: BTreeMap<K, Vec<&WorkoutV2>> = BTreeMap::new();
+        for workout in workouts {
+            groups.entry(key(workout)).or_default().push(workout);
+        }
+        groups
+            .into_iter()
+            .map(|(key, workouts)| (key, Self::new(workouts)))
+            .collect()
+    }
+}
+
+/// How aggregations put records on days.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AggregateOptions {
+    calendar: Calendar,
+    travel: TravelPolicy,
+}
+
+impl AggregateOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_calendar(mut self, calendar: Calendar) -> Self {
+        self.calendar = calendar;
+        self
+    }
+
+    /// What to do with cycles a time zone change doubles up on one day.
+    pub fn with_travel(mut self, travel: TravelPolicy) -> Self {
+        self.travel = travel;
+        self
+    }
+
+    fn cycle_days(&self, cycles: &[Cycle]) -> CycleDays {
+        self.calendar.cycle_days(cycles, self.travel)
+    }
+}
+
+/// One physiological day: a cycle with its recovery and main sleep.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DailySummary {
+    /// The cycle's day, on the default [`Calendar`] unless the
+    /// [`AggregateOptions`] say otherwise.
+    pub date: NaiveDate,
+    pub cycle_id: i64,
+    pub recovery_score: Option<f32>,
+    pub hrv_rmssd_milli: Option<f32>,
+    pub resting_heart_rate: Option<f32>,
+    pub skin_temp_celsius: Option<f32>,
+    pub spo2_percentage: Option<f32>,
+    pub strain: Option<f32>,
+    pub kilojoule: Option<f32>,
+    pub sleep_performance_percentage: Option<f32>,
+    pub respiratory_rate: Option<f32>,
+    pub sleep_milli: Option<i64>,
+    pub sleep_needed_milli: Option<i64>,
+    /// The recovery was scored while WHOOP was still calibrating.
+    pub user_calibrating: bool,
+}
+
+impl DailySummary {
+    /// Joins recoveries and main sleeps onto their cycles, oldest day first.
+    /// Naps are ignored; cycles without a recovery or sleep just leave those fields empty.
+    pub fn from_records(cycles: &[Cycle], recoveries: &[Recovery], sleeps: &[Sleep]) -> Vec<Self> {
+        Self::from_records_with(cycles, recoveries, sleeps, &AggregateOptions::default()).0
+    }
+
+    /// Like [`DailySummary::from_records`], with the days travel affected.
+    pub fn from_records_with(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        options: &AggregateOptions,
+    ) -> (Vec<Self>, TravelReport) {
+        let cycle_days = options.cycle_days(cycles);
+        let days = Self::on_days(cycles, recoveries, sleeps, &cycle_days);
+        (days, cycle_days.report)
+    }
+
+    fn on_days(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        cycle_days: &CycleDays,
+    ) -> Vec<Self> {
+        let recoveries: HashMap<i64, &RecoveryScore> = recoveries
+            .iter()
+            .filter_map(|r| Some((r.cycle_id, r.score.as_ref()?)))
+            .collect();
+        let sleeps: HashMap<i64, &SleepScore> = sleeps
+            .iter()
+            .filter(|s| !s.nap)
+            .filter_map(|s| Some((s.cycle_id, s.score.as_ref()?)))
+            .collect();
+
+        let mut days: Vec<Self> = cycles
+            .iter()
+            .filter_map(|cycle| {
+                // Every cycle has a day, as `cycle_days` was built from them.
+                let date = cycle_days.day(cycle.id)?;
+                let recovery = recoveries.get(&cycle.id);
+                let sleep = sleeps.get(&cycle.id);
+                Some(Self {
+                    date,
+                    cycle_id: cycle.id,
+                    recovery_score: recovery.map(|r| r.recovery_score),
+                    hrv_rmssd_milli: recovery.map(|r| r.hrv_rmssd_milli),
+                    resting_heart_rate: recovery.map(|r| r.resting_heart_rate),
+                    skin_temp_celsius: recovery.and_then(|r| r.skin_temp_celsius),
+                    spo2_percentage: recovery.and_then(|r| r.spo2_percentage),
+                    strain: cycle.score.as_ref().map(|s| s.strain),
+                    kilojoule: cycle.score.as_ref().map(|s| s.kilojoule),
+                    sleep_performance_percentage: sleep
+                        .and_then(|s| s.sleep_performance_percentage),
+                    respiratory_rate: sleep.and_then(|s| s.respiratory_rate),
+                    sleep_milli: sleep.map(|s| s.stage_summary.total_sleep_time_milli()),
+                    sleep_needed_milli: sleep.map(|s| s.sleep_needed.total_milli()),
+                    user_calibrating: recovery.is_some_and(|r| r.user_calibrating),
+                })
+            })
+            .collect();
+        days.sort_by_key(|d| d.date);
+        days
+    }
+}
+
+/// Best and worst days of a period.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Extremes {
+    pub highest_recovery: Option<Point>,
+    pub lowest_recovery: Option<Point>,
+    pub highest_strain: Option<Point>,
+    pub highest_hrv: Option<Point>,
+    pub lowest_hrv: Option<Point>,
+    pub lowest_resting_heart_rate: Option<Point>,
+    pub best_sleep_performance: Option<Point>,
+    pub worst_sleep_performance: Option<Point>,
+}
+
+impl Extremes {
+    pub fn from_days(days: &[DailySummary]) -> Self {
+        let max = |f: fn(&DailySummary) -> Option<f32>| extreme(days, f, true);
+        let min = |f: fn(&DailySummary) -> Option<f32>| extreme(days, f, false);
+        Self {
+            highest_recovery: max(|d| d.recovery_score),
+            lowest_recovery: min(|d| d.recovery_score),
+            highest_strain: max(|d| d.strain),
+            highest_hrv: max(|d| d.hrv_rmssd_milli),
+            lowest_hrv: min(|d| d.hrv_rmssd_milli),
+            lowest_resting_heart_rate: min(|d| d.resting_heart_rate),
+            best_sleep_performance: max(|d| d.sleep_performance_percentage),
+            worst_sleep_performance: min(|d| d.sleep_performance_percentage),
+        }
+    }
+}
+
+/// A calendar week of records, Monday to Sunday in the wearer's local time.
+#[derive(Debug, Clone, Serialize)]
+pub struct WeeklySummary {
+    pub week_start: NaiveDate,
+    pub summary: Summary,
+    pub extremes: Extremes,
+}
+
+impl WeeklySummary {
+    /// One summary per week that has any records, oldest first.
+    pub fn from_records(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        workouts: &[WorkoutV2],
+    ) -> Vec<Self> {
+        let options = AggregateOptions::default();
+        Self::from_records_with(cycles, recoveries, sleeps, workouts, &options).0
+    }
+
+    /// Like [`WeeklySummary::from_records`], with the days travel affected.
+    pub fn from_records_with(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        workouts: &[WorkoutV2],
+        options: &AggregateOptions,
+    ) -> (Vec<Self>, TravelReport) {
+        let (periods, report) = periods(cycles, recoveries, sleeps, workouts, options, |date| {
+            date.week(Weekday::Mon).first_day()
+        });
+        let periods = periods
+            .into_iter()
+            .map(|(week_start, summary, extremes)| Self {
+                week_start,
+                summary,
+                extremes,
+            })
+            .collect();
+        (periods, report)
+    }
+}
+
+/// A calendar month of records in the wearer's local time.
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthlySummary {
+    /// The first of the month.
+    pub month_start: NaiveDate,
+    pub summary: Summary,
+    pub extremes: Extremes,
+}
+
+impl MonthlySummary {
+    /// One summary per month that has any records, oldest first.
+    pub fn from_records(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        workouts: &[WorkoutV2],
+    ) -> Vec<Self> {
+        let options = AggregateOptions::default();
+        Self::from_records_with(cycles, recoveries, sleeps, workouts, &options).0
+    }
+
+    /// Like [`MonthlySummary::from_records`], with the days travel affected.
+    pub fn from_records_with(
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        workouts: &[WorkoutV2],
+        options: &AggregateOptions,
+    ) -> (Vec<Self>, TravelReport) {
+        let (periods, report) = periods(cycles, recoveries, sleeps, workouts, options, |date| {
+            date.with_day(1).unwrap()
+        });
+        let periods = periods
+            .into_iter()
+            .map(|(month_start, summary, extremes)| Self {
+                month_start,
+                summary,
+                extremes,
+            })
+            .collect();
+        (periods, report)
+    }
+}
+
+#[derive(Default)]
+struct Period {
+    cycles: Vec<Cycle>,
+    recoveries: Vec<Recovery>,
+    sleeps: Vec<Sleep>,
+    workouts: Vec<WorkoutV2>,
+}
+
+/// Splits records into periods keyed by `period(local date)`. Recoveries and
+/// sleeps follow their cycle, so a night always lands in the same period as its day.
+/// A recovery without its cycle follows its sleep, and is left out without
+/// either, having no local day of its own.
+fn periods(
+    cycles: &[Cycle],
+    recoveries: &[Recovery],
+    sleeps: &[Sleep],
+    workouts: &[WorkoutV2],
+    options: &AggregateOptions,
+    period: impl Fn(NaiveDate) -> NaiveDate,
+) -> (Vec<(NaiveDate, Summary, Extremes)>, TravelReport) {
+    let calendar = options.calendar;
+    let cycle_days = options.cycle_days(cycles);
+    let mut periods: BTreeMap<NaiveDate, Period> = BTreeMap::new();
+    let mut cycle_periods = HashMap::new();
+    for cycle in cycles {
+        let Some(day) = cycle_days.day(cycle.id) else {
+            continue;
+        };
+        let key = period(day);
+        cycle_periods.insert(cycle.id, key);
+        periods.entry(key).or_default().cycles.push(cycle.clone());
+    }
+    let mut sleep_periods = HashMap::new();
+    for sleep in sleeps {
+        let key = cycle_periods
+            .get(&sleep.cycle_id)
+            .copied()
+            .unwrap_or_else(|| period(calendar.sleep_day(sleep)));
+        sleep_periods.insert(sleep.id, key);
+        periods.entry(key).or_default().sleeps.push(sleep.clone());
+    }
+    for recovery in recoveries {
+        let key = cycle_periods
+            .get(&recovery.cycle_id)
+            .or_else(|| sleep_periods.get(&recovery.sleep_id));
+        let Some(&key) = key else {
+            continue;
+        };
+        periods
+            .entry(key)
+            .or_default()
+            .recoveries
+            .push(recovery.clone());
+    }
+    for workout in workouts {
+        let key = period(calendar.workout_day(workout));
+        periods
+            .entry(key)
+            .or_default()
+            .workouts
+            .push(workout.clone());
+    }
+
+    let periods = periods
+        .into_iter()
+        .map(|(start, p)| {
+            let summary = Summary::new(&p.cycles, &p.recoveries, &p.sleeps, &p.workouts);
+            let days = DailySummary::on_days(&p.cycles, &p.recoveries, &p.sleeps, &cycle_days);
+            (start, summary, Extremes::from_days(&days))
+        })
+        .collect();
+    (periods, cycle_days.report)
+}
+
+fn extreme(
+    days: &[DailySummary],
+    value: fn(&DailySummary) -> Option<f32>,
+    highest: bool,
+) -> Option<Point> {
+    days.iter()
+        .filter_map(|day| {
+            Some(Point {
+                date: day.date,
+                value: value(day)?,
+            })
+        })
+        .reduce(|best, point| {
+            let better = if highest {
+                point.value > best.value
+            } else {
+                point.value < best.value
+            };
+            if better { point } else { best }
+        })
+}
+
+/// Arithmetic mean, or None for an empty input.
+pub(crate) fn mean(values: impl IntoIterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values
+        .into_iter()
+        .fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use chrono::{DateTime, Utc};
+
+    fn cycle(id: i64, start: &str) -> Cycle {
+        let start: DateTime<Utc> = start.parse().unwrap();
+        Cycle {
+            id,
+            start,
+            end: Some(start + chrono::Duration::hours(20)),
+            timezone_offset: "+00:00".to_string(),
+            ..fixtures::cycle_scored()
+        }
+    }
+
+    fn recovery(cycle_id: i64, score: f32) -> Recovery {
+        let mut recovery = fixtures::recovery_scored();
+        recovery.cycle_id = cycle_id;
+        recovery.score.as_mut().unwrap().recovery_score = score;
+        recovery
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_days_roll_over_at_six_pm() {
+        let cycles = [
+            cycle(1, "2024-03-04T17:59:00Z"),
+            cycle(2, "2024-03-04T18:00:00Z"),
+        ];
+        let days = DailySummary::from_records(&cycles, &[recovery(2, 80.0)], &[]);
+        let days: Vec<_> = days
+            .iter()
+            .map(|d| (d.date, d.cycle_id, d.recovery_score))
+            .collect();
+        assert_eq!(
+            days,
+            [
+                (date("2024-03-04"), 1, None),
+                (date("2024-03-05"), 2, Some(80.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weeks_start_on_monday_after_the_rollover() {
+        // Sunday morning belongs to its own week, Sunday evening to the next.
+        let cycles = [
+            cycle(1, "2024-03-10T08:00:00Z"),
+            cycle(2, "2024-03-10T20:00:00Z"),
+            cycle(3, "2024-03-12T08:00:00Z"),
+        ];
+        let recoveries = [recovery(1, 30.0), recovery(2, 60.0), recovery(3, 90.0)];
+        let weeks = WeeklySummary::from_records(&cycles, &recoveries, &[], &[]);
+        let weeks: Vec<_> = weeks
+            .iter()
+            .map(|w| {
+                (
+                    w.week_start,
+                    w.summary.cycle_count,
+                    w.summary.average_recovery,
+                )
+            })
+            .collect();
+        assert_eq!(
+            weeks,
+            [
+                (date("2024-03-04"), 1, Some(30.0)),
+                (date("2024-03-11"), 2, Some(75.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_months_follow_their_cycles_days() {
+        let cycles = [
+            cycle(1, "2024-02-29T10:00:00Z"),
+            cycle(2, "2024-02-29T19:00:00Z"),
+        ];
+        let months = MonthlySummary::from_records(&cycles, &[recovery(2, 50.0)], &[], &[]);
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].month_start, date("2024-02-01"));
+        assert_eq!(months[0].summary.recovery_count, 0);
+        assert_eq!(months[1].month_start, date("2024-03-01"));
+        assert_eq!(months[1].summary.recovery_count, 1);
+        let lowest = months[1].extremes.lowest_recovery.as_ref().unwrap();
+        assert_eq!((lowest.date, lowest.value), (date("2024-03-01"), 50.0));
+    }
+
+    #[test]
+    fn test_recoveries_without_their_cycle_follow_their_sleep() {
+        // Ends on the evening of March 31st in New York, April 1st in UTC.
+        let sleep = Sleep {
+            cycle_id: 7,
+            end: "2024-04-01T02:00:00Z".parse().unwrap(),
+            timezone_offset: "-05:00".to_string(),
+            ..fixtures::sleep_scored()
+        };
+        let mut late = recovery(7, 40.0);
+        late.sleep_id = sleep.id;
+        late.created_at = "2024-04-01T02:05:00Z".parse().unwrap();
+        let mut orphan = recovery(8, 90.0);
+        orphan.sleep_id = uuid::Uuid::new_v4();
+        let months = MonthlySummary::from_records(&[], &[late, orphan], &[sleep], &[]);
+        let months: Vec<_> = months
+            .iter()
+            .map(|m| (m.month_start, m.summary.average_recovery))
+            .collect();
+        assert_eq!(months, [(date("2024-03-01"), Some(40.0))]);
+    }
+}
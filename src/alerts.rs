@@ -0,0 +1,512 @@
+//! Rules over incoming data, and notifiers that hear when they trigger.
+//!
+//! A [`Rule`] is a [`Condition`] on a day, optionally held for several days in
+//! a row. Rules can be built in code or parsed from text:
+//!
+//! - `recovery < 34`, `strain > 18`: a metric against a fixed value;
+//! - `hrv 20% below baseline`: a metric against its mean over the 30 days
+//!   before;
+//! - `sleep_debt > 90`: minutes of sleep needed but not slept;
+//! - any of them followed by `for 3 days`.
+//!
+//! [`Alerts`] checks its rules against the latest of a run of
+//! [`DailySummary`]s and hands each new [`Alert`] to every [`Notifier`]. It
+//! remembers what fired, so checking again after every sync or webhook only
+//! notifies once per rule and day:
+//!
+//! ```no_run
+//! # async fn run(store: impl whoopsy::store::Store) -> whoopsy::Result<()> {
+//! use whoopsy::alerts::{Alert, Alerts, Rule};
+//!
+//! let alerts = Alerts::new()
+//!     .with_rule(Rule::parse("recovery < 34").unwrap())
+//!     .with_rule(Rule::parse("hrv 20% below baseline for 2 days").unwrap())
+//!     .with_notifier(|alert: &Alert| println!("{}", alert.message));
+//! // E.g. after fetching the record a webhook announced into the store:
+//! alerts.process(&alerts.recent_days(&store)?).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A [`Delta`](crate::sync::Delta) given alerts checks them after every run.
+
+use crate::aggregate::{DailySummary, mean};
+use crate::analytics::trends::Metric;
+use crate::error::{Result, WhoopError};
+use crate::store::Store;
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Days a baseline is averaged over when a rule doesn't say.
+pub const BASELINE_DAYS: u32 = 30;
+
+/// The most days a rule can need to hold for.
+pub const MAX_FOR_DAYS: u32 = 366;
+
+/// Something true or false of a day, given the days before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Below(Metric, f32),
+    Above(Metric, f32),
+    /// At least `percent` below the metric's mean over the `days` before.
+    BelowBaseline {
+        metric: Metric,
+        percent: f32,
+        days: u32,
+    },
+    AboveBaseline {
+        metric: Metric,
+        percent: f32,
+        days: u32,
+    },
+    /// More than this many minutes of sleep needed but not slept.
+    SleepDebtAbove(f32),
+}
+
+impl Condition {
+    /// The value that met the condition on `days[i]`, or `None` if it wasn't met.
+    fn check(&self, days: &[DailySummary], i: usize) -> Option<f32> {
+        let day = &days[i];
+        match *self {
+            Condition::Below(metric, limit) => metric.value(day).filter(|v| *v < limit),
+            Condition::Above(metric, limit) => metric.value(day).filter(|v| *v > limit),
+            Condition::BelowBaseline {
+                metric,
+                percent,
+                days: n,
+            } => {
+                let baseline = baseline(metric, days, i, n)?;
+                metric
+                    .value(day)
+                    .filter(|v| *v <= baseline * (1.0 - percent / 100.0))
+            }
+            Condition::AboveBaseline {
+                metric,
+                percent,
+                days: n,
+            } => {
+                let baseline = baseline(metric, days, i, n)?;
+                metric
+                    .value(day)
+                    .filter(|v| *v >= baseline * (1.0 + percent / 100.0))
+            }
+            Condition::SleepDebtAbove(minutes) => {
+                let debt = (day.sleep_needed_milli? - day.sleep_milli?) as f32 / 60_000.0;
+                Some(debt).filter(|d| *d > minutes)
+            }
+        }
+    }
+
+    /// Days of history the condition looks at before the day itself.
+    fn history_days(&self) -> u32 {
+        match self {
+            Condition::BelowBaseline { days, .. } | Condition::AboveBaseline { days, .. } => *days,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Below(metric, limit) => write!(f, "{} < {}", metric.name(), limit),
+            Condition::Above(metric, limit) => write!(f, "{} > {}", metric.name(), limit),
+            Condition::BelowBaseline {
+                metric, percent, ..
+            } => write!(f, "{} {}% below baseline", metric.name(), percent),
+            Condition::AboveBaseline {
+                metric, percent, ..
+            } => write!(f, "{} {}% above baseline", metric.name(), percent),
+            Condition::SleepDebtAbove(minutes) => write!(f, "sleep_debt > {}", minutes),
+        }
+    }
+}
+
+/// The mean of `metric` over the `n` calendar days before `days[i]`, if at
+/// least half of them have a value.
+fn baseline(metric: Metric, days: &[DailySummary], i: usize, n: u32) -> Option<f32> {
+    let from = days[i].date - Duration::days(i64::from(n.max(1)));
+    let values: Vec<f32> = days[..i]
+        .iter()
+        .filter(|d| d.date >= from)
+        .filter_map(|d| metric.value(d))
+        .collect();
+    if values.len() * 2 < n.max(1) as usize {
+        return None;
+    }
+    mean(values)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub condition: Condition,
+    /// Consecutive days, ending on the day checked, the condition must hold.
+    pub for_days: u32,
+}
+
+impl Rule {
+    pub fn new(name: impl Into<String>, condition: Condition) -> Self {
+        Self {
+            name: name.into(),
+            condition,
+            for_days: 1,
+        }
+    }
+
+    /// Clamped to between 1 and [`MAX_FOR_DAYS`].
+    pub fn with_for_days(mut self, days: u32) -> Self {
+        self.for_days = days.clamp(1, MAX_FOR_DAYS);
+        self
+    }
+
+    /// A rule from text like `recovery < 34` or `hrv 20% below baseline for 3
+    /// days`, named after the text. Metrics go by their
+    /// [`name`](Metric::name).
+    pub fn parse(text: &str) -> Option<Rule> {
+        let lower = text.trim().to_lowercase();
+        let mut tokens: Vec<&str> = lower.split_whitespace().collect();
+        let mut for_days = 1;
+        if let ["for", n, "day" | "days"] = tokens[tokens.len().saturating_sub(3)..] {
+            for_days = n.parse().ok().filter(|n| *n <= MAX_FOR_DAYS)?;
+            tokens.truncate(tokens.len() - 3);
+        }
+        let condition = match tokens[..] {
+            ["sleep_debt", ">", minutes] => {
+                Condition::SleepDebtAbove(minutes.trim_end_matches("min").parse().ok()?)
+            }
+            [metric, op, value] => {
+                let metric = Metric::from_name(metric)?;
+                let value = value.parse().ok()?;
+                match op {
+                    "<" => Condition::Below(metric, value),
+                    ">" => Condition::Above(metric, value),
+                    _ => return None,
+                }
+            }
+            [metric, percent, direction, "baseline"] => {
+                let metric = Metric::from_name(metric)?;
+                let percent = percent.strip_suffix('%')?.parse().ok()?;
+                let days = BASELINE_DAYS;
+                match direction {
+                    "below" => Condition::BelowBaseline {
+                        metric,
+                        percent,
+                        days,
+                    },
+                    "above" => Condition::AboveBaseline {
+                        metric,
+                        percent,
+                        days,
+                    },
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(Rule::new(text.trim(), condition).with_for_days(for_days))
+    }
+
+    /// The value on the last of `days` if the rule holds there.
+    fn check(&self, days: &[DailySummary]) -> Option<f32> {
+        let last = days.len().checked_sub(1)?;
+        let first = last.checked_sub(self.for_days.saturating_sub(1) as usize)?;
+        let consecutive = days[first..]
+            .windows(2)
+            .all(|pair| pair[0].date.succ_opt() == Some(pair[1].date));
+        if !consecutive {
+            return None;
+        }
+        (first..last).try_for_each(|i| self.condition.check(days, i).map(|_| ()))?;
+        self.condition.check(days, last)
+    }
+}
+
+/// A rule that triggered.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub date: NaiveDate,
+    /// The metric's value, or the minutes of sleep debt, on `date`.
+    pub value: f32,
+    pub message: String,
+    /// Set by [`Alerts::with_user`], for notifiers serving several users.
+    pub user: Option<String>,
+}
+
+/// What a [`Notifier`] returns: sending an alert may take a request.
+pub type Notification<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Where alerts go. Any `Fn(&Alert)` is a notifier.
+pub trait Notifier: Send + Sync {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> Notification<'a>;
+}
+
+impl<F: Fn(&Alert) + Send + Sync> Notifier for F {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> Notification<'a> {
+        self(alert);
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+/// Rules, the notifiers they fire into and what has fired already.
+#[derive(Default)]
+pub struct Alerts {
+    rules: Vec<Rule>,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    user: Option<String>,
+    fired: Mutex<HashSet<(String, NaiveDate)>>,
+}
+
+impl Alerts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifiers.push(Arc::new(notifier));
+        self
+    }
+
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Every rule holding on the last of `days`, fired before or not. Pass
+    /// days sorted by date, as [`DailySummary::from_records`] returns them.
+    pub fn evaluate(&self, days: &[DailySummary]) -> Vec<Alert> {
+        let Some(last) = days.last() else {
+            return Vec::new();
+        };
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                let value = rule.check(days)?;
+                let mut message = format!("{}: {} is {:.1}", rule.name, last.date, value);
+                if rule.for_days > 1 {
+                    message.push_str(&format!(", {} days running", rule.for_days));
+                }
+                Some(Alert {
+                    rule: rule.name.clone(),
+                    date: last.date,
+                    value,
+                    message,
+                    user: self.user.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Notifies every notifier of the alerts on the last of `days` that
+    /// haven't fired yet, and returns them. Every notifier hears of every
+    /// alert even if one fails; the first failure is returned, and an alert
+    /// a notifier failed on isn't marked fired, so the next call retries it.
+    pub async fn process(&self, days: &[DailySummary]) -> Result<Vec<Alert>> {
+        let alerts: Vec<Alert> = {
+            let fired = self.lock_fired()?;
+            self.evaluate(days)
+                .into_iter()
+                .filter(|alert| !fired.contains(&(alert.rule.clone(), alert.date)))
+                .collect()
+        };
+        let mut outcome = Ok(());
+        for alert in &alerts {
+            let mut sent = Ok(());
+            for notifier in &self.notifiers {
+                let result = notifier.notify(alert).await;
+                if sent.is_ok() {
+                    sent = result;
+                }
+            }
+            if sent.is_ok() {
+                self.lock_fired()?.insert((alert.rule.clone(), alert.date));
+            } else if outcome.is_ok() {
+                outcome = sent;
+            }
+        }
+        outcome.map(|_| alerts)
+    }
+
+    fn lock_fired(&self) -> Result<MutexGuard<'_, HashSet<(String, NaiveDate)>>> {
+        self.fired
+            .lock()
+            .map_err(|_| WhoopError::Unknown("alerts lock poisoned".to_string(), None))
+    }
+
+    /// The days from `store` the rules need, up to today.
+    pub fn recent_days(&self, store: &impl Store) -> Result<Vec<DailySummary>> {
+        let too_long = || WhoopError::Unknown("alert rules look too far back".to_string(), None);
+        let history = self.rules.iter().try_fold(1, |history: u32, r| {
+            let days = r.condition.history_days().checked_add(r.for_days)?;
+            Some(history.max(days))
+        });
+        let history = history.ok_or_else(too_long)?;
+        // Cycles start the evening before their day, and a day covers clock skew.
+        let end = Utc::now() + Duration::days(1);
+        let start = end
+            .checked_sub_signed(Duration::days(i64::from(history) + 2))
+            .ok_or_else(too_long)?;
+        let range = start..end;
+        let cycles = store.cycles_between(range.clone())?;
+        let recoveries = store.recoveries_between(range.clone())?;
+        let sleeps = store.sleeps_between(range)?;
+        Ok(DailySummary::from_records(&cycles, &recoveries, &sleeps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Days;
+
+    fn days(recoveries: &[f32]) -> Vec<DailySummary> {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        recoveries
+            .iter()
+            .zip(0..)
+            .map(|(recovery, i)| DailySummary {
+                date: start + Days::new(i),
+                recovery_score: Some(*recovery),
+                hrv_rmssd_milli: Some(recovery * 0.8),
+                sleep_milli: Some(6 * 3_600_000),
+                sleep_needed_milli: Some(8 * 3_600_000),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parses_rules() {
+        let rule = Rule::parse("recovery < 34").unwrap();
+        assert_eq!(rule.condition, Condition::Below(Metric::Recovery, 34.0));
+        let rule = Rule::parse("HRV 20% below baseline for 3 days").unwrap();
+        assert_eq!(rule.for_days, 3);
+        assert_eq!(
+            rule.condition,
+            Condition::BelowBaseline {
+                metric: Metric::Hrv,
+                percent: 20.0,
+                days: BASELINE_DAYS
+            }
+        );
+        assert_eq!(rule.condition.to_string(), "hrv 20% below baseline");
+        let rule = Rule::parse("sleep_debt > 90min").unwrap();
+        assert_eq!(rule.condition, Condition::SleepDebtAbove(90.0));
+        assert!(Rule::parse("recovery = 34").is_none());
+        assert!(Rule::parse("mood < 3").is_none());
+        assert!(Rule::parse("recovery < 34 for 4000000000 days").is_none());
+        assert_eq!(
+            Rule::parse("recovery < 34 for 366 days").unwrap().for_days,
+            366
+        );
+    }
+
+    #[test]
+    fn test_rules_check_the_latest_day() {
+        let mut history = vec![70.0; 20];
+        history.extend([40.0, 30.0]);
+        let alerts = Alerts::new()
+            .with_rule(Rule::parse("recovery < 34").unwrap())
+            .with_rule(Rule::parse("hrv 20% below baseline for 2 days").unwrap())
+            .with_rule(Rule::parse("sleep_debt > 90 for 3 days").unwrap())
+            .with_rule(Rule::parse("strain > 10").unwrap());
+
+        let fired: Vec<String> = alerts
+            .evaluate(&days(&history))
+            .into_iter()
+            .map(|a| a.rule)
+            .collect();
+        assert_eq!(
+            fired,
+            [
+                "recovery < 34",
+                "hrv 20% below baseline for 2 days",
+                "sleep_debt > 90 for 3 days"
+            ]
+        );
+
+        // A gap breaks a run.
+        let mut gapped = days(&history);
+        gapped.remove(20);
+        let fired = alerts.evaluate(&gapped);
+        assert!(
+            fired
+                .iter()
+                .all(|a| a.rule != "hrv 20% below baseline for 2 days")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notifies_once_per_rule_and_day() {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&heard);
+        let alerts = Alerts::new()
+            .with_rule(Rule::parse("recovery < 34").unwrap())
+            .with_user("42")
+            .with_notifier(move |alert: &Alert| log.lock().unwrap().push(alert.clone()));
+
+        let days = days(&[50.0, 20.0]);
+        assert_eq!(alerts.process(&days).await.unwrap().len(), 1);
+        assert!(alerts.process(&days).await.unwrap().is_empty());
+
+        let heard = heard.lock().unwrap();
+        assert_eq!(heard.len(), 1);
+        assert_eq!(heard[0].user.as_deref(), Some("42"));
+        assert_eq!(heard[0].value, 20.0);
+    }
+
+    #[tokio::test]
+    async fn test_retries_alerts_a_notifier_failed_on() {
+        struct Down;
+        impl Notifier for Down {
+            fn notify<'a>(&'a self, _: &'a Alert) -> Notification<'a> {
+                Box::pin(future::ready(Err(WhoopError::Unknown(
+                    "down".to_string(),
+                    None,
+                ))))
+            }
+        }
+        let heard = Arc::new(Mutex::new(0));
+        let count = Arc::clone(&heard);
+        // A rule built by hand may not say how many days.
+        let rule = Rule {
+            for_days: 0,
+            ..Rule::parse("recovery < 34").unwrap()
+        };
+        let alerts = Alerts::new()
+            .with_rule(rule)
+            .with_notifier(Down)
+            .with_notifier(move |_: &Alert| *count.lock().unwrap() += 1);
+
+        let days = days(&[20.0]);
+        assert!(alerts.process(&days).await.is_err());
+        assert!(alerts.process(&days).await.is_err());
+        assert_eq!(*heard.lock().unwrap(), 2);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_rejects_rules_looking_back_too_far() {
+        let store = crate::store::SqliteStore::open_in_memory().unwrap();
+        let rule = Rule {
+            for_days: u32::MAX,
+            ..Rule::parse("hrv 20% below baseline").unwrap()
+        };
+        let alerts = Alerts::new().with_rule(rule);
+        assert!(alerts.recent_days(&store).is_err());
+
+        let alerts = Alerts::new().with_rule(Rule::parse("recovery < 34 for 3 days").unwrap());
+        assert!(alerts.recent_days(&store).unwrap().is_empty());
+    }
+}
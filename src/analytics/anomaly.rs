@@ -0,0 +1,84 @@
+//! Unusual jumps in the metrics that tend to move first when you're getting ill.
+//!
+//! Resting heart rate, respiratory rate and skin temperature are each compared
+//! against their own personal [baseline](super::baseline). Respiratory rate and
+//! skin temperature only exist for WHOOP 4.0 and later; older data just has no
+//! anomalies for them.
+
+use super::baseline::{BaselineDeviation, BaselineOptions, Deviation, metric_baseline};
+use super::trends::Metric;
+use crate::aggregate::DailySummary;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// The metrics checked by [`anomalies`].
+pub const ILLNESS_METRICS: [Metric; 3] = [
+    Metric::RestingHeartRate,
+    Metric::RespiratoryRate,
+    Metric::SkinTemperature,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Anomaly {
+    pub metric: Metric,
+    #[serde(flatten)]
+    pub deviation: BaselineDeviation,
+}
+
+impl Anomaly {
+    pub fn date(&self) -> NaiveDate {
+        self.deviation.date
+    }
+
+    /// Rises are the illness signal; drops are usually benign.
+    pub fn is_rise(&self) -> bool {
+        self.deviation.deviation == Deviation::Above
+    }
+}
+
+/// Every flagged day across [`ILLNESS_METRICS`], oldest first. Several metrics
+/// rising on the same day is a much stronger signal than any one alone.
+pub fn anomalies(days: &[DailySummary], options: BaselineOptions) -> Vec<Anomaly> {
+    let mut anomalies: Vec<Anomaly> = ILLNESS_METRICS
+        .into_iter()
+        .flat_map(|metric| {
+            metric_baseline(metric, days, options)
+                .into_iter()
+                .filter(BaselineDeviation::is_flagged)
+                .map(move |deviation| Anomaly { metric, deviation })
+        })
+        .collect();
+    anomalies.sort_by_key(Anomaly::date);
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Days;
+
+    #[test]
+    fn test_flags_rises_across_metrics() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let days: Vec<DailySummary> = (0..15)
+            .map(|i| {
+                let sick = i == 14;
+                let wobble = (i % 2) as f32;
+                DailySummary {
+                    date: start + Days::new(i),
+                    resting_heart_rate: Some(if sick { 62.0 } else { 52.0 + wobble }),
+                    respiratory_rate: Some(if sick { 17.5 } else { 14.5 + wobble * 0.2 }),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let anomalies = anomalies(&days, BaselineOptions::default());
+        assert_eq!(anomalies.len(), 2);
+        assert!(
+            anomalies
+                .iter()
+                .all(|a| a.is_rise() && a.date() == days[14].date)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Personal baselines and days that stray from them.
+//!
+//! The baseline is an exponentially weighted mean and standard deviation over
+//! earlier days. Each day is compared against the baseline *before* it, so a
+//! sudden drop isn't hidden by folding itself into its own reference.
+
+use super::Point;
+use super::calibration::Calibration;
+use super::trends::Metric;
+use crate::aggregate::DailySummary;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaselineOptions {
+    /// EWMA span in days; the smoothing factor is `2 / (span + 1)`.
+    pub span_days: u32,
+    /// How many standard deviations away a day must be to be flagged.
+    pub threshold: f32,
+    /// Days used to seed the baseline; nothing is compared before them.
+    pub min_days: usize,
+    /// What functions taking [`DailySummary`]s do with calibrating days.
+    pub calibration: Calibration,
+}
+
+impl Default for BaselineOptions {
+    fn default() -> Self {
+        Self {
+            span_days: 30,
+            threshold: 1.5,
+            min_days: 7,
+            calibration: Calibration::Include,
+        }
+    }
+}
+
+impl BaselineOptions {
+    pub fn with_span_days(mut self, span_days: u32) -> Self {
+        self.span_days = span_days;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_min_days(mut self, min_days: usize) -> Self {
+        self.min_days = min_days;
+        self
+    }
+
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Deviation {
+    Within,
+    Above,
+    Below,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BaselineDeviation {
+    pub date: NaiveDate,
+    pub value: f32,
+    pub baseline: f32,
+    pub standard_deviation: f32,
+    /// Standard deviations from the baseline; zero while the spread is still zero.
+    pub z_score: f32,
+    pub deviation: Deviation,
+}
+
+impl BaselineDeviation {
+    pub fn is_flagged(&self) -> bool {
+        self.deviation != Deviation::Within
+    }
+}
+
+/// Compares every day after the warm-up against its baseline. Pass a series sorted by date.
+pub fn baseline(series: &[Point], options: BaselineOptions) -> Vec<BaselineDeviation> {
+    let weighted: Vec<(Point, f32)> = series.iter().map(|p| (*p, 1.0)).collect();
+    weighted_baseline(&weighted, options)
+}
+
+/// `metric` against its baseline, with calibrating days handled as `options` say.
+pub(crate) fn metric_baseline(
+    metric: Metric,
+    days: &[DailySummary],
+    options: BaselineOptions,
+) -> Vec<BaselineDeviation> {
+    let series: Vec<(Point, f32)> = days
+        .iter()
+        .filter_map(|day| {
+            let point = Point {
+                date: day.date,
+                value: metric.value(day)?,
+            };
+            Some((point, options.calibration.weight(day)?))
+        })
+        .collect();
+    weighted_baseline(&series, options)
+}
+
+/// [`baseline`] with each point counting for its weight, between 0 and 1.
+fn weighted_baseline(series: &[(Point, f32)], options: BaselineOptions) -> Vec<BaselineDeviation> {
+    let alpha = 2.0 / (options.span_days.max(1) as f32 + 1.0);
+    let warm_up = options.min_days.max(2);
+    let mut points = Vec::new();
+    if series.len() <= warm_up {
+        return points;
+    }
+
+    // Seeding from one day would make the spread near zero for the first few weeks,
+    // so start from the weighted mean and variance of the warm-up days.
+    let seed = &series[..warm_up];
+    let total: f32 = seed.iter().map(|(_, w)| w).sum();
+    let mut mean = seed.iter().map(|(p, w)| p.value * w).sum::<f32>() / total;
+    let squares: f32 = seed.iter().map(|(_, w)| w * w).sum();
+    let spread = seed
+        .iter()
+        .map(|(p, w)| w * (p.value - mean).powi(2))
+        .sum::<f32>();
+    let denominator = total - squares / total;
+    let mut variance = if denominator > f32::EPSILON {
+        spread / denominator
+    } else {
+        0.0
+    };
+
+    // How much the baseline rests on, decaying like the mean itself. A full
+    // weight day moves it by alpha once settled, and by more while it rests on
+    // down-weighted days.
+    let mut mass = total / warm_up as f32 / alpha;
+
+    for &(point, weight) in &series[warm_up..] {
+        let standard_deviation = variance.sqrt();
+        let z_score = if standard_deviation > 0.0 {
+            (point.value - mean) / standard_deviation
+        } else {
+            0.0
+        };
+        let deviation = if z_score >= options.threshold {
+            Deviation::Above
+        } else if z_score <= -options.threshold {
+            Deviation::Below
+        } else {
+            Deviation::Within
+        };
+        points.push(BaselineDeviation {
+            date: point.date,
+            value: point.value,
+            baseline: mean,
+            standard_deviation,
+            z_score,
+            deviation,
+        });
+
+        mass = (1.0 - alpha) * mass + weight;
+        let step = weight / mass;
+        let diff = point.value - mean;
+        mean += step * diff;
+        variance = (1.0 - step) * (variance + step * diff * diff);
+    }
+    points
+}
+
+/// HRV against its baseline. Days [`Deviation::Below`] are the usual early sign of
+/// illness, overreaching or poor recovery.
+pub fn hrv_baseline(days: &[DailySummary], options: BaselineOptions) -> Vec<BaselineDeviation> {
+    metric_baseline(Metric::Hrv, days, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_is_flagged_against_earlier_days() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let mut series: Vec<Point> = (0..20)
+            .map(|i| Point {
+                date: start + chrono::Days::new(i),
+                value: if i % 2 == 0 { 60.0 } else { 64.0 },
+            })
+            .collect();
+        series.push(Point {
+            date: start + chrono::Days::new(20),
+            value: 40.0,
+        });
+
+        let points = baseline(&series, BaselineOptions::default());
+        assert_eq!(points.len(), 14);
+        assert!(points[..13].iter().all(|p| !p.is_flagged()));
+        assert_eq!(points[13].deviation, Deviation::Below);
+    }
+
+    #[test]
+    fn test_calibrating_days_stay_out_of_the_baseline() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        // Two weeks of calibrating HRV far above the settled 60ms.
+        let days: Vec<DailySummary> = (0..30)
+            .map(|i| DailySummary {
+                date: start + chrono::Days::new(i),
+                hrv_rmssd_milli: Some(if i < 14 { 90.0 } else { 60.0 + (i % 2) as f32 }),
+                user_calibrating: i < 14,
+                ..Default::default()
+            })
+            .collect();
+
+        let included = hrv_baseline(&days, BaselineOptions::default());
+        assert!(included.iter().any(BaselineDeviation::is_flagged));
+
+        let options = BaselineOptions::default().with_calibration(Calibration::Exclude);
+        let excluded = hrv_baseline(&days, options);
+        assert_eq!(excluded.len(), 9);
+        assert!(excluded.iter().all(|p| !p.is_flagged()));
+
+        let options = BaselineOptions::default().with_calibration(Calibration::DownWeight(0.1));
+        let weighted = hrv_baseline(&days, options);
+        let last = |points: &[BaselineDeviation]| points.last().unwrap().baseline;
+        assert!(last(&weighted) < last(&included));
+    }
+}
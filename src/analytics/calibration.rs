@@ -0,0 +1,97 @@
+//! The first weeks of wear, while WHOOP is still learning the wearer.
+//!
+//! Recoveries scored then carry `user_calibrating`, and their numbers are
+//! rough: a baseline seeded from them takes weeks to forget it. The helpers
+//! here find and drop those days, and [`Calibration`] tells the baseline based
+//! analytics what to do with them.
+
+use crate::aggregate::DailySummary;
+use crate::models::Recovery;
+use chrono::NaiveDate;
+
+/// What analytics do with days whose recovery was scored while calibrating.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Calibration {
+    /// Count them like any other day.
+    #[default]
+    Include,
+    Exclude,
+    /// Count them with this weight, between 0 and 1, against 1 for the rest.
+    DownWeight(f32),
+}
+
+impl Calibration {
+    /// The weight `day` gets, or None if it's left out.
+    pub fn weight(self, day: &DailySummary) -> Option<f32> {
+        if !day.user_calibrating {
+            return Some(1.0);
+        }
+        match self {
+            Calibration::Include => Some(1.0),
+            Calibration::Exclude => None,
+            Calibration::DownWeight(weight) => Some(weight.min(1.0)).filter(|w| *w > 0.0),
+        }
+    }
+}
+
+pub fn is_calibrating(recovery: &Recovery) -> bool {
+    recovery.score.as_ref().is_some_and(|s| s.user_calibrating)
+}
+
+/// `recoveries` without those scored while calibrating.
+pub fn calibrated_recoveries(recoveries: &[Recovery]) -> Vec<Recovery> {
+    recoveries
+        .iter()
+        .filter(|r| !is_calibrating(r))
+        .cloned()
+        .collect()
+}
+
+/// `days` without those whose recovery was scored while calibrating.
+pub fn calibrated_days(days: &[DailySummary]) -> Vec<DailySummary> {
+    days.iter()
+        .filter(|d| !d.user_calibrating)
+        .cloned()
+        .collect()
+}
+
+/// The last calibrating day, if any. Pass days sorted by date, as
+/// [`DailySummary::from_records`] returns them.
+pub fn calibration_end(days: &[DailySummary]) -> Option<NaiveDate> {
+    days.iter()
+        .rev()
+        .find(|d| d.user_calibrating)
+        .map(|d| d.date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_calibrating_days_are_dropped_or_weighted() {
+        let recoveries = [
+            fixtures::recovery_calibrating(),
+            fixtures::recovery_scored(),
+        ];
+        assert_eq!(calibrated_recoveries(&recoveries).len(), 1);
+
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let days: Vec<DailySummary> = (0..4)
+            .map(|i| DailySummary {
+                date: start + chrono::Days::new(i),
+                user_calibrating: i < 2,
+                ..Default::default()
+            })
+            .collect();
+        assert_eq!(calibrated_days(&days).len(), 2);
+        assert_eq!(calibration_end(&days), Some(days[1].date));
+
+        assert_eq!(Calibration::Include.weight(&days[0]), Some(1.0));
+        assert_eq!(Calibration::Exclude.weight(&days[0]), None);
+        assert_eq!(Calibration::DownWeight(0.25).weight(&days[0]), Some(0.25));
+        assert_eq!(Calibration::DownWeight(0.0).weight(&days[0]), None);
+        assert_eq!(Calibration::Exclude.weight(&days[3]), Some(1.0));
+    }
+}
@@ -0,0 +1,135 @@
+//! How a day's strain relates to the next morning's recovery.
+
+use super::RecoveryZone;
+use crate::aggregate::DailySummary;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Fewest qualifying days a zone needs before it gets a strain target.
+const MIN_TARGET_DAYS: usize = 3;
+
+/// One day's strain paired with the recovery that followed it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LaggedPair {
+    /// The day the strain was accumulated.
+    pub date: NaiveDate,
+    /// That day's own recovery, which decides its zone.
+    pub recovery: f32,
+    pub strain: f32,
+    pub next_recovery: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StrainTarget {
+    pub zone: RecoveryZone,
+    /// Mean strain on days in this zone after which recovery held or improved.
+    pub strain: Option<f32>,
+    /// Days in this zone with a next-day recovery.
+    pub days: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrainRecovery {
+    /// Pearson correlation between strain and next-day recovery; `None` with fewer
+    /// than three pairs or no variation.
+    pub correlation: Option<f32>,
+    /// The scatter data the correlation is computed from.
+    pub pairs: Vec<LaggedPair>,
+    /// One target per zone, red to green.
+    pub targets: Vec<StrainTarget>,
+}
+
+/// Pairs each day's strain with the following calendar day's recovery.
+/// Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn strain_recovery_correlation(days: &[DailySummary]) -> StrainRecovery {
+    let pairs: Vec<LaggedPair> = days
+        .windows(2)
+        .filter(|w| w[0].date.succ_opt() == Some(w[1].date))
+        .filter_map(|w| {
+            Some(LaggedPair {
+                date: w[0].date,
+                recovery: w[0].recovery_score?,
+                strain: w[0].strain?,
+                next_recovery: w[1].recovery_score?,
+            })
+        })
+        .collect();
+
+    let strain: Vec<f32> = pairs.iter().map(|p| p.strain).collect();
+    let next: Vec<f32> = pairs.iter().map(|p| p.next_recovery).collect();
+
+    let targets = [RecoveryZone::Red, RecoveryZone::Yellow, RecoveryZone::Green]
+        .into_iter()
+        .map(|zone| {
+            let in_zone: Vec<&LaggedPair> = pairs
+                .iter()
+                .filter(|p| RecoveryZone::from_score(p.recovery) == zone)
+                .collect();
+            let held: Vec<f32> = in_zone
+                .iter()
+                .filter(|p| p.next_recovery >= p.recovery)
+                .map(|p| p.strain)
+                .collect();
+            StrainTarget {
+                zone,
+                strain: (held.len() >= MIN_TARGET_DAYS)
+                    .then(|| held.iter().sum::<f32>() / held.len() as f32),
+                days: in_zone.len(),
+            }
+        })
+        .collect();
+
+    StrainRecovery {
+        correlation: pearson(&strain, &next),
+        pairs,
+        targets,
+    }
+}
+
+/// Pearson correlation coefficient of two equally long samples.
+pub fn pearson(xs: &[f32], ys: &[f32]) -> Option<f32> {
+    let n = xs.len().min(ys.len());
+    if n < 3 {
+        return None;
+    }
+    let mean = |v: &[f32]| v[..n].iter().map(|&x| f64::from(x)).sum::<f64>() / n as f64;
+    let (mx, my) = (mean(xs), mean(ys));
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (&x, &y) in xs.iter().zip(ys) {
+        let (dx, dy) = (f64::from(x) - mx, f64::from(y) - my);
+        sxy += dx * dy;
+        sxx += dx * dx;
+        syy += dy * dy;
+    }
+    (sxx > 0.0 && syy > 0.0).then(|| (sxy / (sxx * syy).sqrt()) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32, recovery: f32, strain: f32) -> DailySummary {
+        DailySummary {
+            date: NaiveDate::from_ymd_opt(2024, 3, d).unwrap(),
+            recovery_score: Some(recovery),
+            strain: Some(strain),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pairs_consecutive_days_only() {
+        let days = [
+            day(1, 70.0, 18.0),
+            day(2, 40.0, 8.0),
+            day(3, 75.0, 16.0),
+            day(4, 45.0, 10.0),
+            day(6, 80.0, 5.0),
+        ];
+        let result = strain_recovery_correlation(&days);
+
+        assert_eq!(result.pairs.len(), 3);
+        assert!(result.correlation.unwrap() < -0.9);
+        assert_eq!(result.targets[1].days, 1);
+    }
+}
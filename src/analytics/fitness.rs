@@ -0,0 +1,200 @@
+//! A coarse cardio fitness index, since WHOOP reports no VO2max.
+//!
+//! The index, from 0 to 100, blends three signals over a window of days:
+//! mean resting heart rate (lower is fitter), mean HRV on a log scale (higher
+//! is fitter) and strain tolerance, the mean strain of days the next recovery
+//! wasn't red. Each is mapped linearly from a typical untrained value to a
+//! typical well-trained one, then weighted 2:2:1.
+//!
+//! Treat it as a trend, not a measurement. All three signals move with sleep,
+//! illness, alcohol, heat and stress as much as with fitness, and the scale
+//! isn't calibrated against any lab test. With the wearer's max heart rate,
+//! [`CardioFitness::vo2max_estimate`] adds the Uth–Sørensen estimate
+//! `15.3 × max HR / resting HR`, which is within about 15% for most adults.
+
+use super::RecoveryZone;
+use crate::aggregate::{DailySummary, mean};
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FitnessOptions {
+    pub window_days: u32,
+    /// Days needed in the window with both resting heart rate and HRV.
+    pub min_days: usize,
+    /// From the body measurement, for the VO2max estimate.
+    pub max_heart_rate: Option<i32>,
+}
+
+impl Default for FitnessOptions {
+    fn default() -> Self {
+        Self {
+            window_days: 28,
+            min_days: 10,
+            max_heart_rate: None,
+        }
+    }
+}
+
+impl FitnessOptions {
+    pub fn with_window_days(mut self, window_days: u32) -> Self {
+        self.window_days = window_days;
+        self
+    }
+
+    pub fn with_min_days(mut self, min_days: usize) -> Self {
+        self.min_days = min_days;
+        self
+    }
+
+    pub fn with_max_heart_rate(mut self, max_heart_rate: i32) -> Self {
+        self.max_heart_rate = Some(max_heart_rate);
+        self
+    }
+}
+
+/// How far to trust a [`CardioFitness`], by how much of its window had data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CardioFitness {
+    /// The last day of the window.
+    pub date: NaiveDate,
+    pub index: f32,
+    pub resting_heart_rate: f32,
+    pub hrv_rmssd_milli: f32,
+    /// `None` with fewer than three strain and next-day recovery pairs.
+    pub strain_tolerance: Option<f32>,
+    pub vo2max_estimate: Option<f32>,
+    /// Change in the index from the window before, when that had enough data.
+    pub change: Option<f32>,
+    pub days_used: usize,
+    pub confidence: Confidence,
+}
+
+/// The index over the window ending on the last of `days`, or `None` with
+/// fewer than `min_days` usable days. Days scored while calibrating are left
+/// out. Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn cardio_fitness(days: &[DailySummary], options: FitnessOptions) -> Option<CardioFitness> {
+    let end = days.last()?.date;
+    let mut fitness = window(days, end, options)?;
+    let before = end.checked_sub_days(Days::new(options.window_days.into()))?;
+    fitness.change = window(days, before, options).map(|earlier| fitness.index - earlier.index);
+    Some(fitness)
+}
+
+fn window(days: &[DailySummary], end: NaiveDate, options: FitnessOptions) -> Option<CardioFitness> {
+    let start = end.checked_sub_days(Days::new(options.window_days.max(1).into()))?;
+    let days: Vec<&DailySummary> = days
+        .iter()
+        .filter(|d| d.date > start && d.date <= end && !d.user_calibrating)
+        .collect();
+    let pairs: Vec<(f32, f32)> = days
+        .iter()
+        .filter_map(|d| Some((d.resting_heart_rate?, d.hrv_rmssd_milli?)))
+        .filter(|(_, hrv)| *hrv > 0.0)
+        .collect();
+    if pairs.len() < options.min_days.max(1) {
+        return None;
+    }
+
+    let resting_heart_rate = mean(pairs.iter().map(|p| p.0))?;
+    let hrv_rmssd_milli = mean(pairs.iter().map(|p| p.1))?;
+    let strain_tolerance = strain_tolerance(&days);
+
+    let scale = |value: f32, untrained: f32, trained: f32| {
+        ((value - untrained) / (trained - untrained) * 100.0).clamp(0.0, 100.0)
+    };
+    let mut components = vec![
+        (scale(resting_heart_rate, 80.0, 40.0), 2.0),
+        (scale(hrv_rmssd_milli.ln(), 20f32.ln(), 120f32.ln()), 2.0),
+    ];
+    if let Some(tolerance) = strain_tolerance {
+        components.push((scale(tolerance, 6.0, 18.0), 1.0));
+    }
+    let weights: f32 = components.iter().map(|c| c.1).sum();
+    let index = components.iter().map(|(v, w)| v * w).sum::<f32>() / weights;
+
+    let coverage = pairs.len() as f32 / options.window_days.max(1) as f32;
+    let confidence = if coverage >= 0.8 && strain_tolerance.is_some() {
+        Confidence::High
+    } else if coverage >= 0.5 {
+        Confidence::Medium
+    } else {
+        Confidence::Low
+    };
+
+    Some(CardioFitness {
+        date: end,
+        index,
+        resting_heart_rate,
+        hrv_rmssd_milli,
+        strain_tolerance,
+        vo2max_estimate: options
+            .max_heart_rate
+            .map(|max| 15.3 * max as f32 / resting_heart_rate),
+        change: None,
+        days_used: pairs.len(),
+        confidence,
+    })
+}
+
+/// Mean strain of days whose next day's recovery wasn't red.
+fn strain_tolerance(days: &[&DailySummary]) -> Option<f32> {
+    let tolerated: Vec<f32> = days
+        .windows(2)
+        .filter(|pair| pair[0].date.succ_opt() == Some(pair[1].date))
+        .filter_map(|pair| Some((pair[0].strain?, pair[1].recovery_score?)))
+        .filter(|(_, recovery)| RecoveryZone::from_score(*recovery) != RecoveryZone::Red)
+        .map(|(strain, _)| strain)
+        .collect();
+    if tolerated.len() < 3 {
+        return None;
+    }
+    mean(tolerated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(count: u64, resting_heart_rate: f32, hrv: f32) -> Vec<DailySummary> {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        (0..count)
+            .map(|i| DailySummary {
+                date: start + Days::new(i),
+                resting_heart_rate: Some(resting_heart_rate),
+                hrv_rmssd_milli: Some(hrv),
+                recovery_score: Some(70.0),
+                strain: Some(12.0),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fitter_signals_score_higher() {
+        let options = FitnessOptions::default().with_max_heart_rate(190);
+        let fit = cardio_fitness(&days(28, 48.0, 90.0), options).unwrap();
+        let unfit = cardio_fitness(&days(28, 72.0, 30.0), options).unwrap();
+        assert!(fit.index > unfit.index);
+        assert_eq!(fit.confidence, Confidence::High);
+        assert_eq!(fit.strain_tolerance, Some(12.0));
+        assert!((fit.vo2max_estimate.unwrap() - 60.56).abs() < 0.01);
+        assert_eq!(fit.change, None);
+
+        assert!(cardio_fitness(&days(5, 48.0, 90.0), options).is_none());
+
+        // A month of falling resting heart rate.
+        let mut improving = days(28, 60.0, 60.0);
+        improving.extend(days(56, 52.0, 60.0).into_iter().skip(28));
+        let fitness = cardio_fitness(&improving, options).unwrap();
+        assert!(fitness.change.unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,131 @@
+//! Straight-line trends over a metric series, and where they're heading.
+
+use super::Point;
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrendMethod {
+    /// Ordinary least squares.
+    Linear,
+    /// Median of pairwise slopes; a few outlier days barely move it.
+    #[default]
+    TheilSen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TrendEstimate {
+    pub slope_per_day: f32,
+    /// The fitted value on `last_date`.
+    pub level: f32,
+    pub last_date: NaiveDate,
+    /// Share of the variance the line explains (R²), from 0 to 1.
+    pub confidence: f32,
+    pub points: usize,
+}
+
+impl TrendEstimate {
+    /// The fitted value `days` after the last point.
+    pub fn project(&self, days: i64) -> f32 {
+        self.level + self.slope_per_day * days as f32
+    }
+
+    pub fn projected_date(&self, days: u64) -> NaiveDate {
+        self.last_date + Days::new(days)
+    }
+
+    /// Weekly change as a percentage of the current level, e.g. "HRV up 3% per week".
+    pub fn percent_per_week(&self) -> Option<f32> {
+        (self.level != 0.0).then(|| self.slope_per_day * 7.0 / self.level.abs() * 100.0)
+    }
+}
+
+/// Fits a line through a series sorted by date. `None` with fewer than three
+/// points or when they all fall on the same day.
+pub fn trend(series: &[Point], method: TrendMethod) -> Option<TrendEstimate> {
+    let first = series.first()?.date;
+    let last = series.last()?.date;
+    if series.len() < 3 || first == last {
+        return None;
+    }
+    let xs: Vec<f64> = series
+        .iter()
+        .map(|p| (p.date - first).num_days() as f64)
+        .collect();
+    let ys: Vec<f64> = series.iter().map(|p| f64::from(p.value)).collect();
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+    let (mx, my) = (mean(&xs), mean(&ys));
+
+    let (slope, intercept) = match method {
+        TrendMethod::Linear => {
+            let sxy: f64 = xs.iter().zip(&ys).map(|(x, y)| (x - mx) * (y - my)).sum();
+            let sxx: f64 = xs.iter().map(|x| (x - mx).powi(2)).sum();
+            let slope = sxy / sxx;
+            (slope, my - slope * mx)
+        }
+        TrendMethod::TheilSen => {
+            let mut slopes = Vec::new();
+            for i in 0..xs.len() {
+                for j in i + 1..xs.len() {
+                    if xs[j] != xs[i] {
+                        slopes.push((ys[j] - ys[i]) / (xs[j] - xs[i]));
+                    }
+                }
+            }
+            let slope = median(&mut slopes);
+            let mut offsets: Vec<f64> = xs.iter().zip(&ys).map(|(x, y)| y - slope * x).collect();
+            (slope, median(&mut offsets))
+        }
+    };
+
+    let total: f64 = ys.iter().map(|y| (y - my).powi(2)).sum();
+    let residual: f64 = xs
+        .iter()
+        .zip(&ys)
+        .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+        .sum();
+    let confidence = if total > 0.0 {
+        (1.0 - residual / total).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+
+    Some(TrendEstimate {
+        slope_per_day: slope as f32,
+        level: (intercept + slope * xs[xs.len() - 1]) as f32,
+        last_date: last,
+        confidence: confidence as f32,
+        points: series.len(),
+    })
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theil_sen_ignores_an_outlier() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let series: Vec<Point> = (0..10)
+            .map(|i| Point {
+                date: start + Days::new(i),
+                value: if i == 5 { 200.0 } else { 50.0 + i as f32 },
+            })
+            .collect();
+
+        let estimate = trend(&series, TrendMethod::TheilSen).unwrap();
+        assert_eq!(estimate.slope_per_day, 1.0);
+        assert_eq!(estimate.project(7), 66.0);
+        assert!(trend(&series, TrendMethod::Linear).unwrap().slope_per_day != 1.0);
+    }
+}
@@ -0,0 +1,342 @@
+//! Daily goals, streaks and weekly attainment, and weekly targets for time
+//! in heart rate zones.
+
+use crate::aggregate::DailySummary;
+use crate::calendar::Calendar;
+use crate::models::{WorkoutV2, ZoneDurations};
+use chrono::{Days, NaiveDate, Weekday};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A condition a day either meets or doesn't.
+pub trait Goal {
+    /// `None` when the day has no data to judge it by.
+    fn is_met(&self, day: &DailySummary) -> Option<bool>;
+}
+
+/// Enough sleep by time asleep, sleep performance, or both.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SleepGoal {
+    pub min_sleep_milli: Option<i64>,
+    pub min_performance: Option<f32>,
+}
+
+impl SleepGoal {
+    pub fn hours(hours: f32) -> Self {
+        Self {
+            min_sleep_milli: Some((hours * 3_600_000.0) as i64),
+            min_performance: None,
+        }
+    }
+
+    pub fn performance(percentage: f32) -> Self {
+        Self {
+            min_sleep_milli: None,
+            min_performance: Some(percentage),
+        }
+    }
+
+    pub fn with_hours(mut self, hours: f32) -> Self {
+        self.min_sleep_milli = Some((hours * 3_600_000.0) as i64);
+        self
+    }
+
+    pub fn with_performance(mut self, percentage: f32) -> Self {
+        self.min_performance = Some(percentage);
+        self
+    }
+}
+
+impl Goal for SleepGoal {
+    fn is_met(&self, day: &DailySummary) -> Option<bool> {
+        let mut met = true;
+        if let Some(min) = self.min_sleep_milli {
+            met &= day.sleep_milli? >= min;
+        }
+        if let Some(min) = self.min_performance {
+            met &= day.sleep_performance_percentage? >= min;
+        }
+        Some(met)
+    }
+}
+
+/// Day strain within a range, e.g. at least 10 or between 10 and 14.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrainGoal {
+    pub min: f32,
+    pub max: Option<f32>,
+}
+
+impl StrainGoal {
+    pub fn at_least(min: f32) -> Self {
+        Self { min, max: None }
+    }
+
+    pub fn between(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+}
+
+impl Goal for StrainGoal {
+    fn is_met(&self, day: &DailySummary) -> Option<bool> {
+        let strain = day.strain?;
+        Some(strain >= self.min && self.max.is_none_or(|max| strain <= max))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryGoal {
+    pub min: f32,
+}
+
+impl RecoveryGoal {
+    pub fn at_least(min: f32) -> Self {
+        Self { min }
+    }
+}
+
+impl Goal for RecoveryGoal {
+    fn is_met(&self, day: &DailySummary) -> Option<bool> {
+        Some(day.recovery_score? >= self.min)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WeeklyAttainment {
+    /// The Monday the week starts on.
+    pub week_start: NaiveDate,
+    pub days_met: usize,
+    /// Days with data to judge the goal by.
+    pub days_judged: usize,
+    pub rate: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Streaks {
+    /// Consecutive days met up to and including the last day given.
+    pub current: usize,
+    pub longest: usize,
+    /// The last day of the longest streak.
+    pub longest_end: Option<NaiveDate>,
+    pub weekly: Vec<WeeklyAttainment>,
+}
+
+/// Streaks of consecutive calendar days meeting `goal`. A missing day, or one
+/// without data, ends a streak just like a missed goal does.
+/// Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn streaks(days: &[DailySummary], goal: &impl Goal) -> Streaks {
+    let mut streaks = Streaks::default();
+    let mut weeks: BTreeMap<NaiveDate, (usize, usize)> = BTreeMap::new();
+    let mut previous: Option<NaiveDate> = None;
+
+    for day in days {
+        let met = goal.is_met(day);
+        if previous.and_then(|p| p.succ_opt()) != Some(day.date) {
+            streaks.current = 0;
+        }
+        previous = Some(day.date);
+
+        if met == Some(true) {
+            streaks.current += 1;
+            if streaks.current > streaks.longest {
+                streaks.longest = streaks.current;
+                streaks.longest_end = Some(day.date);
+            }
+        } else {
+            streaks.current = 0;
+        }
+
+        if let Some(met) = met {
+            let week = weeks
+                .entry(day.date.week(Weekday::Mon).first_day())
+                .or_default();
+            week.0 += usize::from(met);
+            week.1 += 1;
+        }
+    }
+
+    streaks.weekly = weeks
+        .into_iter()
+        .map(|(week_start, (days_met, days_judged))| WeeklyAttainment {
+            week_start,
+            days_met,
+            days_judged,
+            rate: days_met as f32 / days_judged as f32,
+        })
+        .collect();
+    streaks
+}
+
+/// Weekly time in a range of heart rate zones, e.g. 150 minutes in zone two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ZoneTarget {
+    pub min_zone: u8,
+    /// Inclusive.
+    pub max_zone: u8,
+    pub target_milli: i64,
+}
+
+impl ZoneTarget {
+    /// `minutes` a week in zones `min_zone` to `max_zone`.
+    pub fn new(min_zone: u8, max_zone: u8, minutes: u32) -> Self {
+        Self {
+            min_zone,
+            max_zone,
+            target_milli: i64::from(minutes) * 60_000,
+        }
+    }
+
+    /// `minutes` a week in `zone` alone.
+    pub fn zone(zone: u8, minutes: u32) -> Self {
+        Self::new(zone, zone, minutes)
+    }
+
+    /// `minutes` a week in `zone` or above.
+    pub fn at_least(zone: u8, minutes: u32) -> Self {
+        Self::new(zone, 5, minutes)
+    }
+
+    /// Time in the target's zones.
+    pub fn milli_in(&self, zones: &ZoneDurations) -> i64 {
+        zones
+            .as_array()
+            .iter()
+            .zip(0u8..)
+            .filter(|(_, zone)| (self.min_zone..=self.max_zone).contains(zone))
+            .map(|(milli, _)| milli)
+            .sum()
+    }
+}
+
+/// A week's progress towards a [`ZoneTarget`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ZoneProgress {
+    /// The Monday the week starts on.
+    pub week_start: NaiveDate,
+    pub target: ZoneTarget,
+    pub milli: i64,
+    /// Of the target, as a percentage; may pass 100.
+    pub percent: f32,
+    pub met: bool,
+    /// The week's total if the pace so far keeps up to Sunday. The same as
+    /// `milli` for weeks already over.
+    pub projected_milli: i64,
+    /// How far `projected_milli` falls short of the target, or zero.
+    pub projected_shortfall_milli: i64,
+}
+
+/// Progress towards each of `targets` for every week from the first workout's
+/// up to the one containing `today`, oldest first, across all workouts.
+pub fn zone_targets(
+    workouts: &[WorkoutV2],
+    targets: &[ZoneTarget],
+    today: NaiveDate,
+) -> Vec<ZoneProgress> {
+    let calendar = Calendar::default();
+    let week_of = |date: NaiveDate| date.week(Weekday::Mon).first_day();
+    let mut weeks: BTreeMap<NaiveDate, ZoneDurations> = BTreeMap::new();
+    for workout in workouts {
+        if let Some(score) = &workout.score {
+            weeks
+                .entry(week_of(calendar.workout_day(workout)))
+                .or_default()
+                .add(&score.zone_durations);
+        }
+    }
+    let Some(&first) = weeks.keys().next() else {
+        return Vec::new();
+    };
+    let current = week_of(today);
+
+    let mut progress = Vec::new();
+    let mut week_start = first;
+    while week_start <= current {
+        let zones = weeks.get(&week_start).cloned().unwrap_or_default();
+        for target in targets {
+            let milli = target.milli_in(&zones);
+            let projected_milli = if week_start == current {
+                let days_in = (today - week_start).num_days() + 1;
+                milli * 7 / days_in
+            } else {
+                milli
+            };
+            progress.push(ZoneProgress {
+                week_start,
+                target: *target,
+                milli,
+                percent: milli as f32 / target.target_milli.max(1) as f32 * 100.0,
+                met: milli >= target.target_milli,
+                projected_milli,
+                projected_shortfall_milli: (target.target_milli - projected_milli).max(0),
+            });
+        }
+        week_start = week_start + Days::new(7);
+    }
+    progress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gaps_and_misses_end_streaks() {
+        let day = |d: u32, recovery: f32| DailySummary {
+            date: NaiveDate::from_ymd_opt(2024, 3, d).unwrap(),
+            recovery_score: Some(recovery),
+            ..Default::default()
+        };
+        let days = [
+            day(1, 70.0),
+            day(2, 80.0),
+            day(3, 75.0),
+            day(4, 20.0),
+            day(5, 90.0),
+            day(7, 90.0),
+        ];
+
+        let streaks = streaks(&days, &RecoveryGoal::at_least(67.0));
+        assert_eq!(streaks.current, 1);
+        assert_eq!(streaks.longest, 3);
+        assert_eq!(streaks.longest_end, days[2].date.into());
+        assert_eq!(streaks.weekly[0].days_met, 3);
+    }
+
+    #[test]
+    fn test_zone_targets_project_the_current_week() {
+        let workout = |date: &str, zone_two: i64, zone_four: i64| {
+            let mut workout = crate::fixtures::workout_scored();
+            workout.timezone_offset = "+00:00".to_string();
+            workout.start = format!("{}T12:00:00Z", date).parse().unwrap();
+            let score = workout.score.as_mut().unwrap();
+            score.zone_durations = ZoneDurations {
+                zone_two_milli: zone_two * 60_000,
+                zone_four_milli: zone_four * 60_000,
+                ..Default::default()
+            };
+            workout
+        };
+        // Mondays 2024-03-04, 03-11 and 03-18.
+        let workouts = [
+            workout("2024-03-05", 100, 10),
+            workout("2024-03-07", 60, 25),
+            workout("2024-03-19", 40, 0),
+        ];
+        let targets = [ZoneTarget::zone(2, 150), ZoneTarget::at_least(4, 30)];
+        let today = NaiveDate::from_ymd_opt(2024, 3, 21).unwrap();
+
+        let progress = zone_targets(&workouts, &targets, today);
+        assert_eq!(progress.len(), 6);
+        assert!(progress[0].met && progress[1].met);
+        assert_eq!(progress[2].milli, 0);
+        assert_eq!(progress[3].projected_shortfall_milli, 30 * 60_000);
+
+        // 40 minutes by Thursday is on pace for 70.
+        let zone_two = progress[4];
+        assert_eq!(zone_two.projected_milli, 70 * 60_000);
+        assert_eq!(zone_two.projected_shortfall_milli, 80 * 60_000);
+    }
+}
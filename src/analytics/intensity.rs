@@ -0,0 +1,114 @@
+//! How hard a workout was, from the heart rate zones in its score.
+//!
+//! Durations are the time spent across all zones, so they leave out any part
+//! of the workout the strap didn't record. TRIMP is Edwards' training impulse:
+//! minutes in each zone weighted by the zone's number, zone zero counting for
+//! nothing. WHOOP's zones one to five are Edwards' bands of 50–100% of max
+//! heart rate.
+
+use super::Point;
+use crate::calendar::Calendar;
+use crate::models::{WorkoutScore, WorkoutV2, ZoneDurations};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const HOUR_MILLI: f32 = 3_600_000.0;
+const MINUTE_MILLI: f32 = 60_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WorkoutIntensity {
+    /// Time in any zone.
+    pub duration_milli: i64,
+    /// `None` for a workout with no time in any zone, as are the other ratios.
+    pub strain_per_hour: Option<f32>,
+    /// The time-weighted mean zone, from 0 to 5.
+    pub zone_index: Option<f32>,
+    /// Share of the duration in zones four and five, as a percentage.
+    pub percent_above_zone_three: Option<f32>,
+    pub trimp: f32,
+}
+
+impl WorkoutScore {
+    pub fn intensity(&self) -> WorkoutIntensity {
+        let zones = self.zone_durations.as_array();
+        let duration_milli = self.zone_durations.total_milli();
+        let per_duration = |value: f32| (duration_milli > 0).then(|| value / duration_milli as f32);
+        let weighted: i64 = zones
+            .iter()
+            .zip(0..)
+            .map(|(milli, zone)| milli * zone)
+            .sum();
+        WorkoutIntensity {
+            duration_milli,
+            strain_per_hour: per_duration(self.strain * HOUR_MILLI),
+            zone_index: per_duration(weighted as f32),
+            percent_above_zone_three: per_duration((zones[4] + zones[5]) as f32 * 100.0),
+            trimp: trimp(&self.zone_durations),
+        }
+    }
+}
+
+/// Edwards' TRIMP for time spent in `zones`.
+pub fn trimp(zones: &ZoneDurations) -> f32 {
+    zones
+        .as_array()
+        .iter()
+        .zip(0..)
+        .map(|(milli, zone)| *milli as f32 / MINUTE_MILLI * zone as f32)
+        .sum()
+}
+
+/// TRIMP summed per workout day, oldest first, with zero for the days in
+/// between without a scored workout.
+pub fn daily_trimp(workouts: &[WorkoutV2]) -> Vec<Point> {
+    let calendar = Calendar::default();
+    let mut days: BTreeMap<_, f32> = BTreeMap::new();
+    for workout in workouts {
+        if let Some(score) = &workout.score {
+            *days.entry(calendar.workout_day(workout)).or_default() += trimp(&score.zone_durations);
+        }
+    }
+    let (Some(&first), Some(&last)) = (days.keys().next(), days.keys().next_back()) else {
+        return Vec::new();
+    };
+    first
+        .iter_days()
+        .take_while(|date| *date <= last)
+        .map(|date| Point {
+            date,
+            value: days.get(&date).copied().unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_intensity_from_zone_durations() {
+        let mut workout = fixtures::workout_scored();
+        let score = workout.score.as_mut().unwrap();
+        score.strain = 12.0;
+        score.zone_durations = ZoneDurations {
+            zone_two_milli: 30 * 60_000,
+            zone_four_milli: 20 * 60_000,
+            zone_five_milli: 10 * 60_000,
+            ..Default::default()
+        };
+
+        let intensity = score.intensity();
+        assert_eq!(intensity.duration_milli, 3_600_000);
+        assert_eq!(intensity.strain_per_hour, Some(12.0));
+        assert_eq!(intensity.zone_index, Some(190.0 / 60.0));
+        assert_eq!(intensity.percent_above_zone_three, Some(50.0));
+        assert_eq!(intensity.trimp, 30.0 * 2.0 + 20.0 * 4.0 + 10.0 * 5.0);
+
+        let mut later = workout.clone();
+        later.start += chrono::Duration::days(2);
+        let days = daily_trimp(&[workout, later]);
+        let values: Vec<f32> = days.iter().map(|d| d.value).collect();
+        assert_eq!(values, [190.0, 0.0, 190.0]);
+    }
+}
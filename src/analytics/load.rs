@@ -0,0 +1,146 @@
+//! Acute:chronic workload ratio (ACWR) from daily strain.
+//!
+//! Acute and chronic load are the mean daily strain over a short and a long
+//! window ending on the same day. A ratio well above one means training has
+//! ramped up faster than the body has had time to adapt to.
+//!
+//! [`trimp_acwr`] measures load as workout TRIMP instead, which counts only
+//! training and not the strain of the rest of the day.
+
+use super::Point;
+use super::intensity::daily_trimp;
+use super::trends::{Metric, rolling_average};
+use crate::aggregate::DailySummary;
+use crate::models::WorkoutV2;
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkloadOptions {
+    pub acute_days: u32,
+    pub chronic_days: u32,
+    /// Ratios below this are [`RiskBand::Undertraining`].
+    pub low: f32,
+    /// Ratios above this are [`RiskBand::Elevated`].
+    pub high: f32,
+    /// Ratios above this are [`RiskBand::High`].
+    pub very_high: f32,
+}
+
+impl Default for WorkloadOptions {
+    /// 7 days against 28, with the commonly cited 0.8–1.3 sweet spot.
+    fn default() -> Self {
+        Self {
+            acute_days: 7,
+            chronic_days: 28,
+            low: 0.8,
+            high: 1.3,
+            very_high: 1.5,
+        }
+    }
+}
+
+impl WorkloadOptions {
+    pub fn with_windows(mut self, acute_days: u32, chronic_days: u32) -> Self {
+        self.acute_days = acute_days;
+        self.chronic_days = chronic_days;
+        self
+    }
+
+    pub fn with_bands(mut self, low: f32, high: f32, very_high: f32) -> Self {
+        self.low = low;
+        self.high = high;
+        self.very_high = very_high;
+        self
+    }
+
+    pub fn band(&self, ratio: f32) -> RiskBand {
+        if ratio > self.very_high {
+            RiskBand::High
+        } else if ratio > self.high {
+            RiskBand::Elevated
+        } else if ratio < self.low {
+            RiskBand::Undertraining
+        } else {
+            RiskBand::Optimal
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskBand {
+    Undertraining,
+    Optimal,
+    Elevated,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WorkloadRatio {
+    pub date: NaiveDate,
+    pub acute: f32,
+    pub chronic: f32,
+    /// `None` when the chronic load is zero.
+    pub ratio: Option<f32>,
+    pub band: Option<RiskBand>,
+}
+
+/// One ratio per day with strain, starting once a full chronic window has elapsed.
+/// Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn acwr(days: &[DailySummary], options: WorkloadOptions) -> Vec<WorkloadRatio> {
+    ratios(&Metric::Strain.series(days), options)
+}
+
+/// Like [`acwr`], with each day's load the summed TRIMP of its workouts.
+/// Rest days between workouts count as zero load.
+pub fn trimp_acwr(workouts: &[WorkoutV2], options: WorkloadOptions) -> Vec<WorkloadRatio> {
+    ratios(&daily_trimp(workouts), options)
+}
+
+fn ratios(series: &[Point], options: WorkloadOptions) -> Vec<WorkloadRatio> {
+    let Some(first) = series.first() else {
+        return Vec::new();
+    };
+    let ready = first.date + Days::new(u64::from(options.chronic_days.max(1)) - 1);
+    let acute = rolling_average(series, options.acute_days);
+    let chronic = rolling_average(series, options.chronic_days);
+
+    acute
+        .iter()
+        .zip(&chronic)
+        .filter(|(a, _)| a.date >= ready)
+        .map(|(acute, chronic)| {
+            let ratio = (chronic.average > 0.0).then(|| acute.average / chronic.average);
+            WorkloadRatio {
+                date: acute.date,
+                acute: acute.average,
+                chronic: chronic.average,
+                ratio,
+                band: ratio.map(|r| options.band(r)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramp_up_is_elevated() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let days: Vec<DailySummary> = (0..28)
+            .map(|i| DailySummary {
+                date: start + Days::new(i),
+                strain: Some(if i < 21 { 8.0 } else { 14.0 }),
+                ..Default::default()
+            })
+            .collect();
+
+        let ratios = acwr(&days, WorkloadOptions::default());
+        assert_eq!(ratios.len(), 1);
+        assert_eq!(ratios[0].acute, 14.0);
+        assert_eq!(ratios[0].band, Some(RiskBand::Elevated));
+    }
+}
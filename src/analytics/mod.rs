@@ -0,0 +1,117 @@
+//! Derived metrics and insights computed from API records.
+//!
+//! Everything here is a pure function over records you already have, so results
+//! are the same whether the records came from the API or a local store. Days are
+//! always the wearer's local days, taken from each record's `timezone_offset`.
+//!
+//! The metrics themselves need the `analytics` feature, on by default; the
+//! shared types below are always available.
+
+#[cfg(feature = "analytics")]
+pub mod anomaly;
+#[cfg(feature = "analytics")]
+pub mod baseline;
+#[cfg(feature = "analytics")]
+pub mod calibration;
+#[cfg(feature = "analytics")]
+pub mod correlation;
+#[cfg(feature = "analytics")]
+pub mod fitness;
+#[cfg(feature = "analytics")]
+pub mod forecast;
+#[cfg(feature = "analytics")]
+pub mod goals;
+#[cfg(feature = "analytics")]
+pub mod intensity;
+#[cfg(feature = "analytics")]
+pub mod load;
+#[cfg(feature = "analytics")]
+pub mod quality;
+#[cfg(feature = "analytics")]
+pub mod respiratory;
+#[cfg(feature = "analytics")]
+pub mod sleep;
+#[cfg(feature = "analytics")]
+pub mod spo2;
+#[cfg(feature = "analytics")]
+pub mod temperature;
+#[cfg(feature = "analytics")]
+pub mod trends;
+
+#[cfg(feature = "analytics")]
+pub use anomaly::{Anomaly, anomalies};
+#[cfg(feature = "analytics")]
+pub use baseline::{BaselineDeviation, BaselineOptions, Deviation, baseline, hrv_baseline};
+#[cfg(feature = "analytics")]
+pub use calibration::{Calibration, calibrated_days, calibrated_recoveries, calibration_end};
+#[cfg(feature = "analytics")]
+pub use correlation::{LaggedPair, StrainRecovery, StrainTarget, strain_recovery_correlation};
+#[cfg(feature = "analytics")]
+pub use fitness::{CardioFitness, Confidence, FitnessOptions, cardio_fitness};
+#[cfg(feature = "analytics")]
+pub use forecast::{TrendEstimate, TrendMethod, trend};
+#[cfg(feature = "analytics")]
+pub use goals::{
+    Goal, RecoveryGoal, SleepGoal, StrainGoal, Streaks, WeeklyAttainment, ZoneProgress, ZoneTarget,
+    streaks, zone_targets,
+};
+#[cfg(feature = "analytics")]
+pub use intensity::{WorkoutIntensity, daily_trimp, trimp};
+#[cfg(feature = "analytics")]
+pub use load::{RiskBand, WorkloadOptions, WorkloadRatio, acwr, trimp_acwr};
+#[cfg(feature = "analytics")]
+pub use quality::{QualityOptions, QualityReport, data_quality};
+#[cfg(feature = "analytics")]
+pub use respiratory::{RespiratoryFlag, RespiratoryMonitor, RespiratoryOptions, respiratory_flags};
+#[cfg(feature = "analytics")]
+pub use sleep::{
+    Chronotype, Circadian, DailySleep, SleepConsistency, SleepDebtPoint, SleepMidpoint, SleepNeed,
+    WeeklyMidpoint, circadian, daily_sleep, sleep_consistency, sleep_debt, sleep_midpoints,
+};
+#[cfg(feature = "analytics")]
+pub use spo2::{Spo2Distribution, Spo2Flag, Spo2Issue, Spo2Options, Spo2Report, spo2};
+#[cfg(feature = "analytics")]
+pub use temperature::{
+    Elevation, SkinTemperature, TemperatureNight, TemperatureOptions, skin_temperature,
+};
+#[cfg(feature = "analytics")]
+pub use trends::{Metric, Rolling, RollingPoint, rolling};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use serde::Serialize;
+
+/// A dated value, the element of every series analytics produces.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Point {
+    pub date: NaiveDate,
+    pub value: f32,
+}
+
+/// WHOOP's recovery bands: red below 34%, yellow below 67%, green above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryZone {
+    Red,
+    Yellow,
+    Green,
+}
+
+impl RecoveryZone {
+    pub fn from_score(score: f32) -> Self {
+        if score >= 67.0 {
+            RecoveryZone::Green
+        } else if score >= 34.0 {
+            RecoveryZone::Yellow
+        } else {
+            RecoveryZone::Red
+        }
+    }
+}
+
+/// A record's time in the wearer's local offset, or UTC if the offset doesn't parse.
+pub(crate) fn to_local(time: DateTime<Utc>, offset: &str) -> DateTime<FixedOffset> {
+    let offset = offset
+        .parse::<FixedOffset>()
+        .unwrap_or_else(|_| FixedOffset::east_opt(0).unwrap());
+    time.with_timezone(&offset)
+}
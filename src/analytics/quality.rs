@@ -0,0 +1,269 @@
+//! Coverage and data quality for a date range.
+//!
+//! Run this before trusting an aggregate: a weekly average over four scored
+//! days out of seven says something different from one over seven.
+
+use crate::calendar::Calendar;
+use crate::models::*;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityOptions {
+    /// Workouts recorded below this percentage are reported.
+    pub min_percent_recorded: f32,
+    /// Time between one cycle ending and the next starting that counts as a gap.
+    pub max_gap: Duration,
+}
+
+impl Default for QualityOptions {
+    fn default() -> Self {
+        Self {
+            min_percent_recorded: 80.0,
+            max_gap: Duration::hours(4),
+        }
+    }
+}
+
+impl QualityOptions {
+    pub fn with_min_percent_recorded(mut self, percentage: f32) -> Self {
+        self.min_percent_recorded = percentage;
+        self
+    }
+
+    pub fn with_max_gap(mut self, max_gap: Duration) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Cycle,
+    Recovery,
+    Sleep,
+    Workout,
+}
+
+/// A record that isn't scored (yet).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnscoredRecord {
+    pub kind: RecordKind,
+    /// The cycle id, or the UUID of sleeps and workouts.
+    pub id: String,
+    pub date: NaiveDate,
+    pub pending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartialWorkout {
+    pub id: uuid::Uuid,
+    pub date: NaiveDate,
+    pub percent_recorded: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Gap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QualityReport {
+    pub days: usize,
+    /// Days in the range no cycle started on.
+    pub missing_cycle_days: Vec<NaiveDate>,
+    pub unscored: Vec<UnscoredRecord>,
+    pub partial_workouts: Vec<PartialWorkout>,
+    pub gaps: Vec<Gap>,
+    /// Share of days with a scored cycle, from 0 to 1.
+    pub coverage: f32,
+}
+
+impl QualityReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing_cycle_days.is_empty()
+            && self.unscored.is_empty()
+            && self.partial_workouts.is_empty()
+            && self.gaps.is_empty()
+    }
+}
+
+/// Checks the records for the local days `start..=end`. Records outside the range
+/// are ignored, so it's fine to pass everything you have.
+pub fn data_quality(
+    start: NaiveDate,
+    end: NaiveDate,
+    cycles: &[Cycle],
+    recoveries: &[Recovery],
+    sleeps: &[Sleep],
+    workouts: &[WorkoutV2],
+    options: QualityOptions,
+) -> QualityReport {
+    let in_range = |date: NaiveDate| start <= date && date <= end;
+    let calendar = Calendar::default();
+    let mut report = QualityReport {
+        days: start.iter_days().take_while(|d| *d <= end).count(),
+        ..Default::default()
+    };
+    let mut unscored_records = Vec::new();
+    let mut unscored = |kind, id: String, date, state: &ScoreState| {
+        if !matches!(state, ScoreState::Scored) {
+            unscored_records.push(UnscoredRecord {
+                kind,
+                id,
+                date,
+                pending: matches!(state, ScoreState::PendingScore),
+            });
+        }
+    };
+
+    let mut cycles: Vec<(&Cycle, NaiveDate)> = cycles
+        .iter()
+        .map(|c| (c, calendar.cycle_day(c)))
+        .filter(|(_, date)| in_range(*date))
+        .collect();
+    cycles.sort_by_key(|(c, _)| c.start);
+    let mut cycle_dates = BTreeSet::new();
+    let mut scored_dates = BTreeSet::new();
+    for (cycle, date) in &cycles {
+        cycle_dates.insert(*date);
+        if cycle.score.is_some() {
+            scored_dates.insert(*date);
+        }
+        unscored(
+            RecordKind::Cycle,
+            cycle.id.to_string(),
+            *date,
+            &cycle.score_state,
+        );
+    }
+
+    for recovery in recoveries {
+        let date = cycles
+            .iter()
+            .find(|(c, _)| c.id == recovery.cycle_id)
+            .map(|(_, date)| *date)
+            .unwrap_or_else(|| recovery.created_at.date_naive());
+        if in_range(date) {
+            unscored(
+                RecordKind::Recovery,
+                recovery.cycle_id.to_string(),
+                date,
+                &recovery.score_state,
+            );
+        }
+    }
+    for sleep in sleeps {
+        let date = calendar.sleep_day(sleep);
+        if in_range(date) {
+            unscored(
+                RecordKind::Sleep,
+                sleep.id.to_string(),
+                date,
+                &sleep.score_state,
+            );
+        }
+    }
+    for workout in workouts {
+        let date = calendar.workout_day(workout);
+        if !in_range(date) {
+            continue;
+        }
+        unscored(
+            RecordKind::Workout,
+            workout.id.to_string(),
+            date,
+            &workout.score_state,
+        );
+        let Some(score) = &workout.score else {
+            continue;
+        };
+        if score.percent_recorded < options.min_percent_recorded {
+            report.partial_workouts.push(PartialWorkout {
+                id: workout.id,
+                date,
+                percent_recorded: score.percent_recorded,
+            });
+        }
+    }
+
+    report.unscored = unscored_records;
+    report.missing_cycle_days = start
+        .iter_days()
+        .take_while(|d| *d <= end)
+        .filter(|d| !cycle_dates.contains(d))
+        .collect();
+    report.gaps = cycles
+        .windows(2)
+        .filter_map(|pair| {
+            let gap = Gap {
+                start: pair[0].0.end?,
+                end: pair[1].0.start,
+            };
+            (gap.end - gap.start > options.max_gap).then_some(gap)
+        })
+        .collect();
+    report.coverage = scored_dates.len() as f32 / report.days.max(1) as f32;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(id: i64, start: &str, hours: i64, scored: bool) -> Cycle {
+        let start: DateTime<Utc> = start.parse().unwrap();
+        Cycle {
+            id,
+            user_id: 10129,
+            created_at: start,
+            updated_at: start,
+            start,
+            end: Some(start + Duration::hours(hours)),
+            timezone_offset: "+00:00".to_string(),
+            score_state: if scored {
+                ScoreState::Scored
+            } else {
+                ScoreState::PendingScore
+            },
+            score: scored.then_some(CycleScore {
+                strain: 10.0,
+                kilojoule: 8000.0,
+                average_heart_rate: 60,
+                max_heart_rate: 150,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_reports_missing_days_pending_records_and_gaps() {
+        let cycles = [
+            cycle(1, "2024-03-01T07:00:00Z", 24, true),
+            cycle(2, "2024-03-02T07:00:00Z", 16, false),
+            cycle(3, "2024-03-04T07:00:00Z", 24, true),
+        ];
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+
+        let report = data_quality(
+            start,
+            end,
+            &cycles,
+            &[],
+            &[],
+            &[],
+            QualityOptions::default(),
+        );
+        assert_eq!(report.days, 4);
+        assert_eq!(
+            report.missing_cycle_days,
+            [NaiveDate::from_ymd_opt(2024, 3, 3).unwrap()]
+        );
+        assert!(report.unscored[0].pending);
+        assert_eq!(report.gaps.len(), 1);
+        assert_eq!(report.coverage, 0.5);
+    }
+}
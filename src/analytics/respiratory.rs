@@ -0,0 +1,194 @@
+//! Nights of raised respiratory rate, often the first sign of getting ill.
+//!
+//! A [`RespiratoryMonitor`] keeps an exponentially weighted baseline of the
+//! respiratory rate of main sleeps and flags a night above a fixed rate or
+//! too far above the baseline. It takes nights one at a time, so a service
+//! can keep one per user and feed it each sleep as it's scored. Flagged nights
+//! stay out of the baseline, so a few sick nights don't raise the bar for the
+//! next.
+
+use crate::calendar::Calendar;
+use crate::models::Sleep;
+use chrono::NaiveDate;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RespiratoryOptions {
+    /// EWMA span in nights.
+    pub span_nights: u32,
+    /// Nights averaged into the first baseline; nothing is compared to the
+    /// baseline before them.
+    pub min_nights: usize,
+    /// Breaths per minute above which a night is flagged, baseline or not.
+    pub absolute: Option<f32>,
+    /// Percent above the baseline at which a night is flagged.
+    pub relative_percent: Option<f32>,
+}
+
+impl Default for RespiratoryOptions {
+    fn default() -> Self {
+        Self {
+            span_nights: 30,
+            min_nights: 7,
+            absolute: None,
+            relative_percent: Some(7.0),
+        }
+    }
+}
+
+impl RespiratoryOptions {
+    pub fn with_span_nights(mut self, span_nights: u32) -> Self {
+        self.span_nights = span_nights;
+        self
+    }
+
+    pub fn with_min_nights(mut self, min_nights: usize) -> Self {
+        self.min_nights = min_nights;
+        self
+    }
+
+    pub fn with_absolute(mut self, breaths_per_minute: f32) -> Self {
+        self.absolute = Some(breaths_per_minute);
+        self
+    }
+
+    pub fn with_relative_percent(mut self, percent: f32) -> Self {
+        self.relative_percent = Some(percent);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Threshold {
+    Absolute,
+    Relative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RespiratoryFlag {
+    /// The day the night was slept for.
+    pub date: NaiveDate,
+    pub sleep_id: Uuid,
+    pub respiratory_rate: f32,
+    /// `None` while the monitor is still warming up.
+    pub baseline: Option<f32>,
+    /// The threshold crossed; relative wins if both were.
+    pub threshold: Threshold,
+}
+
+#[derive(Debug, Clone)]
+pub struct RespiratoryMonitor {
+    options: RespiratoryOptions,
+    warm_up: Vec<f32>,
+    baseline: Option<f32>,
+}
+
+impl RespiratoryMonitor {
+    pub fn new(options: RespiratoryOptions) -> Self {
+        Self {
+            options,
+            warm_up: Vec::new(),
+            baseline: None,
+        }
+    }
+
+    /// The current baseline, once warmed up.
+    pub fn baseline(&self) -> Option<f32> {
+        self.baseline
+    }
+
+    /// Checks `sleep` and folds it into the baseline unless flagged. Naps and
+    /// nights without a respiratory rate are skipped.
+    pub fn observe(&mut self, sleep: &Sleep) -> Option<RespiratoryFlag> {
+        if sleep.nap {
+            return None;
+        }
+        let rate = sleep.score.as_ref()?.respiratory_rate?;
+
+        let relative = self
+            .baseline
+            .zip(self.options.relative_percent)
+            .is_some_and(|(baseline, percent)| rate > baseline * (1.0 + percent / 100.0));
+        let absolute = self.options.absolute.is_some_and(|max| rate > max);
+        let threshold = if relative {
+            Threshold::Relative
+        } else if absolute {
+            Threshold::Absolute
+        } else {
+            self.learn(rate);
+            return None;
+        };
+        Some(RespiratoryFlag {
+            date: Calendar::default().sleep_day(sleep),
+            sleep_id: sleep.id,
+            respiratory_rate: rate,
+            baseline: self.baseline,
+            threshold,
+        })
+    }
+
+    fn learn(&mut self, rate: f32) {
+        match &mut self.baseline {
+            Some(baseline) => {
+                let alpha = 2.0 / (self.options.span_nights.max(1) as f32 + 1.0);
+                *baseline += alpha * (rate - *baseline);
+            }
+            None => {
+                self.warm_up.push(rate);
+                if self.warm_up.len() >= self.options.min_nights.max(1) {
+                    let nights = std::mem::take(&mut self.warm_up);
+                    self.baseline = Some(nights.iter().sum::<f32>() / nights.len() as f32);
+                }
+            }
+        }
+    }
+}
+
+/// Every flagged night in `sleeps`, oldest first.
+pub fn respiratory_flags(sleeps: &[Sleep], options: RespiratoryOptions) -> Vec<RespiratoryFlag> {
+    let mut sleeps: Vec<&Sleep> = sleeps.iter().collect();
+    sleeps.sort_by_key(|s| s.end);
+    let mut monitor = RespiratoryMonitor::new(options);
+    sleeps
+        .into_iter()
+        .filter_map(|s| monitor.observe(s))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_flags_nights_above_the_baseline() {
+        let nights: Vec<Sleep> = [15.0, 15.2, 14.8, 15.0, 15.1, 14.9, 15.0, 16.5, 15.0, 21.0]
+            .into_iter()
+            .zip(0u8..)
+            .map(|(rate, i)| {
+                let mut sleep = fixtures::sleep_scored();
+                sleep.id = Uuid::from_u128(i.into());
+                sleep.end += chrono::Duration::days(i.into());
+                sleep.score.as_mut().unwrap().respiratory_rate = Some(rate);
+                sleep
+            })
+            .collect();
+
+        let flags = respiratory_flags(&nights, RespiratoryOptions::default());
+        assert_eq!(flags.len(), 2);
+        assert_eq!(flags[0].sleep_id, Uuid::from_u128(7));
+        assert_eq!(flags[0].threshold, Threshold::Relative);
+        assert!((flags[0].baseline.unwrap() - 15.0).abs() < 0.01);
+
+        // The warm-up nights are only checked against the absolute limit.
+        let options = RespiratoryOptions::default()
+            .with_min_nights(20)
+            .with_absolute(20.0);
+        let flags = respiratory_flags(&nights, options);
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].threshold, Threshold::Absolute);
+        assert_eq!(flags[0].baseline, None);
+    }
+}
@@ -0,0 +1,467 @@
+//! Sleep debt, consistency and timing.
+
+use super::to_local;
+use crate::calendar::Calendar;
+use crate::models::*;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Timelike, Weekday};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How much sleep each night is measured against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SleepNeed {
+    /// WHOOP's per-night baseline need. Its debt and strain adjustments are left
+    /// out, since those are what the curve itself accumulates.
+    #[default]
+    Baseline,
+    /// A fixed need in milliseconds, e.g. eight hours.
+    Fixed(i64),
+}
+
+/// One day on the sleep debt curve.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SleepDebtPoint {
+    /// The day the sleep ended on, i.e. the day it was slept for.
+    pub date: NaiveDate,
+    /// Time asleep, naps included.
+    pub slept_milli: i64,
+    pub need_milli: i64,
+    /// Surplus (positive) or deficit (negative) for the day.
+    pub balance_milli: i64,
+    /// Debt carried after the day. Surplus pays debt down but is never banked below zero.
+    pub debt_milli: i64,
+}
+
+/// Accumulates nightly deficits and surpluses into a per-day debt curve.
+/// Days without a scored main sleep are skipped, as there's no need to compare against.
+pub fn sleep_debt(sleeps: &[Sleep], need: SleepNeed) -> Vec<SleepDebtPoint> {
+    // (slept, need) per wake day.
+    let mut days: BTreeMap<NaiveDate, (i64, Option<i64>)> = BTreeMap::new();
+    for sleep in sleeps {
+        let Some(score) = &sleep.score else { continue };
+        let day = days
+            .entry(Calendar::default().sleep_day(sleep))
+            .or_default();
+        day.0 += score.stage_summary.total_sleep_time_milli();
+        if !sleep.nap {
+            let night = match need {
+                SleepNeed::Baseline => score.sleep_needed.baseline_milli,
+                SleepNeed::Fixed(milli) => milli,
+            };
+            day.1 = Some(day.1.unwrap_or(0).max(night));
+        }
+    }
+
+    let mut debt = 0;
+    days.into_iter()
+        .filter_map(|(date, (slept, need))| {
+            let need = need?;
+            let balance = slept - need;
+            debt = (debt - balance).max(0);
+            Some(SleepDebtPoint {
+                date,
+                slept_milli: slept,
+                need_milli: need,
+                balance_milli: balance,
+                debt_milli: debt,
+            })
+        })
+        .collect()
+}
+
+/// A day's sleep, split into the main sleep and naps.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailySleep {
+    /// The local day the sleep ended on.
+    pub date: NaiveDate,
+    /// Time asleep in the main sleep; zero when the day had only naps.
+    pub main_milli: i64,
+    pub nap_milli: i64,
+    pub naps: usize,
+    /// WHOOP's performance for the main sleep, which counts only that sleep.
+    pub main_performance: Option<f32>,
+    /// Time asleep, naps included, against the main sleep's need before WHOOP
+    /// discounts it for recent naps, capped at 100 like WHOOP's own. `None`
+    /// without a main sleep.
+    pub combined_performance: Option<f32>,
+}
+
+impl DailySleep {
+    pub fn total_milli(&self) -> i64 {
+        self.main_milli + self.nap_milli
+    }
+}
+
+/// Scored sleeps grouped by the local day they ended on, oldest first. A day
+/// with more than one main sleep adds them up and takes its need and
+/// performance from the longest.
+pub fn daily_sleep(sleeps: &[Sleep]) -> Vec<DailySleep> {
+    // Per wake day: the day so far and the longest main sleep's need.
+    let mut days: BTreeMap<NaiveDate, (DailySleep, i64, Option<i64>)> = BTreeMap::new();
+    for sleep in sleeps {
+        let Some(score) = &sleep.score else { continue };
+        let date = Calendar::default().sleep_day(sleep);
+        let (day, longest, need) = days.entry(date).or_insert_with(|| {
+            let day = DailySleep {
+                date,
+                main_milli: 0,
+                nap_milli: 0,
+                naps: 0,
+                main_performance: None,
+                combined_performance: None,
+            };
+            (day, 0, None)
+        });
+        let slept = score.stage_summary.total_sleep_time_milli();
+        if sleep.nap {
+            day.nap_milli += slept;
+            day.naps += 1;
+            continue;
+        }
+        day.main_milli += slept;
+        if slept >= *longest {
+            *longest = slept;
+            day.main_performance = score.sleep_performance_percentage;
+            let needed = &score.sleep_needed;
+            *need = Some(needed.total_milli() - needed.need_from_recent_nap_milli);
+        }
+    }
+
+    days.into_values()
+        .map(|(mut day, _, need)| {
+            day.combined_performance = need
+                .filter(|&need| need > 0)
+                .map(|need| (day.total_milli() as f32 / need as f32 * 100.0).min(100.0));
+            day
+        })
+        .collect()
+}
+
+/// Bedtime and wake-time variability across a set of nights.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SleepConsistency {
+    pub nights: usize,
+    pub mean_bedtime: NaiveTime,
+    pub mean_wake_time: NaiveTime,
+    pub bedtime_sd_minutes: f32,
+    pub wake_time_sd_minutes: f32,
+    /// How much later sleep is centred on free days (waking on a weekend) than on
+    /// work days; `None` unless there are nights of both kinds.
+    pub social_jetlag_minutes: Option<f32>,
+}
+
+/// Consistency of the main sleeps given, in each night's local time. Filter the
+/// sleeps to the window you care about first. `None` with fewer than two nights.
+pub fn sleep_consistency(sleeps: &[Sleep]) -> Option<SleepConsistency> {
+    let nights: Vec<&Sleep> = sleeps.iter().filter(|s| !s.nap).collect();
+    if nights.len() < 2 {
+        return None;
+    }
+    let local = |time, sleep: &Sleep| to_local(time, &sleep.timezone_offset);
+    let bedtimes: Vec<f32> = nights
+        .iter()
+        .map(|s| clock_minutes(local(s.start, s).time(), BEDTIME_ANCHOR))
+        .collect();
+    let wake_times: Vec<f32> = nights
+        .iter()
+        .map(|s| clock_minutes(local(s.end, s).time(), WAKE_ANCHOR))
+        .collect();
+
+    let (free, work) = midpoints_by_day_kind(&nights);
+    let social_jetlag_minutes =
+        (!free.is_empty() && !work.is_empty()).then(|| mean(&free) - mean(&work));
+
+    Some(SleepConsistency {
+        nights: nights.len(),
+        mean_bedtime: clock_time(mean(&bedtimes), BEDTIME_ANCHOR),
+        mean_wake_time: clock_time(mean(&wake_times), WAKE_ANCHOR),
+        bedtime_sd_minutes: standard_deviation(&bedtimes),
+        wake_time_sd_minutes: standard_deviation(&wake_times),
+        social_jetlag_minutes,
+    })
+}
+
+/// The middle of one night's sleep.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SleepMidpoint {
+    /// The local day the sleep ended on.
+    pub date: NaiveDate,
+    pub midpoint: NaiveTime,
+    pub timezone_offset: String,
+    /// Clock change from the previous night's midpoint. Large shifts alongside a
+    /// changed `timezone_offset` are travel; without one, a schedule change.
+    pub shift_minutes: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeeklyMidpoint {
+    /// The Monday the week starts on.
+    pub week_start: NaiveDate,
+    pub midpoint: NaiveTime,
+    pub nights: usize,
+}
+
+/// Chronotype from the free-day sleep midpoint, the usual proxy for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Chronotype {
+    /// Midpoint before 3 am.
+    Early,
+    Intermediate,
+    /// Midpoint at 5 am or later.
+    Late,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Circadian {
+    pub nights: Vec<SleepMidpoint>,
+    pub weekly: Vec<WeeklyMidpoint>,
+    pub weekday_midpoint: Option<NaiveTime>,
+    pub weekend_midpoint: Option<NaiveTime>,
+    /// How much later the weekend midpoint is than the weekday one.
+    pub drift_minutes: Option<f32>,
+    pub chronotype: Chronotype,
+}
+
+/// Midpoint of every main sleep, oldest first.
+pub fn sleep_midpoints(sleeps: &[Sleep]) -> Vec<SleepMidpoint> {
+    let mut nights: Vec<&Sleep> = sleeps.iter().filter(|s| !s.nap).collect();
+    nights.sort_by_key(|s| s.start);
+
+    let mut previous: Option<f32> = None;
+    nights
+        .into_iter()
+        .map(|sleep| {
+            let midpoint = midpoint(sleep);
+            let minutes = clock_minutes(midpoint.time(), WAKE_ANCHOR);
+            SleepMidpoint {
+                date: Calendar::default().sleep_day(sleep),
+                midpoint: midpoint.time(),
+                timezone_offset: sleep.timezone_offset.clone(),
+                shift_minutes: previous.replace(minutes).map(|p| minutes - p),
+            }
+        })
+        .collect()
+}
+
+/// Sleep midpoints with weekly averages, weekday/weekend drift and a chronotype
+/// estimate. `None` without any main sleep.
+pub fn circadian(sleeps: &[Sleep]) -> Option<Circadian> {
+    let nights = sleep_midpoints(sleeps);
+    if nights.is_empty() {
+        return None;
+    }
+    let main: Vec<&Sleep> = sleeps.iter().filter(|s| !s.nap).collect();
+    let (free, work) = midpoints_by_day_kind(&main);
+
+    let mut weeks: BTreeMap<NaiveDate, Vec<f32>> = BTreeMap::new();
+    for night in &nights {
+        let week_start = night.date.week(Weekday::Mon).first_day();
+        weeks
+            .entry(week_start)
+            .or_default()
+            .push(clock_minutes(night.midpoint, WAKE_ANCHOR));
+    }
+    let weekly = weeks
+        .into_iter()
+        .map(|(week_start, minutes)| WeeklyMidpoint {
+            week_start,
+            midpoint: clock_time(mean(&minutes), WAKE_ANCHOR),
+            nights: minutes.len(),
+        })
+        .collect();
+
+    let average = |minutes: &[f32]| (!minutes.is_empty()).then(|| mean(minutes));
+    let (weekend, weekday) = (average(&free), average(&work));
+    let all: Vec<f32> = nights
+        .iter()
+        .map(|n| clock_minutes(n.midpoint, WAKE_ANCHOR))
+        .collect();
+    let reference = weekend.unwrap_or_else(|| mean(&all));
+    // 3 am and 5 am, counted from the 6 pm anchor.
+    let chronotype = if reference < 9.0 * 60.0 {
+        Chronotype::Early
+    } else if reference < 11.0 * 60.0 {
+        Chronotype::Intermediate
+    } else {
+        Chronotype::Late
+    };
+
+    Some(Circadian {
+        nights,
+        weekly,
+        weekday_midpoint: weekday.map(|m| clock_time(m, WAKE_ANCHOR)),
+        weekend_midpoint: weekend.map(|m| clock_time(m, WAKE_ANCHOR)),
+        drift_minutes: weekend.zip(weekday).map(|(free, work)| free - work),
+        chronotype,
+    })
+}
+
+/// Clock times are measured from an anchor hour, so a night's times don't wrap
+/// around midnight: bedtimes from noon, wake times and midpoints from 6 pm.
+const BEDTIME_ANCHOR: u32 = 12;
+const WAKE_ANCHOR: u32 = 18;
+
+fn clock_minutes(time: NaiveTime, anchor: u32) -> f32 {
+    let minutes = time.hour() * 60 + time.minute() + (24 - anchor) * 60;
+    (minutes % (24 * 60)) as f32 + time.second() as f32 / 60.0
+}
+
+fn clock_time(minutes: f32, anchor: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(anchor, 0, 0).unwrap() + Duration::seconds((minutes * 60.0) as i64)
+}
+
+/// Halfway between falling asleep and waking, in the sleep's local time.
+fn midpoint(sleep: &Sleep) -> DateTime<FixedOffset> {
+    to_local(
+        sleep.start + (sleep.end - sleep.start) / 2,
+        &sleep.timezone_offset,
+    )
+}
+
+/// Midpoint clock minutes of nights ending on free days and on work days.
+fn midpoints_by_day_kind(nights: &[&Sleep]) -> (Vec<f32>, Vec<f32>) {
+    let (mut free, mut work) = (Vec::new(), Vec::new());
+    for sleep in nights {
+        let minutes = clock_minutes(midpoint(sleep).time(), WAKE_ANCHOR);
+        let wake = to_local(sleep.end, &sleep.timezone_offset);
+        if is_free_day(wake.weekday()) {
+            free.push(minutes);
+        } else {
+            work.push(minutes);
+        }
+    }
+    (free, work)
+}
+
+fn is_free_day(wake_day: Weekday) -> bool {
+    matches!(wake_day, Weekday::Sat | Weekday::Sun)
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+/// Sample standard deviation; zero for a single value.
+fn standard_deviation(values: &[f32]) -> f32 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let sum: f32 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    (sum / (values.len() - 1) as f32).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sleep(end: &str, slept_hours: i32, nap: bool) -> Sleep {
+        let end = end.parse().unwrap();
+        let hour = 3_600_000;
+        Sleep {
+            id: uuid::Uuid::new_v4(),
+            cycle_id: 1,
+            v1_id: None,
+            user_id: 10129,
+            created_at: end,
+            updated_at: end,
+            start: end - chrono::Duration::hours(slept_hours.into()),
+            end,
+            timezone_offset: "-05:00".to_string(),
+            nap,
+            score_state: ScoreState::Scored,
+            score: Some(SleepScore {
+                stage_summary: SleepStageSummary {
+                    total_in_bed_time_milli: slept_hours * hour,
+                    total_awake_time_milli: 0,
+                    total_no_data_time_milli: 0,
+                    total_light_sleep_time_milli: slept_hours * hour,
+                    total_slow_wave_sleep_time_milli: 0,
+                    total_rem_sleep_time_milli: 0,
+                    sleep_cycle_count: 4,
+                    disturbance_count: 0,
+                },
+                sleep_needed: SleepNeeded {
+                    baseline_milli: 8 * hour as i64,
+                    need_from_sleep_debt_milli: 0,
+                    need_from_recent_strain_milli: 0,
+                    need_from_recent_nap_milli: 0,
+                },
+                respiratory_rate: None,
+                sleep_performance_percentage: None,
+                sleep_consistency_percentage: None,
+                sleep_efficiency_percentage: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_debt_accumulates_and_naps_pay_it_down() {
+        let hour = 3_600_000;
+        let sleeps = [
+            sleep("2024-03-01T12:00:00Z", 6, false),
+            sleep("2024-03-02T12:00:00Z", 7, false),
+            sleep("2024-03-02T20:00:00Z", 1, true),
+            sleep("2024-03-03T12:00:00Z", 10, false),
+        ];
+
+        let debt: Vec<i64> = sleep_debt(&sleeps, SleepNeed::Baseline)
+            .iter()
+            .map(|p| p.debt_milli / hour)
+            .collect();
+        assert_eq!(debt, [2, 2, 0]);
+    }
+
+    #[test]
+    fn test_consistency_handles_bedtimes_around_midnight() {
+        // Local (-05:00) bedtimes 23:00 and 01:00, waking Friday and Saturday.
+        let sleeps = [
+            sleep("2024-03-01T12:00:00Z", 8, false),
+            sleep("2024-03-02T14:00:00Z", 8, false),
+        ];
+
+        let consistency = sleep_consistency(&sleeps).unwrap();
+        assert_eq!(
+            consistency.mean_bedtime,
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap()
+        );
+        assert!((consistency.bedtime_sd_minutes - 84.85).abs() < 0.1);
+        assert_eq!(consistency.social_jetlag_minutes, Some(120.0));
+    }
+
+    #[test]
+    fn test_circadian_weekend_drift_and_chronotype() {
+        // Local (-05:00) midpoints 03:00 Thursday and Friday, 06:00 Saturday and Sunday.
+        let sleeps = [
+            sleep("2024-02-29T12:00:00Z", 8, false),
+            sleep("2024-03-01T12:00:00Z", 8, false),
+            sleep("2024-03-02T15:00:00Z", 8, false),
+            sleep("2024-03-03T15:00:00Z", 8, false),
+        ];
+
+        let circadian = circadian(&sleeps).unwrap();
+        assert_eq!(circadian.drift_minutes, Some(180.0));
+        assert_eq!(circadian.chronotype, Chronotype::Late);
+        assert_eq!(circadian.nights[2].shift_minutes, Some(180.0));
+        assert_eq!(circadian.weekly[0].nights, 4);
+    }
+
+    #[test]
+    fn test_daily_sleep_adds_naps_to_the_main_sleep() {
+        let hour = 3_600_000;
+        let sleeps = [
+            sleep("2024-03-01T12:00:00Z", 6, false),
+            sleep("2024-03-01T20:00:00Z", 1, true),
+            sleep("2024-03-02T20:00:00Z", 1, true),
+        ];
+
+        let days = daily_sleep(&sleeps);
+        assert_eq!(days.len(), 2);
+        assert_eq!((days[0].main_milli, days[0].nap_milli), (6 * hour, hour));
+        assert_eq!(days[0].total_milli(), 7 * hour);
+        assert_eq!(days[0].combined_performance, Some(87.5));
+        assert_eq!((days[1].main_milli, days[1].naps), (0, 1));
+        assert_eq!(days[1].combined_performance, None);
+    }
+}
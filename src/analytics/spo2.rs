@@ -0,0 +1,207 @@
+//! Blood oxygen during sleep: trend, spread and nights worth a second look.
+//!
+//! SpO2 comes with the recovery, and only from WHOOP 4.0 and later; days
+//! without a reading are counted, not guessed at. A night is flagged when it
+//! reads below an absolute floor, or drops well below the rolling average of
+//! the nights before it. Wrist SpO2 is noisy; a flag is a prompt to look at
+//! the trend, not a diagnosis.
+
+use super::Point;
+use super::trends::{RollingPoint, rolling_average};
+use crate::aggregate::{DailySummary, mean};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spo2Options {
+    pub window_days: u32,
+    /// Nights below this percentage are [`Spo2Issue::Low`].
+    pub low_percentage: f32,
+    /// Nights this many points below the rolling average before them are
+    /// [`Spo2Issue::Drop`].
+    pub drop_points: f32,
+}
+
+impl Default for Spo2Options {
+    fn default() -> Self {
+        Self {
+            window_days: 7,
+            low_percentage: 90.0,
+            drop_points: 3.0,
+        }
+    }
+}
+
+impl Spo2Options {
+    pub fn with_window_days(mut self, window_days: u32) -> Self {
+        self.window_days = window_days;
+        self
+    }
+
+    pub fn with_low_percentage(mut self, percentage: f32) -> Self {
+        self.low_percentage = percentage;
+        self
+    }
+
+    pub fn with_drop_points(mut self, points: f32) -> Self {
+        self.drop_points = points;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spo2Issue {
+    Low,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Spo2Flag {
+    pub date: NaiveDate,
+    pub percentage: f32,
+    /// The rolling average up to the night before, if there was one.
+    pub reference: Option<f32>,
+    /// A night both low and dropped is [`Spo2Issue::Low`].
+    pub issue: Spo2Issue,
+}
+
+/// How readings spread, in the bands clinicians use.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Spo2Distribution {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub below_90: usize,
+    pub from_90_to_95: usize,
+    pub from_95: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Spo2Report {
+    pub rolling: Vec<RollingPoint>,
+    /// `None` when no day has a reading.
+    pub distribution: Option<Spo2Distribution>,
+    pub flags: Vec<Spo2Flag>,
+    pub days_without_reading: usize,
+}
+
+impl Spo2Report {
+    /// Whether any reading came in at all; older straps never send one.
+    pub fn has_readings(&self) -> bool {
+        self.distribution.is_some()
+    }
+}
+
+/// Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn spo2(days: &[DailySummary], options: Spo2Options) -> Spo2Report {
+    let series: Vec<Point> = days
+        .iter()
+        .filter_map(|day| {
+            Some(Point {
+                date: day.date,
+                value: day.spo2_percentage?,
+            })
+        })
+        .collect();
+    let rolling = rolling_average(&series, options.window_days);
+
+    let flags = series
+        .iter()
+        .enumerate()
+        .filter_map(|(i, point)| {
+            let reference = i.checked_sub(1).map(|before| rolling[before].average);
+            let issue = if point.value < options.low_percentage {
+                Spo2Issue::Low
+            } else if reference.is_some_and(|r| r - point.value >= options.drop_points) {
+                Spo2Issue::Drop
+            } else {
+                return None;
+            };
+            Some(Spo2Flag {
+                date: point.date,
+                percentage: point.value,
+                reference,
+                issue,
+            })
+        })
+        .collect();
+
+    Spo2Report {
+        distribution: distribution(&series),
+        days_without_reading: days.len() - series.len(),
+        rolling,
+        flags,
+    }
+}
+
+fn distribution(series: &[Point]) -> Option<Spo2Distribution> {
+    let mut values: Vec<f32> = series.iter().map(|p| p.value).collect();
+    values.sort_by(f32::total_cmp);
+    let middle = values.len() / 2;
+    let median = if values.len().is_multiple_of(2) {
+        (*values.get(middle.checked_sub(1)?)? + values[middle]) / 2.0
+    } else {
+        values[middle]
+    };
+    let count = |band: fn(f32) -> bool| values.iter().filter(|v| band(**v)).count();
+    Some(Spo2Distribution {
+        min: *values.first()?,
+        max: *values.last()?,
+        mean: mean(values.iter().copied())?,
+        median,
+        below_90: count(|v| v < 90.0),
+        from_90_to_95: count(|v| (90.0..95.0).contains(&v)),
+        from_95: count(|v| v >= 95.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Days;
+
+    #[test]
+    fn test_flags_low_and_dropping_nights() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let readings = [
+            Some(96.0),
+            Some(97.0),
+            None,
+            Some(96.5),
+            Some(92.5),
+            Some(89.0),
+        ];
+        let days: Vec<DailySummary> = readings
+            .into_iter()
+            .zip(0..)
+            .map(|(spo2, i)| DailySummary {
+                date: start + Days::new(i),
+                spo2_percentage: spo2,
+                ..Default::default()
+            })
+            .collect();
+
+        let report = spo2(&days, Spo2Options::default());
+        assert_eq!(report.days_without_reading, 1);
+        assert_eq!(report.rolling.len(), 5);
+        let issues: Vec<Spo2Issue> = report.flags.iter().map(|f| f.issue).collect();
+        assert_eq!(issues, [Spo2Issue::Drop, Spo2Issue::Low]);
+        assert_eq!(report.flags[0].reference, Some(96.5));
+
+        let distribution = report.distribution.unwrap();
+        assert_eq!(distribution.median, 96.0);
+        assert_eq!(
+            (
+                distribution.below_90,
+                distribution.from_90_to_95,
+                distribution.from_95
+            ),
+            (1, 1, 3)
+        );
+
+        let older_strap = [DailySummary::default()];
+        assert!(!spo2(&older_strap, Spo2Options::default()).has_readings());
+    }
+}
@@ -0,0 +1,197 @@
+//! Skin temperature against its personal baseline, night by night.
+//!
+//! Only WHOOP 4.0 and later measure skin temperature; recoveries from older
+//! straps just have none, and those days are counted in
+//! [`SkinTemperature::days_without_reading`] rather than treated as normal.
+//! The baseline is an exponentially weighted mean of earlier nights, leaving
+//! out elevated ones so a fever doesn't raise its own reference. A run of
+//! elevated nights on consecutive days is an [`Elevation`], the signal worth
+//! acting on: one warm night is usually just a warm bedroom.
+
+use crate::aggregate::DailySummary;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureOptions {
+    /// EWMA span in nights.
+    pub span_nights: u32,
+    /// Nights averaged into the first baseline.
+    pub warm_up_nights: usize,
+    /// Degrees Celsius above the baseline that make a night elevated.
+    pub threshold_celsius: f32,
+    /// Consecutive elevated nights that make an [`Elevation`].
+    pub min_elevated_nights: usize,
+}
+
+impl Default for TemperatureOptions {
+    fn default() -> Self {
+        Self {
+            span_nights: 30,
+            warm_up_nights: 7,
+            threshold_celsius: 0.5,
+            min_elevated_nights: 2,
+        }
+    }
+}
+
+impl TemperatureOptions {
+    pub fn with_span_nights(mut self, span_nights: u32) -> Self {
+        self.span_nights = span_nights;
+        self
+    }
+
+    pub fn with_warm_up_nights(mut self, nights: usize) -> Self {
+        self.warm_up_nights = nights;
+        self
+    }
+
+    pub fn with_threshold(mut self, celsius: f32) -> Self {
+        self.threshold_celsius = celsius;
+        self
+    }
+
+    pub fn with_min_elevated_nights(mut self, nights: usize) -> Self {
+        self.min_elevated_nights = nights;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TemperatureNight {
+    pub date: NaiveDate,
+    pub celsius: f32,
+    /// The baseline before this night; `None` during the warm-up.
+    pub baseline: Option<f32>,
+    pub delta: Option<f32>,
+    pub elevated: bool,
+}
+
+/// Consecutive elevated nights.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Elevation {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub nights: usize,
+    pub peak_delta: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SkinTemperature {
+    /// Every night with a reading, oldest first.
+    pub nights: Vec<TemperatureNight>,
+    pub elevations: Vec<Elevation>,
+    pub days_without_reading: usize,
+}
+
+/// Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn skin_temperature(days: &[DailySummary], options: TemperatureOptions) -> SkinTemperature {
+    let alpha = 2.0 / (options.span_nights.max(1) as f32 + 1.0);
+    let mut result = SkinTemperature::default();
+    let mut warm_up = Vec::new();
+    let mut baseline: Option<f32> = None;
+
+    for day in days {
+        let Some(celsius) = day.skin_temp_celsius else {
+            result.days_without_reading += 1;
+            continue;
+        };
+        let delta = baseline.map(|b| celsius - b);
+        let elevated = delta.is_some_and(|d| d >= options.threshold_celsius);
+        result.nights.push(TemperatureNight {
+            date: day.date,
+            celsius,
+            baseline,
+            delta,
+            elevated,
+        });
+
+        match &mut baseline {
+            Some(_) if elevated => {}
+            Some(baseline) => *baseline += alpha * (celsius - *baseline),
+            None => {
+                warm_up.push(celsius);
+                if warm_up.len() >= options.warm_up_nights.max(1) {
+                    baseline = Some(warm_up.iter().sum::<f32>() / warm_up.len() as f32);
+                }
+            }
+        }
+    }
+
+    result.elevations = elevations(&result.nights, options.min_elevated_nights.max(1));
+    result
+}
+
+fn elevations(nights: &[TemperatureNight], min_nights: usize) -> Vec<Elevation> {
+    let mut elevations = Vec::new();
+    let mut run: Option<Elevation> = None;
+    for night in nights {
+        let delta = night.delta.filter(|_| night.elevated);
+        run = match (run, delta) {
+            (Some(mut current), Some(delta)) if current.end.succ_opt() == Some(night.date) => {
+                current.end = night.date;
+                current.nights += 1;
+                current.peak_delta = current.peak_delta.max(delta);
+                Some(current)
+            }
+            (current, delta) => {
+                elevations.extend(current.filter(|e| e.nights >= min_nights));
+                delta.map(|delta| Elevation {
+                    start: night.date,
+                    end: night.date,
+                    nights: 1,
+                    peak_delta: delta,
+                })
+            }
+        };
+    }
+    elevations.extend(run.filter(|e| e.nights >= min_nights));
+    elevations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Days;
+
+    #[test]
+    fn test_flags_runs_of_warm_nights_and_skips_missing_readings() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let readings = [
+            None,
+            Some(33.5),
+            Some(33.6),
+            Some(33.4),
+            Some(33.5),
+            Some(33.5),
+            Some(33.6),
+            Some(33.4),
+            Some(34.2),
+            Some(33.5),
+            Some(34.3),
+            Some(34.6),
+            Some(34.4),
+            Some(33.6),
+        ];
+        let days: Vec<DailySummary> = readings
+            .into_iter()
+            .zip(0..)
+            .map(|(celsius, i)| DailySummary {
+                date: start + Days::new(i),
+                skin_temp_celsius: celsius,
+                ..Default::default()
+            })
+            .collect();
+
+        let temperature = skin_temperature(&days, TemperatureOptions::default());
+        assert_eq!(temperature.days_without_reading, 1);
+        assert_eq!(temperature.nights.len(), 13);
+        assert!(temperature.nights[7].elevated);
+        assert_eq!(temperature.elevations.len(), 1);
+
+        let elevation = temperature.elevations[0];
+        assert_eq!(elevation.start, days[10].date);
+        assert_eq!(elevation.nights, 3);
+        assert!((elevation.peak_delta - 1.08).abs() < 0.02);
+    }
+}
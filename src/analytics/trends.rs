@@ -0,0 +1,158 @@
+//! Rolling averages over daily metrics.
+
+use super::Point;
+use crate::aggregate::DailySummary;
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+
+/// A daily metric that trends can be computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Recovery,
+    Strain,
+    Hrv,
+    RestingHeartRate,
+    SleepPerformance,
+    RespiratoryRate,
+    SkinTemperature,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 7] = [
+        Metric::Recovery,
+        Metric::Strain,
+        Metric::Hrv,
+        Metric::RestingHeartRate,
+        Metric::SleepPerformance,
+        Metric::RespiratoryRate,
+        Metric::SkinTemperature,
+    ];
+
+    /// The metric for its snake_case name, e.g. `resting_heart_rate`.
+    pub fn from_name(name: &str) -> Option<Metric> {
+        Metric::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Recovery => "recovery",
+            Metric::Strain => "strain",
+            Metric::Hrv => "hrv",
+            Metric::RestingHeartRate => "resting_heart_rate",
+            Metric::SleepPerformance => "sleep_performance",
+            Metric::RespiratoryRate => "respiratory_rate",
+            Metric::SkinTemperature => "skin_temperature",
+        }
+    }
+
+    pub fn value(self, day: &DailySummary) -> Option<f32> {
+        match self {
+            Metric::Recovery => day.recovery_score,
+            Metric::Strain => day.strain,
+            Metric::Hrv => day.hrv_rmssd_milli,
+            Metric::RestingHeartRate => day.resting_heart_rate,
+            Metric::SleepPerformance => day.sleep_performance_percentage,
+            Metric::RespiratoryRate => day.respiratory_rate,
+            Metric::SkinTemperature => day.skin_temp_celsius,
+        }
+    }
+
+    /// The metric's value on every day that has one.
+    pub fn series(self, days: &[DailySummary]) -> Vec<Point> {
+        days.iter()
+            .filter_map(|day| {
+                Some(Point {
+                    date: day.date,
+                    value: self.value(day)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RollingPoint {
+    pub date: NaiveDate,
+    /// Mean of the values in the window ending on `date`.
+    pub average: f32,
+    /// Change from the average one full window earlier, when there is one.
+    pub delta: Option<f32>,
+}
+
+/// Rolling averages for every [`Metric`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Rolling {
+    pub window_days: u32,
+    pub recovery: Vec<RollingPoint>,
+    pub strain: Vec<RollingPoint>,
+    pub hrv: Vec<RollingPoint>,
+    pub resting_heart_rate: Vec<RollingPoint>,
+    pub sleep_performance: Vec<RollingPoint>,
+}
+
+/// Rolling averages over a calendar window, typically 7, 14 or 30 days.
+/// Pass days sorted by date, as [`DailySummary::from_records`] returns them.
+pub fn rolling(days: &[DailySummary], window_days: u32) -> Rolling {
+    let series = |metric: Metric| rolling_average(&metric.series(days), window_days);
+    Rolling {
+        window_days,
+        recovery: series(Metric::Recovery),
+        strain: series(Metric::Strain),
+        hrv: series(Metric::Hrv),
+        resting_heart_rate: series(Metric::RestingHeartRate),
+        sleep_performance: series(Metric::SleepPerformance),
+    }
+}
+
+/// Rolling average of one series, sorted by date. Days missing inside a window
+/// simply don't count towards it.
+pub fn rolling_average(series: &[Point], window_days: u32) -> Vec<RollingPoint> {
+    let window = Days::new(window_days.max(1).into());
+    let mut points: Vec<RollingPoint> = Vec::with_capacity(series.len());
+    let (mut first, mut sum) = (0, 0.0_f64);
+
+    for (i, point) in series.iter().enumerate() {
+        sum += f64::from(point.value);
+        while series[first].date + window <= point.date {
+            sum -= f64::from(series[first].value);
+            first += 1;
+        }
+        let average = (sum / (i + 1 - first) as f64) as f32;
+
+        let earlier = point.date - window;
+        let delta = points
+            .iter()
+            .rev()
+            .find(|p| p.date <= earlier)
+            .filter(|p| p.date + window > earlier)
+            .map(|p| average - p.average);
+
+        points.push(RollingPoint {
+            date: point.date,
+            average,
+            delta,
+        });
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_drops_old_values_and_skips_gaps() {
+        let day = |d: u32, value: f32| Point {
+            date: NaiveDate::from_ymd_opt(2024, 3, d).unwrap(),
+            value,
+        };
+        let series = [day(1, 10.0), day(2, 20.0), day(3, 30.0), day(5, 50.0)];
+
+        let rolling = rolling_average(&series, 2);
+        let averages: Vec<f32> = rolling.iter().map(|p| p.average).collect();
+        assert_eq!(averages, [10.0, 15.0, 25.0, 50.0]);
+        assert_eq!(rolling[2].delta, Some(15.0));
+        assert_eq!(rolling[3].delta, Some(25.0));
+    }
+}
@@ -0,0 +1,117 @@
+//! The WHOOP API as a trait, so code can run against [`WhoopClient`] in
+//! production and an [`InMemoryWhoop`](crate::memory::InMemoryWhoop) in tests.
+
+use crate::client::WhoopClient;
+use crate::error::Result;
+use crate::models::*;
+use std::future::Future;
+use uuid::Uuid;
+
+/// Read access to every endpoint, mirroring [`WhoopClient`]'s methods.
+pub trait WhoopApi: Send + Sync {
+    fn get_cycle_by_id(&self, cycle_id: i64) -> impl Future<Output = Result<Cycle>> + Send;
+
+    fn get_cycle_collection(
+        &self,
+        params: Option<CycleQueryParams>,
+    ) -> impl Future<Output = Result<PaginatedCycleResponse>> + Send;
+
+    fn get_sleep_for_cycle(&self, cycle_id: i64) -> impl Future<Output = Result<Sleep>> + Send;
+
+    fn get_recovery_for_cycle(
+        &self,
+        cycle_id: i64,
+    ) -> impl Future<Output = Result<Recovery>> + Send;
+
+    fn get_recovery_collection(
+        &self,
+        params: Option<RecoveryQueryParams>,
+    ) -> impl Future<Output = Result<RecoveryCollection>> + Send;
+
+    fn get_sleep_by_id(&self, sleep_id: Uuid) -> impl Future<Output = Result<Sleep>> + Send;
+
+    fn get_sleep_collection(
+        &self,
+        params: Option<SleepQueryParams>,
+    ) -> impl Future<Output = Result<PaginatedSleepResponse>> + Send;
+
+    fn get_body_measurement(&self) -> impl Future<Output = Result<UserBodyMeasurement>> + Send;
+
+    fn get_profile_basic(&self) -> impl Future<Output = Result<UserBasicProfile>> + Send;
+
+    fn revoke_oauth_access(&self) -> impl Future<Output = Result<()>> + Send;
+
+    #[cfg(feature = "workouts")]
+    fn get_workout_by_id(&self, workout_id: Uuid)
+    -> impl Future<Output = Result<WorkoutV2>> + Send;
+
+    #[cfg(feature = "workouts")]
+    fn get_workout_collection(
+        &self,
+        params: Option<WorkoutQueryParams>,
+    ) -> impl Future<Output = Result<WorkoutCollection>> + Send;
+}
+
+impl WhoopApi for WhoopClient {
+    async fn get_cycle_by_id(&self, cycle_id: i64) -> Result<Cycle> {
+        WhoopClient::get_cycle_by_id(self, cycle_id).await
+    }
+
+    async fn get_cycle_collection(
+        &self,
+        params: Option<CycleQueryParams>,
+    ) -> Result<PaginatedCycleResponse> {
+        WhoopClient::get_cycle_collection(self, params).await
+    }
+
+    async fn get_sleep_for_cycle(&self, cycle_id: i64) -> Result<Sleep> {
+        WhoopClient::get_sleep_for_cycle(self, cycle_id).await
+    }
+
+    async fn get_recovery_for_cycle(&self, cycle_id: i64) -> Result<Recovery> {
+        WhoopClient::get_recovery_for_cycle(self, cycle_id).await
+    }
+
+    async fn get_recovery_collection(
+        &self,
+        params: Option<RecoveryQueryParams>,
+    ) -> Result<RecoveryCollection> {
+        WhoopClient::get_recovery_collection(self, params).await
+    }
+
+    async fn get_sleep_by_id(&self, sleep_id: Uuid) -> Result<Sleep> {
+        WhoopClient::get_sleep_by_id(self, sleep_id).await
+    }
+
+    async fn get_sleep_collection(
+        &self,
+        params: Option<SleepQueryParams>,
+    ) -> Result<PaginatedSleepResponse> {
+        WhoopClient::get_sleep_collection(self, params).await
+    }
+
+    async fn get_body_measurement(&self) -> Result<UserBodyMeasurement> {
+        WhoopClient::get_body_measurement(self).await
+    }
+
+    async fn get_profile_basic(&self) -> Result<UserBasicProfile> {
+        WhoopClient::get_profile_basic(self).await
+    }
+
+    async fn revoke_oauth_access(&self) -> Result<()> {
+        WhoopClient::revoke_oauth_access(self).await
+    }
+
+    #[cfg(feature = "workouts")]
+    async fn get_workout_by_id(&self, workout_id: Uuid) -> Result<WorkoutV2> {
+        WhoopClient::get_workout_by_id(self, workout_id).await
+    }
+
+    #[cfg(feature = "workouts")]
+    async fn get_workout_collection(
+        &self,
+        params: Option<WorkoutQueryParams>,
+    ) -> Result<WorkoutCollection> {
+        WhoopClient::get_workout_collection(self, params).await
+    }
+}
@@ -0,0 +1,369 @@
+//! A whole local database in one file, for backups and moving machines.
+//!
+//! An archive is a zip with one `.jsonl` file of [`Envelope`]s per resource and
+//! a `manifest.json` saying which schema version wrote it, how many records
+//! each file holds and the time range they cover. [`export`] writes a
+//! [`Store`] out and [`import`] upserts an archive into one, even one of
+//! another backend. Since the files are plain envelopes,
+//! [`replay::open`](crate::replay::open) reads archives too, for working
+//! offline from a backup.
+//!
+//! ```no_run
+//! # use whoopsy::store::Store;
+//! # fn run(store: &impl Store, elsewhere: &mut impl Store) -> whoopsy::Result<()> {
+//! let manifest = whoopsy::archive::export(store, std::fs::File::create("backup.zip")?)?;
+//! whoopsy::archive::import(std::fs::File::open("backup.zip")?, elsewhere)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::body::BodySnapshot;
+use crate::error::{Result, WhoopError};
+use crate::export::jsonl::{self, Envelope, JsonlRecord, JsonlWriter};
+use crate::json;
+use crate::models::*;
+use crate::store::Store;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read, Seek, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Bumped whenever the layout changes in a way older versions can't read.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+
+/// The resources whose sync watermarks travel with an archive.
+const RESOURCES: [&str; 4] = ["cycles", "sleep", "recovery", "workouts"];
+
+/// What an archive holds, from its `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// The crate version that wrote the archive.
+    pub generator: String,
+    pub files: Vec<ArchiveFile>,
+    /// Sync watermarks per resource, so an imported database syncs on
+    /// incrementally instead of starting over.
+    #[serde(default)]
+    pub watermarks: BTreeMap<String, DateTime<Utc>>,
+}
+
+/// One `.jsonl` file in an archive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    pub name: String,
+    /// The envelope type of every record in the file.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub records: usize,
+    /// Earliest and latest start of the records, or creation for recoveries
+    /// and measurement for body measurements.
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+}
+
+impl Manifest {
+    pub fn file(&self, kind: &str) -> Option<&ArchiveFile> {
+        self.files.iter().find(|f| f.kind == kind)
+    }
+}
+
+/// Writes everything in `store` as an archive to `out`.
+pub fn export(store: &impl Store, out: impl Write + Seek) -> Result<Manifest> {
+    // Timestamps are stored as text, which a far-future bound still sorts past.
+    let all = DateTime::UNIX_EPOCH..DateTime::from_timestamp(253_402_300_799, 0).unwrap();
+    let created_at = Utc::now();
+    let mut zip = ZipWriter::new(out);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut files = Vec::new();
+
+    let cycles = store.cycles_between(all.clone())?;
+    files.push(write_file(
+        &mut zip,
+        options,
+        "cycles.jsonl",
+        &cycles,
+        created_at,
+        |c| c.start,
+    )?);
+    let sleeps = store.sleeps_between(all.clone())?;
+    files.push(write_file(
+        &mut zip,
+        options,
+        "sleep.jsonl",
+        &sleeps,
+        created_at,
+        |s| s.start,
+    )?);
+    let recoveries = store.recoveries_between(all.clone())?;
+    files.push(write_file(
+        &mut zip,
+        options,
+        "recovery.jsonl",
+        &recoveries,
+        created_at,
+        |r| r.created_at,
+    )?);
+    let workouts = store.workouts_between(all)?;
+    files.push(write_file(
+        &mut zip,
+        options,
+        "workouts.jsonl",
+        &workouts,
+        created_at,
+        |w| w.start,
+    )?);
+
+    // A measurement's envelope is stamped with when it was seen, so replaying
+    // the archive ends up with the latest one.
+    let snapshots = store.body_measurements()?;
+    zip.start_file("body_measurements.jsonl", options)?;
+    let mut writer = JsonlWriter::new(&mut zip);
+    for snapshot in &snapshots {
+        writer.write_fetched_at(&snapshot.measurement, snapshot.at)?;
+    }
+    writer.flush()?;
+    files.push(ArchiveFile {
+        name: "body_measurements.jsonl".to_string(),
+        kind: UserBodyMeasurement::TYPE.to_string(),
+        records: snapshots.len(),
+        first: snapshots.first().map(|s| s.at),
+        last: snapshots.last().map(|s| s.at),
+    });
+
+    let mut watermarks = BTreeMap::new();
+    for resource in RESOURCES {
+        if let Some(watermark) = store.watermark(resource)? {
+            watermarks.insert(resource.to_string(), watermark);
+        }
+    }
+
+    let manifest = Manifest {
+        schema_version: SCHEMA_VERSION,
+        created_at,
+        generator: format!("whoopsy {}", env!("CARGO_PKG_VERSION")),
+        files,
+        watermarks,
+    };
+    zip.start_file(MANIFEST, options)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest)?;
+    zip.finish()?;
+    Ok(manifest)
+}
+
+fn write_file<T: JsonlRecord, W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    options: SimpleFileOptions,
+    name: &str,
+    records: &[T],
+    fetched_at: DateTime<Utc>,
+    time: impl Fn(&T) -> DateTime<Utc>,
+) -> Result<ArchiveFile> {
+    zip.start_file(name, options)?;
+    let mut writer = JsonlWriter::new(&mut *zip);
+    for record in records {
+        writer.write_fetched_at(record, fetched_at)?;
+    }
+    writer.flush()?;
+    Ok(ArchiveFile {
+        name: name.to_string(),
+        kind: T::TYPE.to_string(),
+        records: records.len(),
+        first: records.iter().map(&time).min(),
+        last: records.iter().map(&time).max(),
+    })
+}
+
+/// Reads just the manifest of an archive.
+pub fn manifest(input: impl Read + Seek) -> Result<Manifest> {
+    read_manifest(&mut ZipArchive::new(input)?)
+}
+
+fn read_manifest<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Manifest> {
+    let mut contents = Vec::new();
+    match zip.by_name(MANIFEST) {
+        Ok(mut entry) => entry.read_to_end(&mut contents)?,
+        Err(zip::result::ZipError::FileNotFound) => {
+            return Err(WhoopError::Unknown(
+                format!("not a whoopsy archive: no {}", MANIFEST),
+                None,
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let manifest: Manifest = json::from_slice(&mut contents)?;
+    if manifest.schema_version > SCHEMA_VERSION {
+        return Err(WhoopError::Unknown(
+            format!(
+                "archive schema version {} is newer than the supported {}, written by {}",
+                manifest.schema_version, SCHEMA_VERSION, manifest.generator
+            ),
+            None,
+        ));
+    }
+    Ok(manifest)
+}
+
+/// Upserts every record of an archive into `store`, and moves its sync
+/// watermarks forward to the archive's where those are newer.
+///
+/// The whole archive is read and checked against its manifest before
+/// anything is written, so a truncated or tampered file is rejected up front.
+/// The writes aren't one transaction, though: a store failing partway keeps
+/// what was upserted before, and importing the archive again completes it.
+pub fn import(input: impl Read + Seek, store: &mut impl Store) -> Result<Manifest> {
+    let mut zip = ZipArchive::new(input)?;
+    let manifest = read_manifest(&mut zip)?;
+
+    let cycles: Vec<Cycle> = read_file(&mut zip, &manifest)?;
+    let sleeps: Vec<Sleep> = read_file(&mut zip, &manifest)?;
+    let recoveries: Vec<Recovery> = read_file(&mut zip, &manifest)?;
+    let workouts: Vec<WorkoutV2> = read_file(&mut zip, &manifest)?;
+    let measurements = read_envelopes::<UserBodyMeasurement, _>(&mut zip, &manifest)?;
+
+    store.upsert_cycles(&cycles)?;
+    store.upsert_sleeps(&sleeps)?;
+    store.upsert_recoveries(&recoveries)?;
+    store.upsert_workouts(&workouts)?;
+    for envelope in measurements {
+        store.insert_body_measurement(&BodySnapshot {
+            at: envelope.fetched_at,
+            measurement: envelope.payload,
+        })?;
+    }
+    for (resource, &watermark) in &manifest.watermarks {
+        if store.watermark(resource)?.is_none_or(|w| w < watermark) {
+            store.set_watermark(resource, watermark)?;
+        }
+    }
+    Ok(manifest)
+}
+
+fn read_file<T: JsonlRecord + DeserializeOwned, R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    manifest: &Manifest,
+) -> Result<Vec<T>> {
+    Ok(read_envelopes::<T, R>(zip, manifest)?
+        .into_iter()
+        .map(|e| e.payload)
+        .collect())
+}
+
+fn read_envelopes<T: JsonlRecord + DeserializeOwned, R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    manifest: &Manifest,
+) -> Result<Vec<Envelope<T>>> {
+    // Older archives may lack resources added since; that's no records.
+    let Some(file) = manifest.file(T::TYPE) else {
+        return Ok(Vec::new());
+    };
+    let entry = zip.by_name(&file.name)?;
+    let envelopes: Vec<Envelope<T>> = jsonl::read(BufReader::new(entry)).collect::<Result<_>>()?;
+    if envelopes.len() != file.records {
+        return Err(WhoopError::Unknown(
+            format!(
+                "{} holds {} records, its manifest says {}",
+                file.name,
+                envelopes.len(),
+                file.records
+            ),
+            None,
+        ));
+    }
+    Ok(envelopes)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::store::SqliteStore;
+    use std::io::Cursor;
+
+    fn store() -> SqliteStore {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store
+            .upsert_cycles(&[fixtures::cycle_scored(), fixtures::cycle_pending()])
+            .unwrap();
+        store.upsert_sleeps(&[fixtures::sleep_scored()]).unwrap();
+        store
+            .upsert_recoveries(&[fixtures::recovery_scored()])
+            .unwrap();
+        store
+            .upsert_workouts(&[fixtures::workout_scored()])
+            .unwrap();
+        store
+            .insert_body_measurement(&BodySnapshot {
+                at: "2024-03-01T00:00:00Z".parse().unwrap(),
+                measurement: fixtures::body_measurement(),
+            })
+            .unwrap();
+        store
+            .set_watermark("cycles", "2024-03-01T00:00:00Z".parse().unwrap())
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn test_round_trips_a_store() {
+        let mut bytes = Cursor::new(Vec::new());
+        let written = export(&store(), &mut bytes).unwrap();
+        assert_eq!(written.schema_version, SCHEMA_VERSION);
+        let cycles = written.file("cycle").unwrap();
+        assert_eq!(cycles.records, 2);
+        assert_eq!(cycles.first, Some(fixtures::cycle_scored().start));
+        assert_eq!(cycles.last, Some(fixtures::cycle_pending().start));
+
+        let mut copy = SqliteStore::open_in_memory().unwrap();
+        let read = import(Cursor::new(bytes.get_ref()), &mut copy).unwrap();
+        assert_eq!(read, written);
+        let all = DateTime::UNIX_EPOCH..Utc::now();
+        assert_eq!(copy.cycles_between(all.clone()).unwrap().len(), 2);
+        assert_eq!(copy.workouts_between(all).unwrap().len(), 1);
+        assert_eq!(
+            copy.body_measurements().unwrap(),
+            store().body_measurements().unwrap()
+        );
+        assert_eq!(
+            copy.watermark("cycles").unwrap(),
+            written.watermarks.get("cycles").copied()
+        );
+    }
+
+    #[test]
+    fn test_rejects_newer_or_truncated_archives() {
+        let rewrite = |edit: &dyn Fn(&mut Manifest)| {
+            let mut bytes = Cursor::new(Vec::new());
+            let mut manifest = export(&store(), &mut bytes).unwrap();
+            edit(&mut manifest);
+            // Rewrite the archive with the edited manifest.
+            let mut source = ZipArchive::new(Cursor::new(bytes.into_inner())).unwrap();
+            let mut out = ZipWriter::new(Cursor::new(Vec::new()));
+            for file in &manifest.files {
+                out.raw_copy_file(source.by_name(&file.name).unwrap())
+                    .unwrap();
+            }
+            out.start_file(MANIFEST, SimpleFileOptions::default())
+                .unwrap();
+            serde_json::to_writer(&mut out, &manifest).unwrap();
+            out.finish().unwrap()
+        };
+
+        let newer = rewrite(&|m| m.schema_version = SCHEMA_VERSION + 1);
+        let mut copy = SqliteStore::open_in_memory().unwrap();
+        assert!(import(newer, &mut copy).is_err());
+
+        let truncated = rewrite(&|m| m.files[0].records = 3);
+        assert!(import(truncated, &mut copy).is_err());
+        assert!(
+            copy.cycles_between(DateTime::UNIX_EPOCH..Utc::now())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
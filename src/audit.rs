@@ -0,0 +1,160 @@
+//! A record of every request a client sends, for accounting per user.
+//!
+//! A client with an [`AuditLog`] hands an [`AuditEntry`] to its [`AuditSink`]
+//! after each API request, e.g. a [`JsonAuditLog`] writing one JSON object per
+//! line, or a channel feeding something else. Entries carry the user the
+//! client belongs to, so one log can serve every client of a multi-user
+//! service:
+//!
+//! ```no_run
+//! use whoopsy::WhoopClient;
+//! use whoopsy::audit::{AuditLog, JsonAuditLog};
+//!
+//! # fn main() -> whoopsy::Result<()> {
+//! let log = AuditLog::new(JsonAuditLog::new(std::fs::File::create("audit.jsonl")?));
+//! let client = WhoopClient::new("token".to_string()).with_audit_log(log.for_user("42"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests answered by a sandbox or cassette don't reach the API and aren't
+//! logged.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// The header WHOOP reports the requests left in the current window in.
+pub const RATE_LIMIT_REMAINING: &str = "x-ratelimit-remaining";
+/// The header WHOOP reports the requests allowed in each window in.
+pub const RATE_LIMIT_LIMIT: &str = "x-ratelimit-limit";
+/// The header WHOOP reports the seconds until the window resets in.
+pub const RATE_LIMIT_RESET: &str = "x-ratelimit-reset";
+
+/// One API request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the request was sent.
+    pub at: DateTime<Utc>,
+    pub method: String,
+    /// The path, without the query.
+    pub endpoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// `None` when no response arrived.
+    pub status: Option<u16>,
+    /// Until the response headers arrived.
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_remaining: Option<u32>,
+}
+
+/// Where audit entries go. Recording must not block for long, as it runs on
+/// the request path.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, entry: &AuditEntry);
+}
+
+/// Writes each entry as a line of JSON.
+pub struct JsonAuditLog<W> {
+    out: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonAuditLog<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+}
+
+impl<W: Write + Send> AuditSink for JsonAuditLog<W> {
+    fn record(&self, entry: &AuditEntry) {
+        let Ok(mut line) = serde_json::to_vec(entry) else {
+            return;
+        };
+        line.push(b'\n');
+        let mut out = self.out.lock().unwrap();
+        // An audit log that can't be written must not fail the request.
+        let _ = out.write_all(&line).and_then(|()| out.flush());
+    }
+}
+
+/// Sends entries to a receiver, dropping them while the channel is full.
+impl AuditSink for mpsc::Sender<AuditEntry> {
+    fn record(&self, entry: &AuditEntry) {
+        let _ = self.try_send(entry.clone());
+    }
+}
+
+impl AuditSink for mpsc::UnboundedSender<AuditEntry> {
+    fn record(&self, entry: &AuditEntry) {
+        let _ = self.send(entry.clone());
+    }
+}
+
+/// An [`AuditSink`] shared by clients, with the user they log requests for.
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<dyn AuditSink>,
+    user: Option<String>,
+}
+
+impl AuditLog {
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            user: None,
+        }
+    }
+
+    /// The same log, attributing requests to `user`.
+    pub fn for_user(&self, user: impl Into<String>) -> Self {
+        Self {
+            sink: Arc::clone(&self.sink),
+            user: Some(user.into()),
+        }
+    }
+
+    pub(crate) fn record(&self, mut entry: AuditEntry) {
+        entry.user = self.user.clone();
+        self.sink.record(&entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_json_lines_tagged_with_the_user() {
+        let sink = Arc::new(JsonAuditLog::new(Vec::new()));
+        let log = AuditLog {
+            sink: sink.clone(),
+            user: None,
+        }
+        .for_user("42");
+        log.record(AuditEntry {
+            at: Utc::now(),
+            method: "GET".to_string(),
+            endpoint: "/v2/cycle".to_string(),
+            user: None,
+            status: Some(200),
+            latency_ms: 12,
+            rate_limit_remaining: Some(99),
+        });
+        drop(log);
+
+        let out = Arc::into_inner(sink).unwrap().into_inner();
+        let entry: AuditEntry = serde_json::from_slice(&out).unwrap();
+        assert_eq!(entry.user.as_deref(), Some("42"));
+        assert_eq!(entry.rate_limit_remaining, Some(99));
+        assert!(out.ends_with(b"\n"));
+    }
+}
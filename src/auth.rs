@@ -1,9 +1,12 @@
-use crate::error::{Result, WhoopError};
+use crate::error::{ApiErrorBody, Result, WhoopError};
+use crate::http;
+use crate::instrument::event;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 pub const AUTH_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/auth";
 pub const TOKEN_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/token";
+pub const REVOKE_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/revoke";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenResponse {
@@ -49,6 +52,7 @@ impl Scope {
 
     /// Parses a scope from its string representation.
     /// Returns None if the string doesn't match any known scope.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "read:recovery" => Some(Scope::ReadRecovery),
@@ -62,11 +66,18 @@ impl Scope {
     }
 }
 
+#[derive(Clone)]
 pub struct OAuthConfig {
     pub client_id: String,
     pub client_secret: String,
     pub redirect_uri: String,
     pub scopes: HashSet<Scope>,
+    /// Where codes and refresh tokens are exchanged. Defaults to [`TOKEN_URL`].
+    pub token_url: String,
+    /// Where tokens are revoked. Defaults to [`REVOKE_URL`].
+    pub revoke_url: String,
+    /// Shared with any [`WhoopClient`](crate::WhoopClient) built from this config.
+    pub(crate) http: reqwest::Client,
 }
 
 impl OAuthConfig {
@@ -78,9 +89,34 @@ impl OAuthConfig {
             client_secret,
             redirect_uri,
             scopes: HashSet::new(),
+            token_url: TOKEN_URL.to_string(),
+            revoke_url: REVOKE_URL.to_string(),
+            http: reqwest::Client::new(),
         }
     }
 
+    /// Exchanges tokens with a preconfigured HTTP client, e.g. one with a proxy
+    /// or timeouts. Clients built from this config use it for API calls too.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// Exchanges tokens somewhere other than WHOOP, e.g. a mock in tests.
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = token_url.into();
+        self
+    }
+
+    pub fn with_revoke_url(mut self, revoke_url: impl Into<String>) -> Self {
+        self.revoke_url = revoke_url.into();
+        self
+    }
+
     /// Adds a single scope to the OAuth request.
     /// Chain multiple calls to add more scopes.
     pub fn with_scope(mut self, scope: Scope) -> Self {
@@ -122,8 +158,6 @@ impl OAuthConfig {
     /// Exchanges an authorization code for access and refresh tokens.
     /// Call this after the user authorizes and you get the code from the callback.
     pub async fn exchange_code(&self, code: String) -> Result<TokenResponse> {
-        let client = reqwest::Client::new();
-
         let params = TokenRequest {
             grant_type: "authorization_code".to_string(),
             code: Some(code),
@@ -133,7 +167,8 @@ impl OAuthConfig {
             refresh_token: None,
         };
 
-        let response = client.post(TOKEN_URL).form(&params).send().await?;
+        let request = self.http.post(&self.token_url).form(&params).build()?;
+        let response = http::send(&self.http, request).await?;
 
         if response.status().is_success() {
             Ok(response.json::<TokenResponse>().await?)
@@ -142,15 +177,14 @@ impl OAuthConfig {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(WhoopError::AuthenticationError(msg))
+            let body = ApiErrorBody::parse(&msg).map(Box::new);
+            Err(WhoopError::AuthenticationError(msg, body))
         }
     }
 
     /// Gets a new access token using a refresh token.
     /// Use when the access token expires (usually after an hour).
     pub async fn refresh_token(&self, refresh_token: String) -> Result<TokenResponse> {
-        let client = reqwest::Client::new();
-
         let params = TokenRequest {
             grant_type: "refresh_token".to_string(),
             code: None,
@@ -160,16 +194,46 @@ impl OAuthConfig {
             refresh_token: Some(refresh_token),
         };
 
-        let response = client.post(TOKEN_URL).form(&params).send().await?;
+        let request = self.http.post(&self.token_url).form(&params).build()?;
+        let response = http::send(&self.http, request).await?;
 
         if response.status().is_success() {
+            event!(info, "refreshed access token");
             Ok(response.json::<TokenResponse>().await?)
+        } else {
+            event!(warn, "token refresh failed with {}", response.status());
+            let msg = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            let body = ApiErrorBody::parse(&msg).map(Box::new);
+            Err(WhoopError::AuthenticationError(msg, body))
+        }
+    }
+
+    /// Revokes a refresh token at the OAuth server, so it can't be used again.
+    /// Access tokens it issued stay valid until they expire; see
+    /// [`WhoopClient::revoke_oauth_access`](crate::WhoopClient::revoke_oauth_access).
+    pub async fn revoke_token(&self, refresh_token: &str) -> Result<()> {
+        let params = [
+            ("token", refresh_token),
+            ("token_type_hint", "refresh_token"),
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+        ];
+
+        let request = self.http.post(&self.revoke_url).form(&params).build()?;
+        let response = http::send(&self.http, request).await?;
+
+        if response.status().is_success() {
+            Ok(())
         } else {
             let msg = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(WhoopError::AuthenticationError(msg))
+            let body = ApiErrorBody::parse(&msg).map(Box::new);
+            Err(WhoopError::AuthenticationError(msg, body))
         }
     }
 }
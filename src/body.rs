@@ -0,0 +1,172 @@
+//! Body measurements over time.
+//!
+//! The API only reports a user's current height, weight and max heart rate.
+//! [`check`] fetches them and stores a [`BodySnapshot`] in a [`Store`]
+//! whenever they differ from the last one stored, so the store builds up a
+//! history to read trends from. [`poll`] does that on an interval, calling
+//! back with every [`BodyChange`].
+
+use crate::api::WhoopApi;
+use crate::error::Result;
+use crate::models::UserBodyMeasurement;
+use crate::store::Store;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A body measurement and when it was first seen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodySnapshot {
+    pub at: DateTime<Utc>,
+    pub measurement: UserBodyMeasurement,
+}
+
+/// A body measurement that differs from the one before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyChange {
+    /// `None` for the first measurement stored.
+    pub before: Option<BodySnapshot>,
+    pub after: BodySnapshot,
+}
+
+impl BodyChange {
+    /// Kilograms gained, negative when lost.
+    pub fn weight_change(&self) -> f32 {
+        self.before.as_ref().map_or(0.0, |before| {
+            self.after.measurement.weight_kilogram - before.measurement.weight_kilogram
+        })
+    }
+
+    /// Whether WHOOP updated the max heart rate.
+    pub fn max_heart_rate_changed(&self) -> bool {
+        self.before.as_ref().is_some_and(|before| {
+            before.measurement.max_heart_rate != self.after.measurement.max_heart_rate
+        })
+    }
+}
+
+/// How weight moved across a history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightTrend {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Kilograms between the first and last measurement.
+    pub change: f32,
+    /// The least squares slope, in kilograms a week.
+    pub per_week: f32,
+}
+
+/// The weight trend over `history`, oldest first as
+/// [`Store::body_measurements`] returns it. `None` with fewer than two
+/// measurements.
+pub fn weight_trend(history: &[BodySnapshot]) -> Option<WeightTrend> {
+    let (first, last) = (history.first()?, history.last()?);
+    if history.len() < 2 || first.at == last.at {
+        return None;
+    }
+
+    let weeks = |s: &BodySnapshot| (s.at - first.at).num_seconds() as f64 / (7.0 * 86_400.0);
+    let n = history.len() as f64;
+    let mean_x = history.iter().map(weeks).sum::<f64>() / n;
+    let mean_y = history
+        .iter()
+        .map(|s| f64::from(s.measurement.weight_kilogram))
+        .sum::<f64>()
+        / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for s in history {
+        let dx = weeks(s) - mean_x;
+        covariance += dx * (f64::from(s.measurement.weight_kilogram) - mean_y);
+        variance += dx * dx;
+    }
+
+    Some(WeightTrend {
+        from: first.at,
+        to: last.at,
+        change: last.measurement.weight_kilogram - first.measurement.weight_kilogram,
+        per_week: (covariance / variance) as f32,
+    })
+}
+
+/// Stores `measurement` as seen at `at` if it differs from the last one stored.
+pub fn record(
+    store: &impl Store,
+    measurement: UserBodyMeasurement,
+    at: DateTime<Utc>,
+) -> Result<Option<BodyChange>> {
+    let before = store.latest_body_measurement()?;
+    if before
+        .as_ref()
+        .is_some_and(|b| b.measurement == measurement)
+    {
+        return Ok(None);
+    }
+    let after = BodySnapshot { at, measurement };
+    store.insert_body_measurement(&after)?;
+    Ok(Some(BodyChange { before, after }))
+}
+
+/// Fetches the current measurement and [`record`]s it.
+pub async fn check<A: WhoopApi>(api: &A, store: &impl Store) -> Result<Option<BodyChange>> {
+    let measurement = api.get_body_measurement().await?;
+    record(store, measurement, Utc::now())
+}
+
+/// [`check`]s every `interval`, calling `on_change` with every change, until
+/// a check fails.
+pub async fn poll<A: WhoopApi>(
+    api: &A,
+    store: &impl Store,
+    interval: Duration,
+    mut on_change: impl FnMut(&BodyChange),
+) -> Result<()> {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        if let Some(change) = check(api, store).await? {
+            on_change(&change);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::store::SqliteStore;
+    use chrono::Duration;
+
+    fn body(weight_kilogram: f32, max_heart_rate: i32) -> UserBodyMeasurement {
+        UserBodyMeasurement {
+            height_meter: 1.8,
+            weight_kilogram,
+            max_heart_rate,
+        }
+    }
+
+    #[test]
+    fn test_records_only_changes_and_reads_the_trend() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let start: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+
+        let first = record(&store, body(80.0, 190), start).unwrap().unwrap();
+        assert_eq!(first.before, None);
+        assert!(
+            record(&store, body(80.0, 190), start + Duration::days(1))
+                .unwrap()
+                .is_none()
+        );
+
+        let change = record(&store, body(79.0, 188), start + Duration::weeks(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(change.weight_change(), -1.0);
+        assert!(change.max_heart_rate_changed());
+        record(&store, body(78.0, 188), start + Duration::weeks(2)).unwrap();
+
+        let history = store.body_measurements().unwrap();
+        assert_eq!(history.len(), 3);
+        let trend = weight_trend(&history).unwrap();
+        assert_eq!(trend.change, -2.0);
+        assert!((trend.per_week + 1.0).abs() < 1e-4);
+    }
+}
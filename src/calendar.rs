@@ -0,0 +1,346 @@
+//! Calendar days for records that don't line up with them.
+//!
+//! A WHOOP cycle runs from falling asleep to falling asleep again, so it
+//! usually starts the evening before the day it covers, and charting cycles by
+//! their start date shifts every late night onto the wrong day. A [`Calendar`]
+//! gives each record the local day it's about instead:
+//!
+//! - a cycle gets the day it starts on, or the next day if it starts at or
+//!   after the rollover time, 18:00 by default: bedtime belongs to tomorrow;
+//! - a sleep, nap or not, gets the day it ends on, the day it was slept for;
+//! - a workout gets the day it starts on, so one running past midnight counts
+//!   for the evening it began;
+//! - a recovery gets its cycle's day.
+//!
+//! Local time is each record's own `timezone_offset`, unless the calendar is
+//! pinned to one zone with [`Calendar::with_timezone`]. Aggregations in
+//! [`crate::aggregate`] and [`crate::analytics`] all use the default calendar.
+//!
+//! Travel bends these rules: a cycle that starts in a new zone can land on
+//! the same day as the one before it, or a day after the next. Given all the
+//! cycles, [`Calendar::cycle_days`] finds those days and, with
+//! [`TravelPolicy::Consecutive`], moves a doubled up cycle onto the free day
+//! after it.
+
+use crate::analytics::to_local;
+use crate::models::{Cycle, Recovery, Sleep, WorkoutV2};
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveTime, TimeDelta, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// How records map onto calendar days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calendar {
+    rollover: NaiveTime,
+    timezone: Option<FixedOffset>,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Self {
+            rollover: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+            timezone: None,
+        }
+    }
+}
+
+impl Calendar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cycles starting at or after `rollover` local time count for the next
+    /// day. Midnight makes every cycle count for the day it starts on.
+    pub fn with_rollover(mut self, rollover: NaiveTime) -> Self {
+        self.rollover = rollover;
+        self
+    }
+
+    /// Reads every record in `timezone` instead of its own offset, e.g. to
+    /// keep a traveller's chart on home time.
+    pub fn with_timezone(mut self, timezone: FixedOffset) -> Self {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    /// The local date of `time`, for a record logged at `offset`.
+    pub fn date(&self, time: DateTime<Utc>, offset: &str) -> NaiveDate {
+        self.local(time, offset).date_naive()
+    }
+
+    pub fn cycle_day(&self, cycle: &Cycle) -> NaiveDate {
+        let start = self.local(cycle.start, &cycle.timezone_offset);
+        let date = start.date_naive();
+        if self.rollover > NaiveTime::MIN && start.time() >= self.rollover {
+            date + Days::new(1)
+        } else {
+            date
+        }
+    }
+
+    pub fn sleep_day(&self, sleep: &Sleep) -> NaiveDate {
+        self.date(sleep.end, &sleep.timezone_offset)
+    }
+
+    pub fn workout_day(&self, workout: &WorkoutV2) -> NaiveDate {
+        self.date(workout.start, &workout.timezone_offset)
+    }
+
+    /// The day of the recovery's cycle, if it's among `cycles`.
+    pub fn recovery_day(&self, recovery: &Recovery, cycles: &[Cycle]) -> Option<NaiveDate> {
+        cycles
+            .iter()
+            .find(|c| c.id == recovery.cycle_id)
+            .map(|c| self.cycle_day(c))
+    }
+
+    /// `records` by day, oldest first.
+    pub fn group<'a, T: CalendarDay>(&self, records: &'a [T]) -> BTreeMap<NaiveDate, Vec<&'a T>> {
+        let mut days: BTreeMap<NaiveDate, Vec<&T>> = BTreeMap::new();
+        for record in records {
+            days.entry(record.calendar_day(self))
+                .or_default()
+                .push(record);
+        }
+        days
+    }
+
+    /// The day of each of `cycles`, checked for time zone changes between
+    /// back-to-back cycles.
+    pub fn cycle_days(&self, cycles: &[Cycle], policy: TravelPolicy) -> CycleDays {
+        let mut sorted: Vec<&Cycle> = cycles.iter().collect();
+        sorted.sort_by_key(|c| c.start);
+        let mut days: HashMap<i64, NaiveDate> =
+            sorted.iter().map(|c| (c.id, self.cycle_day(c))).collect();
+        let taken: HashSet<NaiveDate> = days.values().copied().collect();
+        let mut report = TravelReport::default();
+
+        for pair in sorted.windows(2) {
+            let (before, cycle) = (pair[0], pair[1]);
+            if before.timezone_offset == cycle.timezone_offset {
+                continue;
+            }
+            report.changes.push(TimezoneChange {
+                cycle_id: cycle.id,
+                at: cycle.start,
+                from: before.timezone_offset.clone(),
+                to: cycle.timezone_offset.clone(),
+            });
+            // A gap in the data isn't travel's doing.
+            let back_to_back = before
+                .end
+                .is_some_and(|end| (cycle.start - end).abs() <= TimeDelta::hours(1));
+            if !back_to_back {
+                continue;
+            }
+
+            let previous = days[&before.id];
+            let day = days[&cycle.id];
+            let next = previous + Days::new(1);
+            if day == previous {
+                if policy == TravelPolicy::Consecutive && !taken.contains(&next) {
+                    days.insert(cycle.id, next);
+                    report.affected.push(AffectedDay {
+                        date: day,
+                        issue: DayIssue::Moved {
+                            cycle_id: cycle.id,
+                            to: next,
+                        },
+                    });
+                } else {
+                    report.affected.push(AffectedDay {
+                        date: day,
+                        issue: DayIssue::Doubled {
+                            cycle_ids: vec![before.id, cycle.id],
+                        },
+                    });
+                }
+            } else if day > next {
+                for skipped in next.iter_days().take_while(|d| *d < day) {
+                    report.affected.push(AffectedDay {
+                        date: skipped,
+                        issue: DayIssue::Skipped {
+                            covered_by: before.id,
+                        },
+                    });
+                }
+            }
+        }
+        CycleDays { days, report }
+    }
+
+    fn local(&self, time: DateTime<Utc>, offset: &str) -> DateTime<FixedOffset> {
+        match self.timezone {
+            Some(timezone) => time.with_timezone(&timezone),
+            None => to_local(time, offset),
+        }
+    }
+}
+
+/// What to do when a time zone change puts two cycles on one day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TravelPolicy {
+    /// Every cycle keeps its own day; doubled days are only reported.
+    #[default]
+    Local,
+    /// The later cycle moves to the next day, if no cycle has it.
+    Consecutive,
+}
+
+/// Cycle days from [`Calendar::cycle_days`].
+#[derive(Debug, Clone, Default)]
+pub struct CycleDays {
+    days: HashMap<i64, NaiveDate>,
+    pub report: TravelReport,
+}
+
+impl CycleDays {
+    pub fn day(&self, cycle_id: i64) -> Option<NaiveDate> {
+        self.days.get(&cycle_id).copied()
+    }
+}
+
+/// Time zone changes in a run of cycles and the days they affected.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TravelReport {
+    /// Oldest first.
+    pub changes: Vec<TimezoneChange>,
+    /// In the order the changes happened.
+    pub affected: Vec<AffectedDay>,
+}
+
+/// The wearer's offset changing between two cycles.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimezoneChange {
+    /// The first cycle in the new zone.
+    pub cycle_id: i64,
+    pub at: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+}
+
+/// A day a time zone change got wrong.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AffectedDay {
+    pub date: NaiveDate,
+    pub issue: DayIssue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum DayIssue {
+    /// Both cycles count for the day.
+    Doubled { cycle_ids: Vec<i64> },
+    /// The cycle was moved off the day to `to`.
+    Moved { cycle_id: i64, to: NaiveDate },
+    /// No cycle starts the day; the one before runs through it.
+    Skipped { covered_by: i64 },
+}
+
+/// A record with a day of its own on a [`Calendar`].
+pub trait CalendarDay {
+    fn calendar_day(&self, calendar: &Calendar) -> NaiveDate;
+}
+
+impl CalendarDay for Cycle {
+    fn calendar_day(&self, calendar: &Calendar) -> NaiveDate {
+        calendar.cycle_day(self)
+    }
+}
+
+impl CalendarDay for Sleep {
+    fn calendar_day(&self, calendar: &Calendar) -> NaiveDate {
+        calendar.sleep_day(self)
+    }
+}
+
+impl CalendarDay for WorkoutV2 {
+    fn calendar_day(&self, calendar: &Calendar) -> NaiveDate {
+        calendar.workout_day(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_evening_cycles_count_for_the_next_day() {
+        let mut cycle = fixtures::cycle_collection().records.unwrap().remove(0);
+        cycle.timezone_offset = "-05:00".to_string();
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        // 22:30 local on the 1st.
+        cycle.start = "2024-03-02T03:30:00Z".parse().unwrap();
+        let calendar = Calendar::new();
+        assert_eq!(calendar.cycle_day(&cycle), date("2024-03-02"));
+        let midnight = Calendar::new().with_rollover(NaiveTime::MIN);
+        assert_eq!(midnight.cycle_day(&cycle), date("2024-03-01"));
+
+        // 00:30 local on the 2nd.
+        cycle.start = "2024-03-02T05:30:00Z".parse().unwrap();
+        assert_eq!(calendar.cycle_day(&cycle), date("2024-03-02"));
+
+        // 22:30 in Tokyo is still the morning of the 2nd back home.
+        let home = calendar.with_timezone(FixedOffset::west_opt(5 * 3600).unwrap());
+        cycle.timezone_offset = "+09:00".to_string();
+        cycle.start = "2024-03-02T13:30:00Z".parse().unwrap();
+        assert_eq!(calendar.cycle_day(&cycle), date("2024-03-03"));
+        assert_eq!(home.cycle_day(&cycle), date("2024-03-02"));
+    }
+
+    #[test]
+    fn test_finds_days_travel_doubled_or_skipped() {
+        let template = fixtures::cycle_collection().records.unwrap().remove(0);
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let cycle = |id, start: &str, end: &str, offset: &str| Cycle {
+            id,
+            start: start.parse().unwrap(),
+            end: Some(end.parse().unwrap()),
+            timezone_offset: offset.to_string(),
+            ..template.clone()
+        };
+        let calendar = Calendar::new();
+
+        // New York to Tokyo: a 34 hour cycle runs through the 3rd.
+        let east = [
+            cycle(1, "2024-03-02T03:30:00Z", "2024-03-03T13:30:00Z", "-05:00"),
+            cycle(2, "2024-03-03T13:30:00Z", "2024-03-04T13:30:00Z", "+09:00"),
+        ];
+        let days = calendar.cycle_days(&east, TravelPolicy::Consecutive);
+        assert_eq!(days.day(2), Some(date("2024-03-04")));
+        assert_eq!(days.report.changes.len(), 1);
+        assert_eq!(
+            days.report.affected,
+            [AffectedDay {
+                date: date("2024-03-03"),
+                issue: DayIssue::Skipped { covered_by: 1 },
+            }]
+        );
+
+        // Tokyo to New York, sleeping before the evening on landing.
+        let west = [
+            cycle(3, "2024-03-10T13:30:00Z", "2024-03-11T19:00:00Z", "+09:00"),
+            cycle(4, "2024-03-11T19:00:00Z", "2024-03-12T03:30:00Z", "-05:00"),
+            cycle(5, "2024-03-12T03:30:00Z", "2024-03-13T03:30:00Z", "-05:00"),
+        ];
+        let local = calendar.cycle_days(&west, TravelPolicy::Local);
+        assert_eq!(local.day(3), local.day(4));
+        assert_eq!(
+            local.report.affected[0].issue,
+            DayIssue::Doubled {
+                cycle_ids: vec![3, 4]
+            }
+        );
+        // The 12th is cycle 5's, so there's nowhere to move cycle 4.
+        let consecutive = calendar.cycle_days(&west[..2], TravelPolicy::Consecutive);
+        assert_eq!(consecutive.day(4), Some(date("2024-03-12")));
+        assert!(matches!(
+            consecutive.report.affected[0].issue,
+            DayIssue::Moved { cycle_id: 4, .. }
+        ));
+        let crowded = calendar.cycle_days(&west, TravelPolicy::Consecutive);
+        assert_eq!(crowded.day(4), Some(date("2024-03-11")));
+    }
+}
@@ -0,0 +1,239 @@
+//! Alerts and daily summaries posted to Slack or Discord incoming webhooks.
+//!
+//! A [`ChatNotifier`] is an alert [`Notifier`], and posts a day's numbers
+//! with [`post_day`](ChatNotifier::post_day). Messages are Slack attachments
+//! or Discord embeds, colored by the day's recovery zone; alerts are always
+//! red.
+//!
+//! ```no_run
+//! # async fn run(days: Vec<whoopsy::aggregate::DailySummary>) -> whoopsy::Result<()> {
+//! use whoopsy::alerts::{Alerts, Rule};
+//! use whoopsy::chat::ChatNotifier;
+//!
+//! let slack = ChatNotifier::slack("https://hooks.slack.com/services/T000/B000/XXXX");
+//! if let Some(today) = days.last() {
+//!     slack.post_day(today).await?;
+//! }
+//! let alerts = Alerts::new()
+//!     .with_rule(Rule::parse("recovery < 34").unwrap())
+//!     .with_notifier(slack);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::aggregate::DailySummary;
+use crate::alerts::{Alert, Notification, Notifier};
+use crate::analytics::RecoveryZone;
+use crate::error::{Result, WhoopError};
+use crate::format::{duration, rgb_color};
+use serde_json::{Value, json};
+
+/// Gray, for days without a recovery.
+const NO_ZONE_COLOR: u32 = 0x8A8A8A;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    Slack,
+    Discord,
+}
+
+/// What a message says, before it's shaped for a service.
+struct Card {
+    title: String,
+    text: String,
+    color: u32,
+    fields: Vec<(&'static str, String)>,
+}
+
+pub struct ChatNotifier {
+    http: reqwest::Client,
+    url: String,
+    service: Service,
+    username: Option<String>,
+}
+
+impl ChatNotifier {
+    pub fn new(service: Service, url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.into(),
+            service,
+            username: None,
+        }
+    }
+
+    pub fn slack(url: impl Into<String>) -> Self {
+        Self::new(Service::Slack, url)
+    }
+
+    pub fn discord(url: impl Into<String>) -> Self {
+        Self::new(Service::Discord, url)
+    }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// The name messages are posted as, instead of the webhook's own.
+    pub fn with_username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    pub async fn post_alert(&self, alert: &Alert) -> Result<()> {
+        self.post(&self.alert_payload(alert)).await
+    }
+
+    /// Posts the day's recovery, strain and sleep.
+    pub async fn post_day(&self, day: &DailySummary) -> Result<()> {
+        self.post(&self.day_payload(day)).await
+    }
+
+    async fn post(&self, payload: &Value) -> Result<()> {
+        let response = self.http.post(&self.url).json(payload).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(WhoopError::from_status(status, response.text().await.ok()));
+        }
+        Ok(())
+    }
+
+    fn alert_payload(&self, alert: &Alert) -> Value {
+        let mut fields = vec![
+            ("Date", alert.date.to_string()),
+            ("Value", format!("{:.1}", alert.value)),
+        ];
+        if let Some(user) = &alert.user {
+            fields.push(("User", user.clone()));
+        }
+        self.payload(Card {
+            title: format!("WHOOP alert: {}", alert.rule),
+            text: alert.message.clone(),
+            color: rgb_color(RecoveryZone::Red),
+            fields,
+        })
+    }
+
+    fn day_payload(&self, day: &DailySummary) -> Value {
+        let number = |value: Option<f32>, unit: &str| {
+            value.map_or("-".to_string(), |v| format!("{:.0}{}", v, unit))
+        };
+        let sleep = day.sleep_milli.map_or("-".to_string(), duration);
+        let text = match day.recovery_score {
+            Some(score) => format!("Recovery {:.0}%", score),
+            None => "No recovery yet".to_string(),
+        };
+        self.payload(Card {
+            title: format!("WHOOP {}", day.date),
+            text,
+            color: day
+                .recovery_score
+                .map_or(NO_ZONE_COLOR, |s| rgb_color(RecoveryZone::from_score(s))),
+            fields: vec![
+                ("HRV", number(day.hrv_rmssd_milli, " ms")),
+                ("Resting HR", number(day.resting_heart_rate, " bpm")),
+                (
+                    "Strain",
+                    day.strain.map_or("-".to_string(), |s| format!("{:.1}", s)),
+                ),
+                ("Sleep", sleep),
+                (
+                    "Sleep performance",
+                    number(day.sleep_performance_percentage, "%"),
+                ),
+            ],
+        })
+    }
+
+    fn payload(&self, card: Card) -> Value {
+        let mut payload = match self.service {
+            Service::Slack => json!({
+                "text": card.title,
+                "attachments": [{
+                    "color": format!("#{:06X}", card.color),
+                    "fallback": format!("{}: {}", card.title, card.text),
+                    "text": card.text,
+                    "fields": card.fields.iter().map(|(title, value)| json!({
+                        "title": title,
+                        "value": value,
+                        "short": true,
+                    })).collect::<Vec<_>>(),
+                }],
+            }),
+            Service::Discord => json!({
+                "embeds": [{
+                    "title": card.title,
+                    "description": card.text,
+                    "color": card.color,
+                    "fields": card.fields.iter().map(|(name, value)| json!({
+                        "name": name,
+                        "value": value,
+                        "inline": true,
+                    })).collect::<Vec<_>>(),
+                }],
+            }),
+        };
+        if let Some(username) = &self.username {
+            payload["username"] = json!(username);
+        }
+        payload
+    }
+}
+
+impl Notifier for ChatNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> Notification<'a> {
+        Box::pin(self.post_alert(alert))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn day(recovery: Option<f32>) -> DailySummary {
+        DailySummary {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            recovery_score: recovery,
+            hrv_rmssd_milli: Some(62.4),
+            strain: Some(12.34),
+            sleep_milli: Some(27_000_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_shapes_payloads_per_service() {
+        let slack = ChatNotifier::slack("http://localhost").with_username("whoopsy");
+        let payload = slack.day_payload(&day(Some(72.0)));
+        let attachment = &payload["attachments"][0];
+        assert_eq!(payload["username"], "whoopsy");
+        assert_eq!(attachment["color"], "#16EC06");
+        assert_eq!(attachment["text"], "Recovery 72%");
+        assert_eq!(attachment["fields"][0]["value"], "62 ms");
+        assert_eq!(attachment["fields"][3]["value"], "7h 30m");
+
+        let discord = ChatNotifier::discord("http://localhost");
+        let payload = discord.day_payload(&day(Some(20.0)));
+        let embed = &payload["embeds"][0];
+        assert!(payload.get("username").is_none());
+        assert_eq!(embed["color"], 0xFF0026);
+        assert_eq!(embed["fields"][2]["value"], "12.3");
+        assert_eq!(
+            discord.day_payload(&day(None))["embeds"][0]["color"],
+            NO_ZONE_COLOR
+        );
+
+        let alert = Alert {
+            rule: "recovery < 34".to_string(),
+            date: day(None).date,
+            value: 20.0,
+            message: "recovery < 34: 2024-03-01 is 20.0".to_string(),
+            user: Some("42".to_string()),
+        };
+        let payload = discord.alert_payload(&alert);
+        assert_eq!(payload["embeds"][0]["title"], "WHOOP alert: recovery < 34");
+        assert_eq!(payload["embeds"][0]["fields"][2]["value"], "42");
+    }
+}
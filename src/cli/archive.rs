@@ -0,0 +1,65 @@
+//! Backs the local database up to an archive, or restores one into it.
+
+use super::*;
+use clap::Subcommand;
+use std::fs::File;
+use whoopsy::Result;
+use whoopsy::archive::{self, Manifest};
+
+#[derive(Subcommand)]
+pub enum ArchiveCommand {
+    /// Writes every record in the database to a zip archive.
+    Export {
+        /// Where to write the archive.
+        path: PathBuf,
+
+        /// SQLite database to archive. Defaults to `whoopsy.db` next to the config file.
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+    /// Merges an archive into the database, e.g. on a new machine.
+    Import {
+        path: PathBuf,
+
+        /// SQLite database to import into. Defaults to `whoopsy.db` next to the config file.
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+}
+
+pub fn run(command: ArchiveCommand) -> Result<()> {
+    match command {
+        ArchiveCommand::Export { path, db } => {
+            let (store, _) = super::sync::open_store(db)?;
+            let manifest = archive::export(&store, File::create(&path)?)?;
+            println!("Wrote {}", path.display());
+            print_files(&manifest);
+        }
+        ArchiveCommand::Import { path, db } => {
+            let (mut store, db) = super::sync::open_store(db)?;
+            let manifest = archive::import(File::open(&path)?, &mut store)?;
+            println!(
+                "Imported {} from {} into {}",
+                path.display(),
+                manifest.generator,
+                db.display()
+            );
+            print_files(&manifest);
+        }
+    }
+    Ok(())
+}
+
+fn print_files(manifest: &Manifest) {
+    for file in &manifest.files {
+        print!("  {:<18} {:>6}", file.kind, file.records);
+        match (file.first, file.last) {
+            (Some(first), Some(last)) => println!(
+                "  {} to {}",
+                first.format("%Y-%m-%d"),
+                last.format("%Y-%m-%d")
+            ),
+            _ => println!(),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use super::dates::local_midnight;
+use super::*;
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use clap::{Args, ValueEnum};
+use whoopsy::Result;
+use whoopsy::format::duration;
+
+#[derive(Args)]
+pub struct CompareArgs {
+    /// Which periods to compare. The current period runs up to now and is compared
+    /// with the same span of the earlier one.
+    #[arg(value_enum, default_value_t = Preset::Week)]
+    preset: Preset,
+
+    /// Start of a custom period, overriding the preset.
+    #[arg(long, value_parser = parse_datetime, requires_all = ["to", "against_from", "against_to"])]
+    from: Option<DateTime<Utc>>,
+
+    /// End of the custom period.
+    #[arg(long, value_parser = parse_datetime, requires = "from")]
+    to: Option<DateTime<Utc>>,
+
+    /// Start of the custom period to compare against.
+    #[arg(long, value_parser = parse_datetime, requires = "from")]
+    against_from: Option<DateTime<Utc>>,
+
+    /// End of the custom period to compare against.
+    #[arg(long, value_parser = parse_datetime, requires = "from")]
+    against_to: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// This week against last week.
+    Week,
+    /// This month against last month.
+    Month,
+    /// This month against the same month last year.
+    YearOverYear,
+}
+
+struct Period {
+    label: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+pub async fn run(ctx: &Context, args: CompareArgs) -> Result<()> {
+    let (current, previous) = match (args.from, args.to, args.against_from, args.against_to) {
+        (Some(from), Some(to), Some(against_from), Some(against_to)) => (
+            Period {
+                label: format!("{} to {}", from.date_naive(), to.date_naive()),
+                start: from,
+                end: to,
+            },
+            Period {
+                label: format!(
+                    "{} to {}",
+                    against_from.date_naive(),
+                    against_to.date_naive()
+                ),
+                start: against_from,
+                end: against_to,
+            },
+        ),
+        _ => preset_periods(args.preset)?,
+    };
+
+    let a = ctx
+        .summarize(Some(current.start), Some(current.end))
+        .await?;
+    let b = ctx
+        .summarize(Some(previous.start), Some(previous.end))
+        .await?;
+
+    let workout_time = |s: &Summary| {
+        s.workouts_by_sport
+            .values()
+            .map(|w| w.total_duration_milli)
+            .sum::<i64>() as f64
+    };
+    let zone =
+        |s: &Summary, from: usize| s.zone_durations.as_array()[from..].iter().sum::<i64>() as f64;
+
+    let rows: Vec<(&str, Option<f64>, Option<f64>, Unit)> = vec![
+        (
+            "Avg recovery",
+            a.average_recovery.map(f64::from),
+            b.average_recovery.map(f64::from),
+            Unit::Percent,
+        ),
+        (
+            "Avg HRV (ms)",
+            a.average_hrv_milli.map(f64::from),
+            b.average_hrv_milli.map(f64::from),
+            Unit::Decimal,
+        ),
+        (
+            "Avg RHR (bpm)",
+            a.average_resting_heart_rate.map(f64::from),
+            b.average_resting_heart_rate.map(f64::from),
+            Unit::Decimal,
+        ),
+        (
+            "Total strain",
+            Some(a.total_strain.into()),
+            Some(b.total_strain.into()),
+            Unit::Decimal,
+        ),
+        (
+            "Avg strain",
+            a.average_strain.map(f64::from),
+            b.average_strain.map(f64::from),
+            Unit::Decimal,
+        ),
+        (
+            "Total sleep",
+            Some(a.total_sleep_milli as f64),
+            Some(b.total_sleep_milli as f64),
+            Unit::Duration,
+        ),
+        (
+            "Avg sleep performance",
+            a.average_sleep_performance.map(f64::from),
+            b.average_sleep_performance.map(f64::from),
+            Unit::Percent,
+        ),
+        (
+            "Sleep debt",
+            Some(a.sleep_debt_milli as f64),
+            Some(b.sleep_debt_milli as f64),
+            Unit::Duration,
+        ),
+        (
+            "Workouts",
+            Some(a.workout_count as f64),
+            Some(b.workout_count as f64),
+            Unit::Count,
+        ),
+        (
+            "Workout time",
+            Some(workout_time(&a)),
+            Some(workout_time(&b)),
+            Unit::Duration,
+        ),
+        (
+            "Time in zone 2",
+            Some(a.zone_durations.zone_two_milli as f64),
+            Some(b.zone_durations.zone_two_milli as f64),
+            Unit::Duration,
+        ),
+        (
+            "Time in zone 4+",
+            Some(zone(&a, 4)),
+            Some(zone(&b, 4)),
+            Unit::Duration,
+        ),
+    ];
+
+    println!(
+        "{:<24}{:>16}{:>16}{:>12}{:>10}",
+        "", current.label, previous.label, "Change", "%"
+    );
+    for (label, now, before, unit) in rows {
+        let delta = now.zip(before).map(|(n, b)| n - b);
+        let percent = now
+            .zip(before)
+            .filter(|(_, b)| *b != 0.0)
+            .map(|(n, b)| format!("{:+.0}%", (n - b) / b.abs() * 100.0));
+        println!(
+            "{:<24}{:>16}{:>16}{:>12}{:>10}",
+            label,
+            unit.format(now),
+            unit.format(before),
+            unit.format_delta(delta),
+            percent.unwrap_or_else(|| "-".to_string())
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Percent,
+    Decimal,
+    Count,
+    Duration,
+}
+
+impl Unit {
+    fn format(self, value: Option<f64>) -> String {
+        let Some(value) = value else {
+            return "-".to_string();
+        };
+        match self {
+            Unit::Percent => format!("{:.0}%", value),
+            Unit::Decimal => format!("{:.1}", value),
+            Unit::Count => format!("{:.0}", value),
+            Unit::Duration => duration(value as i64),
+        }
+    }
+
+    fn format_delta(self, value: Option<f64>) -> String {
+        let Some(value) = value else {
+            return "-".to_string();
+        };
+        match self {
+            Unit::Percent => format!("{:+.0} pts", value),
+            Unit::Decimal => format!("{:+.1}", value),
+            Unit::Count => format!("{:+.0}", value),
+            Unit::Duration if value < 0.0 => format!("-{}", duration(-value as i64)),
+            Unit::Duration => format!("+{}", duration(value as i64)),
+        }
+    }
+}
+
+/// Works out the current period so far and the matching span of the earlier period.
+fn preset_periods(preset: Preset) -> Result<(Period, Period)> {
+    let now = Utc::now();
+    let today = Local::now().date_naive();
+
+    let (current_start, previous_start, current_label, previous_label) = match preset {
+        Preset::Week => {
+            let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+            let last_monday = monday - Days::new(7);
+            (
+                monday,
+                last_monday,
+                "This week".to_string(),
+                "Last week".to_string(),
+            )
+        }
+        Preset::Month | Preset::YearOverYear => {
+            let first = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap();
+            let months = if preset == Preset::Month { 1 } else { 12 };
+            let earlier = first - Months::new(months);
+            (
+                first,
+                earlier,
+                first.format("%b %Y").to_string(),
+                earlier.format("%b %Y").to_string(),
+            )
+        }
+    };
+
+    let invalid = || WhoopError::Unknown("local midnight doesn't exist".to_string(), None);
+    let current_start = local_midnight(current_start).ok_or_else(invalid)?;
+    let previous_start = local_midnight(previous_start).ok_or_else(invalid)?;
+    let previous_end = (previous_start + (now - current_start)).min(current_start);
+
+    Ok((
+        Period {
+            label: current_label,
+            start: current_start,
+            end: now,
+        },
+        Period {
+            label: previous_label,
+            start: previous_start,
+            end: previous_end,
+        },
+    ))
+}
@@ -0,0 +1,305 @@
+use super::export::Format;
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+#[cfg(feature = "email")]
+use whoopsy::email::SmtpSettings;
+use whoopsy::{Result, TokenResponse, WhoopError};
+
+/// Shown in place of the client secret.
+const REDACTED: &str = "********";
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Prints one setting, or all of them when no key is given.
+    Get { key: Option<String> },
+    /// Changes a setting and saves the config file.
+    Set { key: String, value: String },
+    /// Prints where the config file lives.
+    Path,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl From<Units> for whoopsy::export::Units {
+    fn from(units: Units) -> Self {
+        match units {
+            Units::Metric => Self::Metric,
+            Units::Imperial => Self::Imperial,
+        }
+    }
+}
+
+/// Settings read from `~/.config/whoopsy/config.toml`.
+/// Every field is optional so a missing or partial file just falls back to defaults.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<Format>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_store: Option<PathBuf>,
+    /// Rules like `recovery < 34`, checked after every `sync`; see
+    /// [`whoopsy::alerts::Rule::parse`].
+    #[cfg(feature = "analytics")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<String>,
+    // The tables below are edited in the file; `config set` doesn't reach into them.
+    #[cfg(feature = "email")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<SmtpSettings>,
+    #[cfg(feature = "analytics")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ntfy: Option<NtfyConfig>,
+    #[cfg(feature = "analytics")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pushover: Option<PushoverConfig>,
+}
+
+// Hand-written so the client secret stays out of logs.
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut config = f.debug_struct("Config");
+        config
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| REDACTED),
+            )
+            .field("output_format", &self.output_format)
+            .field("units", &self.units)
+            .field("page_size", &self.page_size)
+            .field("token_store", &self.token_store);
+        #[cfg(feature = "analytics")]
+        config.field("alerts", &self.alerts);
+        #[cfg(feature = "email")]
+        config.field("email", &self.email);
+        #[cfg(feature = "analytics")]
+        config
+            .field("ntfy", &self.ntfy)
+            .field("pushover", &self.pushover);
+        config.finish()
+    }
+}
+
+/// The `[ntfy]` table.
+#[cfg(feature = "analytics")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NtfyConfig {
+    pub topic: String,
+    /// Defaults to `https://ntfy.sh`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// The `[pushover]` table.
+#[cfg(feature = "analytics")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushoverConfig {
+    /// The application's API token.
+    pub token: String,
+    /// The user or group key.
+    pub user: String,
+}
+
+impl Config {
+    const KEYS: [&'static str; 6] = [
+        "client_id",
+        "client_secret",
+        "output_format",
+        "units",
+        "page_size",
+        "token_store",
+    ];
+
+    /// Directory holding the config file and, by default, the token store.
+    /// Honors `XDG_CONFIG_HOME` and falls back to `~/.config/whoopsy`.
+    pub fn dir() -> PathBuf {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_else(|| PathBuf::from("."));
+        base.join("whoopsy")
+    }
+
+    pub fn path() -> PathBuf {
+        Self::dir().join("config.toml")
+    }
+
+    /// Loads the config file, returning defaults when it doesn't exist yet.
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| {
+                WhoopError::Unknown(
+                    format!("invalid config file {}: {}", path.display(), e),
+                    None,
+                )
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| WhoopError::Unknown(format!("failed to write config: {}", e), None))?;
+        std::fs::create_dir_all(Self::dir())?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// The `[email]` table, or an error naming it when it's missing.
+    #[cfg(feature = "email")]
+    pub fn email(&self) -> Result<&SmtpSettings> {
+        self.email.as_ref().ok_or_else(|| {
+            WhoopError::BadRequest(
+                format!("no [email] table in {}", Self::path().display()),
+                None,
+            )
+        })
+    }
+
+    /// Where OAuth tokens are persisted.
+    pub fn token_store(&self) -> PathBuf {
+        self.token_store
+            .clone()
+            .unwrap_or_else(|| Self::dir().join("token.json"))
+    }
+
+    /// Writes `token` to the token store, replacing the one there.
+    pub fn save_token(&self, token: &TokenResponse) -> Result<()> {
+        let path = self.token_store();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(token)?)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let value = match key {
+            "client_id" => self.client_id.clone(),
+            "client_secret" => self.client_secret.as_ref().map(|_| REDACTED.to_string()),
+            "output_format" => self.output_format.map(|f| f.extension().to_string()),
+            "units" => self.units.map(|u| format!("{:?}", u).to_lowercase()),
+            "page_size" => self.page_size.map(|n| n.to_string()),
+            "token_store" => Some(self.token_store().display().to_string()),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    fn set(&mut self, key: &str, value: String) -> Result<()> {
+        let invalid =
+            |e: String| WhoopError::BadRequest(format!("invalid value for {}: {}", key, e), None);
+
+        match key {
+            "client_id" => self.client_id = Some(value),
+            "client_secret" => self.client_secret = Some(value),
+            "output_format" => {
+                self.output_format = Some(Format::from_str(&value, true).map_err(invalid)?)
+            }
+            "units" => self.units = Some(Units::from_str(&value, true).map_err(invalid)?),
+            "page_size" => {
+                let size: i32 = value.parse().map_err(|e| invalid(format!("{}", e)))?;
+                if !(1..=25).contains(&size) {
+                    return Err(invalid("must be between 1 and 25".to_string()));
+                }
+                self.page_size = Some(size);
+            }
+            "token_store" => self.token_store = Some(PathBuf::from(value)),
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_key(key: &str) -> WhoopError {
+    WhoopError::BadRequest(
+        format!(
+            "unknown config key '{}', expected one of: {}",
+            key,
+            Config::KEYS.join(", ")
+        ),
+        None,
+    )
+}
+
+pub fn run(command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Get { key: Some(key) } => {
+            println!("{}", Config::load()?.get(&key)?.unwrap_or_default());
+        }
+        ConfigCommand::Get { key: None } => {
+            let config = Config::load()?;
+            for key in Config::KEYS {
+                if let Some(value) = config.get(key)? {
+                    println!("{} = {}", key, value);
+                }
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut config = Config::load()?;
+            config.set(&key, value)?;
+            config.save()?;
+        }
+        ConfigCommand::Path => println!("{}", Config::path().display()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_redacts_the_client_secret() {
+        let config = Config {
+            client_id: Some("id".to_string()),
+            client_secret: Some("hunter2".to_string()),
+            ..Config::default()
+        };
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("\"id\""));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_saves_tokens_to_the_token_store() {
+        let dir = std::env::temp_dir().join(format!("whoopsy-config-{}", uuid::Uuid::new_v4()));
+        let config = Config {
+            token_store: Some(dir.join("token.json")),
+            ..Config::default()
+        };
+        let token = TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: Some("refresh".to_string()),
+            scope: None,
+        };
+        config.save_token(&token).unwrap();
+        let saved: TokenResponse =
+            serde_json::from_str(&std::fs::read_to_string(config.token_store()).unwrap()).unwrap();
+        assert_eq!(saved.refresh_token.as_deref(), Some("refresh"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+//! Keeps the local database synced, and mails reports, until Ctrl-C.
+//!
+//! Every sync checks the config's alert rules, as `sync` does; an alert is
+//! sent once per rule and day for as long as the daemon runs. With the
+//! `email` feature, `--report` mails the previous week's report.
+
+use super::*;
+use clap::Args;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use whoopsy::Result;
+use whoopsy::schedule::{Schedule, Scheduler};
+use whoopsy::sync::Delta;
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// SQLite database to sync into. Defaults to `whoopsy.db` next to the config file.
+    #[arg(long)]
+    db: Option<PathBuf>,
+
+    /// When to sync: an interval like `every 1h`, or a cron expression.
+    #[arg(long, value_parser = parse_schedule, default_value = "every 1h")]
+    sync: Schedule,
+
+    /// When to mail the previous week's report, e.g. `0 7 * * 1` for Mondays at 7.
+    #[cfg(feature = "email")]
+    #[arg(long, value_parser = parse_schedule)]
+    report: Option<Schedule>,
+}
+
+fn parse_schedule(s: &str) -> std::result::Result<Schedule, String> {
+    Schedule::parse(s).ok_or_else(|| {
+        format!(
+            "invalid schedule '{}', expected e.g. 'every 1h' or '0 7 * * 1'",
+            s
+        )
+    })
+}
+
+pub async fn run(ctx: Context, args: DaemonArgs) -> Result<()> {
+    let ctx = Arc::new(ctx);
+    let (store, path) = super::sync::open_store(args.db)?;
+    let store = Arc::new(Mutex::new(store));
+    let delta = Delta::new();
+    #[cfg(feature = "analytics")]
+    let delta = match super::sync::configured_alerts(&ctx.config)? {
+        Some(alerts) => delta.with_alerts(Arc::new(alerts)),
+        None => delta,
+    };
+    let delta = Arc::new(delta);
+
+    let sync_ctx = Arc::clone(&ctx);
+    let scheduler = Scheduler::new().with_task("sync", args.sync, move || {
+        let ctx = Arc::clone(&sync_ctx);
+        let store = Arc::clone(&store);
+        let delta = Arc::clone(&delta);
+        async move {
+            let mut store = store.lock().await;
+            let applied = delta.run(&*ctx.client, &mut *store).await?;
+            println!("Synced, {} records changed", applied.changed);
+            Ok(())
+        }
+    });
+
+    #[cfg(feature = "email")]
+    let scheduler = match args.report {
+        Some(schedule) => {
+            use chrono::{Days, Local};
+
+            let notifier = Arc::new(whoopsy::email::EmailNotifier::new(ctx.config.email()?)?);
+            scheduler.with_task("report", schedule, move || {
+                let ctx = Arc::clone(&ctx);
+                let notifier = Arc::clone(&notifier);
+                async move {
+                    let last_week = Local::now().date_naive() - Days::new(7);
+                    let report = super::report::build(&ctx, last_week, false).await?;
+                    notifier.send_report(&report).await?;
+                    println!("Mailed {}", report.title);
+                    Ok(())
+                }
+            })
+        }
+        None => scheduler,
+    };
+
+    println!("Syncing into {}, press Ctrl-C to stop", path.display());
+    let reports = scheduler
+        .run(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await;
+    for report in reports {
+        print!(
+            "{}: {} runs, {} failed",
+            report.name, report.runs, report.failures
+        );
+        match report.last_error {
+            Some(error) => println!(", last with: {}", error),
+            None => println!(),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeZone, Utc, Weekday};
+
+/// Parses a `--start`/`--end` value, resolving relative dates in the local timezone.
+///
+/// Accepts RFC 3339 timestamps, `YYYY-MM-DD`, `YYYY-MM`, `now`, `today`, `yesterday`,
+/// weekday names like `monday` or `last monday`, and offsets like `12h`, `7d` or `2w`.
+pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, String> {
+    resolve(s, Local::now())
+}
+
+fn resolve<Tz: TimeZone>(s: &str, now: DateTime<Tz>) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s.trim()) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    let input = s.trim().to_lowercase();
+
+    let tz = now.timezone();
+    let today = now.date_naive();
+
+    let date = match input.as_str() {
+        "now" => return Ok(now.with_timezone(&Utc)),
+        "today" => Some(today),
+        "yesterday" => today.pred_opt(),
+        _ => None,
+    };
+
+    let date = date
+        .or_else(|| NaiveDate::parse_from_str(&input, "%Y-%m-%d").ok())
+        .or_else(|| NaiveDate::parse_from_str(&format!("{}-01", input), "%Y-%m-%d").ok())
+        .or_else(|| {
+            let weekday = input.strip_prefix("last ").unwrap_or(&input);
+            weekday
+                .parse::<Weekday>()
+                .ok()
+                .map(|weekday| last_weekday(today, weekday))
+        });
+
+    if let Some(date) = date {
+        return start_of_day(&tz, date).ok_or_else(|| format!("'{}' doesn't exist locally", s));
+    }
+
+    if let Some(offset) = parse_offset(&input) {
+        return now
+            .with_timezone(&Utc)
+            .checked_sub_signed(offset)
+            .ok_or_else(|| format!("'{}' is too far back", s));
+    }
+
+    Err(format!(
+        "invalid date '{}', expected e.g. 2024-03-01, 2024-03, yesterday, last monday or 7d",
+        s
+    ))
+}
+
+/// The most recent `weekday` strictly before `today`.
+fn last_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    let back = if back == 0 { 7 } else { back };
+    today - Days::new(back as u64)
+}
+
+/// Midnight at the start of `date` in the local timezone.
+pub fn local_midnight(date: NaiveDate) -> Option<DateTime<Utc>> {
+    start_of_day(&Local, date)
+}
+
+fn start_of_day<Tz: TimeZone>(tz: &Tz, date: NaiveDate) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Parses relative offsets like `12h`, `7d` or `2w` into how far back to go.
+fn parse_offset(s: &str) -> Option<chrono::Duration> {
+    let (split, unit) = s.char_indices().next_back()?;
+    let number: i64 = s[..split].parse().ok()?;
+
+    match unit {
+        'h' => chrono::Duration::try_hours(number),
+        'd' => chrono::Duration::try_days(number),
+        'w' => chrono::Duration::try_weeks(number),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn now() -> DateTime<FixedOffset> {
+        // A Wednesday afternoon two hours east of UTC.
+        DateTime::parse_from_rfc3339("2024-03-13T15:30:00+02:00").unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_resolves_relative_days_in_local_time() {
+        assert_eq!(resolve("today", now()), Ok(utc("2024-03-12T22:00:00Z")));
+        assert_eq!(resolve("yesterday", now()), Ok(utc("2024-03-11T22:00:00Z")));
+        assert_eq!(
+            resolve("last monday", now()),
+            Ok(utc("2024-03-10T22:00:00Z"))
+        );
+        assert_eq!(resolve("Wednesday", now()), Ok(utc("2024-03-05T22:00:00Z")));
+        assert_eq!(resolve("2024-03", now()), Ok(utc("2024-02-29T22:00:00Z")));
+        assert_eq!(
+            resolve("2024-03-01", now()),
+            Ok(utc("2024-02-29T22:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_resolves_offsets_and_timestamps() {
+        assert_eq!(resolve("7d", now()), Ok(utc("2024-03-06T13:30:00Z")));
+        assert_eq!(resolve("12h", now()), Ok(utc("2024-03-13T01:30:00Z")));
+        assert_eq!(
+            resolve("2024-01-01T00:00:00Z", now()),
+            Ok(utc("2024-01-01T00:00:00Z"))
+        );
+        assert!(resolve("someday", now()).is_err());
+        assert!(resolve("7ü", now()).is_err());
+        assert_eq!(
+            resolve("99999999w", now()),
+            Err("'99999999w' is too far back".to_string())
+        );
+    }
+}
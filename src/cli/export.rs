@@ -0,0 +1,121 @@
+use super::*;
+use chrono::{DateTime, Utc};
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use whoopsy::export::csv::{CsvOptions, CsvRecord, CsvWriter};
+use whoopsy::export::jsonl::{JsonlRecord, JsonlWriter};
+use whoopsy::export::pipeline::{JsonArrayWriter, Pipeline};
+use whoopsy::models::*;
+use whoopsy::pagination::Paged;
+use whoopsy::sport;
+use whoopsy::{Pages, Result, WhoopClient};
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Only export records starting at or after this date, e.g. `2024-03`, `last monday` or `7d`.
+    #[arg(long, value_parser = parse_datetime)]
+    start: Option<DateTime<Utc>>,
+
+    /// Only export records starting before this date.
+    #[arg(long, value_parser = parse_datetime)]
+    end: Option<DateTime<Utc>>,
+
+    /// File format to write. Defaults to `output_format` from the config, then CSV.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Directory the export files are written to.
+    #[arg(long, short, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Resources to export. Exports everything when omitted.
+    #[arg(long = "resource", value_enum)]
+    resources: Vec<Resource>,
+
+    /// Only export workouts of these sports.
+    #[arg(long = "sport", value_parser = SportNameParser, hide_possible_values = true)]
+    sports: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Json,
+    Jsonl,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+pub async fn run(ctx: &Context, args: ExportArgs) -> Result<()> {
+    let format = args
+        .format
+        .or(ctx.config.output_format)
+        .unwrap_or(Format::Csv);
+    let options = CsvOptions::default().with_units(ctx.config.units.unwrap_or_default().into());
+    std::fs::create_dir_all(&args.output_dir)?;
+
+    for resource in Resource::or_all(&args.resources) {
+        let path = args
+            .output_dir
+            .join(resource.name())
+            .with_extension(format.extension());
+
+        let count = match resource {
+            Resource::Cycles => {
+                let pages = ctx.pages::<Cycle>(args.start, args.end);
+                export(Pipeline::new(), pages, &path, format, options).await?
+            }
+            Resource::Sleep => {
+                let pages = ctx.pages::<Sleep>(args.start, args.end);
+                export(Pipeline::new(), pages, &path, format, options).await?
+            }
+            Resource::Recovery => {
+                let pages = ctx.pages::<Recovery>(args.start, args.end);
+                export(Pipeline::new(), pages, &path, format, options).await?
+            }
+            Resource::Workouts => {
+                let mut pipeline = Pipeline::new();
+                if !args.sports.is_empty() {
+                    let sports: Vec<_> = args.sports.iter().map(|s| sport::normalize(s)).collect();
+                    pipeline = pipeline.with_filter(move |w: &WorkoutV2| {
+                        sports.contains(&sport::normalize(&w.sport_name))
+                    });
+                }
+                let pages = ctx.pages::<WorkoutV2>(args.start, args.end);
+                export(pipeline, pages, &path, format, options).await?
+            }
+        };
+
+        println!("Wrote {} records to {}", count, path.display());
+    }
+
+    Ok(())
+}
+
+/// Streams pages to `path` in the given format and returns how many records were written.
+async fn export<T: Paged + CsvRecord + JsonlRecord>(
+    pipeline: Pipeline<T>,
+    pages: Pages<'_, WhoopClient, T>,
+    path: &Path,
+    format: Format,
+    options: CsvOptions,
+) -> Result<usize> {
+    let out = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Json => pipeline.run(pages, &mut JsonArrayWriter::new(out)).await,
+        Format::Jsonl => pipeline.run(pages, &mut JsonlWriter::new(out)).await,
+        Format::Csv => pipeline.run(pages, &mut CsvWriter::new(out, options)).await,
+    }
+}
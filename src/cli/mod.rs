@@ -0,0 +1,256 @@
+#[cfg(feature = "zip")]
+pub mod archive;
+pub mod compare;
+pub mod config;
+pub mod daemon;
+pub mod dates;
+pub mod export;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+pub mod report;
+pub mod sync;
+pub mod tui;
+pub mod watch;
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use clap::builder::{PossibleValue, StringValueParser, TypedValueParser};
+use config::Config;
+pub use dates::parse_datetime;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use whoopsy::pagination::{MAX_PAGE_SIZE, Paged};
+use whoopsy::sandbox::Sandbox;
+use whoopsy::sport::Sport;
+use whoopsy::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Resource {
+    Cycles,
+    Sleep,
+    Recovery,
+    Workouts,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 4] = [
+        Resource::Cycles,
+        Resource::Sleep,
+        Resource::Recovery,
+        Resource::Workouts,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Cycles => "cycles",
+            Resource::Sleep => "sleep",
+            Resource::Recovery => "recovery",
+            Resource::Workouts => "workouts",
+        }
+    }
+
+    /// Returns the selected resources, or all of them when none were picked.
+    pub fn or_all(selected: &[Resource]) -> Vec<Resource> {
+        if selected.is_empty() {
+            Resource::ALL.to_vec()
+        } else {
+            selected.to_vec()
+        }
+    }
+}
+
+/// Accepts any sport name, but advertises the known ones to shell completion.
+/// WHOOP adds sports over time, so unknown names are still passed through.
+#[derive(Clone)]
+pub struct SportNameParser;
+
+impl TypedValueParser for SportNameParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> std::result::Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            Sport::ALL
+                .iter()
+                .map(|sport| PossibleValue::new(sport.name())),
+        ))
+    }
+}
+
+/// Shared state handed to every subcommand that talks to the API.
+pub struct Context {
+    /// Shared, so long-running work like a backfill can hold on to it.
+    pub client: Arc<WhoopClient>,
+    pub config: Config,
+}
+
+impl Context {
+    /// Builds a client from the `--token` flag or `WHOOP_ACCESS_TOKEN`,
+    /// falling back to the token saved in the configured token store.
+    /// With `--sandbox`, no token is needed.
+    pub async fn new(
+        token: Option<String>,
+        sandbox: Option<PathBuf>,
+        config: Config,
+    ) -> Result<Self> {
+        if let Some(dir) = sandbox {
+            let client = WhoopClient::new(String::new()).with_sandbox(Sandbox::open(dir)?);
+            return Ok(Self {
+                client: Arc::new(client),
+                config,
+            });
+        }
+
+        let token = match token {
+            Some(token) => token,
+            None => Self::stored_token(&config).await?,
+        };
+
+        Ok(Self {
+            client: Arc::new(WhoopClient::new(token)),
+            config,
+        })
+    }
+
+    /// Reads the token store, refreshing an expired token with the
+    /// configured client id and secret and saving the new one.
+    async fn stored_token(config: &Config) -> Result<String> {
+        let path = config.token_store();
+        let (token, saved_at) = match std::fs::read_to_string(&path) {
+            Ok(contents) => (
+                serde_json::from_str::<TokenResponse>(&contents)?,
+                std::fs::metadata(&path)?.modified()?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(WhoopError::AuthenticationError(
+                    "No access token given, pass --token or set WHOOP_ACCESS_TOKEN".to_string(),
+                    None,
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let lifetime = Duration::from_secs(token.expires_in.unwrap_or(3600).max(0) as u64);
+        let expired = saved_at.elapsed().is_ok_and(|age| age >= lifetime);
+        match (
+            &config.client_id,
+            &config.client_secret,
+            &token.refresh_token,
+        ) {
+            (Some(id), Some(secret), Some(refresh_token)) if expired => {
+                let oauth = OAuthConfig::new(id.clone(), secret.clone(), String::new());
+                let fresh = oauth.refresh_token(refresh_token.clone()).await?;
+                config.save_token(&fresh)?;
+                Ok(fresh.access_token)
+            }
+            _ => Ok(token.access_token),
+        }
+    }
+
+    fn page_size(&self) -> i32 {
+        self.config
+            .page_size
+            .unwrap_or(MAX_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// The pages of one collection, as large as the config allows.
+    pub fn pages<T: Paged>(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Pages<'_, WhoopClient, T> {
+        Pages::new(&*self.client)
+            .with_range(start, end)
+            .with_limit(self.page_size())
+    }
+
+    /// Fetches everything in a range and summarizes it.
+    pub async fn summarize(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Summary> {
+        let cycles = self.pages(start, end).collect_all().await?;
+        let recoveries = self.pages(start, end).collect_all().await?;
+        let sleeps = self.pages(start, end).collect_all().await?;
+        let workouts = self.pages(start, end).collect_all().await?;
+        Ok(Summary::new(&cycles, &recoveries, &sleeps, &workouts))
+    }
+}
+
+/// Parses durations like `30s`, `15m`, `2h` or `1d`.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 15m", s))?;
+
+    let seconds = match unit {
+        "s" | "" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown duration unit '{}', use s, m, h or d",
+                unit
+            ));
+        }
+    };
+
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{}' is too long", s))
+}
+
+/// The furthest back `watch` looks for updated records.
+const MAX_LOOKBACK: Duration = Duration::from_secs(3650 * 24 * 60 * 60);
+
+/// Parses a lookback like [`parse_duration`], up to ten years.
+pub fn parse_lookback(s: &str) -> std::result::Result<Duration, String> {
+    match parse_duration(s)? {
+        lookback if lookback > MAX_LOOKBACK => {
+            Err(format!("lookback '{}' is longer than ten years", s.trim()))
+        }
+        lookback => Ok(lookback),
+    }
+}
+
+/// Parses a polling interval like [`parse_duration`], rejecting zero.
+pub fn parse_interval(s: &str) -> std::result::Result<Duration, String> {
+    match parse_duration(s)? {
+        Duration::ZERO => Err(format!("interval '{}' must be longer than zero", s.trim())),
+        interval => Ok(interval),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_durations_and_rejects_bad_intervals() {
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("0s"), Ok(Duration::ZERO));
+        assert!(parse_duration("99999999999999999d").is_err());
+        assert!(parse_duration("3w").is_err());
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_interval("0s").is_err());
+        assert_eq!(parse_lookback("2d"), Ok(Duration::from_secs(172_800)));
+        assert!(parse_lookback("99999999d").is_err());
+    }
+}
@@ -0,0 +1,34 @@
+//! Serves WHOOP data to local tools over HTTP, from the database.
+
+use super::*;
+use clap::Args;
+use std::net::SocketAddr;
+use whoopsy::Result;
+use whoopsy::proxy::Proxy;
+
+#[derive(Args)]
+pub struct ProxyArgs {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8765")]
+    addr: SocketAddr,
+
+    /// SQLite database to answer from. Defaults to `whoopsy.db` next to the config file.
+    #[arg(long)]
+    db: Option<PathBuf>,
+
+    /// How long fetched data is answered from the database before it's fetched again.
+    #[arg(long, value_parser = parse_duration, default_value = "5m")]
+    max_age: Duration,
+}
+
+pub async fn run(ctx: &Context, args: ProxyArgs) -> Result<()> {
+    let (store, path) = super::sync::open_store(args.db)?;
+    let max_age = chrono::Duration::from_std(args.max_age).unwrap_or(chrono::Duration::MAX);
+    let proxy = Proxy::new(Arc::clone(&ctx.client), store).with_max_age(max_age);
+    println!(
+        "Serving http://{}/cycles and friends from {}",
+        args.addr,
+        path.display()
+    );
+    proxy.serve(args.addr).await
+}
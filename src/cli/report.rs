@@ -0,0 +1,170 @@
+use super::dates::local_midnight;
+use super::*;
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use clap::{Args, Subcommand, ValueEnum};
+use std::fmt::Write;
+use whoopsy::Result;
+use whoopsy::aggregate::ZoneShare;
+use whoopsy::format::duration;
+use whoopsy::report::Report;
+
+#[derive(Subcommand)]
+pub enum ReportCommand {
+    /// Summarizes a Monday to Sunday week, compared with the week before.
+    Week(PeriodArgs),
+    /// Summarizes a calendar month, compared with the month before.
+    Month(PeriodArgs),
+}
+
+#[derive(Args)]
+pub struct PeriodArgs {
+    /// Any day in the period to report on. Defaults to the current one.
+    #[arg(long, value_parser = parse_datetime)]
+    date: Option<DateTime<Utc>>,
+
+    /// How to format the report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    format: ReportFormat,
+
+    /// Mail the report to the recipients in the config's `[email]` table
+    /// instead of printing it.
+    #[cfg(feature = "email")]
+    #[arg(long)]
+    email: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Markdown,
+    /// A self-contained HTML page with inline charts.
+    Html,
+}
+
+pub async fn run(ctx: &Context, command: ReportCommand) -> Result<()> {
+    let (args, monthly) = match command {
+        ReportCommand::Week(args) => (args, false),
+        ReportCommand::Month(args) => (args, true),
+    };
+    let day = args.date.map_or_else(
+        || Local::now().date_naive(),
+        |d| d.with_timezone(&Local).date_naive(),
+    );
+    let report = build(ctx, day, monthly).await?;
+
+    #[cfg(feature = "email")]
+    if args.email {
+        let notifier = whoopsy::email::EmailNotifier::new(ctx.config.email()?)?;
+        notifier.send_report(&report).await?;
+        println!("Mailed {}", report.title);
+        return Ok(());
+    }
+
+    let output = match args.format {
+        ReportFormat::Text => render_text(&report.title, &report.summary),
+        ReportFormat::Markdown => report.to_markdown(),
+        ReportFormat::Html => report.to_html(),
+    };
+    print!("{}", output);
+    Ok(())
+}
+
+/// The report on the Monday to Sunday week, or with `monthly` the calendar
+/// month, holding `day`.
+pub async fn build(ctx: &Context, day: NaiveDate, monthly: bool) -> Result<Report> {
+    let (first, next, previous, title) = if monthly {
+        let first = day.with_day(1).unwrap();
+        (
+            first,
+            first + Months::new(1),
+            first - Months::new(1),
+            first.format("%B %Y").to_string(),
+        )
+    } else {
+        let monday = day - Days::new(day.weekday().num_days_from_monday() as u64);
+        (
+            monday,
+            monday + Days::new(7),
+            monday - Days::new(7),
+            format!("Week of {} to {}", monday, monday + Days::new(6)),
+        )
+    };
+    let start = local_midnight(first);
+    let end = local_midnight(next);
+
+    let cycles = ctx.pages(start, end).collect_all().await?;
+    let recoveries = ctx.pages(start, end).collect_all().await?;
+    let sleeps = ctx.pages(start, end).collect_all().await?;
+    let workouts = ctx.pages(start, end).collect_all().await?;
+    Ok(Report::new(title, &cycles, &recoveries, &sleeps, &workouts)
+        .with_previous(ctx.summarize(local_midnight(previous), start).await?))
+}
+
+fn percent(value: Option<f32>) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.0}%", v))
+}
+
+fn number(value: Option<f32>, precision: usize) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.*}", precision, v))
+}
+
+fn render_text(title: &str, s: &Summary) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", title).unwrap();
+    writeln!(out, "{}", "=".repeat(title.len())).unwrap();
+    writeln!(
+        out,
+        "Recovery   avg {} (HRV {} ms, RHR {} bpm)",
+        percent(s.average_recovery),
+        number(s.average_hrv_milli, 1),
+        number(s.average_resting_heart_rate, 0)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "Strain     total {:.1}, avg {} per day, {:.0} kJ",
+        s.total_strain,
+        number(s.average_strain, 1),
+        s.total_kilojoule
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "Sleep      {} total, avg performance {}, debt {}",
+        duration(s.total_sleep_milli),
+        percent(s.average_sleep_performance),
+        duration(s.sleep_debt_milli)
+    )
+    .unwrap();
+
+    writeln!(out, "\nTime in zones").unwrap();
+    for ZoneShare {
+        zone,
+        milli,
+        percentage,
+    } in ZoneShare::of(&s.zone_durations)
+    {
+        writeln!(
+            out,
+            "  Zone {}  {:>8}  {:>3.0}%",
+            zone,
+            duration(milli),
+            percentage
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\nWorkouts ({})", s.workout_count).unwrap();
+    for (sport, w) in &s.workouts_by_sport {
+        writeln!(
+            out,
+            "  {:<20} {:>2}x  {:>8}  strain {:.1}",
+            sport,
+            w.count,
+            duration(w.total_duration_milli),
+            w.total_strain
+        )
+        .unwrap();
+    }
+    out
+}
@@ -0,0 +1,160 @@
+//! Syncs WHOOP data into a local SQLite database.
+//!
+//! The schema lives in [`whoopsy::store::sqlite`]. What each run fetches is planned
+//! by [`whoopsy::sync::Delta`]: records from the oldest one WHOOP may still change, or
+//! else from the newest one synced, minus an overlap window that catches records
+//! WHOOP re-scores later. `--dry-run` prints the plan without fetching anything.
+//! `--backfill` imports the whole history instead, with [`whoopsy::sync::Engine`].
+//! The config's `alerts` rules are checked after the sync, and sent through its
+//! `[email]`, `[ntfy]` and `[pushover]` tables when they fire.
+
+use super::*;
+use clap::Args;
+use std::path::PathBuf;
+use whoopsy::Result;
+use whoopsy::store::SqliteStore;
+use whoopsy::sync::{self, Delta, Engine};
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// SQLite database to sync into. Defaults to `whoopsy.db` next to the config file.
+    #[arg(long)]
+    db: Option<PathBuf>,
+
+    /// Resources to sync. Syncs everything when omitted.
+    #[arg(long = "resource", value_enum)]
+    resources: Vec<Resource>,
+
+    /// How far before the watermark to re-fetch, catching records scored late.
+    #[arg(long, value_parser = parse_duration, default_value = "3d")]
+    overlap: Duration,
+
+    /// Print what would be fetched without fetching it.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Import the account's whole history, from its first year to now.
+    /// Running it again after an interruption resumes the import.
+    #[arg(long, conflicts_with = "dry_run")]
+    backfill: bool,
+}
+
+pub async fn run(ctx: &Context, args: SyncArgs) -> Result<()> {
+    let (mut store, path) = open_store(args.db.clone())?;
+
+    let overlap = chrono::Duration::from_std(args.overlap)
+        .map_err(|e| WhoopError::Unknown(format!("overlap too large: {}", e), None))?;
+    let resources: Vec<_> = Resource::or_all(&args.resources)
+        .into_iter()
+        .map(|resource| match resource {
+            Resource::Cycles => sync::Resource::Cycles,
+            Resource::Sleep => sync::Resource::Sleep,
+            Resource::Recovery => sync::Resource::Recovery,
+            Resource::Workouts => sync::Resource::Workouts,
+        })
+        .collect();
+    if args.backfill {
+        let engine = Engine::new(Arc::clone(&ctx.client))
+            .with_resources(&resources)
+            .with_progress(|p| {
+                println!(
+                    "  {} through {} ({}/{} windows)",
+                    p.resource.name(),
+                    p.through.format("%Y-%m-%d"),
+                    p.windows_done,
+                    p.windows
+                )
+            });
+        let synced = engine.backfill(&mut store).await?;
+        println!(
+            "  {} records fetched in {} requests",
+            synced.total(),
+            synced.requests
+        );
+        println!("Backfilled into {}", path.display());
+        return Ok(());
+    }
+
+    let delta = Delta::new()
+        .with_resources(&resources)
+        .with_overlap(overlap);
+    #[cfg(feature = "analytics")]
+    let delta = match configured_alerts(&ctx.config)? {
+        Some(alerts) => delta.with_alerts(Arc::new(alerts)),
+        None => delta,
+    };
+
+    let plan = delta.plan(&store)?;
+    println!("{}", plan);
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let applied = delta.run(&*ctx.client, &mut store).await?;
+    let synced = applied.synced;
+    println!(
+        "  {} cycles, {} sleep, {} recovery, {} workouts fetched in {} requests, {} changed",
+        synced.cycles,
+        synced.sleeps,
+        synced.recoveries,
+        synced.workouts,
+        synced.requests,
+        applied.changed
+    );
+    println!("Synced into {}", path.display());
+    Ok(())
+}
+
+/// Opens `db`, or `whoopsy.db` next to the config file, creating it if needed.
+pub fn open_store(db: Option<PathBuf>) -> Result<(SqliteStore, PathBuf)> {
+    let path = db.unwrap_or_else(|| Config::dir().join("whoopsy.db"));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok((SqliteStore::open(&path)?, path))
+}
+
+/// The config's alert rules, sent to every notifier it has a table for, or
+/// printed when it has none.
+#[cfg(feature = "analytics")]
+pub fn configured_alerts(config: &Config) -> Result<Option<whoopsy::alerts::Alerts>> {
+    use whoopsy::alerts::{Alert, Alerts, Rule};
+    use whoopsy::push::{Ntfy, Pushover};
+
+    if config.alerts.is_empty() {
+        return Ok(None);
+    }
+    let mut alerts = Alerts::new();
+    for text in &config.alerts {
+        let rule = Rule::parse(text).ok_or_else(|| {
+            WhoopError::BadRequest(format!("invalid alert rule '{}'", text), None)
+        })?;
+        alerts = alerts.with_rule(rule);
+    }
+
+    let mut notified = false;
+    #[cfg(feature = "email")]
+    if let Some(email) = &config.email {
+        alerts = alerts.with_notifier(whoopsy::email::EmailNotifier::new(email)?);
+        notified = true;
+    }
+    if let Some(ntfy) = &config.ntfy {
+        let mut notifier = Ntfy::new(&ntfy.topic);
+        if let Some(server) = &ntfy.server {
+            notifier = notifier.with_server(server);
+        }
+        if let Some(token) = &ntfy.token {
+            notifier = notifier.with_token(token);
+        }
+        alerts = alerts.with_notifier(notifier);
+        notified = true;
+    }
+    if let Some(pushover) = &config.pushover {
+        alerts = alerts.with_notifier(Pushover::new(&pushover.token, &pushover.user));
+        notified = true;
+    }
+    if !notified {
+        alerts = alerts.with_notifier(|alert: &Alert| println!("Alert: {}", alert.message));
+    }
+    Ok(Some(alerts))
+}
@@ -0,0 +1,276 @@
+use super::*;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use clap::Args;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph, Row, Sparkline, Table};
+use ratatui::{DefaultTerminal, Frame};
+use whoopsy::Result;
+use whoopsy::analytics::RecoveryZone;
+use whoopsy::calendar::Calendar;
+use whoopsy::format;
+
+#[derive(Args)]
+pub struct TuiArgs {
+    /// How many days of history to load, up to ten years.
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=3650))]
+    days: u32,
+}
+
+/// Everything shown for a single day on the dashboard.
+struct Day {
+    date: NaiveDate,
+    cycle: Cycle,
+    recovery: Option<Recovery>,
+    sleep: Option<Sleep>,
+    workouts: Vec<WorkoutV2>,
+}
+
+struct App {
+    days: Vec<Day>,
+    selected: usize,
+}
+
+pub async fn run(ctx: &Context, args: TuiArgs) -> Result<()> {
+    let start = Utc::now()
+        .checked_sub_signed(chrono::Duration::days(i64::from(args.days)))
+        .ok_or_else(|| WhoopError::Unknown(format!("{} days is too far back", args.days), None))?;
+    let start = Some(start);
+
+    println!("Loading the last {} days...", args.days);
+    let cycles = ctx.pages(start, None).collect_all().await?;
+    let recoveries = ctx.pages(start, None).collect_all().await?;
+    let sleeps = ctx.pages(start, None).collect_all().await?;
+    let workouts = ctx.pages(start, None).collect_all().await?;
+
+    let days = group_days(cycles, recoveries, sleeps, workouts);
+    if days.is_empty() {
+        println!("No cycles found in the last {} days", args.days);
+        return Ok(());
+    }
+
+    let mut app = App {
+        selected: days.len() - 1,
+        days,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// Pairs every cycle with its recovery, main sleep and workouts, oldest first.
+fn group_days(
+    mut cycles: Vec<Cycle>,
+    recoveries: Vec<Recovery>,
+    sleeps: Vec<Sleep>,
+    workouts: Vec<WorkoutV2>,
+) -> Vec<Day> {
+    cycles.sort_by_key(|c| c.start);
+
+    cycles
+        .into_iter()
+        .map(|cycle| {
+            let recovery = recoveries.iter().find(|r| r.cycle_id == cycle.id).cloned();
+            let sleep = sleeps
+                .iter()
+                .find(|s| s.cycle_id == cycle.id && !s.nap)
+                .cloned();
+            let mut workouts: Vec<WorkoutV2> = workouts
+                .iter()
+                .filter(|w| w.start >= cycle.start && cycle.end.is_none_or(|end| w.start < end))
+                .cloned()
+                .collect();
+            workouts.sort_by_key(|w| w.start);
+
+            Day {
+                date: Calendar::default().cycle_day(&cycle),
+                cycle,
+                recovery,
+                sleep,
+                workouts,
+            }
+        })
+        .collect()
+}
+
+/// Shifts a UTC timestamp into the record's own timezone offset.
+fn local_time(time: DateTime<Utc>, offset: &str) -> DateTime<FixedOffset> {
+    let offset = offset
+        .parse::<FixedOffset>()
+        .unwrap_or_else(|_| FixedOffset::east_opt(0).unwrap());
+    time.with_timezone(&offset)
+}
+
+fn recovery_color(score: f32) -> Color {
+    match RecoveryZone::from_score(score) {
+        RecoveryZone::Green => Color::Green,
+        RecoveryZone::Yellow => Color::Yellow,
+        RecoveryZone::Red => Color::Red,
+    }
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Left | KeyCode::Char('h') => {
+                        self.selected = self.selected.saturating_sub(1)
+                    }
+                    KeyCode::Right | KeyCode::Char('l') => {
+                        self.selected = (self.selected + 1).min(self.days.len() - 1)
+                    }
+                    KeyCode::Home => self.selected = 0,
+                    KeyCode::End => self.selected = self.days.len() - 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let day = &self.days[self.selected];
+
+        let [header, gauges, trend, workouts, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(3),
+            Constraint::Length(6),
+            Constraint::Min(4),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                "whoopsy ".bold(),
+                format!(
+                    "{}  (day {} of {})",
+                    day.date.format("%A %Y-%m-%d"),
+                    self.selected + 1,
+                    self.days.len()
+                )
+                .into(),
+            ])),
+            header,
+        );
+
+        let [recovery_area, strain_area, sleep_area] =
+            Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(gauges);
+
+        let recovery = day
+            .recovery
+            .as_ref()
+            .and_then(|r| r.score.as_ref())
+            .map(|s| s.recovery_score);
+        frame.render_widget(
+            percent_gauge(
+                "Recovery",
+                recovery,
+                recovery.map_or(Color::Gray, recovery_color),
+            ),
+            recovery_area,
+        );
+
+        let strain = day.cycle.score.as_ref().map(|s| s.strain);
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title("Strain"))
+                .gauge_style(Style::default().fg(Color::Cyan))
+                .ratio(strain.map_or(0.0, |s| (s as f64 / 21.0).clamp(0.0, 1.0)))
+                .label(strain.map_or("-".to_string(), |s| format!("{:.1}", s))),
+            strain_area,
+        );
+
+        let performance = day
+            .sleep
+            .as_ref()
+            .and_then(|s| s.score.as_ref())
+            .and_then(|s| s.sleep_performance_percentage);
+        frame.render_widget(
+            percent_gauge("Sleep performance", performance, Color::Blue),
+            sleep_area,
+        );
+
+        let hrv: Vec<u64> = self
+            .days
+            .iter()
+            .map(|d| {
+                d.recovery
+                    .as_ref()
+                    .and_then(|r| r.score.as_ref())
+                    .map_or(0, |s| s.hrv_rmssd_milli.round() as u64)
+            })
+            .collect();
+        let hrv_title = match day.recovery.as_ref().and_then(|r| r.score.as_ref()) {
+            Some(score) => format!(
+                "HRV trend, {:.1} ms today (RHR {:.0})",
+                score.hrv_rmssd_milli, score.resting_heart_rate
+            ),
+            None => "HRV trend".to_string(),
+        };
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::bordered().title(hrv_title))
+                .style(Style::default().fg(Color::Magenta))
+                .data(&hrv),
+            trend,
+        );
+
+        let rows = day.workouts.iter().map(|w| {
+            let (strain, heart_rate) = match &w.score {
+                Some(score) => (
+                    format!("{:.1}", score.strain),
+                    format!("{} / {}", score.average_heart_rate, score.max_heart_rate),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            Row::new(vec![
+                w.sport_name.clone(),
+                local_time(w.start, &w.timezone_offset)
+                    .format("%H:%M")
+                    .to_string(),
+                format::duration((w.end - w.start).num_milliseconds()),
+                strain,
+                heart_rate,
+            ])
+        });
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Fill(2),
+                    Constraint::Length(6),
+                    Constraint::Length(8),
+                    Constraint::Length(7),
+                    Constraint::Length(10),
+                ],
+            )
+            .header(Row::new(["Sport", "Start", "Duration", "Strain", "Avg/Max HR"]).bold())
+            .block(Block::bordered().title("Workouts")),
+            workouts,
+        );
+
+        frame.render_widget(
+            Paragraph::new("←/h previous day  →/l next day  Home/End jump  q quit").dark_gray(),
+            footer,
+        );
+    }
+}
+
+fn percent_gauge(title: &str, value: Option<f32>, color: Color) -> Gauge<'_> {
+    Gauge::default()
+        .block(Block::bordered().title(title))
+        .gauge_style(Style::default().fg(color))
+        .ratio(value.map_or(0.0, |v| (v as f64 / 100.0).clamp(0.0, 1.0)))
+        .label(value.map_or("-".to_string(), |v| format!("{:.0}%", v)))
+}
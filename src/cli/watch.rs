@@ -0,0 +1,212 @@
+use super::*;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use whoopsy::Result;
+use whoopsy::merge::Versioned;
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// How often to poll for changes, e.g. `30s`, `15m`, `1h`.
+    #[arg(long, value_parser = parse_interval, default_value = "15m")]
+    interval: Duration,
+
+    /// How far back to look for updated records on every poll.
+    #[arg(long, value_parser = parse_lookback, default_value = "2d")]
+    lookback: Duration,
+
+    /// Resources to watch. Watches everything when omitted.
+    #[arg(long = "resource", value_enum)]
+    resources: Vec<Resource>,
+
+    /// Appends every change as a JSON line to this file.
+    #[arg(long)]
+    append: Option<PathBuf>,
+}
+
+/// A record we can detect changes on and describe in one line.
+trait Watched: Versioned + Serialize {
+    fn summary(&self) -> String;
+}
+
+impl Watched for Cycle {
+    fn summary(&self) -> String {
+        match &self.score {
+            Some(score) => format!("cycle {} strain {:.1}", self.start, score.strain),
+            None => format!("cycle {} {:?}", self.start, self.score_state),
+        }
+    }
+}
+
+impl Watched for Sleep {
+    fn summary(&self) -> String {
+        let kind = if self.nap { "nap" } else { "sleep" };
+        match self
+            .score
+            .as_ref()
+            .and_then(|s| s.sleep_performance_percentage)
+        {
+            Some(performance) => format!("{} {} performance {:.0}%", kind, self.start, performance),
+            None => format!("{} {} {:?}", kind, self.start, self.score_state),
+        }
+    }
+}
+
+impl Watched for Recovery {
+    fn summary(&self) -> String {
+        match &self.score {
+            Some(score) => format!(
+                "recovery for cycle {} {:.0}% (HRV {:.1} ms, RHR {:.0})",
+                self.cycle_id,
+                score.recovery_score,
+                score.hrv_rmssd_milli,
+                score.resting_heart_rate
+            ),
+            None => format!(
+                "recovery for cycle {} {:?}",
+                self.cycle_id, self.score_state
+            ),
+        }
+    }
+}
+
+impl Watched for WorkoutV2 {
+    fn summary(&self) -> String {
+        match &self.score {
+            Some(score) => format!(
+                "{} {} strain {:.1}",
+                self.sport_name, self.start, score.strain
+            ),
+            None => format!("{} {} {:?}", self.sport_name, self.start, self.score_state),
+        }
+    }
+}
+
+/// Remembers the last seen `updated_at` of every record.
+struct Tracker {
+    seen: HashMap<(Resource, String), DateTime<Utc>>,
+    append: Option<std::fs::File>,
+}
+
+impl Tracker {
+    /// Records a batch and reports everything new or updated since the last poll.
+    /// The first poll only seeds the tracker so existing history isn't printed.
+    fn observe<T: Watched>(
+        &mut self,
+        resource: Resource,
+        records: &[T],
+        report: bool,
+    ) -> Result<()> {
+        for record in records {
+            let key = (resource, record.key());
+            let change = match self.seen.insert(key, record.updated_at()) {
+                None => "new",
+                Some(previous) if previous < record.updated_at() => "updated",
+                Some(_) => continue,
+            };
+
+            if !report {
+                continue;
+            }
+
+            println!(
+                "[{}] {} {}: {}",
+                Utc::now().format("%Y-%m-%d %H:%M:%S"),
+                change,
+                resource.name(),
+                record.summary()
+            );
+
+            if let Some(file) = &mut self.append {
+                let line = serde_json::json!({
+                    "resource": resource.name(),
+                    "change": change,
+                    "observed_at": Utc::now(),
+                    "record": record,
+                });
+                writeln!(file, "{}", line)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn run(ctx: &Context, args: WatchArgs) -> Result<()> {
+    let append = match &args.append {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let mut tracker = Tracker {
+        seen: HashMap::new(),
+        append,
+    };
+    let resources = Resource::or_all(&args.resources);
+    let lookback = chrono::Duration::from_std(args.lookback)
+        .map_err(|e| WhoopError::Unknown(format!("lookback too large: {}", e), None))?;
+
+    let mut ticker = tokio::time::interval(args.interval);
+    let mut first = true;
+
+    println!(
+        "Watching {} every {}s, press Ctrl-C to stop",
+        resources
+            .iter()
+            .map(|r| r.name())
+            .collect::<Vec<_>>()
+            .join(", "),
+        args.interval.as_secs()
+    );
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        let start = Some(Utc::now() - lookback);
+        for &resource in &resources {
+            // A failed poll shouldn't kill a long-running watch, just try again next tick.
+            if let Err(e) = poll(ctx, &mut tracker, resource, start, !first).await {
+                eprintln!("Failed to poll {}: {}", resource.name(), e);
+            }
+        }
+
+        if first {
+            println!("Tracking {} records", tracker.seen.len());
+            first = false;
+        }
+    }
+}
+
+async fn poll(
+    ctx: &Context,
+    tracker: &mut Tracker,
+    resource: Resource,
+    start: Option<DateTime<Utc>>,
+    report: bool,
+) -> Result<()> {
+    match resource {
+        Resource::Cycles => {
+            let records = ctx.pages::<Cycle>(start, None).collect_all().await?;
+            tracker.observe(resource, &records, report)
+        }
+        Resource::Sleep => {
+            let records = ctx.pages::<Sleep>(start, None).collect_all().await?;
+            tracker.observe(resource, &records, report)
+        }
+        Resource::Recovery => {
+            let records = ctx.pages::<Recovery>(start, None).collect_all().await?;
+            tracker.observe(resource, &records, report)
+        }
+        Resource::Workouts => {
+            let records = ctx.pages::<WorkoutV2>(start, None).collect_all().await?;
+            tracker.observe(resource, &records, report)
+        }
+    }
+}
@@ -1,10 +1,24 @@
+use crate::audit::{AuditEntry, AuditLog, RATE_LIMIT_REMAINING};
 use crate::auth::{OAuthConfig, TokenResponse};
 use crate::error::{Result, WhoopError};
+use crate::http;
+use crate::instrument::event;
+use crate::json;
 use crate::models::*;
-use reqwest::{Client, Method, RequestBuilder, StatusCode};
-use serde::Serialize;
+use crate::query::{TimestampPrecision, ToQuery};
+use crate::rate_limit::RateLimiter;
+use crate::reporting::{ErrorReports, REQUEST_ID};
+use crate::sandbox::Sandbox;
+use crate::scoring::{self, ScorePolicy};
+use crate::stream;
+use crate::version::{ApiVersion, ApiVersions, ResourceFamily};
+use chrono::Utc;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use uuid::Uuid;
 
 const BASE_URL: &str = "https://api.prod.whoop.com/developer";
@@ -12,13 +26,35 @@ const BASE_URL: &str = "https://api.prod.whoop.com/developer";
 pub struct WhoopClient {
     client: Client,
     auth: Auth,
+    base_url: String,
+    versions: ApiVersions,
+    timestamp_precision: TimestampPrecision,
+    default_limit: Option<i32>,
+    sandbox: Option<Sandbox>,
+    rate_limiter: Option<RateLimiter>,
+    in_flight: Option<Semaphore>,
+    audit_log: Option<AuditLog>,
+    error_reports: Option<ErrorReports>,
+    #[cfg(feature = "raw-archive")]
+    raw_archive: Option<crate::raw::RawArchive>,
+    #[cfg(feature = "test-support")]
+    cassette: Option<crate::vcr::Cassette>,
 }
 
+/// A response, either still downloading or already in memory.
+enum Reply {
+    Network(Response),
+    Text(StatusCode, String),
+}
+
+/// The token is shared rather than cloned into every request, and only
+/// `refresh_token` replaces it, through `&mut self`, so no lock is needed.
 enum Auth {
-    AccessToken(String),
+    AccessToken(Arc<str>),
     OAuth {
         config: OAuthConfig,
-        token: Arc<Mutex<TokenResponse>>,
+        access_token: Arc<str>,
+        refresh_token: Option<String>,
     },
 }
 
@@ -29,23 +65,142 @@ impl WhoopClient {
         let client = Client::new();
         Self {
             client,
-            auth: Auth::AccessToken(access_token),
+            auth: Auth::AccessToken(access_token.into()),
+            base_url: BASE_URL.to_string(),
+            versions: ApiVersions::default(),
+            timestamp_precision: TimestampPrecision::default(),
+            default_limit: None,
+            sandbox: None,
+            rate_limiter: None,
+            in_flight: None,
+            audit_log: None,
+            error_reports: None,
+            #[cfg(feature = "raw-archive")]
+            raw_archive: None,
+            #[cfg(feature = "test-support")]
+            cassette: None,
         }
     }
 
     /// Creates a client that can manage OAuth tokens.
     /// Use when you've already done the OAuth dance.
+    /// API calls share the config's HTTP client, so they're pooled and
+    /// configured the same as token refreshes.
     pub fn new_with_oauth(config: OAuthConfig, token: TokenResponse) -> Self {
-        let client = Client::new();
+        let client = config.http_client().clone();
         Self {
             client,
             auth: Auth::OAuth {
                 config,
-                token: Arc::new(Mutex::new(token)),
+                access_token: token.access_token.into(),
+                refresh_token: token.refresh_token,
             },
+            base_url: BASE_URL.to_string(),
+            versions: ApiVersions::default(),
+            timestamp_precision: TimestampPrecision::default(),
+            default_limit: None,
+            sandbox: None,
+            rate_limiter: None,
+            in_flight: None,
+            audit_log: None,
+            error_reports: None,
+            #[cfg(feature = "raw-archive")]
+            raw_archive: None,
+            #[cfg(feature = "test-support")]
+            cassette: None,
         }
     }
 
+    /// Uses a preconfigured HTTP client, e.g. with a proxy, custom TLS or
+    /// timeouts, for API calls and token refreshes alike.
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        if let Auth::OAuth { config, .. } = &mut self.auth {
+            config.http = client.clone();
+        }
+        self.client = client;
+        self
+    }
+
+    /// Points the client at another server, e.g. a mock in tests.
+    /// Paths like `/v2/cycle` are appended to it.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Requests a family's endpoints from `version`. Everything defaults to
+    /// v2. See [`crate::version`].
+    pub fn with_api_version(mut self, family: ResourceFamily, version: ApiVersion) -> Self {
+        self.versions.set(family, version);
+        self
+    }
+
+    /// Sends query timestamps with `precision`. Defaults to milliseconds.
+    pub fn with_timestamp_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    /// Asks collection endpoints for `limit` records a page whenever the
+    /// params don't set a limit themselves. WHOOP allows up to 25.
+    pub fn with_default_limit(mut self, limit: i32) -> Self {
+        self.default_limit = Some(limit);
+        self
+    }
+
+    /// Answers every request from exported files instead of the API.
+    /// See [`crate::sandbox`].
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Waits for `rate_limiter` before every API request. Clients for many
+    /// users of one app share WHOOP's per-app budget by holding clones of the
+    /// same limiter.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Lets at most `requests` requests be in flight at once, counting until
+    /// the response body is read. Further calls wait their turn, so code
+    /// firing off many calls together can't burst past the API's limits or
+    /// run out of sockets.
+    pub fn with_max_concurrency(mut self, requests: usize) -> Self {
+        self.in_flight = Some(Semaphore::new(requests.max(1)));
+        self
+    }
+
+    /// Records every API request in `audit_log`. See [`crate::audit`].
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Reports every failed request to `error_reports`. See
+    /// [`crate::reporting`].
+    pub fn with_error_reports(mut self, error_reports: ErrorReports) -> Self {
+        self.error_reports = Some(error_reports);
+        self
+    }
+
+    /// Saves the body of every response from the API in `raw_archive`. See
+    /// [`crate::raw`].
+    #[cfg(feature = "raw-archive")]
+    pub fn with_raw_archive(mut self, raw_archive: crate::raw::RawArchive) -> Self {
+        self.raw_archive = Some(raw_archive);
+        self
+    }
+
+    /// Records responses to, or replays them from, a cassette instead of only
+    /// talking to the API. See [`crate::vcr`].
+    #[cfg(feature = "test-support")]
+    pub fn with_cassette(mut self, cassette: crate::vcr::Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
     /// Creates a client directly from an authorization code.
     /// Handles the token exchange for you.
     pub async fn from_authorization_code(config: OAuthConfig, code: String) -> Result<Self> {
@@ -53,74 +208,267 @@ impl WhoopClient {
         Ok(Self::new_with_oauth(config, token))
     }
 
-    fn get_access_token(&self) -> String {
+    fn get_access_token(&self) -> &Arc<str> {
         match &self.auth {
-            Auth::AccessToken(token) => token.clone(),
-            Auth::OAuth { token, .. } => {
-                let token_lock = token.lock().unwrap();
-                token_lock.access_token.clone()
-            }
+            Auth::AccessToken(token) => token,
+            Auth::OAuth { access_token, .. } => access_token,
         }
     }
 
     /// Refreshes an expired OAuth token.
     /// Only works if you're using OAuth (does nothing for static tokens).
     pub async fn refresh_token(&mut self) -> Result<()> {
-        match &self.auth {
+        match &mut self.auth {
             Auth::AccessToken(_) => Ok(()),
-            Auth::OAuth { config, token } => {
-                let refresh_token = {
-                    let token_lock = token.lock().unwrap();
-                    token_lock.refresh_token.clone().ok_or_else(|| {
-                        WhoopError::AuthenticationError("No refresh token available".to_string())
-                    })?
-                };
-
-                let new_token = config.refresh_token(refresh_token).await?;
-
-                let mut token_lock = token.lock().unwrap();
-                *token_lock = new_token;
+            Auth::OAuth {
+                config,
+                access_token,
+                refresh_token,
+            } => {
+                let current = refresh_token.clone().ok_or_else(|| {
+                    WhoopError::AuthenticationError("No refresh token available".to_string(), None)
+                })?;
+
+                let new_token = config.refresh_token(current).await?;
+
+                *access_token = new_token.access_token.into();
+                *refresh_token = new_token.refresh_token;
                 Ok(())
             }
         }
     }
 
     fn request(&self, method: Method, path: &str) -> RequestBuilder {
-        let url = format!("{}{}", BASE_URL, path);
+        let url = format!("{}{}", self.base_url, path);
         self.client
             .request(method, url)
-            .bearer_auth(self.get_access_token())
+            .bearer_auth(&**self.get_access_token())
     }
 
-    async fn execute<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
-        let response = request.send().await?;
-        let status = response.status();
+    /// Adds a collection query, falling back to the default limit.
+    fn collection_query(
+        &self,
+        request: RequestBuilder,
+        params: Option<&impl ToQuery>,
+    ) -> RequestBuilder {
+        let mut query = params.map_or_else(Vec::new, |p| p.to_query(self.timestamp_precision));
+        if let Some(limit) = self
+            .default_limit
+            .filter(|_| !query.iter().any(|(name, _)| *name == "limit"))
+        {
+            query.insert(0, ("limit", limit.to_string()));
+        }
+        if query.is_empty() {
+            request
+        } else {
+            request.query(&query)
+        }
+    }
+
+    async fn execute<T>(&self, request: RequestBuilder) -> Result<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        self.reported(request, |request| self.fetch(request)).await
+    }
+
+    async fn execute_no_content(&self, request: RequestBuilder) -> Result<()> {
+        self.reported(request, |request| self.fetch_no_content(request))
+            .await
+    }
+
+    /// Runs `fetch` on the request, reporting a failure to the error reports.
+    async fn reported<T, F>(
+        &self,
+        request: RequestBuilder,
+        fetch: impl FnOnce(Request) -> F,
+    ) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let mut request = request.build()?;
+        let Some(error_reports) = &self.error_reports else {
+            return fetch(request).await;
+        };
+        let correlation_id = Uuid::new_v4();
+        if let Ok(value) = HeaderValue::try_from(correlation_id.to_string()) {
+            request.headers_mut().insert(REQUEST_ID, value);
+        }
+        let method = request.method().to_string();
+        let endpoint = request.url().path().to_string();
+        let result = fetch(request).await;
+        if let Err(error) = &result {
+            error_reports.report(error, &method, &endpoint, correlation_id);
+        }
+        result
+    }
+
+    async fn fetch<T>(&self, request: Request) -> Result<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let _permit = self.permit().await;
+        #[cfg(feature = "raw-archive")]
+        let method = request.method().clone();
+        let (status, headers, body) = match self.send(request).await? {
+            // Archived bodies are read whole, to be stored as they came.
+            #[cfg(feature = "raw-archive")]
+            Reply::Network(response) if self.raw_archive.is_some() => {
+                self.read_archived(&method, response).await?
+            }
+            // Straight from the network, big pages are parsed as they arrive.
+            Reply::Network(response) if response.status().is_success() => {
+                return stream::deserialize(response).await;
+            }
+            Reply::Network(response) => (
+                response.status(),
+                response.headers().clone(),
+                response.text().await?,
+            ),
+            Reply::Text(status, body) => (status, HeaderMap::new(), body),
+        };
 
         if status.is_success() {
-            let data = response.json::<T>().await?;
-            Ok(data)
+            json::from_string(body)
         } else {
-            let message = response.text().await.ok();
-            Err(WhoopError::from_status(status, message))
+            Err(WhoopError::from_response(
+                status,
+                &headers,
+                (!body.is_empty()).then_some(body),
+            ))
         }
     }
 
-    async fn execute_no_content(&self, request: RequestBuilder) -> Result<()> {
-        let response = request.send().await?;
+    /// Reads a response whole, saving its body to the raw archive. A body
+    /// that can't be saved is logged rather than failing the call.
+    #[cfg(feature = "raw-archive")]
+    async fn read_archived(
+        &self,
+        method: &Method,
+        response: Response,
+    ) -> Result<(StatusCode, HeaderMap, String)> {
         let status = response.status();
+        let headers = response.headers().clone();
+        let url = response.url();
+        let endpoint = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let body = response.bytes().await?;
+        if let Some(raw_archive) = self.raw_archive.clone() {
+            // Gzipping and writing files would block the runtime.
+            let task = {
+                let (method, endpoint, body) = (method.clone(), endpoint.clone(), body.clone());
+                tokio::task::spawn_blocking(move || {
+                    raw_archive.store(method.as_str(), &endpoint, status.as_u16(), &body)
+                })
+            };
+            let stored = match task.await {
+                Ok(stored) => stored,
+                Err(e) => Err(WhoopError::Unknown(
+                    format!("archive task failed: {}", e),
+                    None,
+                )),
+            };
+            if let Err(e) = stored {
+                event!(warn, "Couldn't archive {} {}: {}", method, endpoint, e);
+            }
+        }
+        Ok((status, headers, String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    async fn fetch_no_content(&self, request: Request) -> Result<()> {
+        let _permit = self.permit().await;
+        let (status, headers, body) = match self.send(request).await? {
+            Reply::Network(response) => (
+                response.status(),
+                response.headers().clone(),
+                response.text().await?,
+            ),
+            Reply::Text(status, body) => (status, HeaderMap::new(), body),
+        };
 
         if status == StatusCode::NO_CONTENT {
             Ok(())
         } else {
-            let message = response.text().await.ok();
-            Err(WhoopError::from_status(status, message))
+            Err(WhoopError::from_response(
+                status,
+                &headers,
+                (!body.is_empty()).then_some(body),
+            ))
+        }
+    }
+
+    async fn permit(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.in_flight {
+            // The semaphore is never closed.
+            Some(in_flight) => in_flight.acquire().await.ok(),
+            None => None,
+        }
+    }
+
+    async fn send(&self, request: Request) -> Result<Reply> {
+        if let Some(sandbox) = &self.sandbox {
+            let (status, body) = sandbox.respond(&request).await?;
+            return Ok(Reply::Text(status, body));
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        #[cfg(feature = "test-support")]
+        if let Some(cassette) = &self.cassette {
+            let (status, body) = cassette.send(&self.client, request).await?;
+            return Ok(Reply::Text(status, body));
+        }
+
+        let at = Utc::now();
+        let method = request.method().to_string();
+        let endpoint = request.url().path().to_string();
+        let started = Instant::now();
+        let response = http::send(&self.client, request).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match &response {
+            Ok(r) => event!(
+                debug,
+                "{} {} -> {} in {}ms",
+                method,
+                endpoint,
+                r.status().as_u16(),
+                latency_ms
+            ),
+            Err(e) => event!(warn, "{} {} failed: {}", method, endpoint, e),
         }
+
+        if let Some(audit_log) = &self.audit_log {
+            let rate_limit_remaining = response.as_ref().ok().and_then(|r| {
+                r.headers()
+                    .get(RATE_LIMIT_REMAINING)?
+                    .to_str()
+                    .ok()?
+                    .parse()
+                    .ok()
+            });
+            audit_log.record(AuditEntry {
+                at,
+                method,
+                endpoint,
+                user: None,
+                status: response.as_ref().ok().map(|r| r.status().as_u16()),
+                latency_ms,
+                rate_limit_remaining,
+            });
+        }
+        Ok(Reply::Network(response?))
     }
 
     // Cycle endpoints
 
     pub async fn get_cycle_by_id(&self, cycle_id: i64) -> Result<Cycle> {
-        let path = format!("/v2/cycle/{}", cycle_id);
+        let path = self
+            .versions
+            .path(ResourceFamily::Cycle, &format!("/cycle/{}", cycle_id));
         let request = self.request(Method::GET, &path);
         self.execute(request).await
     }
@@ -129,12 +477,9 @@ impl WhoopClient {
         &self,
         params: Option<CycleQueryParams>,
     ) -> Result<PaginatedCycleResponse> {
-        let mut request = self.request(Method::GET, "/v2/cycle");
-
-        if let Some(p) = params {
-            request = request.query(&p);
-        }
-
+        let path = self.versions.path(ResourceFamily::Cycle, "/cycle");
+        let request = self.request(Method::GET, &path);
+        let request = self.collection_query(request, params.as_ref());
         self.execute(request).await
     }
 
@@ -156,12 +501,8 @@ impl WhoopClient {
         &self,
         params: Option<RecoveryQueryParams>,
     ) -> Result<RecoveryCollection> {
-        let mut request = self.request(Method::GET, "/v2/recovery");
-
-        if let Some(p) = params {
-            request = request.query(&p);
-        }
-
+        let request = self.request(Method::GET, "/v2/recovery");
+        let request = self.collection_query(request, params.as_ref());
         self.execute(request).await
     }
 
@@ -177,24 +518,26 @@ impl WhoopClient {
         &self,
         params: Option<SleepQueryParams>,
     ) -> Result<PaginatedSleepResponse> {
-        let mut request = self.request(Method::GET, "/v2/activity/sleep");
-
-        if let Some(p) = params {
-            request = request.query(&p);
-        }
-
+        let request = self.request(Method::GET, "/v2/activity/sleep");
+        let request = self.collection_query(request, params.as_ref());
         self.execute(request).await
     }
 
     // User endpoints
 
     pub async fn get_body_measurement(&self) -> Result<UserBodyMeasurement> {
-        let request = self.request(Method::GET, "/v2/user/measurement/body");
+        let path = self
+            .versions
+            .path(ResourceFamily::User, "/user/measurement/body");
+        let request = self.request(Method::GET, &path);
         self.execute(request).await
     }
 
     pub async fn get_profile_basic(&self) -> Result<UserBasicProfile> {
-        let request = self.request(Method::GET, "/v2/user/profile/basic");
+        let path = self
+            .versions
+            .path(ResourceFamily::User, "/user/profile/basic");
+        let request = self.request(Method::GET, &path);
         self.execute(request).await
     }
 
@@ -205,23 +548,81 @@ impl WhoopClient {
 
     // Workout endpoints
 
+    #[cfg(feature = "workouts")]
     pub async fn get_workout_by_id(&self, workout_id: Uuid) -> Result<WorkoutV2> {
         let path = format!("/v2/activity/workout/{}", workout_id);
         let request = self.request(Method::GET, &path);
         self.execute(request).await
     }
 
+    #[cfg(feature = "workouts")]
     pub async fn get_workout_collection(
         &self,
         params: Option<WorkoutQueryParams>,
     ) -> Result<WorkoutCollection> {
-        let mut request = self.request(Method::GET, "/v2/activity/workout");
+        let request = self.request(Method::GET, "/v2/activity/workout");
+        let request = self.collection_query(request, params.as_ref());
+        self.execute(request).await
+    }
 
-        if let Some(p) = params {
-            request = request.query(&p);
-        }
+    // Waiting for scores
 
-        self.execute(request).await
+    /// Polls the sleep until it's scored. See [`scoring`](crate::scoring).
+    pub async fn wait_for_scored_sleep(
+        &self,
+        sleep_id: Uuid,
+        policy: &ScorePolicy,
+    ) -> Result<Sleep> {
+        scoring::wait(
+            policy,
+            |s: &Sleep| &s.score_state,
+            || self.get_sleep_by_id(sleep_id),
+        )
+        .await
+    }
+
+    /// Polls the workout until it's scored. See [`scoring`](crate::scoring).
+    #[cfg(feature = "workouts")]
+    pub async fn wait_for_scored_workout(
+        &self,
+        workout_id: Uuid,
+        policy: &ScorePolicy,
+    ) -> Result<WorkoutV2> {
+        scoring::wait(
+            policy,
+            |w: &WorkoutV2| &w.score_state,
+            || self.get_workout_by_id(workout_id),
+        )
+        .await
+    }
+
+    /// Polls the cycle's recovery until it's scored. See [`scoring`](crate::scoring).
+    pub async fn wait_for_scored_recovery(
+        &self,
+        cycle_id: i64,
+        policy: &ScorePolicy,
+    ) -> Result<Recovery> {
+        scoring::wait(
+            policy,
+            |r: &Recovery| &r.score_state,
+            || self.get_recovery_for_cycle(cycle_id),
+        )
+        .await
+    }
+
+    /// Polls the cycle until it's scored, which only happens once it has ended.
+    /// See [`scoring`](crate::scoring).
+    pub async fn wait_for_scored_cycle(
+        &self,
+        cycle_id: i64,
+        policy: &ScorePolicy,
+    ) -> Result<Cycle> {
+        scoring::wait(
+            policy,
+            |c: &Cycle| &c.score_state,
+            || self.get_cycle_by_id(cycle_id),
+        )
+        .await
     }
 }
 
@@ -232,6 +633,180 @@ mod tests {
     #[test]
     fn test_client_creation() {
         let client = WhoopClient::new("test_token".to_string());
-        assert_eq!(client.get_access_token(), "test_token");
+        assert_eq!(&**client.get_access_token(), "test_token");
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_token_refresh_uses_the_configured_http_client() {
+        use crate::test_support::MockWhoop;
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let mock = MockWhoop::start().await;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-whoopsy-test", HeaderValue::from_static("shared"));
+        let http = Client::builder().default_headers(headers).build().unwrap();
+
+        let mut client = mock.expired_oauth_client().with_http_client(http);
+        client.refresh_token().await.unwrap();
+        client.get_profile_basic().await.unwrap();
+
+        let requests = mock.server().received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.headers.contains_key("x-whoopsy-test"))
+        );
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_default_limit_fills_in_for_params_without_one() {
+        use crate::test_support::MockWhoop;
+
+        let mock = MockWhoop::start().await;
+        let client = mock.client().with_default_limit(25);
+        client.get_cycle_collection(None).await.unwrap();
+        client
+            .get_cycle_collection(Some(CycleQueryParams {
+                limit: Some(5),
+                start: None,
+                end: None,
+                next_token: None,
+            }))
+            .await
+            .unwrap();
+
+        let requests = mock.server().received_requests().await.unwrap();
+        let queries: Vec<_> = requests.iter().map(|r| r.url.query()).collect();
+        assert_eq!(queries, [Some("limit=25"), Some("limit=5")]);
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_max_concurrency_queues_requests() {
+        use crate::test_support::MockWhoop;
+        use std::time::{Duration, Instant};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockWhoop::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/v2/user/access"))
+            .respond_with(ResponseTemplate::new(204).set_delay(Duration::from_millis(100)))
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+        let client = mock.client().with_max_concurrency(2);
+
+        let started = Instant::now();
+        let (a, b, c) = tokio::join!(
+            client.revoke_oauth_access(),
+            client.revoke_oauth_access(),
+            client.revoke_oauth_access()
+        );
+        a.and(b).and(c).unwrap();
+        // Two go at once, the third waits for one of them.
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_reports_failures_with_a_correlation_id() {
+        use crate::test_support::MockWhoop;
+        use std::sync::Mutex;
+
+        let mock = MockWhoop::start().await;
+        mock.rate_limit("/v2/user/profile/basic", 1).await;
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let reports = ErrorReports::new(move |failure: &crate::reporting::FailedRequest<'_>| {
+            sink.lock().unwrap().push((
+                failure.error.kind(),
+                failure.endpoint.to_string(),
+                failure.user.map(str::to_string),
+                failure.correlation_id,
+            ));
+        });
+        let client = mock.client().with_error_reports(reports.for_user("42"));
+        assert!(client.get_profile_basic().await.is_err());
+        client.get_profile_basic().await.unwrap();
+
+        let reported = std::mem::take(&mut *reported.lock().unwrap());
+        assert_eq!(reported.len(), 1);
+        let (kind, endpoint, user, correlation_id) = &reported[0];
+        assert_eq!(*kind, crate::ErrorKind::RateLimited);
+        assert_eq!(endpoint, "/v2/user/profile/basic");
+        assert_eq!(user.as_deref(), Some("42"));
+        let requests = mock.server().received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].headers.get(REQUEST_ID).unwrap(),
+            correlation_id.to_string().as_str()
+        );
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_audit_log_records_each_request() {
+        use crate::test_support::MockWhoop;
+        use tokio::sync::mpsc;
+
+        let mock = MockWhoop::start().await;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let client = mock
+            .client()
+            .with_audit_log(AuditLog::new(sender).for_user("42"));
+        client.get_profile_basic().await.unwrap();
+
+        let entry = receiver.recv().await.unwrap();
+        assert_eq!(entry.endpoint, "/v2/user/profile/basic");
+        assert_eq!((entry.method.as_str(), entry.status), ("GET", Some(200)));
+        assert_eq!(entry.user.as_deref(), Some("42"));
+    }
+
+    #[cfg(all(feature = "test-support", feature = "raw-archive"))]
+    #[tokio::test]
+    async fn test_raw_archive_keeps_response_bodies() {
+        use crate::raw::RawArchive;
+        use crate::test_support::MockWhoop;
+
+        let mock = MockWhoop::start().await;
+        let dir = std::env::temp_dir().join(format!("whoopsy-{}", Uuid::new_v4()));
+        let archive = RawArchive::open(&dir).unwrap();
+        let client = mock.client().with_raw_archive(archive.clone());
+        let profile = client.get_profile_basic().await.unwrap();
+
+        let entries = archive.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].endpoint, "/v2/user/profile/basic");
+        assert_eq!(
+            (entries[0].method.as_str(), entries[0].status),
+            ("GET", 200)
+        );
+        let reparsed: UserBasicProfile = archive.reparse(&entries[0]).unwrap();
+        assert_eq!(reparsed.user_id, profile.user_id);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_clients_share_a_rate_limiter() {
+        use crate::test_support::MockWhoop;
+        use std::time::{Duration, Instant};
+
+        let mock = MockWhoop::start().await;
+        let limiter = RateLimiter::new(2, Duration::from_millis(300));
+        let alice = mock.client().with_rate_limiter(limiter.clone());
+        let bob = mock.client().with_rate_limiter(limiter);
+
+        let started = Instant::now();
+        alice.get_profile_basic().await.unwrap();
+        bob.get_profile_basic().await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        // Two requests spent the budget, so the third waits whoever sends it.
+        alice.get_profile_basic().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(140));
     }
 }
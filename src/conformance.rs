@@ -0,0 +1,462 @@
+//! Checks the crate against WHOOP's published OpenAPI document, behind the
+//! `openapi` feature.
+//!
+//! [`Spec::check`] compares every endpoint the client calls and every model it
+//! parses with the document: field names, which fields are required, JSON types,
+//! `uuid`/`date-time` formats and enum values. Models are checked by
+//! serializing the [`Fixtures`] and, for each field the spec marks optional,
+//! making sure the model still parses without it.
+//!
+//! The ignored test in this module fetches the live document, so upstream drift
+//! shows up as a failing test:
+//!
+//! ```text
+//! cargo test --features openapi -- --ignored conformance
+//! ```
+
+use crate::error::Result;
+use crate::models::*;
+use crate::test_support::Fixtures;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+/// Where WHOOP publishes the document.
+pub const SPEC_URL: &str = "https://api.prod.whoop.com/developer/doc/openapi.json";
+
+/// Every endpoint [`WhoopClient`](crate::WhoopClient) calls, with path
+/// parameters written as `{}`.
+pub const ENDPOINTS: [(&str, &str); 12] = [
+    ("GET", "/v2/cycle"),
+    ("GET", "/v2/cycle/{}"),
+    ("GET", "/v2/cycle/{}/sleep"),
+    ("GET", "/v2/cycle/{}/recovery"),
+    ("GET", "/v2/recovery"),
+    ("GET", "/v2/activity/sleep"),
+    ("GET", "/v2/activity/sleep/{}"),
+    ("GET", "/v2/activity/workout"),
+    ("GET", "/v2/activity/workout/{}"),
+    ("GET", "/v2/user/profile/basic"),
+    ("GET", "/v2/user/measurement/body"),
+    ("DELETE", "/v2/user/access"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriftKind {
+    /// The spec has no such path and method.
+    MissingPath,
+    /// The spec has no schema by that name.
+    MissingSchema,
+    /// The spec requires a field the model doesn't serialize.
+    MissingRequiredField,
+    /// The model has a field the spec doesn't.
+    UnknownField,
+    /// The spec has an optional field the model ignores.
+    UnmodelledField,
+    /// The spec marks the field optional, but the model fails to parse without it.
+    OptionalFieldRequired,
+    WrongType {
+        expected: String,
+    },
+    WrongFormat {
+        expected: String,
+    },
+    /// A value outside the spec's `enum`.
+    UnexpectedValue(String),
+}
+
+/// One difference between the crate and the spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    /// `GET /v2/cycle` for paths, `Cycle/score/strain` for fields.
+    pub location: String,
+    pub kind: DriftKind,
+}
+
+impl Drift {
+    /// Whether parsing real responses could fail because of it. Fields the
+    /// model simply doesn't read are harmless.
+    pub fn is_breaking(&self) -> bool {
+        self.kind != DriftKind::UnmodelledField
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DriftKind::MissingPath => write!(f, "{}: not in the spec", self.location),
+            DriftKind::MissingSchema => write!(f, "{}: no such schema", self.location),
+            DriftKind::MissingRequiredField => {
+                write!(
+                    f,
+                    "{}: required by the spec, missing from the model",
+                    self.location
+                )
+            }
+            DriftKind::UnknownField => write!(f, "{}: not in the spec", self.location),
+            DriftKind::UnmodelledField => write!(f, "{}: not modelled", self.location),
+            DriftKind::OptionalFieldRequired => {
+                write!(
+                    f,
+                    "{}: optional in the spec, required by the model",
+                    self.location
+                )
+            }
+            DriftKind::WrongType { expected } => {
+                write!(f, "{}: expected type {}", self.location, expected)
+            }
+            DriftKind::WrongFormat { expected } => {
+                write!(f, "{}: expected format {}", self.location, expected)
+            }
+            DriftKind::UnexpectedValue(value) => {
+                write!(f, "{}: {} is not an allowed value", self.location, value)
+            }
+        }
+    }
+}
+
+/// A parsed OpenAPI document.
+pub struct Spec {
+    document: Value,
+}
+
+impl Spec {
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(Self {
+            document: serde_json::from_str(json)?,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Downloads the document, e.g. from [`SPEC_URL`].
+    pub async fn fetch(url: &str) -> Result<Self> {
+        let body = reqwest::get(url).await?.error_for_status()?.text().await?;
+        Self::from_json(&body)
+    }
+
+    /// Checks every endpoint and every model against the spec.
+    pub fn check(&self) -> Vec<Drift> {
+        let mut fixtures = Fixtures::default();
+        // Fill in optional fields the fixtures leave out, so they get checked too.
+        for sleep in &mut fixtures.sleeps {
+            sleep.v1_id = Some(93_845);
+        }
+        for workout in &mut fixtures.workouts {
+            workout.v1_id = Some(1_043);
+        }
+        let cycle = fixtures.cycles[0].clone();
+        let recovery = fixtures.recoveries[0].clone();
+        let sleep = fixtures.sleeps[0].clone();
+        let workout = fixtures.workouts[0].clone();
+
+        let mut drifts = self.check_paths();
+        drifts.extend(self.check_model("Cycle", &cycle));
+        drifts.extend(self.check_model("Recovery", &recovery));
+        drifts.extend(self.check_model("Sleep", &sleep));
+        drifts.extend(self.check_model("WorkoutV2", &workout));
+        drifts.extend(self.check_model("UserBasicProfile", &fixtures.profile));
+        drifts.extend(self.check_model("UserBodyMeasurement", &fixtures.body));
+        drifts.extend(self.check_model(
+            "PaginatedCycleResponse",
+            &PaginatedCycleResponse {
+                records: Some(vec![cycle]),
+                next_token: Some("MTIzOjEyMzEyMw".to_string()),
+            },
+        ));
+        drifts.extend(self.check_model(
+            "RecoveryCollection",
+            &RecoveryCollection {
+                records: Some(vec![recovery]),
+                next_token: Some("MTIzOjEyMzEyMw".to_string()),
+            },
+        ));
+        drifts.extend(self.check_model(
+            "PaginatedSleepResponse",
+            &PaginatedSleepResponse {
+                records: Some(vec![sleep]),
+                next_token: Some("MTIzOjEyMzEyMw".to_string()),
+            },
+        ));
+        drifts.extend(self.check_model(
+            "WorkoutCollection",
+            &WorkoutCollection {
+                records: Some(vec![workout]),
+                next_token: Some("MTIzOjEyMzEyMw".to_string()),
+            },
+        ));
+        drifts
+    }
+
+    /// Checks that every entry in [`ENDPOINTS`] exists in the spec.
+    pub fn check_paths(&self) -> Vec<Drift> {
+        let paths = self.document["paths"].as_object();
+        ENDPOINTS
+            .iter()
+            .filter(|(method, path)| {
+                !paths.is_some_and(|paths| {
+                    paths.iter().any(|(spec_path, operations)| {
+                        // Servers may carry a prefix such as `/developer`.
+                        normalize(spec_path).ends_with(path)
+                            && operations.get(method.to_lowercase()).is_some()
+                    })
+                })
+            })
+            .map(|(method, path)| Drift {
+                location: format!("{} {}", method, path),
+                kind: DriftKind::MissingPath,
+            })
+            .collect()
+    }
+
+    /// Checks `sample`, serialized, against the schema called `schema`.
+    pub fn check_model<T: Serialize + DeserializeOwned>(
+        &self,
+        schema: &str,
+        sample: &T,
+    ) -> Vec<Drift> {
+        let mut drifts = Vec::new();
+        let Some(definition) = self.document["components"]["schemas"].get(schema) else {
+            drifts.push(Drift {
+                location: schema.to_string(),
+                kind: DriftKind::MissingSchema,
+            });
+            return drifts;
+        };
+        let root = match serde_json::to_value(sample) {
+            Ok(root) => root,
+            Err(_) => return drifts,
+        };
+        let parses = |value: Value| serde_json::from_value::<T>(value).is_ok();
+        let mut walk = Walk {
+            spec: self,
+            schema,
+            root: &root,
+            parses: &parses,
+            drifts: &mut drifts,
+        };
+        walk.value(definition, &root, "");
+        drifts
+    }
+
+    /// Follows `$ref`s and single-entry `allOf`s to the schema they point at.
+    fn resolve<'a>(&'a self, mut schema: &'a Value) -> &'a Value {
+        loop {
+            if let Some(reference) = schema["$ref"].as_str() {
+                let pointer = reference.trim_start_matches('#');
+                match self.document.pointer(pointer) {
+                    Some(target) => schema = target,
+                    None => return schema,
+                }
+            } else if let Some([only]) = schema["allOf"].as_array().map(Vec::as_slice) {
+                schema = only;
+            } else {
+                return schema;
+            }
+        }
+    }
+}
+
+/// Walks a sample value alongside its schema, collecting drift.
+struct Walk<'a> {
+    spec: &'a Spec,
+    schema: &'a str,
+    root: &'a Value,
+    parses: &'a dyn Fn(Value) -> bool,
+    drifts: &'a mut Vec<Drift>,
+}
+
+impl Walk<'_> {
+    fn push(&mut self, pointer: &str, kind: DriftKind) {
+        self.drifts.push(Drift {
+            location: format!("{}{}", self.schema, pointer),
+            kind,
+        });
+    }
+
+    fn value(&mut self, schema: &Value, value: &Value, pointer: &str) {
+        let schema = self.spec.resolve(schema);
+
+        if let Some(expected) = schema["type"].as_str() {
+            let matches = match expected {
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => true,
+            };
+            if !matches {
+                let expected = expected.to_string();
+                self.push(pointer, DriftKind::WrongType { expected });
+                return;
+            }
+        }
+
+        if let (Some(format), Some(text)) = (schema["format"].as_str(), value.as_str()) {
+            let matches = match format {
+                "uuid" => uuid::Uuid::parse_str(text).is_ok(),
+                "date-time" => text.parse::<chrono::DateTime<chrono::Utc>>().is_ok(),
+                _ => true,
+            };
+            if !matches {
+                let expected = format.to_string();
+                self.push(pointer, DriftKind::WrongFormat { expected });
+            }
+        }
+
+        let allowed = schema["enum"].as_array();
+        if allowed.is_some_and(|allowed| !allowed.contains(value)) {
+            self.push(pointer, DriftKind::UnexpectedValue(value.to_string()));
+        }
+
+        if let (Some(items), Some(first)) = (
+            schema.get("items"),
+            value.as_array().and_then(|v| v.first()),
+        ) {
+            self.value(items, first, &format!("{}/0", pointer));
+        }
+
+        if let (Some(properties), Some(object)) =
+            (schema["properties"].as_object(), value.as_object())
+        {
+            self.object(schema, properties, object, pointer);
+        }
+    }
+
+    fn object(
+        &mut self,
+        schema: &Value,
+        properties: &serde_json::Map<String, Value>,
+        object: &serde_json::Map<String, Value>,
+        pointer: &str,
+    ) {
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .map(|r| r.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        for (name, property) in properties {
+            let field = format!("{}/{}", pointer, name);
+            match object.get(name).filter(|v| !v.is_null()) {
+                Some(value) => {
+                    if !required.contains(&name.as_str()) && !self.parses_without(pointer, name) {
+                        self.push(&field, DriftKind::OptionalFieldRequired);
+                    }
+                    self.value(property, value, &field);
+                }
+                None if required.contains(&name.as_str()) => {
+                    self.push(&field, DriftKind::MissingRequiredField);
+                }
+                None => self.push(&field, DriftKind::UnmodelledField),
+            }
+        }
+
+        for name in object.keys().filter(|name| !properties.contains_key(*name)) {
+            self.push(&format!("{}/{}", pointer, name), DriftKind::UnknownField);
+        }
+    }
+
+    /// Whether the whole sample still parses with the field at `pointer/name` removed.
+    fn parses_without(&self, pointer: &str, name: &str) -> bool {
+        let mut trimmed = self.root.clone();
+        if let Some(object) = trimmed.pointer_mut(pointer).and_then(Value::as_object_mut) {
+            object.remove(name);
+        }
+        (self.parses)(trimmed)
+    }
+}
+
+/// Writes path parameters as `{}`, e.g. `/v2/cycle/{cycleId}` as `/v2/cycle/{}`.
+fn normalize(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                "{}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reports_drift_against_a_spec() {
+        let spec = json!({
+            "paths": {
+                "/developer/v2/cycle/{cycleId}": { "get": {} },
+            },
+            "components": { "schemas": {
+                "UserBodyMeasurement": {
+                    "type": "object",
+                    "required": ["height_meter", "max_heart_rate", "bmi"],
+                    "properties": {
+                        "height_meter": { "type": "number" },
+                        "max_heart_rate": { "type": "string" },
+                        "weight_kilogram": { "type": "number" },
+                        "bmi": { "type": "number" },
+                        "body_fat": { "type": "number" },
+                    },
+                },
+            }},
+        });
+        let spec = Spec::from_json(&spec.to_string()).unwrap();
+
+        let paths = spec.check_paths();
+        assert_eq!(paths.len(), ENDPOINTS.len() - 1);
+        assert!(!paths.iter().any(|d| d.location == "GET /v2/cycle/{}"));
+
+        let drifts = spec.check_model("UserBodyMeasurement", &Fixtures::default().body);
+        let kind = |location: &str| {
+            drifts
+                .iter()
+                .find(|d| d.location == location)
+                .map(|d| d.kind.clone())
+        };
+        assert_eq!(
+            kind("UserBodyMeasurement/max_heart_rate"),
+            Some(DriftKind::WrongType {
+                expected: "string".to_string()
+            })
+        );
+        assert_eq!(
+            kind("UserBodyMeasurement/weight_kilogram"),
+            Some(DriftKind::OptionalFieldRequired)
+        );
+        assert_eq!(
+            kind("UserBodyMeasurement/bmi"),
+            Some(DriftKind::MissingRequiredField)
+        );
+        assert_eq!(
+            kind("UserBodyMeasurement/body_fat"),
+            Some(DriftKind::UnmodelledField)
+        );
+        assert_eq!(drifts.len(), 4);
+        assert!(spec.check_model("Cycle", &Fixtures::default().cycles[0])[0].is_breaking());
+    }
+
+    /// Needs network access; run with `--ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn test_conformance_with_published_spec() {
+        let spec = Spec::fetch(SPEC_URL).await.unwrap();
+        let breaking: Vec<String> = spec
+            .check()
+            .iter()
+            .filter(|d| d.is_breaking())
+            .map(Drift::to_string)
+            .collect();
+        assert!(breaking.is_empty(), "{}", breaking.join("\n"));
+    }
+}
@@ -0,0 +1,280 @@
+//! What changed between two snapshots of the same records.
+//!
+//! WHOOP rescores and edits records after the fact: a sleep moved in the app,
+//! a recovery recalculated once calibration ends. Taking a [`Snapshot`] of the
+//! store before and after a sync and [`diff`]ing them tells which records were
+//! added, removed or updated, and for updates which fields changed, so
+//! downstream systems can react to just that.
+//!
+//! ```no_run
+//! # async fn run(api: &whoopsy::WhoopClient, store: &mut impl whoopsy::store::Store) -> whoopsy::Result<()> {
+//! use chrono::{Duration, Utc};
+//! use whoopsy::diff::{Change, Snapshot, diff};
+//! use whoopsy::sync::Delta;
+//!
+//! let last_month = Utc::now() - Duration::days(30)..Utc::now();
+//! let before = Snapshot::from_store(store, last_month.clone())?;
+//! Delta::new().run(api, store).await?;
+//! let changes = diff(&before, &Snapshot::from_store(store, last_month)?);
+//! for change in &changes.sleeps {
+//!     if let Change::Updated { after, fields, .. } = change {
+//!         println!("sleep {} changed: {:?}", after.id, fields);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Fields are compared by their JSON form, nested ones by a dotted path like
+//! `score.stage_summary.total_rem_sleep_time_milli`; `updated_at` is left
+//! out, so a record WHOOP only touched doesn't count as changed.
+
+use crate::error::Result;
+use crate::merge::Versioned;
+use crate::models::*;
+use crate::store::Store;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// Records of every resource, e.g. a store's before a sync.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub cycles: Vec<Cycle>,
+    pub sleeps: Vec<Sleep>,
+    pub recoveries: Vec<Recovery>,
+    pub workouts: Vec<WorkoutV2>,
+}
+
+impl Snapshot {
+    /// The store's records starting in `range`, recoveries by their cycle's start.
+    pub fn from_store(store: &impl Store, range: Range<DateTime<Utc>>) -> Result<Self> {
+        Ok(Self {
+            cycles: store.cycles_between(range.clone())?,
+            sleeps: store.sleeps_between(range.clone())?,
+            recoveries: store.recoveries_between(range.clone())?,
+            workouts: store.workouts_between(range)?,
+        })
+    }
+}
+
+/// One field whose value differs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// Dotted path to the field, e.g. `score.strain`.
+    pub field: String,
+    /// `Null` for a field that wasn't set.
+    pub before: Value,
+    pub after: Value,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.before, self.after)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Change<T> {
+    Added(T),
+    Updated {
+        before: T,
+        after: T,
+        fields: Vec<FieldChange>,
+    },
+    Removed(T),
+}
+
+impl<T> Change<T> {
+    /// The record as it is now, or was last seen when removed.
+    pub fn record(&self) -> &T {
+        match self {
+            Change::Added(record) | Change::Removed(record) => record,
+            Change::Updated { after, .. } => after,
+        }
+    }
+}
+
+/// Every change between two [`Snapshot`]s, per resource.
+#[derive(Debug, Clone, Default)]
+pub struct Changeset {
+    pub cycles: Vec<Change<Cycle>>,
+    pub sleeps: Vec<Change<Sleep>>,
+    pub recoveries: Vec<Change<Recovery>>,
+    pub workouts: Vec<Change<WorkoutV2>>,
+}
+
+impl Changeset {
+    pub fn len(&self) -> usize {
+        self.cycles.len() + self.sleeps.len() + self.recoveries.len() + self.workouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Changeset {
+    Changeset {
+        cycles: diff_records(&before.cycles, &after.cycles),
+        sleeps: diff_records(&before.sleeps, &after.sleeps),
+        recoveries: diff_records(&before.recoveries, &after.recoveries),
+        workouts: diff_records(&before.workouts, &after.workouts),
+    }
+}
+
+/// Changes from `before` to `after`, in `after`'s order with removals last.
+pub fn diff_records<T: Versioned + Serialize>(before: &[T], after: &[T]) -> Vec<Change<T>> {
+    let mut old: HashMap<String, &T> = before.iter().map(|r| (r.key(), r)).collect();
+    let mut changes = Vec::new();
+    for record in after {
+        let Some(previous) = old.remove(&record.key()) else {
+            changes.push(Change::Added(record.clone()));
+            continue;
+        };
+        let fields = diff_fields(previous, record);
+        if !fields.is_empty() {
+            changes.push(Change::Updated {
+                before: previous.clone(),
+                after: record.clone(),
+                fields,
+            });
+        }
+    }
+    // In `before`'s order, so removals come out the same on every run.
+    changes.extend(
+        before
+            .iter()
+            .filter(|r| old.contains_key(&r.key()))
+            .map(|r| Change::Removed(r.clone())),
+    );
+    changes
+}
+
+/// Fields that differ between two versions of a record, `updated_at` aside.
+pub fn diff_fields<T: Serialize>(before: &T, after: &T) -> Vec<FieldChange> {
+    let (Ok(Value::Object(mut before)), Ok(Value::Object(mut after))) =
+        (serde_json::to_value(before), serde_json::to_value(after))
+    else {
+        return Vec::new();
+    };
+    before.remove("updated_at");
+    after.remove("updated_at");
+    let mut changes = Vec::new();
+    diff_objects("", &before, &after, &mut changes);
+    changes
+}
+
+fn diff_objects(
+    prefix: &str,
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+    changes: &mut Vec<FieldChange>,
+) {
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    for field in fields {
+        let path = if prefix.is_empty() {
+            field.clone()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        let empty = Map::new();
+        match (before.get(field), after.get(field)) {
+            (Some(Value::Object(b)), Some(Value::Object(a))) => diff_objects(&path, b, a, changes),
+            // A score that was just added or dropped shows as its fields.
+            (None | Some(Value::Null), Some(Value::Object(a))) => {
+                diff_objects(&path, &empty, a, changes)
+            }
+            (Some(Value::Object(b)), None | Some(Value::Null)) => {
+                diff_objects(&path, b, &empty, changes)
+            }
+            (b, a) if b != a => changes.push(FieldChange {
+                field: path,
+                before: b.cloned().unwrap_or(Value::Null),
+                after: a.cloned().unwrap_or(Value::Null),
+            }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use chrono::Duration;
+    use serde_json::json;
+
+    #[test]
+    fn test_diffs_records_field_by_field() {
+        let scored = fixtures::cycle_scored();
+        let pending = fixtures::cycle_pending();
+        let before = Snapshot {
+            cycles: vec![scored.clone(), pending.clone()],
+            ..Default::default()
+        };
+
+        // Scored once it ended; a touched record without other edits isn't a change.
+        let mut touched = scored.clone();
+        touched.updated_at += Duration::hours(1);
+        let mut rescored = pending.clone();
+        rescored.end = Some(pending.start + Duration::hours(20));
+        rescored.score_state = ScoreState::Scored;
+        rescored.score = scored.score.clone();
+        let mut new = fixtures::cycle_unscorable();
+        new.id = 1;
+        let after = Snapshot {
+            cycles: vec![touched, rescored, new],
+            ..Default::default()
+        };
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 2);
+        let Change::Updated { fields, .. } = &changes.cycles[0] else {
+            panic!("expected an update, got {:?}", changes.cycles[0]);
+        };
+        let fields: Vec<_> = fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "end",
+                "score.average_heart_rate",
+                "score.kilojoule",
+                "score.max_heart_rate",
+                "score.strain",
+                "score_state"
+            ]
+        );
+        assert!(matches!(&changes.cycles[1], Change::Added(c) if c.id == 1));
+
+        let removed = diff(&after, &Snapshot::default());
+        assert!(
+            removed
+                .cycles
+                .iter()
+                .all(|c| matches!(c, Change::Removed(_)))
+        );
+        assert_eq!(removed.cycles[2].record().id, 1);
+    }
+
+    #[test]
+    fn test_formats_field_changes() {
+        let mut before = fixtures::recovery_scored();
+        let mut after = before.clone();
+        after.score.as_mut().unwrap().recovery_score = 71.0;
+        let fields = diff_fields(&before, &after);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].after, json!(71.0));
+        assert!(fields[0].to_string().starts_with("score.recovery_score: "));
+
+        before.score = None;
+        let fields = diff_fields(&before, &after);
+        assert!(fields.iter().all(|f| f.field.starts_with("score.")));
+        assert!(fields.iter().all(|f| f.before == Value::Null));
+    }
+}
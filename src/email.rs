@@ -0,0 +1,259 @@
+//! Email over SMTP, for alerts and reports.
+//!
+//! An [`EmailNotifier`] is an alert [`Notifier`] that mails each alert, and
+//! mails [`Report`]s with [`send_report`](EmailNotifier::send_report): the
+//! Markdown as the plain text part and the HTML page as the rich one.
+//! [`SmtpSettings`] deserialize, so they can live in a config file:
+//!
+//! ```toml
+//! host = "smtp.example.com"
+//! username = "me@example.com"
+//! password = "app-password"
+//! from = "WHOOP <me@example.com>"
+//! to = ["me@example.com"]
+//! ```
+
+use crate::alerts::{Alert, Notification, Notifier};
+use crate::error::{Result, WhoopError};
+use crate::report::Report;
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// TLS from the first byte, on port 465 by default.
+    Tls,
+    /// Plain text upgraded with STARTTLS, on port 587 by default.
+    #[default]
+    StartTls,
+    /// No encryption, on port 25 by default. Only for a relay on the same
+    /// host or network.
+    None,
+}
+
+/// Debug output hides the password.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    /// Defaults to the port [`security`](Self::security) implies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: Security,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// An address, optionally with a name: `WHOOP <me@example.com>`.
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl fmt::Debug for SmtpSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpSettings")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("security", &self.security)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "********"))
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish()
+    }
+}
+
+impl SmtpSettings {
+    pub fn new(host: impl Into<String>, from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port: None,
+            security: Security::default(),
+            username: None,
+            password: None,
+            from: from.into(),
+            to: vec![to.into()],
+        }
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_security(mut self, security: Security) -> Self {
+        self.security = security;
+        self
+    }
+
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn with_recipient(mut self, to: impl Into<String>) -> Self {
+        self.to.push(to.into());
+        self
+    }
+}
+
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    /// Checks the addresses and sets up the transport. Nothing connects until
+    /// the first mail is sent.
+    pub fn new(settings: &SmtpSettings) -> Result<Self> {
+        let mut builder = match settings.security {
+            Security::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)?,
+            Security::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)?
+            }
+            Security::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(settings.host.as_str())
+            }
+        };
+        if let Some(port) = settings.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        if settings.to.is_empty() {
+            return Err(WhoopError::BadRequest(
+                "no email recipients".to_string(),
+                None,
+            ));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: mailbox(&settings.from)?,
+            to: settings
+                .to
+                .iter()
+                .map(|to| mailbox(to))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Mails `text` to every recipient, with `html` as an alternative part.
+    pub async fn send(&self, subject: &str, text: String, html: Option<String>) -> Result<()> {
+        self.transport
+            .send(self.message(subject, text, html)?)
+            .await?;
+        Ok(())
+    }
+
+    /// Mails the report, titled after it.
+    pub async fn send_report(&self, report: &Report) -> Result<()> {
+        self.send(&report.title, report.to_markdown(), Some(report.to_html()))
+            .await
+    }
+
+    fn message(&self, subject: &str, text: String, html: Option<String>) -> Result<Message> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let message = match html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(text, html)),
+            None => builder.header(ContentType::TEXT_PLAIN).body(text),
+        };
+        message.map_err(|e| WhoopError::BadRequest(format!("invalid email: {}", e), None))
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> Notification<'a> {
+        Box::pin(async move {
+            let mut text = format!("{}\n", alert.message);
+            if let Some(user) = &alert.user {
+                text.push_str(&format!("\nUser: {}\n", user));
+            }
+            self.send(&format!("WHOOP alert: {}", alert.rule), text, None)
+                .await
+        })
+    }
+}
+
+fn mailbox(address: &str) -> Result<Mailbox> {
+    address.parse().map_err(|e| {
+        WhoopError::BadRequest(format!("invalid email address {}: {}", address, e), None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_plain_and_html_messages() {
+        let settings = SmtpSettings::new("localhost", "WHOOP <me@example.com>", "me@example.com")
+            .with_security(Security::None)
+            .with_recipient("coach@example.com");
+        let notifier = EmailNotifier::new(&settings).unwrap();
+
+        let message = notifier
+            .message("Week of 2024-03-04", "# Week".to_string(), None)
+            .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("From: WHOOP <me@example.com>"));
+        assert!(formatted.contains("To: me@example.com, coach@example.com"));
+        assert!(formatted.contains("Subject: Week of 2024-03-04"));
+        assert!(formatted.contains("text/plain"));
+
+        let message = notifier
+            .message(
+                "Week",
+                "# Week".to_string(),
+                Some("<h1>Week</h1>".to_string()),
+            )
+            .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("text/html"));
+
+        let settings = SmtpSettings::new("localhost", "not an address", "me@example.com");
+        assert!(EmailNotifier::new(&settings).is_err());
+    }
+
+    #[test]
+    fn test_reads_settings_from_toml() {
+        let settings: SmtpSettings = toml::from_str(
+            r#"
+            host = "smtp.example.com"
+            security = "tls"
+            from = "me@example.com"
+            to = ["me@example.com"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.security, Security::Tls);
+        assert_eq!(settings.port, None);
+        assert_eq!(settings.username, None);
+    }
+
+    #[test]
+    fn test_debug_hides_the_password() {
+        let settings = SmtpSettings::new("localhost", "me@example.com", "me@example.com")
+            .with_credentials("me@example.com", "app-password");
+        let debug = format!("{:?}", settings);
+        assert!(debug.contains("me@example.com"));
+        assert!(!debug.contains("app-password"));
+    }
+}
@@ -1,6 +1,14 @@
+use crate::audit::{RATE_LIMIT_LIMIT, RATE_LIMIT_REMAINING, RATE_LIMIT_RESET};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 
+/// Everything that can go wrong. New variants may be added, so match on
+/// [`kind`](WhoopError::kind) where a catch-all arm doesn't fit.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum WhoopError {
     #[error("HTTP request failed: {0}")]
     RequestError(#[from] reqwest::Error),
@@ -8,39 +16,306 @@ pub enum WhoopError {
     #[error("Failed to serialize/deserialize data: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[cfg(feature = "sqlite")]
+    #[error("Storage error: {0}")]
+    StorageError(#[from] rusqlite::Error),
+
+    #[cfg(feature = "sled")]
+    #[error("sled error: {0}")]
+    SledError(#[from] sled::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres error: {0}")]
+    PostgresError(#[from] tokio_postgres::Error),
+
+    #[cfg(feature = "polars")]
+    #[error("DataFrame error: {0}")]
+    PolarsError(#[from] polars::error::PolarsError),
+
+    #[cfg(feature = "mqtt")]
+    #[error("MQTT error: {0}")]
+    MqttError(#[from] rumqttc::ClientError),
+
+    #[cfg(feature = "email")]
+    #[error("Email error: {0}")]
+    EmailError(#[from] lettre::transport::smtp::Error),
+
+    #[cfg(feature = "zip")]
+    #[error("Archive error: {0}")]
+    ArchiveError(#[from] zip::result::ZipError),
+
     #[error("Authentication failed: {0}")]
-    AuthenticationError(String),
+    AuthenticationError(String, Option<Box<ApiErrorBody>>),
 
     #[error("Rate limit exceeded")]
-    RateLimitExceeded,
+    RateLimitExceeded(RateLimit, Option<Box<ApiErrorBody>>),
 
     #[error("Resource not found")]
-    NotFound,
+    NotFound(Option<Box<ApiErrorBody>>),
+
+    /// A record waited on with a [`ScorePolicy`](crate::scoring::ScorePolicy)
+    /// didn't get scored: it's unscorable, or still pending at the timeout.
+    #[error("Record not scored: {}", .0.as_str())]
+    NotScored(crate::models::ScoreState),
 
     #[error("Bad request: {0}")]
-    BadRequest(String),
+    BadRequest(String, Option<Box<ApiErrorBody>>),
 
     #[error("Server error: {0}")]
-    ServerError(String),
+    ServerError(String, Option<Box<ApiErrorBody>>),
 
     #[error("Unknown error: {0}")]
-    Unknown(String),
+    Unknown(String, Option<Box<ApiErrorBody>>),
 }
 
 impl WhoopError {
     /// Maps HTTP status codes to our error types.
     /// Helps us handle API errors consistently.
     pub fn from_status(status: reqwest::StatusCode, message: Option<String>) -> Self {
+        let body = message
+            .as_deref()
+            .and_then(ApiErrorBody::parse)
+            .map(Box::new);
         let msg = message.unwrap_or_else(|| status.to_string());
         match status.as_u16() {
-            400 => Self::BadRequest(msg),
-            401 => Self::AuthenticationError(msg),
-            404 => Self::NotFound,
-            429 => Self::RateLimitExceeded,
-            500..=599 => Self::ServerError(msg),
-            _ => Self::Unknown(msg),
+            400 => Self::BadRequest(msg, body),
+            401 => Self::AuthenticationError(msg, body),
+            404 => Self::NotFound(body),
+            429 => Self::RateLimitExceeded(RateLimit::default(), body),
+            500..=599 => Self::ServerError(msg, body),
+            _ => Self::Unknown(msg, body),
+        }
+    }
+
+    /// Like [`from_status`](Self::from_status), also keeping the rate limit
+    /// headers of a 429.
+    pub fn from_response(
+        status: reqwest::StatusCode,
+        headers: &HeaderMap,
+        message: Option<String>,
+    ) -> Self {
+        match Self::from_status(status, message) {
+            Self::RateLimitExceeded(_, body) => {
+                Self::RateLimitExceeded(RateLimit::from_headers(headers), body)
+            }
+            error => error,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::RequestError(_) => ErrorKind::Transport,
+            Self::SerializationError(_) => ErrorKind::Decode,
+            Self::IoError(_) => ErrorKind::Io,
+            #[cfg(feature = "zip")]
+            Self::ArchiveError(_) => ErrorKind::Io,
+            #[cfg(feature = "email")]
+            Self::EmailError(_) => ErrorKind::Transport,
+            #[cfg(feature = "sqlite")]
+            Self::StorageError(_) => ErrorKind::Storage,
+            #[cfg(feature = "sled")]
+            Self::SledError(_) => ErrorKind::Storage,
+            #[cfg(feature = "postgres")]
+            Self::PostgresError(_) => ErrorKind::Storage,
+            #[cfg(feature = "polars")]
+            Self::PolarsError(_) => ErrorKind::Other,
+            #[cfg(feature = "mqtt")]
+            Self::MqttError(_) => ErrorKind::Other,
+            Self::AuthenticationError(..) => ErrorKind::Authentication,
+            Self::RateLimitExceeded(..) => ErrorKind::RateLimited,
+            Self::NotFound(_) => ErrorKind::NotFound,
+            Self::NotScored(_) => ErrorKind::NotScored,
+            Self::BadRequest(..) => ErrorKind::BadRequest,
+            Self::ServerError(..) => ErrorKind::Server,
+            Self::Unknown(..) => ErrorKind::Other,
+        }
+    }
+
+    /// The structured body WHOOP sent with a failed request, if it sent one.
+    pub fn api_error(&self) -> Option<&ApiErrorBody> {
+        match self {
+            Self::AuthenticationError(_, body)
+            | Self::RateLimitExceeded(_, body)
+            | Self::NotFound(body)
+            | Self::BadRequest(_, body)
+            | Self::ServerError(_, body)
+            | Self::Unknown(_, body) => body.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// How long to wait before retrying a rate limited request, if the
+    /// response said.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimitExceeded(rate_limit, _) => rate_limit.wait(),
+            _ => None,
         }
     }
 }
 
+/// The JSON body of an API or OAuth error. WHOOP's API and its OAuth server
+/// name fields differently, so each field takes either name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiErrorBody {
+    /// A machine-readable code like `invalid_grant`.
+    #[serde(alias = "error")]
+    pub code: Option<String>,
+    #[serde(alias = "error_description")]
+    pub message: Option<String>,
+    /// The status code, when the body repeats it.
+    pub status: Option<u16>,
+    /// Whatever else the body held.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ApiErrorBody {
+    /// Reads a response body. `None` unless it's JSON with a code or message.
+    pub fn parse(text: &str) -> Option<Self> {
+        let body: Self = serde_json::from_str(text).ok()?;
+        (body.code.is_some() || body.message.is_some()).then_some(body)
+    }
+}
+
+/// What kind of failure a [`WhoopError`] is. Kinds stay put while variants
+/// come and go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The request didn't get a response.
+    Transport,
+    /// A response or file couldn't be parsed.
+    Decode,
+    Io,
+    Storage,
+    Authentication,
+    RateLimited,
+    NotFound,
+    /// A record never got a score.
+    NotScored,
+    BadRequest,
+    Server,
+    Other,
+}
+
+/// The rate limit headers of a 429 response. Each is `None` when missing or
+/// unreadable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// From `Retry-After`, given as seconds or a date.
+    pub retry_after: Option<Duration>,
+    /// Requests allowed in the shortest window.
+    pub limit: Option<u32>,
+    pub remaining: Option<u32>,
+    /// Until the current window resets.
+    pub reset: Option<Duration>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self::from_headers_at(headers, Utc::now())
+    }
+
+    fn from_headers_at(headers: &HeaderMap, now: DateTime<Utc>) -> Self {
+        let header = |name| headers.get(name)?.to_str().ok().map(str::trim);
+        // WHOOP lists every window, e.g. `100, 100;window=60, 10000;window=86400`.
+        let first = |name| Some(header(name)?.split([',', ';']).next()?.trim());
+        let retry_after = header(RETRY_AFTER.as_str()).and_then(|value| match value.parse() {
+            Ok(seconds) => Some(Duration::from_secs(seconds)),
+            Err(_) => {
+                let at = DateTime::parse_from_rfc2822(value).ok()?;
+                (at.with_timezone(&Utc) - now).to_std().ok()
+            }
+        });
+        Self {
+            retry_after,
+            limit: first(RATE_LIMIT_LIMIT).and_then(|v| v.parse().ok()),
+            remaining: first(RATE_LIMIT_REMAINING).and_then(|v| v.parse().ok()),
+            reset: first(RATE_LIMIT_RESET)
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs),
+        }
+    }
+
+    /// `Retry-After`, or else when the window resets.
+    pub fn wait(&self) -> Option<Duration> {
+        self.retry_after.or(self.reset)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, WhoopError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_reads_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RATE_LIMIT_LIMIT,
+            HeaderValue::from_static("100, 100;window=60, 10000;window=86400"),
+        );
+        headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from_static("0"));
+        headers.insert(RATE_LIMIT_RESET, HeaderValue::from_static("42"));
+        let error =
+            WhoopError::from_response(reqwest::StatusCode::TOO_MANY_REQUESTS, &headers, None);
+        assert_eq!(error.kind(), ErrorKind::RateLimited);
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(42)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Fri, 01 Mar 2024 08:00:30 GMT"),
+        );
+        let now = "2024-03-01T08:00:00Z".parse().unwrap();
+        assert_eq!(
+            RateLimit::from_headers_at(&headers, now),
+            RateLimit {
+                retry_after: Some(Duration::from_secs(30)),
+                limit: Some(100),
+                remaining: Some(0),
+                reset: Some(Duration::from_secs(42)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parses_error_bodies() {
+        let body =
+            r#"{"error":"invalid_grant","error_description":"Token revoked","hint":"reconnect"}"#;
+        let refused =
+            WhoopError::from_status(reqwest::StatusCode::BAD_REQUEST, Some(body.to_string()));
+        let body = refused.api_error().unwrap();
+        assert_eq!(body.code.as_deref(), Some("invalid_grant"));
+        assert_eq!(body.message.as_deref(), Some("Token revoked"));
+        assert_eq!(body.extra["hint"], "reconnect");
+
+        let plain = WhoopError::from_status(
+            reqwest::StatusCode::BAD_REQUEST,
+            Some("plain text".to_string()),
+        );
+        assert_eq!(plain.api_error(), None);
+        let unrelated =
+            WhoopError::from_status(reqwest::StatusCode::BAD_REQUEST, Some(r#"{"id":1}"#.into()));
+        assert_eq!(unrelated.api_error(), None);
+
+        let body = r#"{"message":"No such cycle","status":404}"#;
+        let missing =
+            WhoopError::from_status(reqwest::StatusCode::NOT_FOUND, Some(body.to_string()));
+        assert_eq!(missing.api_error().unwrap().status, Some(404));
+        let limited = WhoopError::from_response(
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            &HeaderMap::new(),
+            Some(r#"{"error":"rate_limited"}"#.to_string()),
+        );
+        assert_eq!(
+            limited.api_error().unwrap().code.as_deref(),
+            Some("rate_limited")
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Apple Health `export.xml` writer.
+//!
+//! Produces the same `HealthData` document the Health app exports, so the file can
+//! be fed to importers that read that format. Sleeps become `SleepAnalysis` in-bed
+//! records and workouts carry energy burned, distance and a heart rate summary.
+//! Dates are written in each record's own offset, as Health does.
+
+use crate::error::Result;
+use crate::models::*;
+use chrono::{DateTime, FixedOffset, Utc};
+use std::io::Write;
+
+const SOURCE_NAME: &str = "WHOOP";
+const KILOJOULES_PER_KILOCALORIE: f32 = 4.184;
+
+/// A record that can be written as Apple Health XML elements.
+pub trait AppleHealthRecord {
+    fn write_xml<W: Write>(&self, out: &mut W) -> Result<()>;
+}
+
+pub struct AppleHealthWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> AppleHealthWriter<W> {
+    /// Writes the document prologue straight away.
+    pub fn new(mut out: W) -> Result<Self> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<HealthData locale="en_US">"#)?;
+        writeln!(
+            out,
+            r#" <ExportDate value="{}"/>"#,
+            date(Utc::now(), "+00:00")
+        )?;
+        Ok(Self { out })
+    }
+
+    pub fn write<T: AppleHealthRecord>(&mut self, record: &T) -> Result<()> {
+        record.write_xml(&mut self.out)
+    }
+
+    pub fn write_all<'a, T: AppleHealthRecord + 'a>(
+        &mut self,
+        records: impl IntoIterator<Item = &'a T>,
+    ) -> Result<()> {
+        for record in records {
+            self.write(record)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the document and hands back the writer.
+    pub fn finish(mut self) -> Result<W> {
+        writeln!(self.out, "</HealthData>")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl AppleHealthRecord for Sleep {
+    fn write_xml<W: Write>(&self, out: &mut W) -> Result<()> {
+        let tz = &self.timezone_offset;
+        writeln!(
+            out,
+            r#" <Record type="HKCategoryTypeIdentifierSleepAnalysis" sourceName="{}" creationDate="{}" startDate="{}" endDate="{}" value="HKCategoryValueSleepAnalysisInBed">"#,
+            SOURCE_NAME,
+            date(self.created_at, tz),
+            date(self.start, tz),
+            date(self.end, tz),
+        )?;
+        metadata(out, "HKMetadataKeyExternalUUID", &self.id.to_string())?;
+        if let Some(score) = &self.score {
+            metadata(
+                out,
+                "WHOOPTotalSleepTimeMilli",
+                &score.stage_summary.total_sleep_time_milli().to_string(),
+            )?;
+            if let Some(performance) = score.sleep_performance_percentage {
+                metadata(out, "WHOOPSleepPerformance", &performance.to_string())?;
+            }
+        }
+        writeln!(out, " </Record>")?;
+        Ok(())
+    }
+}
+
+impl AppleHealthRecord for WorkoutV2 {
+    fn write_xml<W: Write>(&self, out: &mut W) -> Result<()> {
+        let tz = &self.timezone_offset;
+        let (start, end) = (date(self.start, tz), date(self.end, tz));
+        let minutes = (self.end - self.start).num_milliseconds() as f64 / 60_000.0;
+        writeln!(
+            out,
+            r#" <Workout workoutActivityType="{}" duration="{:.2}" durationUnit="min" sourceName="{}" creationDate="{}" startDate="{}" endDate="{}">"#,
+            activity_type(&self.sport_name),
+            minutes,
+            SOURCE_NAME,
+            date(self.created_at, tz),
+            start,
+            end,
+        )?;
+        metadata(out, "HKMetadataKeyExternalUUID", &self.id.to_string())?;
+
+        if let Some(score) = &self.score {
+            metadata(out, "WHOOPStrain", &score.strain.to_string())?;
+            writeln!(
+                out,
+                r#"  <WorkoutStatistics type="HKQuantityTypeIdentifierActiveEnergyBurned" startDate="{}" endDate="{}" sum="{:.1}" unit="kcal"/>"#,
+                start,
+                end,
+                score.kilojoule / KILOJOULES_PER_KILOCALORIE
+            )?;
+            writeln!(
+                out,
+                r#"  <WorkoutStatistics type="HKQuantityTypeIdentifierHeartRate" startDate="{}" endDate="{}" average="{}" maximum="{}" unit="count/min"/>"#,
+                start, end, score.average_heart_rate, score.max_heart_rate
+            )?;
+            if let Some(meters) = score.distance_meter {
+                writeln!(
+                    out,
+                    r#"  <WorkoutStatistics type="{}" startDate="{}" endDate="{}" sum="{:.3}" unit="km"/>"#,
+                    distance_type(&self.sport_name),
+                    start,
+                    end,
+                    meters / 1000.0
+                )?;
+            }
+        }
+
+        writeln!(out, " </Workout>")?;
+        Ok(())
+    }
+}
+
+fn metadata<W: Write>(out: &mut W, key: &str, value: &str) -> Result<()> {
+    writeln!(
+        out,
+        r#"  <MetadataEntry key="{}" value="{}"/>"#,
+        key,
+        escape(value)
+    )?;
+    Ok(())
+}
+
+/// Health's `2024-03-01 01:30:00 -0500` format, in the record's offset when it parses.
+fn date(time: DateTime<Utc>, offset: &str) -> String {
+    let offset = offset
+        .parse::<FixedOffset>()
+        .unwrap_or_else(|_| FixedOffset::east_opt(0).unwrap());
+    time.with_timezone(&offset)
+        .format("%Y-%m-%d %H:%M:%S %z")
+        .to_string()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Maps a WHOOP sport name onto the closest `HKWorkoutActivityType`.
+fn activity_type(sport: &str) -> &'static str {
+    match sport.to_lowercase().replace(' ', "-").as_str() {
+        "running" => "HKWorkoutActivityTypeRunning",
+        "cycling" | "spin" | "spinning" | "mountain-biking" => "HKWorkoutActivityTypeCycling",
+        "walking" => "HKWorkoutActivityTypeWalking",
+        "hiking-rucking" => "HKWorkoutActivityTypeHiking",
+        "swimming" => "HKWorkoutActivityTypeSwimming",
+        "rowing" => "HKWorkoutActivityTypeRowing",
+        "yoga" => "HKWorkoutActivityTypeYoga",
+        "pilates" => "HKWorkoutActivityTypePilates",
+        "weightlifting" | "powerlifting" => "HKWorkoutActivityTypeTraditionalStrengthTraining",
+        "functional-fitness" => "HKWorkoutActivityTypeFunctionalStrengthTraining",
+        "hiit" => "HKWorkoutActivityTypeHighIntensityIntervalTraining",
+        "elliptical" => "HKWorkoutActivityTypeElliptical",
+        "stairmaster" | "climber" => "HKWorkoutActivityTypeStairClimbing",
+        "stretching" => "HKWorkoutActivityTypeFlexibility",
+        "meditation" => "HKWorkoutActivityTypeMindAndBody",
+        "dance" => "HKWorkoutActivityTypeDance",
+        "boxing" => "HKWorkoutActivityTypeBoxing",
+        "martial-arts" | "jiu-jitsu" => "HKWorkoutActivityTypeMartialArts",
+        "wrestling" => "HKWorkoutActivityTypeWrestling",
+        "rock-climbing" => "HKWorkoutActivityTypeClimbing",
+        "skiing" => "HKWorkoutActivityTypeDownhillSkiing",
+        "cross-country-skiing" => "HKWorkoutActivityTypeCrossCountrySkiing",
+        "snowboarding" => "HKWorkoutActivityTypeSnowboarding",
+        "surfing" => "HKWorkoutActivityTypeSurfingSports",
+        "paddleboarding" | "kayaking" => "HKWorkoutActivityTypePaddleSports",
+        "sailing" => "HKWorkoutActivityTypeSailing",
+        "golf" => "HKWorkoutActivityTypeGolf",
+        "tennis" => "HKWorkoutActivityTypeTennis",
+        "squash" => "HKWorkoutActivityTypeSquash",
+        "pickleball" => "HKWorkoutActivityTypePickleball",
+        "padel" => "HKWorkoutActivityTypeRacquetball",
+        "soccer" => "HKWorkoutActivityTypeSoccer",
+        "football" => "HKWorkoutActivityTypeAmericanFootball",
+        "basketball" => "HKWorkoutActivityTypeBasketball",
+        "baseball" => "HKWorkoutActivityTypeBaseball",
+        "softball" => "HKWorkoutActivityTypeSoftball",
+        "volleyball" => "HKWorkoutActivityTypeVolleyball",
+        "ice-hockey" | "field-hockey" => "HKWorkoutActivityTypeHockey",
+        "lacrosse" => "HKWorkoutActivityTypeLacrosse",
+        "rugby" => "HKWorkoutActivityTypeRugby",
+        "fencing" => "HKWorkoutActivityTypeFencing",
+        "gymnastics" => "HKWorkoutActivityTypeGymnastics",
+        "track-and-field" => "HKWorkoutActivityTypeTrackAndField",
+        "water-polo" => "HKWorkoutActivityTypeWaterPolo",
+        "horseback-riding" => "HKWorkoutActivityTypeEquestrianSports",
+        "triathlon" | "duathlon" => "HKWorkoutActivityTypeMixedCardio",
+        _ => "HKWorkoutActivityTypeOther",
+    }
+}
+
+fn distance_type(sport: &str) -> &'static str {
+    match activity_type(sport) {
+        "HKWorkoutActivityTypeCycling" => "HKQuantityTypeIdentifierDistanceCycling",
+        "HKWorkoutActivityTypeSwimming" => "HKQuantityTypeIdentifierDistanceSwimming",
+        "HKWorkoutActivityTypeDownhillSkiing" | "HKWorkoutActivityTypeSnowboarding" => {
+            "HKQuantityTypeIdentifierDistanceDownhillSnowSports"
+        }
+        _ => "HKQuantityTypeIdentifierDistanceWalkingRunning",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workout_in_local_time_with_energy_in_kcal() {
+        let start = "2024-03-01T11:00:00Z".parse().unwrap();
+        let workout = WorkoutV2 {
+            id: uuid::Uuid::nil(),
+            v1_id: None,
+            user_id: 10129,
+            created_at: start,
+            updated_at: start,
+            start,
+            end: "2024-03-01T11:30:00Z".parse().unwrap(),
+            timezone_offset: "-05:00".to_string(),
+            sport_name: "running".to_string(),
+            score_state: ScoreState::Scored,
+            score: Some(WorkoutScore {
+                strain: 8.25,
+                average_heart_rate: 123,
+                max_heart_rate: 146,
+                kilojoule: 1569.34,
+                percent_recorded: 100.0,
+                distance_meter: Some(5000.0),
+                altitude_gain_meter: None,
+                altitude_change_meter: None,
+                zone_durations: ZoneDurations::default(),
+            }),
+            sport_id: Some(0),
+        };
+
+        let mut writer = AppleHealthWriter::new(Vec::new()).unwrap();
+        writer.write(&workout).unwrap();
+        let xml = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert!(
+            xml.contains(r#"workoutActivityType="HKWorkoutActivityTypeRunning" duration="30.00""#)
+        );
+        assert!(xml.contains(r#"startDate="2024-03-01 06:00:00 -0500""#));
+        assert!(xml.contains(r#"sum="375.1" unit="kcal""#));
+        assert!(xml.trim_end().ends_with("</HealthData>"));
+    }
+}
@@ -0,0 +1,485 @@
+//! Streaming CSV writers for every resource.
+//!
+//! Each record is written as soon as it's passed in, so exporting years of
+//! history never holds more than one row in memory. Writers also take the
+//! borrowed records of [`crate::models::borrowed`], so rows can go straight
+//! from a page's JSON to CSV without owning the records in between.
+
+use super::Units;
+use super::flat::FlatRecovery;
+use crate::error::Result;
+use crate::models::borrowed::{CycleRef, SleepRef, WorkoutRef};
+use crate::models::*;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use std::fmt::Display;
+use std::io::Write;
+use std::marker::PhantomData;
+
+/// Which timezone timestamps are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timezone {
+    #[default]
+    Utc,
+    /// The record's own `timezone_offset`, i.e. the wearer's local time.
+    /// Records without an offset (recoveries) fall back to UTC.
+    Record,
+    Fixed(FixedOffset),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub units: Units,
+    pub timezone: Timezone,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            units: Units::Metric,
+            timezone: Timezone::Utc,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    fn timestamp(&self, time: DateTime<Utc>, offset: Option<&str>) -> String {
+        let offset = match self.timezone {
+            Timezone::Utc => None,
+            Timezone::Record => offset.and_then(|o| o.parse::<FixedOffset>().ok()),
+            Timezone::Fixed(offset) => Some(offset),
+        };
+        match offset {
+            Some(offset) => time
+                .with_timezone(&offset)
+                .to_rfc3339_opts(SecondsFormat::Millis, false),
+            None => time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        }
+    }
+}
+
+/// A record that can be written as one CSV row.
+pub trait CsvRecord {
+    /// Column names. Converted columns are named after the unit they're in.
+    fn header(units: Units) -> Vec<&'static str>;
+
+    fn row(&self, options: &CsvOptions) -> Vec<String>;
+}
+
+/// Writes records of one resource type as CSV.
+/// The header goes out with the first record, or on `finish()` for empty exports.
+pub struct CsvWriter<W: Write, R> {
+    out: W,
+    options: CsvOptions,
+    header_written: bool,
+    record: PhantomData<fn(&R)>,
+}
+
+pub type CycleCsvWriter<W> = CsvWriter<W, Cycle>;
+pub type SleepCsvWriter<W> = CsvWriter<W, Sleep>;
+pub type RecoveryCsvWriter<W> = CsvWriter<W, Recovery>;
+pub type WorkoutCsvWriter<W> = CsvWriter<W, WorkoutV2>;
+
+impl<W: Write, R: CsvRecord> CsvWriter<W, R> {
+    pub fn new(out: W, options: CsvOptions) -> Self {
+        Self {
+            out,
+            options,
+            header_written: false,
+            record: PhantomData,
+        }
+    }
+
+    pub fn write(&mut self, record: &R) -> Result<()> {
+        self.write_header()?;
+        let row = record.row(&self.options);
+        self.write_line(row.iter().map(String::as_str))
+    }
+
+    pub fn write_all<'a>(&mut self, records: impl IntoIterator<Item = &'a R>) -> Result<()>
+    where
+        R: 'a,
+    {
+        for record in records {
+            self.write(record)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Makes sure the header is written and flushes the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_header()?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    pub(super) fn write_header(&mut self) -> Result<()> {
+        if !self.header_written {
+            self.header_written = true;
+            self.write_line(R::header(self.options.units).into_iter())?;
+        }
+        Ok(())
+    }
+
+    fn write_line<'a>(&mut self, fields: impl Iterator<Item = &'a str>) -> Result<()> {
+        let delimiter = self.options.delimiter as char;
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                write!(self.out, "{}", delimiter)?;
+            }
+            if field.contains([delimiter, '"', '\n', '\r']) {
+                write!(self.out, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.out.write_all(field.as_bytes())?;
+            }
+        }
+        writeln!(self.out)?;
+        Ok(())
+    }
+}
+
+fn opt<T: Display>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+const FEET_PER_METER: f32 = 3.280_84;
+const MILES_PER_METER: f32 = 0.000_621_371;
+
+impl CsvRecord for CycleRef<'_> {
+    fn header(_units: Units) -> Vec<&'static str> {
+        vec![
+            "id",
+            "user_id",
+            "created_at",
+            "updated_at",
+            "start",
+            "end",
+            "timezone_offset",
+            "score_state",
+            "strain",
+            "kilojoule",
+            "average_heart_rate",
+            "max_heart_rate",
+        ]
+    }
+
+    fn row(&self, options: &CsvOptions) -> Vec<String> {
+        let score = self.score.as_ref();
+        let tz = Some(&*self.timezone_offset);
+        vec![
+            self.id.to_string(),
+            self.user_id.to_string(),
+            options.timestamp(self.created_at, tz),
+            options.timestamp(self.updated_at, tz),
+            options.timestamp(self.start, tz),
+            opt(self.end.map(|end| options.timestamp(end, tz))),
+            self.timezone_offset.to_string(),
+            self.score_state.as_str().to_string(),
+            opt(score.map(|s| s.strain)),
+            opt(score.map(|s| s.kilojoule)),
+            opt(score.map(|s| s.average_heart_rate)),
+            opt(score.map(|s| s.max_heart_rate)),
+        ]
+    }
+}
+
+impl CsvRecord for SleepRef<'_> {
+    fn header(_units: Units) -> Vec<&'static str> {
+        vec![
+            "id",
+            "cycle_id",
+            "user_id",
+            "created_at",
+            "updated_at",
+            "start",
+            "end",
+            "timezone_offset",
+            "nap",
+            "score_state",
+            "total_in_bed_time_milli",
+            "total_awake_time_milli",
+            "total_no_data_time_milli",
+            "total_light_sleep_time_milli",
+            "total_slow_wave_sleep_time_milli",
+            "total_rem_sleep_time_milli",
+            "sleep_cycle_count",
+            "disturbance_count",
+            "baseline_milli",
+            "need_from_sleep_debt_milli",
+            "need_from_recent_strain_milli",
+            "need_from_recent_nap_milli",
+            "respiratory_rate",
+            "sleep_performance_percentage",
+            "sleep_consistency_percentage",
+            "sleep_efficiency_percentage",
+        ]
+    }
+
+    fn row(&self, options: &CsvOptions) -> Vec<String> {
+        let score = self.score.as_ref();
+        let stages = score.map(|s| &s.stage_summary);
+        let needed = score.map(|s| &s.sleep_needed);
+        let tz = Some(&*self.timezone_offset);
+        vec![
+            self.id.to_string(),
+            self.cycle_id.to_string(),
+            self.user_id.to_string(),
+            options.timestamp(self.created_at, tz),
+            options.timestamp(self.updated_at, tz),
+            options.timestamp(self.start, tz),
+            options.timestamp(self.end, tz),
+            self.timezone_offset.to_string(),
+            self.nap.to_string(),
+            self.score_state.as_str().to_string(),
+            opt(stages.map(|s| s.total_in_bed_time_milli)),
+            opt(stages.map(|s| s.total_awake_time_milli)),
+            opt(stages.map(|s| s.total_no_data_time_milli)),
+            opt(stages.map(|s| s.total_light_sleep_time_milli)),
+            opt(stages.map(|s| s.total_slow_wave_sleep_time_milli)),
+            opt(stages.map(|s| s.total_rem_sleep_time_milli)),
+            opt(stages.map(|s| s.sleep_cycle_count)),
+            opt(stages.map(|s| s.disturbance_count)),
+            opt(needed.map(|n| n.baseline_milli)),
+            opt(needed.map(|n| n.need_from_sleep_debt_milli)),
+            opt(needed.map(|n| n.need_from_recent_strain_milli)),
+            opt(needed.map(|n| n.need_from_recent_nap_milli)),
+            opt(score.and_then(|s| s.respiratory_rate)),
+            opt(score.and_then(|s| s.sleep_performance_percentage)),
+            opt(score.and_then(|s| s.sleep_consistency_percentage)),
+            opt(score.and_then(|s| s.sleep_efficiency_percentage)),
+        ]
+    }
+}
+
+impl CsvRecord for Recovery {
+    fn header(units: Units) -> Vec<&'static str> {
+        vec![
+            "cycle_id",
+            "sleep_id",
+            "user_id",
+            "created_at",
+            "updated_at",
+            "score_state",
+            "user_calibrating",
+            "recovery_score",
+            "resting_heart_rate",
+            "hrv_rmssd_milli",
+            "spo2_percentage",
+            match units {
+                Units::Metric => "skin_temp_celsius",
+                Units::Imperial => "skin_temp_fahrenheit",
+            },
+        ]
+    }
+
+    fn row(&self, options: &CsvOptions) -> Vec<String> {
+        let r = FlatRecovery::from(self);
+        let skin_temp = match options.units {
+            Units::Metric => r.skin_temp_celsius,
+            Units::Imperial => r.skin_temp_celsius.map(|c| c * 9.0 / 5.0 + 32.0),
+        };
+        vec![
+            r.cycle_id.to_string(),
+            r.sleep_id.to_string(),
+            r.user_id.to_string(),
+            options.timestamp(r.created_at, None),
+            options.timestamp(r.updated_at, None),
+            r.score_state.to_string(),
+            opt(r.user_calibrating),
+            opt(r.recovery_score),
+            opt(r.resting_heart_rate),
+            opt(r.hrv_rmssd_milli),
+            opt(r.spo2_percentage),
+            opt(skin_temp),
+        ]
+    }
+}
+
+impl CsvRecord for WorkoutRef<'_> {
+    fn header(units: Units) -> Vec<&'static str> {
+        let (distance, gain, change) = match units {
+            Units::Metric => (
+                "distance_meter",
+                "altitude_gain_meter",
+                "altitude_change_meter",
+            ),
+            Units::Imperial => (
+                "distance_mile",
+                "altitude_gain_foot",
+                "altitude_change_foot",
+            ),
+        };
+        vec![
+            "id",
+            "user_id",
+            "created_at",
+            "updated_at",
+            "start",
+            "end",
+            "timezone_offset",
+            "sport_name",
+            "sport_id",
+            "score_state",
+            "strain",
+            "average_heart_rate",
+            "max_heart_rate",
+            "kilojoule",
+            "percent_recorded",
+            distance,
+            gain,
+            change,
+            "zone_zero_milli",
+            "zone_one_milli",
+            "zone_two_milli",
+            "zone_three_milli",
+            "zone_four_milli",
+            "zone_five_milli",
+        ]
+    }
+
+    fn row(&self, options: &CsvOptions) -> Vec<String> {
+        let score = self.score.as_ref();
+        let zones = score.map(|s| &s.zone_durations);
+        let tz = Some(&*self.timezone_offset);
+        let (distance, gain, change) = (
+            score.and_then(|s| s.distance_meter),
+            score.and_then(|s| s.altitude_gain_meter),
+            score.and_then(|s| s.altitude_change_meter),
+        );
+        let (distance, gain, change) = match options.units {
+            Units::Metric => (distance, gain, change),
+            Units::Imperial => (
+                distance.map(|m| m * MILES_PER_METER),
+                gain.map(|m| m * FEET_PER_METER),
+                change.map(|m| m * FEET_PER_METER),
+            ),
+        };
+        vec![
+            self.id.to_string(),
+            self.user_id.to_string(),
+            options.timestamp(self.created_at, tz),
+            options.timestamp(self.updated_at, tz),
+            options.timestamp(self.start, tz),
+            options.timestamp(self.end, tz),
+            self.timezone_offset.to_string(),
+            self.sport_name.to_string(),
+            opt(self.sport_id),
+            self.score_state.as_str().to_string(),
+            opt(score.map(|s| s.strain)),
+            opt(score.map(|s| s.average_heart_rate)),
+            opt(score.map(|s| s.max_heart_rate)),
+            opt(score.map(|s| s.kilojoule)),
+            opt(score.map(|s| s.percent_recorded)),
+            opt(distance),
+            opt(gain),
+            opt(change),
+            opt(zones.map(|z| z.zone_zero_milli)),
+            opt(zones.map(|z| z.zone_one_milli)),
+            opt(zones.map(|z| z.zone_two_milli)),
+            opt(zones.map(|z| z.zone_three_milli)),
+            opt(zones.map(|z| z.zone_four_milli)),
+            opt(zones.map(|z| z.zone_five_milli)),
+        ]
+    }
+}
+
+/// Owned records are written through their borrowed views.
+macro_rules! impl_csv_record_via_ref {
+    ($($owned:ty => $borrowed:ident),*) => {
+        $(impl CsvRecord for $owned {
+            fn header(units: Units) -> Vec<&'static str> {
+                $borrowed::header(units)
+            }
+
+            fn row(&self, options: &CsvOptions) -> Vec<String> {
+                $borrowed::from(self).row(options)
+            }
+        })*
+    };
+}
+
+impl_csv_record_via_ref!(Cycle => CycleRef, Sleep => SleepRef, WorkoutV2 => WorkoutRef);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle() -> Cycle {
+        let time = "2024-03-01T06:30:00Z".parse().unwrap();
+        Cycle {
+            id: 93845,
+            user_id: 10129,
+            created_at: time,
+            updated_at: time,
+            start: time,
+            end: None,
+            timezone_offset: "-05:00".to_string(),
+            score_state: ScoreState::Scored,
+            score: Some(CycleScore {
+                strain: 5.2,
+                kilojoule: 8288.297,
+                average_heart_rate: 68,
+                max_heart_rate: 141,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_writes_header_then_rows() {
+        let options = CsvOptions::default()
+            .with_delimiter(b';')
+            .with_timezone(Timezone::Record);
+        let mut writer = CycleCsvWriter::new(Vec::new(), options);
+        writer.write(&cycle()).unwrap();
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("id;user_id;created_at"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "93845;10129;2024-03-01T01:30:00.000-05:00;2024-03-01T01:30:00.000-05:00;\
+             2024-03-01T01:30:00.000-05:00;;-05:00;SCORED;5.2;8288.297;68;141"
+        );
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_borrowed_records_write_the_same_rows() {
+        let json = serde_json::to_string(&cycle()).unwrap();
+        let borrowed: CycleRef = serde_json::from_str(&json).unwrap();
+        let options = CsvOptions::default().with_timezone(Timezone::Record);
+        assert_eq!(borrowed.row(&options), cycle().row(&options));
+    }
+
+    #[test]
+    fn test_empty_export_still_has_header() {
+        let writer = WorkoutCsvWriter::new(
+            Vec::new(),
+            CsvOptions::default().with_units(Units::Imperial),
+        );
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(csv.contains("distance_mile"));
+        assert_eq!(csv.lines().count(), 1);
+    }
+}
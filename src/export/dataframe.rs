@@ -0,0 +1,259 @@
+//! Polars `DataFrame` conversions for record collections.
+//!
+//! Columns follow the flattened records in [`super::flat`]. Timestamps become
+//! UTC `Datetime(ms)` columns and every `*_milli` field a `Duration(ms)` column,
+//! so time arithmetic works without manual casts.
+
+use super::flat::{FlatCycle, FlatRecovery, FlatSleep, FlatWorkout};
+use crate::error::Result;
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+
+/// Converts a collection of records into a `DataFrame`, one row per record.
+pub trait ToDataFrame {
+    fn to_dataframe(&self) -> Result<DataFrame>;
+}
+
+fn datetime(name: &str, values: Vec<Option<DateTime<Utc>>>) -> Column {
+    Int64Chunked::from_iter_options(
+        name.into(),
+        values.into_iter().map(|t| t.map(|t| t.timestamp_millis())),
+    )
+    .into_datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC))
+    .into_column()
+}
+
+fn duration(name: &str, values: Vec<Option<i64>>) -> Column {
+    Int64Chunked::from_iter_options(name.into(), values.into_iter())
+        .into_duration(TimeUnit::Milliseconds)
+        .into_column()
+}
+
+fn uuid(name: &str, ids: Vec<uuid::Uuid>) -> Column {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    Column::new(name.into(), ids)
+}
+
+fn values<R, T>(rows: &[R], f: impl Fn(&R) -> T) -> Vec<T> {
+    rows.iter().map(f).collect()
+}
+
+impl ToDataFrame for [Cycle] {
+    fn to_dataframe(&self) -> Result<DataFrame> {
+        let rows: Vec<FlatCycle> = self.iter().map(FlatCycle::from).collect();
+        Ok(DataFrame::new(vec![
+            Column::new("id".into(), values(&rows, |r| r.id)),
+            Column::new("user_id".into(), values(&rows, |r| r.user_id)),
+            datetime("created_at", values(&rows, |r| Some(r.created_at))),
+            datetime("updated_at", values(&rows, |r| Some(r.updated_at))),
+            datetime("start", values(&rows, |r| Some(r.start))),
+            datetime("end", values(&rows, |r| r.end)),
+            Column::new(
+                "timezone_offset".into(),
+                values(&rows, |r| r.timezone_offset.clone()),
+            ),
+            Column::new("score_state".into(), values(&rows, |r| r.score_state)),
+            Column::new("strain".into(), values(&rows, |r| r.strain)),
+            Column::new("kilojoule".into(), values(&rows, |r| r.kilojoule)),
+            Column::new(
+                "average_heart_rate".into(),
+                values(&rows, |r| r.average_heart_rate),
+            ),
+            Column::new("max_heart_rate".into(), values(&rows, |r| r.max_heart_rate)),
+        ])?)
+    }
+}
+
+impl ToDataFrame for [Sleep] {
+    fn to_dataframe(&self) -> Result<DataFrame> {
+        let rows: Vec<FlatSleep> = self.iter().map(FlatSleep::from).collect();
+        Ok(DataFrame::new(vec![
+            uuid("id", values(&rows, |r| r.id)),
+            Column::new("cycle_id".into(), values(&rows, |r| r.cycle_id)),
+            Column::new("user_id".into(), values(&rows, |r| r.user_id)),
+            datetime("created_at", values(&rows, |r| Some(r.created_at))),
+            datetime("updated_at", values(&rows, |r| Some(r.updated_at))),
+            datetime("start", values(&rows, |r| Some(r.start))),
+            datetime("end", values(&rows, |r| Some(r.end))),
+            Column::new(
+                "timezone_offset".into(),
+                values(&rows, |r| r.timezone_offset.clone()),
+            ),
+            Column::new("nap".into(), values(&rows, |r| r.nap)),
+            Column::new("score_state".into(), values(&rows, |r| r.score_state)),
+            duration(
+                "total_in_bed_time_milli",
+                values(&rows, |r| r.total_in_bed_time_milli.map(i64::from)),
+            ),
+            duration(
+                "total_awake_time_milli",
+                values(&rows, |r| r.total_awake_time_milli.map(i64::from)),
+            ),
+            duration(
+                "total_no_data_time_milli",
+                values(&rows, |r| r.total_no_data_time_milli.map(i64::from)),
+            ),
+            duration(
+                "total_light_sleep_time_milli",
+                values(&rows, |r| r.total_light_sleep_time_milli.map(i64::from)),
+            ),
+            duration(
+                "total_slow_wave_sleep_time_milli",
+                values(&rows, |r| r.total_slow_wave_sleep_time_milli.map(i64::from)),
+            ),
+            duration(
+                "total_rem_sleep_time_milli",
+                values(&rows, |r| r.total_rem_sleep_time_milli.map(i64::from)),
+            ),
+            Column::new(
+                "sleep_cycle_count".into(),
+                values(&rows, |r| r.sleep_cycle_count),
+            ),
+            Column::new(
+                "disturbance_count".into(),
+                values(&rows, |r| r.disturbance_count),
+            ),
+            duration("baseline_milli", values(&rows, |r| r.baseline_milli)),
+            duration(
+                "need_from_sleep_debt_milli",
+                values(&rows, |r| r.need_from_sleep_debt_milli),
+            ),
+            duration(
+                "need_from_recent_strain_milli",
+                values(&rows, |r| r.need_from_recent_strain_milli),
+            ),
+            duration(
+                "need_from_recent_nap_milli",
+                values(&rows, |r| r.need_from_recent_nap_milli),
+            ),
+            Column::new(
+                "respiratory_rate".into(),
+                values(&rows, |r| r.respiratory_rate),
+            ),
+            Column::new(
+                "sleep_performance_percentage".into(),
+                values(&rows, |r| r.sleep_performance_percentage),
+            ),
+            Column::new(
+                "sleep_consistency_percentage".into(),
+                values(&rows, |r| r.sleep_consistency_percentage),
+            ),
+            Column::new(
+                "sleep_efficiency_percentage".into(),
+                values(&rows, |r| r.sleep_efficiency_percentage),
+            ),
+        ])?)
+    }
+}
+
+impl ToDataFrame for [Recovery] {
+    fn to_dataframe(&self) -> Result<DataFrame> {
+        let rows: Vec<FlatRecovery> = self.iter().map(FlatRecovery::from).collect();
+        Ok(DataFrame::new(vec![
+            Column::new("cycle_id".into(), values(&rows, |r| r.cycle_id)),
+            uuid("sleep_id", values(&rows, |r| r.sleep_id)),
+            Column::new("user_id".into(), values(&rows, |r| r.user_id)),
+            datetime("created_at", values(&rows, |r| Some(r.created_at))),
+            datetime("updated_at", values(&rows, |r| Some(r.updated_at))),
+            Column::new("score_state".into(), values(&rows, |r| r.score_state)),
+            Column::new(
+                "user_calibrating".into(),
+                values(&rows, |r| r.user_calibrating),
+            ),
+            Column::new("recovery_score".into(), values(&rows, |r| r.recovery_score)),
+            Column::new(
+                "resting_heart_rate".into(),
+                values(&rows, |r| r.resting_heart_rate),
+            ),
+            Column::new(
+                "hrv_rmssd_milli".into(),
+                values(&rows, |r| r.hrv_rmssd_milli),
+            ),
+            Column::new(
+                "spo2_percentage".into(),
+                values(&rows, |r| r.spo2_percentage),
+            ),
+            Column::new(
+                "skin_temp_celsius".into(),
+                values(&rows, |r| r.skin_temp_celsius),
+            ),
+        ])?)
+    }
+}
+
+impl ToDataFrame for [WorkoutV2] {
+    fn to_dataframe(&self) -> Result<DataFrame> {
+        let rows: Vec<FlatWorkout> = self.iter().map(FlatWorkout::from).collect();
+        Ok(DataFrame::new(vec![
+            uuid("id", values(&rows, |r| r.id)),
+            Column::new("user_id".into(), values(&rows, |r| r.user_id)),
+            datetime("created_at", values(&rows, |r| Some(r.created_at))),
+            datetime("updated_at", values(&rows, |r| Some(r.updated_at))),
+            datetime("start", values(&rows, |r| Some(r.start))),
+            datetime("end", values(&rows, |r| Some(r.end))),
+            Column::new(
+                "timezone_offset".into(),
+                values(&rows, |r| r.timezone_offset.clone()),
+            ),
+            Column::new("sport_name".into(), values(&rows, |r| r.sport_name.clone())),
+            Column::new("sport_id".into(), values(&rows, |r| r.sport_id)),
+            Column::new("score_state".into(), values(&rows, |r| r.score_state)),
+            Column::new("strain".into(), values(&rows, |r| r.strain)),
+            Column::new(
+                "average_heart_rate".into(),
+                values(&rows, |r| r.average_heart_rate),
+            ),
+            Column::new("max_heart_rate".into(), values(&rows, |r| r.max_heart_rate)),
+            Column::new("kilojoule".into(), values(&rows, |r| r.kilojoule)),
+            Column::new(
+                "percent_recorded".into(),
+                values(&rows, |r| r.percent_recorded),
+            ),
+            Column::new("distance_meter".into(), values(&rows, |r| r.distance_meter)),
+            Column::new(
+                "altitude_gain_meter".into(),
+                values(&rows, |r| r.altitude_gain_meter),
+            ),
+            Column::new(
+                "altitude_change_meter".into(),
+                values(&rows, |r| r.altitude_change_meter),
+            ),
+            duration("zone_zero_milli", values(&rows, |r| r.zone_zero_milli)),
+            duration("zone_one_milli", values(&rows, |r| r.zone_one_milli)),
+            duration("zone_two_milli", values(&rows, |r| r.zone_two_milli)),
+            duration("zone_three_milli", values(&rows, |r| r.zone_three_milli)),
+            duration("zone_four_milli", values(&rows, |r| r.zone_four_milli)),
+            duration("zone_five_milli", values(&rows, |r| r.zone_five_milli)),
+        ])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_are_utc_datetimes() {
+        let time = "2024-03-01T06:30:00Z".parse().unwrap();
+        let cycles = [Cycle {
+            id: 93845,
+            user_id: 10129,
+            created_at: time,
+            updated_at: time,
+            start: time,
+            end: None,
+            timezone_offset: "-05:00".to_string(),
+            score_state: ScoreState::Unscorable,
+            score: None,
+        }];
+
+        let df = cycles.to_dataframe().unwrap();
+        assert_eq!(df.height(), 1);
+        assert_eq!(
+            df.column("start").unwrap().dtype(),
+            &DataType::Datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC))
+        );
+        assert_eq!(df.column("end").unwrap().null_count(), 1);
+    }
+}
@@ -0,0 +1,208 @@
+//! Flattened, one-level versions of the API records.
+//!
+//! Nested scores are pulled up into plain optional columns, so every record
+//! maps to exactly one row. Unscored records simply leave the score columns empty.
+
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatCycle {
+    pub id: i64,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub timezone_offset: String,
+    pub score_state: &'static str,
+    pub strain: Option<f32>,
+    pub kilojoule: Option<f32>,
+    pub average_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatSleep {
+    pub id: Uuid,
+    pub cycle_id: i64,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub timezone_offset: String,
+    pub nap: bool,
+    pub score_state: &'static str,
+    pub total_in_bed_time_milli: Option<i32>,
+    pub total_awake_time_milli: Option<i32>,
+    pub total_no_data_time_milli: Option<i32>,
+    pub total_light_sleep_time_milli: Option<i32>,
+    pub total_slow_wave_sleep_time_milli: Option<i32>,
+    pub total_rem_sleep_time_milli: Option<i32>,
+    pub sleep_cycle_count: Option<i32>,
+    pub disturbance_count: Option<i32>,
+    pub baseline_milli: Option<i64>,
+    pub need_from_sleep_debt_milli: Option<i64>,
+    pub need_from_recent_strain_milli: Option<i64>,
+    pub need_from_recent_nap_milli: Option<i64>,
+    pub respiratory_rate: Option<f32>,
+    pub sleep_performance_percentage: Option<f32>,
+    pub sleep_consistency_percentage: Option<f32>,
+    pub sleep_efficiency_percentage: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatRecovery {
+    pub cycle_id: i64,
+    pub sleep_id: Uuid,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub score_state: &'static str,
+    pub user_calibrating: Option<bool>,
+    pub recovery_score: Option<f32>,
+    pub resting_heart_rate: Option<f32>,
+    pub hrv_rmssd_milli: Option<f32>,
+    pub spo2_percentage: Option<f32>,
+    pub skin_temp_celsius: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatWorkout {
+    pub id: Uuid,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub timezone_offset: String,
+    pub sport_name: String,
+    pub sport_id: Option<i32>,
+    pub score_state: &'static str,
+    pub strain: Option<f32>,
+    pub average_heart_rate: Option<i32>,
+    pub max_heart_rate: Option<i32>,
+    pub kilojoule: Option<f32>,
+    pub percent_recorded: Option<f32>,
+    pub distance_meter: Option<f32>,
+    pub altitude_gain_meter: Option<f32>,
+    pub altitude_change_meter: Option<f32>,
+    pub zone_zero_milli: Option<i64>,
+    pub zone_one_milli: Option<i64>,
+    pub zone_two_milli: Option<i64>,
+    pub zone_three_milli: Option<i64>,
+    pub zone_four_milli: Option<i64>,
+    pub zone_five_milli: Option<i64>,
+}
+
+impl From<&Cycle> for FlatCycle {
+    fn from(cycle: &Cycle) -> Self {
+        let score = cycle.score.as_ref();
+        Self {
+            id: cycle.id,
+            user_id: cycle.user_id,
+            created_at: cycle.created_at,
+            updated_at: cycle.updated_at,
+            start: cycle.start,
+            end: cycle.end,
+            timezone_offset: cycle.timezone_offset.clone(),
+            score_state: cycle.score_state.as_str(),
+            strain: score.map(|s| s.strain),
+            kilojoule: score.map(|s| s.kilojoule),
+            average_heart_rate: score.map(|s| s.average_heart_rate),
+            max_heart_rate: score.map(|s| s.max_heart_rate),
+        }
+    }
+}
+
+impl From<&Sleep> for FlatSleep {
+    fn from(sleep: &Sleep) -> Self {
+        let score = sleep.score.as_ref();
+        let stages = score.map(|s| &s.stage_summary);
+        let needed = score.map(|s| &s.sleep_needed);
+        Self {
+            id: sleep.id,
+            cycle_id: sleep.cycle_id,
+            user_id: sleep.user_id,
+            created_at: sleep.created_at,
+            updated_at: sleep.updated_at,
+            start: sleep.start,
+            end: sleep.end,
+            timezone_offset: sleep.timezone_offset.clone(),
+            nap: sleep.nap,
+            score_state: sleep.score_state.as_str(),
+            total_in_bed_time_milli: stages.map(|s| s.total_in_bed_time_milli),
+            total_awake_time_milli: stages.map(|s| s.total_awake_time_milli),
+            total_no_data_time_milli: stages.map(|s| s.total_no_data_time_milli),
+            total_light_sleep_time_milli: stages.map(|s| s.total_light_sleep_time_milli),
+            total_slow_wave_sleep_time_milli: stages.map(|s| s.total_slow_wave_sleep_time_milli),
+            total_rem_sleep_time_milli: stages.map(|s| s.total_rem_sleep_time_milli),
+            sleep_cycle_count: stages.map(|s| s.sleep_cycle_count),
+            disturbance_count: stages.map(|s| s.disturbance_count),
+            baseline_milli: needed.map(|n| n.baseline_milli),
+            need_from_sleep_debt_milli: needed.map(|n| n.need_from_sleep_debt_milli),
+            need_from_recent_strain_milli: needed.map(|n| n.need_from_recent_strain_milli),
+            need_from_recent_nap_milli: needed.map(|n| n.need_from_recent_nap_milli),
+            respiratory_rate: score.and_then(|s| s.respiratory_rate),
+            sleep_performance_percentage: score.and_then(|s| s.sleep_performance_percentage),
+            sleep_consistency_percentage: score.and_then(|s| s.sleep_consistency_percentage),
+            sleep_efficiency_percentage: score.and_then(|s| s.sleep_efficiency_percentage),
+        }
+    }
+}
+
+impl From<&Recovery> for FlatRecovery {
+    fn from(recovery: &Recovery) -> Self {
+        let score = recovery.score.as_ref();
+        Self {
+            cycle_id: recovery.cycle_id,
+            sleep_id: recovery.sleep_id,
+            user_id: recovery.user_id,
+            created_at: recovery.created_at,
+            updated_at: recovery.updated_at,
+            score_state: recovery.score_state.as_str(),
+            user_calibrating: score.map(|s| s.user_calibrating),
+            recovery_score: score.map(|s| s.recovery_score),
+            resting_heart_rate: score.map(|s| s.resting_heart_rate),
+            hrv_rmssd_milli: score.map(|s| s.hrv_rmssd_milli),
+            spo2_percentage: score.and_then(|s| s.spo2_percentage),
+            skin_temp_celsius: score.and_then(|s| s.skin_temp_celsius),
+        }
+    }
+}
+
+impl From<&WorkoutV2> for FlatWorkout {
+    fn from(workout: &WorkoutV2) -> Self {
+        let score = workout.score.as_ref();
+        let zones = score.map(|s| &s.zone_durations);
+        Self {
+            id: workout.id,
+            user_id: workout.user_id,
+            created_at: workout.created_at,
+            updated_at: workout.updated_at,
+            start: workout.start,
+            end: workout.end,
+            timezone_offset: workout.timezone_offset.clone(),
+            sport_name: workout.sport_name.clone(),
+            sport_id: workout.sport_id,
+            score_state: workout.score_state.as_str(),
+            strain: score.map(|s| s.strain),
+            average_heart_rate: score.map(|s| s.average_heart_rate),
+            max_heart_rate: score.map(|s| s.max_heart_rate),
+            kilojoule: score.map(|s| s.kilojoule),
+            percent_recorded: score.map(|s| s.percent_recorded),
+            distance_meter: score.and_then(|s| s.distance_meter),
+            altitude_gain_meter: score.and_then(|s| s.altitude_gain_meter),
+            altitude_change_meter: score.and_then(|s| s.altitude_change_meter),
+            zone_zero_milli: zones.map(|z| z.zone_zero_milli),
+            zone_one_milli: zones.map(|z| z.zone_one_milli),
+            zone_two_milli: zones.map(|z| z.zone_two_milli),
+            zone_three_milli: zones.map(|z| z.zone_three_milli),
+            zone_four_milli: zones.map(|z| z.zone_four_milli),
+            zone_five_milli: zones.map(|z| z.zone_five_milli),
+        }
+    }
+}
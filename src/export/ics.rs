@@ -0,0 +1,251 @@
+//! iCalendar feed of sleeps and workouts.
+//!
+//! Every record becomes a `VEVENT` with its scores in the description. The UID is
+//! derived from the record id, so re-importing or re-subscribing updates events in
+//! place instead of duplicating them.
+
+use crate::error::Result;
+use crate::format::duration;
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+const KILOJOULES_PER_KILOCALORIE: f32 = 4.184;
+
+/// A record that can be written as one calendar event.
+pub trait IcsEvent {
+    fn uid(&self) -> String;
+    fn summary(&self) -> String;
+    fn description(&self) -> String;
+    fn start(&self) -> DateTime<Utc>;
+    fn end(&self) -> DateTime<Utc>;
+    fn updated_at(&self) -> DateTime<Utc>;
+}
+
+pub struct IcsWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> IcsWriter<W> {
+    /// Writes the calendar header straight away.
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(b"BEGIN:VCALENDAR\r\n")?;
+        out.write_all(b"VERSION:2.0\r\n")?;
+        out.write_all(b"PRODID:-//whoopsy//WHOOP export//EN\r\n")?;
+        out.write_all(b"CALSCALE:GREGORIAN\r\n")?;
+        out.write_all(b"X-WR-CALNAME:WHOOP\r\n")?;
+        Ok(Self { out })
+    }
+
+    pub fn write<T: IcsEvent>(&mut self, event: &T) -> Result<()> {
+        let lines = [
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", event.uid()),
+            format!("DTSTAMP:{}", date(event.updated_at())),
+            format!("DTSTART:{}", date(event.start())),
+            format!("DTEND:{}", date(event.end())),
+            format!("SUMMARY:{}", escape(&event.summary())),
+            format!("DESCRIPTION:{}", escape(&event.description())),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ];
+        for line in lines {
+            self.out.write_all(fold(&line).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn write_all<'a, T: IcsEvent + 'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a T>,
+    ) -> Result<()> {
+        for event in events {
+            self.write(event)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the calendar and hands back the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.out.write_all(b"END:VCALENDAR\r\n")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl IcsEvent for Sleep {
+    fn uid(&self) -> String {
+        format!("sleep-{}@whoopsy", self.id)
+    }
+
+    fn summary(&self) -> String {
+        let kind = if self.nap { "Nap" } else { "Sleep" };
+        match self
+            .score
+            .as_ref()
+            .and_then(|s| s.sleep_performance_percentage)
+        {
+            Some(performance) => format!("{} ({:.0}%)", kind, performance),
+            None => kind.to_string(),
+        }
+    }
+
+    fn description(&self) -> String {
+        let Some(score) = &self.score else {
+            return format!("Score: {}", self.score_state.as_str());
+        };
+        let stages = &score.stage_summary;
+        let mut lines = vec![
+            format!("Time asleep: {}", duration(stages.total_sleep_time_milli())),
+            format!(
+                "Light {} / Deep {} / REM {}",
+                duration(stages.total_light_sleep_time_milli.into()),
+                duration(stages.total_slow_wave_sleep_time_milli.into()),
+                duration(stages.total_rem_sleep_time_milli.into())
+            ),
+            format!("Disturbances: {}", stages.disturbance_count),
+        ];
+        if let Some(v) = score.sleep_performance_percentage {
+            lines.push(format!("Performance: {:.0}%", v));
+        }
+        if let Some(v) = score.sleep_efficiency_percentage {
+            lines.push(format!("Efficiency: {:.0}%", v));
+        }
+        if let Some(v) = score.sleep_consistency_percentage {
+            lines.push(format!("Consistency: {:.0}%", v));
+        }
+        if let Some(v) = score.respiratory_rate {
+            lines.push(format!("Respiratory rate: {:.1}", v));
+        }
+        lines.join("\n")
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    fn end(&self) -> DateTime<Utc> {
+        self.end
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl IcsEvent for WorkoutV2 {
+    fn uid(&self) -> String {
+        format!("workout-{}@whoopsy", self.id)
+    }
+
+    fn summary(&self) -> String {
+        let mut sport = self.sport_name.replace('-', " ");
+        if let Some(first) = sport.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        match &self.score {
+            Some(score) => format!("{} (strain {:.1})", sport, score.strain),
+            None => sport,
+        }
+    }
+
+    fn description(&self) -> String {
+        let Some(score) = &self.score else {
+            return format!("Score: {}", self.score_state.as_str());
+        };
+        let mut lines = vec![
+            format!("Strain: {:.1}", score.strain),
+            format!(
+                "Heart rate: {} avg / {} max",
+                score.average_heart_rate, score.max_heart_rate
+            ),
+            format!(
+                "Energy: {:.0} kcal",
+                score.kilojoule / KILOJOULES_PER_KILOCALORIE
+            ),
+        ];
+        if let Some(meters) = score.distance_meter {
+            lines.push(format!("Distance: {:.2} km", meters / 1000.0));
+        }
+        lines.join("\n")
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    fn end(&self) -> DateTime<Utc> {
+        self.end
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+fn date(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes text values per RFC 5545.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line at 75 octets without splitting a UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// Serves the calendar returned by `feed` at `/calendar.ics`, so calendar apps can subscribe.
+/// `feed` runs on every request; back it with a local store rather than the API.
+#[cfg(feature = "ics-server")]
+pub async fn serve<F>(addr: std::net::SocketAddr, feed: F) -> Result<()>
+where
+    F: Fn() -> Result<String> + Clone + Send + Sync + 'static,
+{
+    crate::server::serve(addr, move |path| {
+        if path != "/calendar.ics" {
+            return None;
+        }
+        // A failed render still answers, with an empty calendar, so subscribers keep polling.
+        let content = feed().unwrap_or_else(|_| {
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//whoopsy//WHOOP export//EN\r\nEND:VCALENDAR\r\n"
+                .to_string()
+        });
+        Some(crate::server::Body {
+            content_type: "text/calendar; charset=utf-8",
+            content,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_keeps_lines_under_75_octets() {
+        let folded = fold(&format!("DESCRIPTION:{}", "é".repeat(60)));
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(
+            folded.replace("\r\n ", ""),
+            format!("DESCRIPTION:{}\r\n", "é".repeat(60))
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! JSON Lines writer: one enveloped record per line.
+//!
+//! Every line is a self-contained `{"type", "fetched_at", "payload"}` object, so
+//! files can be appended to across runs, concatenated, and fed straight into log
+//! pipelines. Opening a file with [`JsonlWriter::append`] drops a trailing partial
+//! line left behind by a crash, so an interrupted export can simply be resumed.
+
+use crate::error::Result;
+use crate::json;
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A record tagged with its resource type and when it was fetched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub fetched_at: DateTime<Utc>,
+    pub payload: T,
+}
+
+/// A record with a stable type tag for the envelope.
+pub trait JsonlRecord: Serialize {
+    const TYPE: &'static str;
+}
+
+impl JsonlRecord for Cycle {
+    const TYPE: &'static str = "cycle";
+}
+
+impl JsonlRecord for Sleep {
+    const TYPE: &'static str = "sleep";
+}
+
+impl JsonlRecord for Recovery {
+    const TYPE: &'static str = "recovery";
+}
+
+impl JsonlRecord for WorkoutV2 {
+    const TYPE: &'static str = "workout";
+}
+
+impl JsonlRecord for UserBodyMeasurement {
+    const TYPE: &'static str = "body_measurement";
+}
+
+impl JsonlRecord for UserBasicProfile {
+    const TYPE: &'static str = "profile";
+}
+
+pub struct JsonlWriter<W: Write> {
+    out: W,
+}
+
+impl JsonlWriter<File> {
+    /// Opens `path` for appending, creating it if needed.
+    /// A trailing line without a newline is an interrupted write and gets truncated.
+    pub fn append(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let complete = complete_len(&mut file)?;
+        if complete < file.metadata()?.len() {
+            file.set_len(complete)?;
+        }
+
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Writes one record stamped with the current time.
+    pub fn write<T: JsonlRecord>(&mut self, record: &T) -> Result<()> {
+        self.write_fetched_at(record, Utc::now())
+    }
+
+    /// Writes one record with an explicit fetch time.
+    pub fn write_fetched_at<T: JsonlRecord>(
+        &mut self,
+        record: &T,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let envelope = Envelope {
+            kind: T::TYPE.to_string(),
+            fetched_at,
+            payload: record,
+        };
+        // Serialize first so a failure never leaves half a line behind.
+        let mut line = serde_json::to_vec(&envelope)?;
+        line.push(b'\n');
+        self.out.write_all(&line)?;
+        Ok(())
+    }
+
+    pub fn write_all<'a, T: JsonlRecord + 'a>(
+        &mut self,
+        records: impl IntoIterator<Item = &'a T>,
+    ) -> Result<()> {
+        let fetched_at = Utc::now();
+        for record in records {
+            self.write_fetched_at(record, fetched_at)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Reads envelopes back, skipping blank lines.
+/// Use `serde_json::Value` as the payload to read files mixing several types.
+pub fn read<T: DeserializeOwned, R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<Envelope<T>>> {
+    reader
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|line| json::from_string(line?))
+}
+
+/// Length of the file up to and including its last newline.
+fn complete_len(file: &mut File) -> Result<u64> {
+    let len = file.metadata()?.len();
+    let mut pos = len;
+    let mut buf = [0u8; 4096];
+
+    while pos > 0 {
+        let chunk = (buf.len() as u64).min(pos);
+        pos -= chunk;
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut buf[..chunk as usize])?;
+        if let Some(i) = buf[..chunk as usize].iter().rposition(|&b| b == b'\n') {
+            return Ok(pos + i as u64 + 1);
+        }
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_append_drops_partial_trailing_line() {
+        let path = std::env::temp_dir().join(format!("whoopsy-{}.jsonl", uuid::Uuid::new_v4()));
+        let profile = UserBasicProfile {
+            user_id: 10129,
+            email: "jsmith123@whoop.com".to_string(),
+            first_name: "John".to_string(),
+            last_name: "Smith".to_string(),
+        };
+
+        let mut writer = JsonlWriter::append(&path).unwrap();
+        writer.write(&profile).unwrap();
+        let mut file = writer.into_inner().unwrap();
+        file.write_all(br#"{"type":"profile","fetch"#).unwrap();
+        drop(file);
+
+        let mut writer = JsonlWriter::append(&path).unwrap();
+        writer.write(&profile).unwrap();
+        writer.flush().unwrap();
+
+        let envelopes: Vec<Envelope<UserBasicProfile>> =
+            read(BufReader::new(File::open(&path).unwrap()))
+                .collect::<Result<_>>()
+                .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(envelopes.len(), 2);
+        assert_eq!(envelopes[1].kind, "profile");
+        assert_eq!(envelopes[1].payload.user_id, 10129);
+    }
+}
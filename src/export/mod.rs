@@ -0,0 +1,36 @@
+//! Writers that turn API records into files for analysis elsewhere.
+//!
+//! Everything but [`jsonl`], which archives build on, needs the `export`
+//! feature, on by default. [`tcx`] also needs `workouts`.
+
+#[cfg(feature = "export")]
+pub mod apple_health;
+#[cfg(feature = "export")]
+pub mod csv;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+#[cfg(feature = "export")]
+pub mod flat;
+#[cfg(feature = "export")]
+pub mod ics;
+pub mod jsonl;
+#[cfg(feature = "export")]
+pub mod pipeline;
+#[cfg(feature = "sheets")]
+pub mod sheets;
+#[cfg(all(feature = "export", feature = "workouts"))]
+pub mod tcx;
+
+#[cfg(feature = "polars")]
+pub use dataframe::ToDataFrame;
+#[cfg(feature = "export")]
+pub use flat::{FlatCycle, FlatRecovery, FlatSleep, FlatWorkout};
+
+/// Unit system for exported values.
+/// Records are always metric on the wire, imperial output is converted on write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
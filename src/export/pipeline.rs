@@ -0,0 +1,227 @@
+//! Exports a collection page by page, without holding all of it.
+//!
+//! [`Pipeline`] takes one page from [`Pages`] at a time and writes it to a
+//! [`PageSink`] before fetching the next, so memory stays bounded by a page no
+//! matter how much history there is. The sink is flushed every few pages, so a
+//! long export shows up on disk as it goes and an interrupted one keeps what
+//! it wrote.
+//!
+//! ```no_run
+//! # async fn run(client: whoopsy::WhoopClient) -> whoopsy::Result<()> {
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//! use whoopsy::Pages;
+//! use whoopsy::export::csv::{CsvOptions, CsvWriter};
+//! use whoopsy::export::pipeline::Pipeline;
+//! use whoopsy::models::Sleep;
+//!
+//! let out = BufWriter::new(File::create("sleep.csv")?);
+//! let mut sink = CsvWriter::<_, Sleep>::new(out, CsvOptions::default());
+//! let written = Pipeline::new().run(Pages::new(&client), &mut sink).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::csv::{CsvRecord, CsvWriter};
+use super::jsonl::{JsonlRecord, JsonlWriter};
+use crate::api::WhoopApi;
+use crate::error::Result;
+use crate::pagination::{Paged, Pages};
+use chrono::Utc;
+use serde::Serialize;
+use std::io::Write;
+
+const DEFAULT_FLUSH_EVERY: usize = 10;
+
+type Filter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
+/// A writer taking records a page at a time.
+pub trait PageSink<T> {
+    fn write_page(&mut self, records: &[T]) -> Result<()>;
+
+    /// Pushes buffered output on to the underlying writer.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Writes whatever closes the output, after the last page.
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: Write, R: CsvRecord> PageSink<R> for CsvWriter<W, R> {
+    fn write_page(&mut self, records: &[R]) -> Result<()> {
+        self.write_all(records)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        CsvWriter::flush(self)
+    }
+
+    /// Writes the header even when there were no records.
+    fn finish(&mut self) -> Result<()> {
+        self.write_header()?;
+        CsvWriter::flush(self)
+    }
+}
+
+impl<W: Write, T: JsonlRecord> PageSink<T> for JsonlWriter<W> {
+    fn write_page(&mut self, records: &[T]) -> Result<()> {
+        let fetched_at = Utc::now();
+        for record in records {
+            self.write_fetched_at(record, fetched_at)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        JsonlWriter::flush(self)
+    }
+}
+
+/// Writes records as one JSON array, opening it before the first record and
+/// closing it on [`finish`](PageSink::finish).
+pub struct JsonArrayWriter<W: Write> {
+    out: W,
+    written: usize,
+}
+
+impl<W: Write> JsonArrayWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, written: 0 }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write, T: Serialize> PageSink<T> for JsonArrayWriter<W> {
+    fn write_page(&mut self, records: &[T]) -> Result<()> {
+        for record in records {
+            self.out
+                .write_all(if self.written == 0 { b"[\n" } else { b",\n" })?;
+            serde_json::to_writer_pretty(&mut self.out, record)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        JsonArrayWriter::flush(self)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out
+            .write_all(if self.written == 0 { b"[]\n" } else { b"\n]\n" })?;
+        JsonArrayWriter::flush(self)
+    }
+}
+
+/// Moves records from [`Pages`] into a [`PageSink`] one page at a time.
+pub struct Pipeline<T> {
+    flush_every: usize,
+    filter: Option<Filter<T>>,
+}
+
+impl<T> Default for Pipeline<T> {
+    fn default() -> Self {
+        Self {
+            flush_every: DEFAULT_FLUSH_EVERY,
+            filter: None,
+        }
+    }
+}
+
+impl<T: Paged> Pipeline<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flushes the sink after every `pages` pages. Defaults to 10.
+    pub fn with_flush_every(mut self, pages: usize) -> Self {
+        self.flush_every = pages.max(1);
+        self
+    }
+
+    /// Only writes records `filter` keeps.
+    pub fn with_filter(mut self, filter: impl Fn(&T) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Writes every remaining page to `sink` and finishes it, returning how
+    /// many records were written.
+    pub async fn run<A: WhoopApi, S: PageSink<T>>(
+        &self,
+        mut pages: Pages<'_, A, T>,
+        sink: &mut S,
+    ) -> Result<usize> {
+        let mut written = 0;
+        let mut unflushed = 0;
+        while let Some(page) = pages.next_page().await {
+            let mut records = page?;
+            if let Some(filter) = &self.filter {
+                records.retain(|r| filter(r));
+            }
+            sink.write_page(&records)?;
+            written += records.len();
+
+            unflushed += 1;
+            if unflushed == self.flush_every {
+                sink.flush()?;
+                unflushed = 0;
+            }
+        }
+        sink.finish()?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::csv::CsvOptions;
+    use crate::fixtures;
+    use crate::memory::InMemoryWhoop;
+    use crate::models::Cycle;
+
+    #[tokio::test]
+    async fn test_writes_every_page() {
+        let api = InMemoryWhoop::new().with_cycles(fixtures::cycle_collection().records.unwrap());
+        let mut sink = CsvWriter::<_, Cycle>::new(Vec::new(), CsvOptions::default());
+        let written = Pipeline::new()
+            .with_flush_every(1)
+            .run(Pages::new(&api).with_limit(1), &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(written, 2);
+        let csv = String::from_utf8(sink.finish().unwrap()).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+    }
+
+    #[cfg(feature = "workouts")]
+    #[tokio::test]
+    async fn test_json_array_is_valid_when_filtered() {
+        use crate::models::WorkoutV2;
+
+        let workouts = fixtures::workout_collection().records.unwrap();
+        let sport = workouts[0].sport_name.clone();
+        let api = InMemoryWhoop::new().with_workouts(workouts);
+        let mut sink = JsonArrayWriter::new(Vec::new());
+        let pipeline = Pipeline::<WorkoutV2>::new().with_filter(move |w| w.sport_name == sport);
+        let written = pipeline.run(Pages::new(&api), &mut sink).await.unwrap();
+
+        let parsed: Vec<WorkoutV2> = serde_json::from_slice(&sink.into_inner()).unwrap();
+        assert_eq!(parsed.len(), written);
+        assert!(written >= 1);
+
+        let mut empty = JsonArrayWriter::new(Vec::new());
+        PageSink::<WorkoutV2>::finish(&mut empty).unwrap();
+        assert_eq!(empty.into_inner(), b"[]\n");
+    }
+}
@@ -0,0 +1,164 @@
+//! Appends daily summary rows to a Google Sheet through the Sheets API.
+//!
+//! Authentication is up to the caller: pass a Google OAuth access token with the
+//! `spreadsheets` scope. Rows go through `values:append`, so they land after the
+//! last filled row of the target range and existing data is never overwritten.
+
+use crate::aggregate::DailySummary;
+use crate::error::{Result, WhoopError};
+use reqwest::Client;
+use serde_json::{Value, json};
+
+pub const SHEETS_URL: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+/// Column headers matching [`SheetsExporter::append_days`].
+pub const HEADER: [&str; 6] = [
+    "date",
+    "recovery",
+    "strain",
+    "sleep_performance",
+    "hrv_rmssd_milli",
+    "resting_heart_rate",
+];
+
+pub struct SheetsExporter {
+    client: Client,
+    url: String,
+    access_token: String,
+    spreadsheet_id: String,
+    range: String,
+}
+
+impl SheetsExporter {
+    /// Appends to the first sheet. Use [`with_range`](Self::with_range) to pick another.
+    pub fn new(access_token: impl Into<String>, spreadsheet_id: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            url: SHEETS_URL.to_string(),
+            access_token: access_token.into(),
+            spreadsheet_id: spreadsheet_id.into(),
+            range: "A:F".to_string(),
+        }
+    }
+
+    /// Sets the A1 range rows are appended to, e.g. `WHOOP!A:F`.
+    pub fn with_range(mut self, range: impl Into<String>) -> Self {
+        self.range = range.into();
+        self
+    }
+
+    /// Another Sheets API base URL than [`SHEETS_URL`], e.g. a mock.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub async fn append_header(&self) -> Result<()> {
+        self.append(vec![HEADER.iter().map(|h| json!(h)).collect()])
+            .await
+    }
+
+    /// Appends one row per day. Missing values are left as empty cells.
+    pub async fn append_days(&self, days: &[DailySummary]) -> Result<()> {
+        if days.is_empty() {
+            return Ok(());
+        }
+        let cell = |value: Option<f32>| value.map_or(Value::Null, |v| json!(v));
+        let rows = days
+            .iter()
+            .map(|day| {
+                vec![
+                    json!(day.date.to_string()),
+                    cell(day.recovery_score),
+                    cell(day.strain),
+                    cell(day.sleep_performance_percentage),
+                    cell(day.hrv_rmssd_milli),
+                    cell(day.resting_heart_rate),
+                ]
+            })
+            .collect();
+        self.append(rows).await
+    }
+
+    async fn append(&self, rows: Vec<Vec<Value>>) -> Result<()> {
+        let url = format!(
+            "{}/{}/values/{}:append",
+            self.url,
+            urlencoding::encode(&self.spreadsheet_id),
+            urlencoding::encode(&self.range)
+        );
+        let response = self
+            .client
+            .post(url)
+            .bearer_auth(&self.access_token)
+            .query(&[
+                ("valueInputOption", "USER_ENTERED"),
+                ("insertDataOption", "INSERT_ROWS"),
+            ])
+            .json(&json!({ "majorDimension": "ROWS", "values": rows }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            let message = response.text().await.ok();
+            Err(WhoopError::from_status(status, message))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_appends_one_row_per_day() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/sheet-1/values/WHOOP%21A%3AF:append"))
+            .and(header("authorization", "Bearer ya29.token"))
+            .and(query_param("valueInputOption", "USER_ENTERED"))
+            .and(body_json(json!({
+                "majorDimension": "ROWS",
+                "values": [
+                    ["2024-03-01", 64.0, 12.5, 91.0, 48.5, 52.0],
+                    ["2024-03-02", null, 8.0, null, null, null],
+                ],
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let day = |date: &str| DailySummary {
+            date: date.parse().unwrap(),
+            ..DailySummary::default()
+        };
+        let days = [
+            DailySummary {
+                recovery_score: Some(64.0),
+                strain: Some(12.5),
+                sleep_performance_percentage: Some(91.0),
+                hrv_rmssd_milli: Some(48.5),
+                resting_heart_rate: Some(52.0),
+                ..day("2024-03-01")
+            },
+            DailySummary {
+                strain: Some(8.0),
+                ..day("2024-03-02")
+            },
+        ];
+        let sheets = SheetsExporter::new("ya29.token", "sheet-1")
+            .with_url(server.uri())
+            .with_range("WHOOP!A:F");
+        sheets.append_days(&days).await.unwrap();
+        sheets.append_days(&[]).await.unwrap();
+
+        let missing = SheetsExporter::new("ya29.token", "sheet-2").with_url(server.uri());
+        assert!(missing.append_header().await.is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Training Center XML (TCX) writer for workouts.
+//!
+//! WHOOP has no GPS or per-second samples, so each activity is a summary: start
+//! time, duration, distance, calories and heart rate. With [`TcxOptions::zone_laps`]
+//! the activity is split into one lap per heart rate zone instead. The API only
+//! reports time per zone, not when it happened, so those laps run in zone order.
+
+use crate::error::Result;
+use crate::models::*;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use std::io::Write;
+
+const KILOJOULES_PER_KILOCALORIE: f32 = 4.184;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcxOptions {
+    /// Write one lap per heart rate zone rather than a single lap.
+    pub zone_laps: bool,
+}
+
+impl TcxOptions {
+    pub fn with_zone_laps(mut self, zone_laps: bool) -> Self {
+        self.zone_laps = zone_laps;
+        self
+    }
+}
+
+pub struct TcxWriter<W: Write> {
+    out: W,
+    options: TcxOptions,
+}
+
+impl<W: Write> TcxWriter<W> {
+    /// Writes the document header straight away.
+    pub fn new(mut out: W, options: TcxOptions) -> Result<Self> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">"#
+        )?;
+        writeln!(out, "  <Activities>")?;
+        Ok(Self { out, options })
+    }
+
+    pub fn write(&mut self, workout: &WorkoutV2) -> Result<()> {
+        let out = &mut self.out;
+        writeln!(
+            out,
+            r#"    <Activity Sport="{}">"#,
+            sport(&workout.sport_name)
+        )?;
+        writeln!(out, "      <Id>{}</Id>", time(workout.start))?;
+
+        let total = workout.end - workout.start;
+        let score = workout.score.as_ref();
+        let calories = score.map_or(0.0, |s| s.kilojoule / KILOJOULES_PER_KILOCALORIE);
+        let distance = score.and_then(|s| s.distance_meter);
+        let zones = score
+            .map(|s| s.zone_durations.as_array())
+            .filter(|z| self.options.zone_laps && z.iter().any(|&milli| milli > 0));
+
+        match zones {
+            Some(zones) => {
+                let zone_total = zones.iter().sum::<i64>() as f32;
+                let mut start = workout.start;
+                for (zone, &milli) in zones.iter().enumerate().filter(|(_, m)| **m > 0) {
+                    let share = milli as f32 / zone_total;
+                    write_lap(
+                        out,
+                        Lap {
+                            start,
+                            duration: Duration::milliseconds(milli),
+                            distance_meter: distance.map(|d| d * share),
+                            calories: calories * share,
+                            heart_rate: None,
+                            notes: Some(format!("Heart rate zone {}", zone)),
+                        },
+                    )?;
+                    start += Duration::milliseconds(milli);
+                }
+            }
+            None => write_lap(
+                out,
+                Lap {
+                    start: workout.start,
+                    duration: total,
+                    distance_meter: distance,
+                    calories,
+                    heart_rate: score.map(|s| (s.average_heart_rate, s.max_heart_rate)),
+                    notes: None,
+                },
+            )?,
+        }
+
+        let mut notes = workout.sport_name.clone();
+        if let Some(score) = score {
+            notes.push_str(&format!(
+                ", strain {:.1}, heart rate {} avg / {} max",
+                score.strain, score.average_heart_rate, score.max_heart_rate
+            ));
+        }
+        writeln!(out, "      <Notes>{}</Notes>", escape(&notes))?;
+        writeln!(out, "    </Activity>")?;
+        Ok(())
+    }
+
+    pub fn write_all<'a>(
+        &mut self,
+        workouts: impl IntoIterator<Item = &'a WorkoutV2>,
+    ) -> Result<()> {
+        for workout in workouts {
+            self.write(workout)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the document and hands back the writer.
+    pub fn finish(mut self) -> Result<W> {
+        writeln!(self.out, "  </Activities>")?;
+        writeln!(self.out, "</TrainingCenterDatabase>")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+struct Lap {
+    start: DateTime<Utc>,
+    duration: Duration,
+    distance_meter: Option<f32>,
+    calories: f32,
+    /// Average and maximum.
+    heart_rate: Option<(i32, i32)>,
+    notes: Option<String>,
+}
+
+fn write_lap<W: Write>(out: &mut W, lap: Lap) -> Result<()> {
+    writeln!(out, r#"      <Lap StartTime="{}">"#, time(lap.start))?;
+    writeln!(
+        out,
+        "        <TotalTimeSeconds>{:.1}</TotalTimeSeconds>",
+        lap.duration.num_milliseconds() as f64 / 1000.0
+    )?;
+    writeln!(
+        out,
+        "        <DistanceMeters>{:.1}</DistanceMeters>",
+        lap.distance_meter.unwrap_or(0.0)
+    )?;
+    writeln!(out, "        <Calories>{:.0}</Calories>", lap.calories)?;
+    if let Some((average, max)) = lap.heart_rate {
+        writeln!(
+            out,
+            "        <AverageHeartRateBpm><Value>{}</Value></AverageHeartRateBpm>",
+            average
+        )?;
+        writeln!(
+            out,
+            "        <MaximumHeartRateBpm><Value>{}</Value></MaximumHeartRateBpm>",
+            max
+        )?;
+    }
+    writeln!(out, "        <Intensity>Active</Intensity>")?;
+    writeln!(out, "        <TriggerMethod>Manual</TriggerMethod>")?;
+    if let Some(notes) = lap.notes {
+        writeln!(out, "        <Notes>{}</Notes>", escape(&notes))?;
+    }
+    writeln!(out, "      </Lap>")?;
+    Ok(())
+}
+
+/// TCX only knows three sports.
+fn sport(name: &str) -> &'static str {
+    match name.to_lowercase().as_str() {
+        "running" | "track-and-field" => "Running",
+        "cycling" | "spin" | "spinning" | "mountain-biking" => "Biking",
+        _ => "Other",
+    }
+}
+
+fn time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn tcx(options: TcxOptions) -> String {
+        let mut writer = TcxWriter::new(Vec::new(), options).unwrap();
+        writer.write(&fixtures::workout_scored()).unwrap();
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_writes_a_summary_lap() {
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Activities>
+    <Activity Sport="Running">
+      <Id>2022-04-24T02:25:44.774Z</Id>
+      <Lap StartTime="2022-04-24T02:25:44.774Z">
+        <TotalTimeSeconds>28800.0</TotalTimeSeconds>
+        <DistanceMeters>1772.8</DistanceMeters>
+        <Calories>375</Calories>
+        <AverageHeartRateBpm><Value>123</Value></AverageHeartRateBpm>
+        <MaximumHeartRateBpm><Value>146</Value></MaximumHeartRateBpm>
+        <Intensity>Active</Intensity>
+        <TriggerMethod>Manual</TriggerMethod>
+      </Lap>
+      <Notes>running, strain 8.2, heart rate 123 avg / 146 max</Notes>
+    </Activity>
+  </Activities>
+</TrainingCenterDatabase>
+"#;
+        assert_eq!(tcx(TcxOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_zone_laps_follow_each_other() {
+        let tcx = tcx(TcxOptions::default().with_zone_laps(true));
+        let starts: Vec<&str> = tcx
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("<Lap StartTime=\""))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                "2022-04-24T02:25:44.774Z\">",
+                "2022-04-24T02:30:44.774Z\">",
+                "2022-04-24T02:40:44.774Z\">",
+                "2022-04-24T02:55:44.774Z\">",
+                "2022-04-24T03:10:44.774Z\">",
+                "2022-04-24T03:20:44.774Z\">",
+            ]
+        );
+        assert!(tcx.contains("<Notes>Heart rate zone 5</Notes>"));
+        assert!(!tcx.contains("AverageHeartRateBpm"));
+    }
+}
@@ -0,0 +1,327 @@
+//! Synthetic but plausible WHOOP data for demos, screenshots and tests.
+//!
+//! Records reference each other the way real ones do: each cycle starts when its
+//! night's sleep does, and the recovery points at both. Scores follow simple
+//! physiology: hard days and short nights lower the next recovery, and HRV and
+//! resting heart rate move with it. The same seed always yields the same data.
+
+use crate::models::*;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc, Weekday};
+use fastrand::Rng;
+use uuid::Uuid;
+
+const SPORTS: [(&str, i32); 5] = [
+    ("running", 0),
+    ("cycling", 1),
+    ("functional-fitness", 48),
+    ("yoga", 44),
+    ("swimming", 33),
+];
+
+/// Records from a [`Generator`], oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Dataset {
+    pub cycles: Vec<Cycle>,
+    pub recoveries: Vec<Recovery>,
+    pub sleeps: Vec<Sleep>,
+    pub workouts: Vec<WorkoutV2>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Generator {
+    start: NaiveDate,
+    days: u32,
+    seed: u64,
+    user_id: i64,
+    timezone_offset: FixedOffset,
+    workouts_per_week: f32,
+}
+
+impl Generator {
+    /// `days` days of data, the first night falling asleep on the evening before `start`.
+    pub fn new(start: NaiveDate, days: u32) -> Self {
+        Self {
+            start,
+            days,
+            seed: 42,
+            user_id: 10129,
+            timezone_offset: FixedOffset::west_opt(5 * 3600).unwrap(),
+            workouts_per_week: 4.0,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: i64) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    pub fn with_timezone_offset(mut self, offset: FixedOffset) -> Self {
+        self.timezone_offset = offset;
+        self
+    }
+
+    pub fn with_workouts_per_week(mut self, workouts_per_week: f32) -> Self {
+        self.workouts_per_week = workouts_per_week;
+        self
+    }
+
+    pub fn generate(&self) -> Dataset {
+        let mut rng = Rng::with_seed(self.seed);
+        let offset = self.timezone_offset.to_string();
+        let baseline_need = hours(7.5 + rng.f64() * 0.75);
+
+        // Sleep onsets first, since each cycle runs from one onset to the next.
+        let onsets: Vec<DateTime<Utc>> = (0..=self.days)
+            .map(|day| {
+                let date = self.start + Duration::days(i64::from(day)) - Duration::days(1);
+                let weekend = matches!(date.weekday(), Weekday::Fri | Weekday::Sat);
+                let local = date.and_hms_opt(22, 45, 0).unwrap()
+                    + minutes(normal(&mut rng, if weekend { 60.0 } else { 0.0 }, 35.0));
+                local
+                    .and_local_timezone(self.timezone_offset)
+                    .unwrap()
+                    .to_utc()
+            })
+            .collect();
+
+        let mut data = Dataset::default();
+        let (mut previous_strain, mut debt) = (10.0_f32, 0_i64);
+        for day in 0..self.days as usize {
+            let id = 1_000_000 + day as i64;
+            let start = onsets[day];
+            let end = (day + 1 < self.days as usize).then(|| onsets[day + 1]);
+
+            let in_bed = hours(normal(&mut rng, 7.9, 0.8).clamp(4.0, 11.0));
+            let wake = start + Duration::milliseconds(in_bed);
+            let need = SleepNeeded {
+                baseline_milli: baseline_need,
+                need_from_sleep_debt_milli: debt / 2,
+                need_from_recent_strain_milli: hours(
+                    f64::from(previous_strain - 10.0).max(0.0) * 0.08,
+                ),
+                need_from_recent_nap_milli: 0,
+            };
+            let sleep = self.sleep(&mut rng, id, start, wake, &need, &offset);
+            let stages = &sleep.score.as_ref().unwrap().stage_summary;
+            let slept = stages.total_sleep_time_milli();
+            debt = (debt + need.total_milli() - slept).clamp(0, hours(4.0));
+            let performance = (slept as f32 / need.total_milli() as f32 * 100.0).min(100.0);
+
+            let recovery_score = (62.0 + (performance - 85.0) * 0.8
+                - (previous_strain - 12.0) * 2.5
+                + normal(&mut rng, 0.0, 12.0) as f32)
+                .clamp(1.0, 99.0)
+                .round();
+            data.recoveries.push(Recovery {
+                cycle_id: id,
+                sleep_id: sleep.id,
+                user_id: self.user_id,
+                created_at: wake + Duration::minutes(4),
+                updated_at: wake + Duration::minutes(6),
+                score_state: ScoreState::Scored,
+                score: Some(RecoveryScore {
+                    user_calibrating: day < 4,
+                    recovery_score,
+                    resting_heart_rate: (58.0 - (recovery_score - 50.0) * 0.08
+                        + normal(&mut rng, 0.0, 1.5) as f32)
+                        .round(),
+                    hrv_rmssd_milli: 48.0 * (0.6 + recovery_score / 200.0)
+                        + normal(&mut rng, 0.0, 3.0) as f32,
+                    spo2_percentage: Some(normal(&mut rng, 96.0, 1.0).min(100.0) as f32),
+                    skin_temp_celsius: Some(normal(&mut rng, 33.6, 0.25) as f32),
+                }),
+            });
+
+            let mut strain = normal(&mut rng, 7.0, 1.5) as f32;
+            let mut kilojoule = normal(&mut rng, 8_400.0, 600.0) as f32;
+            if rng.f32() < self.workouts_per_week / 7.0 {
+                let workout = self.workout(&mut rng, wake, recovery_score, &offset);
+                let score = workout.score.as_ref().unwrap();
+                strain = strain.max(score.strain) + score.strain * 0.15;
+                kilojoule += score.kilojoule;
+                data.workouts.push(workout);
+            }
+            let strain = strain.clamp(0.1, 21.0);
+            previous_strain = strain;
+
+            let updated_at = end.unwrap_or(wake + Duration::hours(12));
+            data.cycles.push(Cycle {
+                id,
+                user_id: self.user_id,
+                created_at: start,
+                updated_at,
+                start,
+                end,
+                timezone_offset: offset.clone(),
+                score_state: ScoreState::Scored,
+                score: Some(CycleScore {
+                    strain,
+                    kilojoule,
+                    average_heart_rate: (62.0 + strain * 1.2).round() as i32,
+                    max_heart_rate: (120.0 + strain * 3.5).round() as i32,
+                }),
+            });
+            data.sleeps.push(sleep);
+        }
+        data
+    }
+
+    pub(crate) fn sleep(
+        &self,
+        rng: &mut Rng,
+        cycle_id: i64,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        need: &SleepNeeded,
+        offset: &str,
+    ) -> Sleep {
+        let in_bed = (end - start).num_milliseconds();
+        let awake = (in_bed as f64 * (0.05 + rng.f64() * 0.07)) as i64;
+        let asleep = in_bed - awake;
+        let slow_wave = (asleep as f64 * normal(rng, 0.21, 0.03)) as i64;
+        let rem = (asleep as f64 * normal(rng, 0.24, 0.03)) as i64;
+        let light = asleep - slow_wave - rem;
+        let performance = (asleep as f32 / need.total_milli() as f32 * 100.0).min(100.0);
+
+        Sleep {
+            id: uuid(rng),
+            cycle_id,
+            v1_id: None,
+            user_id: self.user_id,
+            created_at: end + Duration::minutes(2),
+            updated_at: end + Duration::minutes(5),
+            start,
+            end,
+            timezone_offset: offset.to_string(),
+            nap: false,
+            score_state: ScoreState::Scored,
+            score: Some(SleepScore {
+                stage_summary: SleepStageSummary {
+                    total_in_bed_time_milli: in_bed as i32,
+                    total_awake_time_milli: awake as i32,
+                    total_no_data_time_milli: 0,
+                    total_light_sleep_time_milli: light as i32,
+                    total_slow_wave_sleep_time_milli: slow_wave as i32,
+                    total_rem_sleep_time_milli: rem as i32,
+                    sleep_cycle_count: (asleep / hours(1.5)) as i32,
+                    disturbance_count: rng.i32(4..20),
+                },
+                sleep_needed: need.clone(),
+                respiratory_rate: Some(normal(rng, 15.4, 0.4) as f32),
+                sleep_performance_percentage: Some(performance.round()),
+                sleep_consistency_percentage: Some(rng.i32(60..96) as f32),
+                sleep_efficiency_percentage: Some(asleep as f32 / in_bed as f32 * 100.0),
+            }),
+        }
+    }
+
+    fn workout(
+        &self,
+        rng: &mut Rng,
+        after: DateTime<Utc>,
+        recovery_score: f32,
+        offset: &str,
+    ) -> WorkoutV2 {
+        let (sport_name, sport_id) = SPORTS[rng.usize(..SPORTS.len())];
+        let start = after + minutes(normal(rng, 600.0, 90.0).max(30.0));
+        let duration = rng.i64(25..95);
+        // Greener days get harder sessions.
+        let strain =
+            (normal(rng, 9.0, 2.0) as f32 + recovery_score / 25.0 + duration as f32 / 30.0)
+                .clamp(2.0, 19.5);
+        let milli = duration * 60_000;
+        let shares = [0.03, 0.15, 0.35, 0.3, 0.13, 0.04];
+        let zone = |i: usize| (milli as f64 * shares[i]) as i64;
+        let distance = match sport_name {
+            "running" => Some(duration as f32 * normal(rng, 170.0, 20.0) as f32),
+            "cycling" => Some(duration as f32 * normal(rng, 450.0, 60.0) as f32),
+            "swimming" => Some(duration as f32 * normal(rng, 40.0, 5.0) as f32),
+            _ => None,
+        };
+
+        WorkoutV2 {
+            id: uuid(rng),
+            v1_id: None,
+            user_id: self.user_id,
+            created_at: start + Duration::minutes(duration + 3),
+            updated_at: start + Duration::minutes(duration + 8),
+            start,
+            end: start + Duration::minutes(duration),
+            timezone_offset: offset.to_string(),
+            sport_name: sport_name.to_string(),
+            score_state: ScoreState::Scored,
+            score: Some(WorkoutScore {
+                strain,
+                average_heart_rate: (110.0 + strain * 2.5) as i32,
+                max_heart_rate: (140.0 + strain * 2.2) as i32,
+                kilojoule: duration as f32 * normal(rng, 38.0, 6.0) as f32,
+                percent_recorded: if rng.f32() < 0.95 {
+                    100.0
+                } else {
+                    rng.i32(70..100) as f32
+                },
+                distance_meter: distance,
+                altitude_gain_meter: distance.map(|d| d / 200.0),
+                altitude_change_meter: distance.map(|_| normal(rng, 0.0, 2.0) as f32),
+                zone_durations: ZoneDurations {
+                    zone_zero_milli: zone(0),
+                    zone_one_milli: zone(1),
+                    zone_two_milli: zone(2),
+                    zone_three_milli: zone(3),
+                    zone_four_milli: zone(4),
+                    zone_five_milli: zone(5),
+                },
+            }),
+            sport_id: Some(sport_id),
+        }
+    }
+}
+
+/// Box–Muller; plenty for fake data.
+pub(crate) fn normal(rng: &mut Rng, mean: f64, sd: f64) -> f64 {
+    let u = rng.f64().max(f64::MIN_POSITIVE);
+    let v = rng.f64();
+    mean + sd * (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+pub(crate) fn uuid(rng: &mut Rng) -> Uuid {
+    let mut bytes = [0; 16];
+    rng.fill(&mut bytes);
+    uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
+
+pub(crate) fn hours(hours: f64) -> i64 {
+    (hours * 3_600_000.0) as i64
+}
+
+pub(crate) fn minutes(minutes: f64) -> Duration {
+    Duration::seconds((minutes * 60.0) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_reference_each_other_and_are_deterministic() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let data = Generator::new(start, 30).generate();
+
+        assert_eq!(data.cycles.len(), 30);
+        for ((cycle, sleep), recovery) in data.cycles.iter().zip(&data.sleeps).zip(&data.recoveries)
+        {
+            assert_eq!(sleep.cycle_id, cycle.id);
+            assert_eq!(sleep.start, cycle.start);
+            assert_eq!(recovery.sleep_id, sleep.id);
+        }
+        assert!(data.cycles.windows(2).all(|w| w[0].end == Some(w[1].start)));
+
+        let again = Generator::new(start, 30).generate();
+        assert_eq!(again.sleeps[7].id, data.sleeps[7].id);
+    }
+}
@@ -0,0 +1,484 @@
+//! Canned API responses for unit tests.
+//!
+//! Every payload comes twice: as the raw JSON the API sends (`CYCLE_SCORED`)
+//! and parsed into its model (`cycle_scored()`). Each resource has the shapes
+//! code has to cope with: scored, pending, unscorable, naps, and records with
+//! every optional field left out.
+
+use crate::models::*;
+
+macro_rules! fixtures {
+    ($($(#[$doc:meta])* $name:ident, $parsed:ident: $model:ty = $json:literal;)*) => {
+        $(
+            $(#[$doc])*
+            pub const $name: &str = $json;
+
+            $(#[$doc])*
+            pub fn $parsed() -> $model {
+                serde_json::from_str($name).expect(concat!(stringify!($name), " parses"))
+            }
+        )*
+
+        #[cfg(test)]
+        fn parse_all() {
+            $(let _ = $parsed();)*
+        }
+    };
+}
+
+fixtures! {
+    /// A finished, scored cycle.
+    CYCLE_SCORED, cycle_scored: Cycle = r#"{
+        "id": 93845,
+        "user_id": 10129,
+        "created_at": "2022-04-24T11:25:44.774Z",
+        "updated_at": "2022-04-24T14:25:44.774Z",
+        "start": "2022-04-24T02:25:44.774Z",
+        "end": "2022-04-24T10:25:44.774Z",
+        "timezone_offset": "-05:00",
+        "score_state": "SCORED",
+        "score": {
+            "strain": 5.2951527,
+            "kilojoule": 8288.297,
+            "average_heart_rate": 68,
+            "max_heart_rate": 141
+        }
+    }"#;
+
+    /// The current cycle: still open, so no `end`, and not scored yet.
+    CYCLE_PENDING, cycle_pending: Cycle = r#"{
+        "id": 93846,
+        "user_id": 10129,
+        "created_at": "2022-04-25T11:25:44.774Z",
+        "updated_at": "2022-04-25T11:25:44.774Z",
+        "start": "2022-04-25T02:25:44.774Z",
+        "timezone_offset": "-05:00",
+        "score_state": "PENDING_SCORE"
+    }"#;
+
+    /// A cycle WHOOP couldn't score, e.g. the strap was off all day.
+    CYCLE_UNSCORABLE, cycle_unscorable: Cycle = r#"{
+        "id": 93844,
+        "user_id": 10129,
+        "created_at": "2022-04-23T11:25:44.774Z",
+        "updated_at": "2022-04-23T14:25:44.774Z",
+        "start": "2022-04-23T02:25:44.774Z",
+        "end": "2022-04-24T02:25:44.774Z",
+        "timezone_offset": "-05:00",
+        "score_state": "UNSCORABLE"
+    }"#;
+
+    /// A page of cycles with more to come.
+    CYCLE_COLLECTION, cycle_collection: PaginatedCycleResponse = r#"{
+        "records": [
+            {
+                "id": 93846,
+                "user_id": 10129,
+                "created_at": "2022-04-25T11:25:44.774Z",
+                "updated_at": "2022-04-25T11:25:44.774Z",
+                "start": "2022-04-25T02:25:44.774Z",
+                "timezone_offset": "-05:00",
+                "score_state": "PENDING_SCORE"
+            },
+            {
+                "id": 93845,
+                "user_id": 10129,
+                "created_at": "2022-04-24T11:25:44.774Z",
+                "updated_at": "2022-04-24T14:25:44.774Z",
+                "start": "2022-04-24T02:25:44.774Z",
+                "end": "2022-04-24T10:25:44.774Z",
+                "timezone_offset": "-05:00",
+                "score_state": "SCORED",
+                "score": {
+                    "strain": 5.2951527,
+                    "kilojoule": 8288.297,
+                    "average_heart_rate": 68,
+                    "max_heart_rate": 141
+                }
+            }
+        ],
+        "next_token": "MTIzOjEyMzEyMw"
+    }"#;
+
+    /// A scored night's sleep.
+    SLEEP_SCORED, sleep_scored: Sleep = r#"{
+        "id": "ecfc6a15-4661-442f-a9a4-f160dd7afae8",
+        "cycle_id": 93845,
+        "v1_id": 93845,
+        "user_id": 10129,
+        "created_at": "2022-04-24T11:25:44.774Z",
+        "updated_at": "2022-04-24T14:25:44.774Z",
+        "start": "2022-04-24T02:25:44.774Z",
+        "end": "2022-04-24T10:25:44.774Z",
+        "timezone_offset": "-05:00",
+        "nap": false,
+        "score_state": "SCORED",
+        "score": {
+            "stage_summary": {
+                "total_in_bed_time_milli": 30272735,
+                "total_awake_time_milli": 1403507,
+                "total_no_data_time_milli": 0,
+                "total_light_sleep_time_milli": 14905851,
+                "total_slow_wave_sleep_time_milli": 6630370,
+                "total_rem_sleep_time_milli": 5879573,
+                "sleep_cycle_count": 3,
+                "disturbance_count": 12
+            },
+            "sleep_needed": {
+                "baseline_milli": 27395716,
+                "need_from_sleep_debt_milli": 352230,
+                "need_from_recent_strain_milli": 208595,
+                "need_from_recent_nap_milli": -12312
+            },
+            "respiratory_rate": 16.11328125,
+            "sleep_performance_percentage": 98,
+            "sleep_consistency_percentage": 90,
+            "sleep_efficiency_percentage": 91.69533848
+        }
+    }"#;
+
+    /// A scored afternoon nap.
+    SLEEP_NAP, sleep_nap: Sleep = r#"{
+        "id": "2a5b3c71-8e1d-4f0a-9c3b-7d6e5f4a3b2c",
+        "cycle_id": 93845,
+        "user_id": 10129,
+        "created_at": "2022-04-24T19:05:12.000Z",
+        "updated_at": "2022-04-24T19:35:12.000Z",
+        "start": "2022-04-24T18:20:00.000Z",
+        "end": "2022-04-24T18:55:00.000Z",
+        "timezone_offset": "-05:00",
+        "nap": true,
+        "score_state": "SCORED",
+        "score": {
+            "stage_summary": {
+                "total_in_bed_time_milli": 2100000,
+                "total_awake_time_milli": 240000,
+                "total_no_data_time_milli": 0,
+                "total_light_sleep_time_milli": 1380000,
+                "total_slow_wave_sleep_time_milli": 480000,
+                "total_rem_sleep_time_milli": 0,
+                "sleep_cycle_count": 0,
+                "disturbance_count": 1
+            },
+            "sleep_needed": {
+                "baseline_milli": 0,
+                "need_from_sleep_debt_milli": 0,
+                "need_from_recent_strain_milli": 0,
+                "need_from_recent_nap_milli": 0
+            },
+            "respiratory_rate": 15.8203125,
+            "sleep_efficiency_percentage": 88.57142857
+        }
+    }"#;
+
+    /// A sleep that was just detected and isn't scored yet.
+    SLEEP_PENDING, sleep_pending: Sleep = r#"{
+        "id": "5d7e9f11-2b3c-4d5e-8f6a-1b2c3d4e5f60",
+        "cycle_id": 93846,
+        "user_id": 10129,
+        "created_at": "2022-04-25T10:30:00.000Z",
+        "updated_at": "2022-04-25T10:30:00.000Z",
+        "start": "2022-04-25T02:40:00.000Z",
+        "end": "2022-04-25T10:20:00.000Z",
+        "timezone_offset": "-05:00",
+        "nap": false,
+        "score_state": "PENDING_SCORE"
+    }"#;
+
+    /// A sleep with too little data to score.
+    SLEEP_UNSCORABLE, sleep_unscorable: Sleep = r#"{
+        "id": "9c8b7a65-4d3e-4f2a-8b1c-0d9e8f7a6b5c",
+        "cycle_id": 93844,
+        "user_id": 10129,
+        "created_at": "2022-04-23T11:00:00.000Z",
+        "updated_at": "2022-04-23T12:00:00.000Z",
+        "start": "2022-04-23T03:10:00.000Z",
+        "end": "2022-04-23T10:45:00.000Z",
+        "timezone_offset": "-05:00",
+        "nap": false,
+        "score_state": "UNSCORABLE"
+    }"#;
+
+    /// A scored sleep without `v1_id` or any of the optional score fields.
+    SLEEP_MINIMAL, sleep_minimal: Sleep = r#"{
+        "id": "0f1e2d3c-4b5a-4968-8776-655443322110",
+        "cycle_id": 93843,
+        "user_id": 10129,
+        "created_at": "2022-04-22T11:25:44.774Z",
+        "updated_at": "2022-04-22T14:25:44.774Z",
+        "start": "2022-04-22T02:25:44.774Z",
+        "end": "2022-04-22T10:25:44.774Z",
+        "timezone_offset": "-05:00",
+        "nap": false,
+        "score_state": "SCORED",
+        "score": {
+            "stage_summary": {
+                "total_in_bed_time_milli": 28800000,
+                "total_awake_time_milli": 1800000,
+                "total_no_data_time_milli": 0,
+                "total_light_sleep_time_milli": 14400000,
+                "total_slow_wave_sleep_time_milli": 6300000,
+                "total_rem_sleep_time_milli": 6300000,
+                "sleep_cycle_count": 4,
+                "disturbance_count": 9
+            },
+            "sleep_needed": {
+                "baseline_milli": 27395716,
+                "need_from_sleep_debt_milli": 0,
+                "need_from_recent_strain_milli": 0,
+                "need_from_recent_nap_milli": 0
+            }
+        }
+    }"#;
+
+    /// A page of sleeps, the last one.
+    SLEEP_COLLECTION, sleep_collection: PaginatedSleepResponse = r#"{
+        "records": [
+            {
+                "id": "5d7e9f11-2b3c-4d5e-8f6a-1b2c3d4e5f60",
+                "cycle_id": 93846,
+                "user_id": 10129,
+                "created_at": "2022-04-25T10:30:00.000Z",
+                "updated_at": "2022-04-25T10:30:00.000Z",
+                "start": "2022-04-25T02:40:00.000Z",
+                "end": "2022-04-25T10:20:00.000Z",
+                "timezone_offset": "-05:00",
+                "nap": false,
+                "score_state": "PENDING_SCORE"
+            }
+        ]
+    }"#;
+
+    /// A scored recovery.
+    RECOVERY_SCORED, recovery_scored: Recovery = r#"{
+        "cycle_id": 93845,
+        "sleep_id": "ecfc6a15-4661-442f-a9a4-f160dd7afae8",
+        "user_id": 10129,
+        "created_at": "2022-04-24T11:25:44.774Z",
+        "updated_at": "2022-04-24T14:25:44.774Z",
+        "score_state": "SCORED",
+        "score": {
+            "user_calibrating": false,
+            "recovery_score": 44,
+            "resting_heart_rate": 64,
+            "hrv_rmssd_milli": 31.813562,
+            "spo2_percentage": 95.6875,
+            "skin_temp_celsius": 33.7
+        }
+    }"#;
+
+    /// A recovery from the first weeks of wear, while WHOOP is still calibrating.
+    RECOVERY_CALIBRATING, recovery_calibrating: Recovery = r#"{
+        "cycle_id": 93801,
+        "sleep_id": "1a2b3c4d-5e6f-4a7b-8c9d-0e1f2a3b4c5d",
+        "user_id": 10129,
+        "created_at": "2022-04-03T11:00:00.000Z",
+        "updated_at": "2022-04-03T11:30:00.000Z",
+        "score_state": "SCORED",
+        "score": {
+            "user_calibrating": true,
+            "recovery_score": 58,
+            "resting_heart_rate": 61,
+            "hrv_rmssd_milli": 38.2,
+            "spo2_percentage": 96.1,
+            "skin_temp_celsius": 33.9
+        }
+    }"#;
+
+    RECOVERY_PENDING, recovery_pending: Recovery = r#"{
+        "cycle_id": 93846,
+        "sleep_id": "5d7e9f11-2b3c-4d5e-8f6a-1b2c3d4e5f60",
+        "user_id": 10129,
+        "created_at": "2022-04-25T10:30:00.000Z",
+        "updated_at": "2022-04-25T10:30:00.000Z",
+        "score_state": "PENDING_SCORE"
+    }"#;
+
+    RECOVERY_UNSCORABLE, recovery_unscorable: Recovery = r#"{
+        "cycle_id": 93844,
+        "sleep_id": "9c8b7a65-4d3e-4f2a-8b1c-0d9e8f7a6b5c",
+        "user_id": 10129,
+        "created_at": "2022-04-23T11:00:00.000Z",
+        "updated_at": "2022-04-23T12:00:00.000Z",
+        "score_state": "UNSCORABLE"
+    }"#;
+
+    /// A scored recovery without SpO2 or skin temperature, as older straps report.
+    RECOVERY_MINIMAL, recovery_minimal: Recovery = r#"{
+        "cycle_id": 93843,
+        "sleep_id": "0f1e2d3c-4b5a-4968-8776-655443322110",
+        "user_id": 10129,
+        "created_at": "2022-04-22T11:25:44.774Z",
+        "updated_at": "2022-04-22T14:25:44.774Z",
+        "score_state": "SCORED",
+        "score": {
+            "user_calibrating": false,
+            "recovery_score": 81,
+            "resting_heart_rate": 52,
+            "hrv_rmssd_milli": 64.3
+        }
+    }"#;
+
+    /// A page of recoveries with more to come.
+    RECOVERY_COLLECTION, recovery_collection: RecoveryCollection = r#"{
+        "records": [
+            {
+                "cycle_id": 93845,
+                "sleep_id": "ecfc6a15-4661-442f-a9a4-f160dd7afae8",
+                "user_id": 10129,
+                "created_at": "2022-04-24T11:25:44.774Z",
+                "updated_at": "2022-04-24T14:25:44.774Z",
+                "score_state": "SCORED",
+                "score": {
+                    "user_calibrating": false,
+                    "recovery_score": 44,
+                    "resting_heart_rate": 64,
+                    "hrv_rmssd_milli": 31.813562,
+                    "spo2_percentage": 95.6875,
+                    "skin_temp_celsius": 33.7
+                }
+            }
+        ],
+        "next_token": "MTIzOjEyMzEyMw"
+    }"#;
+
+    /// A scored run with distance and altitude.
+    WORKOUT_SCORED, workout_scored: WorkoutV2 = r#"{
+        "id": "ecfc6a15-4661-442f-a9a4-f160dd7afae8",
+        "v1_id": 1043,
+        "user_id": 10129,
+        "created_at": "2022-04-24T11:25:44.774Z",
+        "updated_at": "2022-04-24T14:25:44.774Z",
+        "start": "2022-04-24T02:25:44.774Z",
+        "end": "2022-04-24T10:25:44.774Z",
+        "timezone_offset": "-05:00",
+        "sport_name": "running",
+        "score_state": "SCORED",
+        "score": {
+            "strain": 8.2463,
+            "average_heart_rate": 123,
+            "max_heart_rate": 146,
+            "kilojoule": 1569.34033203125,
+            "percent_recorded": 100,
+            "distance_meter": 1772.77035916,
+            "altitude_gain_meter": 46.64384460449,
+            "altitude_change_meter": -0.781372010707855,
+            "zone_durations": {
+                "zone_zero_milli": 300000,
+                "zone_one_milli": 600000,
+                "zone_two_milli": 900000,
+                "zone_three_milli": 900000,
+                "zone_four_milli": 600000,
+                "zone_five_milli": 300000
+            }
+        },
+        "sport_id": 0
+    }"#;
+
+    WORKOUT_PENDING, workout_pending: WorkoutV2 = r#"{
+        "id": "3e4f5a6b-7c8d-4e9f-a0b1-c2d3e4f5a6b7",
+        "user_id": 10129,
+        "created_at": "2022-04-25T18:05:00.000Z",
+        "updated_at": "2022-04-25T18:05:00.000Z",
+        "start": "2022-04-25T17:00:00.000Z",
+        "end": "2022-04-25T18:00:00.000Z",
+        "timezone_offset": "-05:00",
+        "sport_name": "cycling",
+        "score_state": "PENDING_SCORE",
+        "sport_id": 1
+    }"#;
+
+    WORKOUT_UNSCORABLE, workout_unscorable: WorkoutV2 = r#"{
+        "id": "8a9b0c1d-2e3f-4a5b-9c6d-7e8f9a0b1c2d",
+        "user_id": 10129,
+        "created_at": "2022-04-23T16:10:00.000Z",
+        "updated_at": "2022-04-23T16:40:00.000Z",
+        "start": "2022-04-23T15:00:00.000Z",
+        "end": "2022-04-23T15:05:00.000Z",
+        "timezone_offset": "-05:00",
+        "sport_name": "weightlifting",
+        "score_state": "UNSCORABLE",
+        "sport_id": 45
+    }"#;
+
+    /// A partially recorded gym session: no distance, altitude, `v1_id` or `sport_id`.
+    WORKOUT_MINIMAL, workout_minimal: WorkoutV2 = r#"{
+        "id": "6f7e8d9c-0b1a-4c2d-8e3f-4a5b6c7d8e9f",
+        "user_id": 10129,
+        "created_at": "2022-04-22T19:10:00.000Z",
+        "updated_at": "2022-04-22T19:40:00.000Z",
+        "start": "2022-04-22T18:00:00.000Z",
+        "end": "2022-04-22T19:00:00.000Z",
+        "timezone_offset": "-05:00",
+        "sport_name": "functional-fitness",
+        "score_state": "SCORED",
+        "score": {
+            "strain": 11.4,
+            "average_heart_rate": 131,
+            "max_heart_rate": 168,
+            "kilojoule": 1842.5,
+            "percent_recorded": 72.5,
+            "zone_durations": {
+                "zone_zero_milli": 420000,
+                "zone_one_milli": 900000,
+                "zone_two_milli": 1200000,
+                "zone_three_milli": 780000,
+                "zone_four_milli": 300000,
+                "zone_five_milli": 0
+            }
+        }
+    }"#;
+
+    /// A page of workouts, the last one.
+    WORKOUT_COLLECTION, workout_collection: WorkoutCollection = r#"{
+        "records": [
+            {
+                "id": "3e4f5a6b-7c8d-4e9f-a0b1-c2d3e4f5a6b7",
+                "user_id": 10129,
+                "created_at": "2022-04-25T18:05:00.000Z",
+                "updated_at": "2022-04-25T18:05:00.000Z",
+                "start": "2022-04-25T17:00:00.000Z",
+                "end": "2022-04-25T18:00:00.000Z",
+                "timezone_offset": "-05:00",
+                "sport_name": "cycling",
+                "score_state": "PENDING_SCORE",
+                "sport_id": 1
+            }
+        ]
+    }"#;
+
+    PROFILE, profile: UserBasicProfile = r#"{
+        "user_id": 10129,
+        "email": "jsmith123@whoop.com",
+        "first_name": "John",
+        "last_name": "Smith"
+    }"#;
+
+    BODY_MEASUREMENT, body_measurement: UserBodyMeasurement = r#"{
+        "height_meter": 1.8288,
+        "weight_kilogram": 90.7185,
+        "max_heart_rate": 200
+    }"#;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_fixture_parses_into_its_shape() {
+        parse_all();
+        assert!(cycle_pending().end.is_none());
+        assert!(matches!(
+            cycle_unscorable().score_state,
+            ScoreState::Unscorable
+        ));
+        assert!(sleep_nap().nap);
+        assert!(sleep_minimal().score.unwrap().respiratory_rate.is_none());
+        assert!(recovery_calibrating().score.unwrap().user_calibrating);
+        assert!(recovery_minimal().score.unwrap().spo2_percentage.is_none());
+        assert!(workout_minimal().sport_id.is_none());
+        assert_eq!(cycle_collection().records.unwrap().len(), 2);
+        assert!(workout_collection().next_token.is_none());
+    }
+}
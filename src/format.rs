@@ -0,0 +1,175 @@
+//! Human-friendly strings for frontends: durations, relative dates, colored
+//! scores and numbers in the reader's locale.
+//!
+//! Words are English; [`Locale`] only changes how numbers are written.
+
+use crate::analytics::RecoveryZone;
+use chrono::NaiveDate;
+
+/// Formats milliseconds as e.g. `7h 42m`.
+pub fn duration(milli: i64) -> String {
+    let minutes = milli / 60_000;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Formats milliseconds as e.g. `7h 42m`, or `42m` under an hour.
+pub fn short_duration(milli: i64) -> String {
+    let minutes = milli / 60_000;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        duration(milli)
+    }
+}
+
+/// `date` relative to `today`: `today`, `yesterday`, `3 days ago`, `in 2 days`,
+/// or the date itself more than a week away.
+pub fn relative_date(date: NaiveDate, today: NaiveDate) -> String {
+    match (today - date).num_days() {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        -1 => "tomorrow".to_string(),
+        days @ 2..=7 => format!("{} days ago", days),
+        days @ -7..=-2 => format!("in {} days", -days),
+        _ => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// The night before waking on `woke`: `last night` for this morning,
+/// `the night before last` for yesterday's, else like [`relative_date`].
+pub fn relative_night(woke: NaiveDate, today: NaiveDate) -> String {
+    match (today - woke).num_days() {
+        0 => "last night".to_string(),
+        1 => "the night before last".to_string(),
+        _ => format!("the night before {}", relative_date(woke, today)),
+    }
+}
+
+/// The ANSI escape code coloring a recovery zone in a terminal.
+pub fn ansi_color(zone: RecoveryZone) -> &'static str {
+    match zone {
+        RecoveryZone::Green => "\x1b[32m",
+        RecoveryZone::Yellow => "\x1b[33m",
+        RecoveryZone::Red => "\x1b[31m",
+    }
+}
+
+/// The RGB color of a recovery zone, as WHOOP's app draws it, e.g.
+/// `0x16EC06` for green.
+pub fn rgb_color(zone: RecoveryZone) -> u32 {
+    match zone {
+        RecoveryZone::Green => 0x16EC06,
+        RecoveryZone::Yellow => 0xFFDE00,
+        RecoveryZone::Red => 0xFF0026,
+    }
+}
+
+/// A recovery score as e.g. `67%`, colored by its zone when `color` is set.
+pub fn recovery_score(score: f32, color: bool) -> String {
+    let text = format!("{:.0}%", score);
+    if color {
+        format!(
+            "{}{}\x1b[0m",
+            ansi_color(RecoveryZone::from_score(score)),
+            text
+        )
+    } else {
+        text
+    }
+}
+
+/// How numbers are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal: char,
+    /// Separates thousands, if anything.
+    pub grouping: Option<char>,
+}
+
+impl Locale {
+    /// `1,234.5`
+    pub const EN: Locale = Locale {
+        decimal: '.',
+        grouping: Some(','),
+    };
+    /// `1.234,5`
+    pub const DE: Locale = Locale {
+        decimal: ',',
+        grouping: Some('.'),
+    };
+    /// `1 234,5`, with a narrow no-break space.
+    pub const FR: Locale = Locale {
+        decimal: ',',
+        grouping: Some('\u{202f}'),
+    };
+    /// `1234.5`
+    pub const PLAIN: Locale = Locale {
+        decimal: '.',
+        grouping: None,
+    };
+
+    /// The locale for a tag like `de-DE` or `fr`, by its language.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Self::EN),
+            "de" | "da" | "es" | "id" | "it" | "nl" | "pt" | "tr" => Some(Self::DE),
+            "fr" | "cs" | "fi" | "nb" | "pl" | "ru" | "sv" | "uk" => Some(Self::FR),
+            _ => None,
+        }
+    }
+
+    /// `value` with `precision` decimals.
+    pub fn number(&self, value: f64, precision: usize) -> String {
+        let plain = format!("{:.*}", precision, value.abs());
+        let (whole, fraction) = plain.split_once('.').unwrap_or((&plain, ""));
+
+        let mut out = String::new();
+        if value < 0.0 && plain.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                out.extend(self.grouping);
+            }
+            out.push(digit);
+        }
+        if !fraction.is_empty() {
+            out.push(self.decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::EN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_for_people() {
+        assert_eq!(duration(27_720_000), "7h 42m");
+        assert_eq!(short_duration(2_520_000), "42m");
+
+        let today = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let days_ago = |days| today - chrono::Duration::days(days);
+        assert_eq!(relative_date(days_ago(1), today), "yesterday");
+        assert_eq!(relative_date(days_ago(3), today), "3 days ago");
+        assert_eq!(relative_date(days_ago(30), today), "2024-02-11");
+        assert_eq!(relative_night(today, today), "last night");
+
+        assert_eq!(recovery_score(71.4, false), "71%");
+        assert!(recovery_score(20.0, true).starts_with("\x1b[31m"));
+
+        assert_eq!(Locale::EN.number(1234567.891, 2), "1,234,567.89");
+        assert_eq!(Locale::DE.number(-1234.5, 1), "-1.234,5");
+        assert_eq!(Locale::PLAIN.number(999.0, 0), "999");
+        assert_eq!(Locale::from_tag("de-AT"), Some(Locale::DE));
+    }
+}
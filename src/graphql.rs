@@ -0,0 +1,247 @@
+//! A read-only GraphQL API over the local store.
+//!
+//! Frontends pick the fields they need from synced records instead of
+//! waiting on a REST endpoint for every view:
+//!
+//! ```graphql
+//! {
+//!   sleeps(from: "2025-06-01T00:00:00Z", to: "2025-07-01T00:00:00Z") {
+//!     start
+//!     score { stageSummary { totalRemSleepTimeMilli totalSlowWaveSleepTimeMilli } }
+//!   }
+//! }
+//! ```
+//!
+//! Fields are the models' in camelCase. Ranges go by start time as in
+//! [`Store`], and `to` defaults to now. [`serve`] answers queries over HTTP;
+//! give it its own handle on the database a sync writes to, e.g. a second
+//! `SqliteStore` on the same file. Queries take turns on that one handle,
+//! each on a blocking thread.
+//!
+//! ```no_run
+//! # async fn run(store: impl whoopsy::store::Store + Send + 'static) -> whoopsy::Result<()> {
+//! let schema = whoopsy::graphql::schema(store);
+//! whoopsy::graphql::serve("127.0.0.1:8000".parse().unwrap(), schema).await
+//! # }
+//! ```
+
+use crate::error::Result;
+use crate::models::*;
+use crate::server::{self, Body};
+use crate::store::Store;
+use async_graphql::http::parse_query_string;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema, ServerError};
+use chrono::{DateTime, Utc};
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
+use hyper::{Method, Request};
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+pub type WhoopSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// The schema's queries over `store`.
+pub fn schema<S: Store + Send + 'static>(store: S) -> WhoopSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(Shared(Arc::new(Mutex::new(Box::new(store)))))
+        .finish()
+}
+
+/// Answers GraphQL queries at `/graphql`, POSTed as JSON or in a GET's query
+/// string, until the listener fails.
+pub async fn serve(addr: SocketAddr, schema: WhoopSchema) -> Result<()> {
+    server::serve_requests(addr, move |request| {
+        let schema = schema.clone();
+        async move {
+            if request.uri().path() != "/graphql" {
+                return server::respond(None);
+            }
+            let response = match parse(request).await {
+                Ok(query) => schema.execute(query).await,
+                Err(e) => async_graphql::Response::from_errors(vec![ServerError::new(e, None)]),
+            };
+            server::respond(Some(Body {
+                content_type: "application/json",
+                content: serde_json::to_string(&response).unwrap_or_default(),
+            }))
+        }
+    })
+    .await
+}
+
+async fn parse(request: Request<Incoming>) -> std::result::Result<async_graphql::Request, String> {
+    if request.method() == Method::GET {
+        let query = request.uri().query().unwrap_or_default();
+        return parse_query_string(query).map_err(|e| e.to_string());
+    }
+    let body = request
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_slice(&body.to_bytes()).map_err(|e| e.to_string())
+}
+
+/// What queries read from a store, which [`Store`] itself is too generic to
+/// be boxed as.
+trait Reads: Send {
+    fn cycles(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Cycle>>;
+    fn sleeps(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Sleep>>;
+    fn recoveries(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Recovery>>;
+    fn workouts(&self, range: Range<DateTime<Utc>>) -> Result<Vec<WorkoutV2>>;
+    fn workouts_by_sport(&self, sport: &str, range: Range<DateTime<Utc>>)
+    -> Result<Vec<WorkoutV2>>;
+    fn recoveries_below(&self, score: f32) -> Result<Vec<Recovery>>;
+}
+
+impl<S: Store + Send> Reads for S {
+    fn cycles(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Cycle>> {
+        self.cycles_between(range)
+    }
+
+    fn sleeps(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Sleep>> {
+        self.sleeps_between(range)
+    }
+
+    fn recoveries(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Recovery>> {
+        self.recoveries_between(range)
+    }
+
+    fn workouts(&self, range: Range<DateTime<Utc>>) -> Result<Vec<WorkoutV2>> {
+        self.workouts_between(range)
+    }
+
+    fn workouts_by_sport(
+        &self,
+        sport: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<WorkoutV2>> {
+        Store::workouts_by_sport(self, sport, range)
+    }
+
+    fn recoveries_below(&self, score: f32) -> Result<Vec<Recovery>> {
+        Store::recoveries_below(self, score)
+    }
+}
+
+struct Shared(Arc<Mutex<Box<dyn Reads>>>);
+
+/// Runs `query` on a blocking thread, since store calls do disk I/O.
+async fn read<T: Send + 'static>(
+    ctx: &Context<'_>,
+    query: impl FnOnce(&dyn Reads) -> Result<T> + Send + 'static,
+) -> async_graphql::Result<T> {
+    let store = Arc::clone(&ctx.data::<Shared>()?.0);
+    let task = tokio::task::spawn_blocking(move || -> async_graphql::Result<T> {
+        let store = store.lock().map_err(|_| "store lock poisoned")?;
+        Ok(query(store.as_ref())?)
+    });
+    task.await?
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn cycles(
+        &self,
+        ctx: &Context<'_>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+    ) -> async_graphql::Result<Vec<Cycle>> {
+        read(ctx, move |store| {
+            store.cycles(from..to.unwrap_or_else(Utc::now))
+        })
+        .await
+    }
+
+    async fn sleeps(
+        &self,
+        ctx: &Context<'_>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+    ) -> async_graphql::Result<Vec<Sleep>> {
+        read(ctx, move |store| {
+            store.sleeps(from..to.unwrap_or_else(Utc::now))
+        })
+        .await
+    }
+
+    /// By their cycle's start.
+    async fn recoveries(
+        &self,
+        ctx: &Context<'_>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+    ) -> async_graphql::Result<Vec<Recovery>> {
+        read(ctx, move |store| {
+            store.recoveries(from..to.unwrap_or_else(Utc::now))
+        })
+        .await
+    }
+
+    /// Only workouts of `sport`, matched by its normalized name, if given.
+    async fn workouts(
+        &self,
+        ctx: &Context<'_>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        sport: Option<String>,
+    ) -> async_graphql::Result<Vec<WorkoutV2>> {
+        let range = from..to.unwrap_or_else(Utc::now);
+        read(ctx, move |store| match &sport {
+            Some(sport) => store.workouts_by_sport(sport, range),
+            None => store.workouts(range),
+        })
+        .await
+    }
+
+    /// Scored recoveries below `score` percent.
+    async fn recoveries_below(
+        &self,
+        ctx: &Context<'_>,
+        score: f32,
+    ) -> async_graphql::Result<Vec<Recovery>> {
+        read(ctx, move |store| store.recoveries_below(score)).await
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::store::SqliteStore;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_queries_nested_fields() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let sleep = fixtures::sleep_scored();
+        store.upsert_sleeps(std::slice::from_ref(&sleep)).unwrap();
+        let schema = schema(store);
+
+        let query = format!(
+            r#"{{ sleeps(from: "{}") {{ id scoreState score {{ stageSummary {{ totalRemSleepTimeMilli }} }} }} }}"#,
+            (sleep.start - chrono::Duration::days(1)).to_rfc3339()
+        );
+        let response = schema.execute(query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let rem = sleep
+            .score
+            .unwrap()
+            .stage_summary
+            .total_rem_sleep_time_milli;
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "sleeps": [{
+                "id": sleep.id.to_string(),
+                "scoreState": "SCORED",
+                "score": { "stageSummary": { "totalRemSleepTimeMilli": rem } }
+            }] })
+        );
+
+        let response = schema.execute("{ sleeps { id } }").await;
+        assert_eq!(response.errors.len(), 1);
+    }
+}
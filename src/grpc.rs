@@ -0,0 +1,418 @@
+//! A gRPC service serving synced data, for services that talk gRPC.
+//!
+//! `proto/whoopsy.proto` defines the `whoopsy.v1.WhoopData` service and
+//! messages mirroring the [models](crate::models); generate a client from it
+//! in any language. [`WhoopService`] answers its list calls from a store,
+//! one at a time on a blocking thread, and streams [`Updates`] to
+//! subscribers:
+//!
+//! ```no_run
+//! # async fn run(store: impl whoopsy::store::Store + Send + 'static) -> whoopsy::Result<()> {
+//! use whoopsy::grpc::WhoopService;
+//!
+//! let service = WhoopService::new(store);
+//! let updates = service.updates();
+//! // In a webhook handler, once the record it names is fetched and stored:
+//! // updates.publish_sleep(&sleep);
+//! service.serve("127.0.0.1:50051".parse().unwrap()).await
+//! # }
+//! ```
+//!
+//! Subscribers get what's published after they subscribe. One that falls
+//! more than 256 updates behind has its stream ended with `DATA_LOSS`, and
+//! should list what it missed before subscribing again.
+
+use crate::error::{Result, WhoopError};
+use crate::models;
+use crate::store::Store;
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+/// Code generated from `proto/whoopsy.proto`.
+pub mod proto {
+    tonic::include_proto!("whoopsy.v1");
+}
+
+use proto::update::Record;
+use proto::whoop_data_server::{WhoopData, WhoopDataServer};
+
+const UPDATE_BUFFER: usize = 256;
+
+pub struct WhoopService<S> {
+    store: Arc<Mutex<S>>,
+    updates: Updates,
+}
+
+impl<S: Store + Send + 'static> WhoopService<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(Mutex::new(store)),
+            updates: Updates(broadcast::channel(UPDATE_BUFFER).0),
+        }
+    }
+
+    /// A handle publishing to this service's subscribers.
+    pub fn updates(&self) -> Updates {
+        self.updates.clone()
+    }
+
+    /// The service, to add to a tonic server of your own.
+    pub fn into_server(self) -> WhoopDataServer<Self> {
+        WhoopDataServer::new(self)
+    }
+
+    /// Serves the service alone on `addr` until the server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_server())
+            .serve(addr)
+            .await
+            .map_err(|e| WhoopError::Unknown(format!("gRPC server failed: {}", e), None))
+    }
+
+    /// Runs `query` on a blocking thread, since store calls do disk I/O.
+    async fn read<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&S) -> Result<T> + Send + 'static,
+    ) -> std::result::Result<T, Status> {
+        let store = Arc::clone(&self.store);
+        let task = tokio::task::spawn_blocking(move || {
+            let store = store
+                .lock()
+                .map_err(|_| Status::internal("store lock poisoned"))?;
+            query(&store).map_err(|e| Status::internal(e.to_string()))
+        });
+        task.await
+            .map_err(|e| Status::internal(format!("store task failed: {}", e)))?
+    }
+}
+
+/// Publishes added or changed records to subscribers. Clones publish to the
+/// same subscribers.
+#[derive(Clone)]
+pub struct Updates(broadcast::Sender<proto::Update>);
+
+impl Updates {
+    pub fn publish_cycle(&self, cycle: &models::Cycle) {
+        self.publish(Record::Cycle(cycle.into()));
+    }
+
+    pub fn publish_sleep(&self, sleep: &models::Sleep) {
+        self.publish(Record::Sleep(sleep.into()));
+    }
+
+    pub fn publish_recovery(&self, recovery: &models::Recovery) {
+        self.publish(Record::Recovery(recovery.into()));
+    }
+
+    pub fn publish_workout(&self, workout: &models::WorkoutV2) {
+        self.publish(Record::Workout(workout.into()));
+    }
+
+    fn publish(&self, record: Record) {
+        // Fails only when nobody is subscribed, which is fine.
+        let _ = self.0.send(proto::Update {
+            record: Some(record),
+        });
+    }
+}
+
+type UpdateStream = Pin<Box<dyn Stream<Item = std::result::Result<proto::Update, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl<S: Store + Send + 'static> WhoopData for WhoopService<S> {
+    async fn list_cycles(
+        &self,
+        request: Request<proto::RangeRequest>,
+    ) -> std::result::Result<Response<proto::Cycles>, Status> {
+        let range = range(request.get_ref())?;
+        let records = self.read(move |store| store.cycles_between(range)).await?;
+        Ok(Response::new(proto::Cycles {
+            records: records.iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn list_sleeps(
+        &self,
+        request: Request<proto::RangeRequest>,
+    ) -> std::result::Result<Response<proto::Sleeps>, Status> {
+        let range = range(request.get_ref())?;
+        let records = self.read(move |store| store.sleeps_between(range)).await?;
+        Ok(Response::new(proto::Sleeps {
+            records: records.iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn list_recoveries(
+        &self,
+        request: Request<proto::RangeRequest>,
+    ) -> std::result::Result<Response<proto::Recoveries>, Status> {
+        let range = range(request.get_ref())?;
+        let records = self
+            .read(move |store| store.recoveries_between(range))
+            .await?;
+        Ok(Response::new(proto::Recoveries {
+            records: records.iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn list_workouts(
+        &self,
+        request: Request<proto::WorkoutsRequest>,
+    ) -> std::result::Result<Response<proto::Workouts>, Status> {
+        let request = request.into_inner();
+        let range = range(&request.range.unwrap_or_default())?;
+        let records = self
+            .read(move |store| match &request.sport {
+                Some(sport) => store.workouts_by_sport(sport, range),
+                None => store.workouts_between(range),
+            })
+            .await?;
+        Ok(Response::new(proto::Workouts {
+            records: records.iter().map(Into::into).collect(),
+        }))
+    }
+
+    type SubscribeStream = UpdateStream;
+
+    async fn subscribe(
+        &self,
+        _request: Request<proto::SubscribeRequest>,
+    ) -> std::result::Result<Response<UpdateStream>, Status> {
+        let updates = BroadcastStream::new(self.updates.0.subscribe()).map(|update| {
+            update.map_err(|BroadcastStreamRecvError::Lagged(missed)| {
+                Status::data_loss(format!("fell {} updates behind", missed))
+            })
+        });
+        // Nothing follows a lag, so the subscriber knows to catch up.
+        let mut lagged = false;
+        let updates = updates.take_while(move |update| {
+            let keep = !lagged;
+            lagged = lagged || update.is_err();
+            keep
+        });
+        Ok(Response::new(Box::pin(updates)))
+    }
+}
+
+fn range(request: &proto::RangeRequest) -> std::result::Result<Range<DateTime<Utc>>, Status> {
+    let start = request
+        .start
+        .and_then(time)
+        .ok_or_else(|| Status::invalid_argument("start is required"))?;
+    let end = match request.end {
+        Some(end) => time(end).ok_or_else(|| Status::invalid_argument("invalid end"))?,
+        None => Utc::now(),
+    };
+    if start >= end {
+        return Err(Status::invalid_argument("start must be before end"));
+    }
+    Ok(start..end)
+}
+
+fn time(timestamp: Timestamp) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(timestamp.seconds, u32::try_from(timestamp.nanos).ok()?)
+}
+
+fn timestamp(time: DateTime<Utc>) -> Option<Timestamp> {
+    Some(Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    })
+}
+
+impl From<models::ScoreState> for proto::ScoreState {
+    fn from(state: models::ScoreState) -> Self {
+        match state {
+            models::ScoreState::Scored => proto::ScoreState::Scored,
+            models::ScoreState::PendingScore => proto::ScoreState::PendingScore,
+            models::ScoreState::Unscorable => proto::ScoreState::Unscorable,
+        }
+    }
+}
+
+impl From<&models::Cycle> for proto::Cycle {
+    fn from(cycle: &models::Cycle) -> Self {
+        Self {
+            id: cycle.id,
+            user_id: cycle.user_id,
+            created_at: timestamp(cycle.created_at),
+            updated_at: timestamp(cycle.updated_at),
+            start: timestamp(cycle.start),
+            end: cycle.end.and_then(timestamp),
+            timezone_offset: cycle.timezone_offset.clone(),
+            score_state: proto::ScoreState::from(cycle.score_state).into(),
+            score: cycle.score.as_ref().map(|score| proto::CycleScore {
+                strain: score.strain,
+                kilojoule: score.kilojoule,
+                average_heart_rate: score.average_heart_rate,
+                max_heart_rate: score.max_heart_rate,
+            }),
+        }
+    }
+}
+
+impl From<&models::Sleep> for proto::Sleep {
+    fn from(sleep: &models::Sleep) -> Self {
+        Self {
+            id: sleep.id.to_string(),
+            cycle_id: sleep.cycle_id,
+            v1_id: sleep.v1_id,
+            user_id: sleep.user_id,
+            created_at: timestamp(sleep.created_at),
+            updated_at: timestamp(sleep.updated_at),
+            start: timestamp(sleep.start),
+            end: timestamp(sleep.end),
+            timezone_offset: sleep.timezone_offset.clone(),
+            nap: sleep.nap,
+            score_state: proto::ScoreState::from(sleep.score_state).into(),
+            score: sleep.score.as_ref().map(|score| proto::SleepScore {
+                stage_summary: Some(proto::SleepStageSummary {
+                    total_in_bed_time_milli: score.stage_summary.total_in_bed_time_milli,
+                    total_awake_time_milli: score.stage_summary.total_awake_time_milli,
+                    total_no_data_time_milli: score.stage_summary.total_no_data_time_milli,
+                    total_light_sleep_time_milli: score.stage_summary.total_light_sleep_time_milli,
+                    total_slow_wave_sleep_time_milli: score
+                        .stage_summary
+                        .total_slow_wave_sleep_time_milli,
+                    total_rem_sleep_time_milli: score.stage_summary.total_rem_sleep_time_milli,
+                    sleep_cycle_count: score.stage_summary.sleep_cycle_count,
+                    disturbance_count: score.stage_summary.disturbance_count,
+                }),
+                sleep_needed: Some(proto::SleepNeeded {
+                    baseline_milli: score.sleep_needed.baseline_milli,
+                    need_from_sleep_debt_milli: score.sleep_needed.need_from_sleep_debt_milli,
+                    need_from_recent_strain_milli: score.sleep_needed.need_from_recent_strain_milli,
+                    need_from_recent_nap_milli: score.sleep_needed.need_from_recent_nap_milli,
+                }),
+                respiratory_rate: score.respiratory_rate,
+                sleep_performance_percentage: score.sleep_performance_percentage,
+                sleep_consistency_percentage: score.sleep_consistency_percentage,
+                sleep_efficiency_percentage: score.sleep_efficiency_percentage,
+            }),
+        }
+    }
+}
+
+impl From<&models::Recovery> for proto::Recovery {
+    fn from(recovery: &models::Recovery) -> Self {
+        Self {
+            cycle_id: recovery.cycle_id,
+            sleep_id: recovery.sleep_id.to_string(),
+            user_id: recovery.user_id,
+            created_at: timestamp(recovery.created_at),
+            updated_at: timestamp(recovery.updated_at),
+            score_state: proto::ScoreState::from(recovery.score_state).into(),
+            score: recovery.score.as_ref().map(|score| proto::RecoveryScore {
+                user_calibrating: score.user_calibrating,
+                recovery_score: score.recovery_score,
+                resting_heart_rate: score.resting_heart_rate,
+                hrv_rmssd_milli: score.hrv_rmssd_milli,
+                spo2_percentage: score.spo2_percentage,
+                skin_temp_celsius: score.skin_temp_celsius,
+            }),
+        }
+    }
+}
+
+impl From<&models::WorkoutV2> for proto::Workout {
+    fn from(workout: &models::WorkoutV2) -> Self {
+        Self {
+            id: workout.id.to_string(),
+            v1_id: workout.v1_id,
+            user_id: workout.user_id,
+            created_at: timestamp(workout.created_at),
+            updated_at: timestamp(workout.updated_at),
+            start: timestamp(workout.start),
+            end: timestamp(workout.end),
+            timezone_offset: workout.timezone_offset.clone(),
+            sport_name: workout.sport_name.clone(),
+            score_state: proto::ScoreState::from(workout.score_state).into(),
+            score: workout.score.as_ref().map(|score| proto::WorkoutScore {
+                strain: score.strain,
+                average_heart_rate: score.average_heart_rate,
+                max_heart_rate: score.max_heart_rate,
+                kilojoule: score.kilojoule,
+                percent_recorded: score.percent_recorded,
+                distance_meter: score.distance_meter,
+                altitude_gain_meter: score.altitude_gain_meter,
+                altitude_change_meter: score.altitude_change_meter,
+                zone_durations: Some(proto::ZoneDurations {
+                    zone_zero_milli: score.zone_durations.zone_zero_milli,
+                    zone_one_milli: score.zone_durations.zone_one_milli,
+                    zone_two_milli: score.zone_durations.zone_two_milli,
+                    zone_three_milli: score.zone_durations.zone_three_milli,
+                    zone_four_milli: score.zone_durations.zone_four_milli,
+                    zone_five_milli: score.zone_durations.zone_five_milli,
+                }),
+            }),
+            sport_id: workout.sport_id,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::store::SqliteStore;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_lists_stored_records_and_streams_updates() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let sleep = fixtures::sleep_scored();
+        store.upsert_sleeps(std::slice::from_ref(&sleep)).unwrap();
+        let service = WhoopService::new(store);
+
+        let request = proto::RangeRequest {
+            start: timestamp(sleep.start - Duration::days(1)),
+            end: None,
+        };
+        let sleeps = service
+            .list_sleeps(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(sleeps.records.len(), 1);
+        assert_eq!(sleeps.records[0].id, sleep.id.to_string());
+        assert_eq!(time(sleeps.records[0].start.unwrap()), Some(sleep.start));
+        let score = sleeps.records[0].score.as_ref().unwrap();
+        assert_eq!(
+            score
+                .stage_summary
+                .as_ref()
+                .unwrap()
+                .total_rem_sleep_time_milli,
+            sleep
+                .score
+                .as_ref()
+                .unwrap()
+                .stage_summary
+                .total_rem_sleep_time_milli
+        );
+
+        let missing = service
+            .list_cycles(Request::new(proto::RangeRequest::default()))
+            .await;
+        assert_eq!(missing.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let mut stream = service
+            .subscribe(Request::new(proto::SubscribeRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        service.updates().publish_sleep(&sleep);
+        let update = stream.next().await.unwrap().unwrap();
+        assert!(matches!(update.record, Some(Record::Sleep(s)) if s.id == sleep.id.to_string()));
+    }
+}
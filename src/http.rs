@@ -0,0 +1,305 @@
+//! Tuning for the HTTP client behind [`WhoopClient`](crate::WhoopClient).
+//!
+//! The defaults suit one user. Backends syncing many users through one client
+//! benefit from keeping connections warm and, with the `http2` feature, from
+//! multiplexing requests over a few HTTP/2 connections instead of opening one
+//! per request in flight:
+//!
+//! ```no_run
+//! # fn main() -> whoopsy::Result<()> {
+//! use std::time::Duration;
+//! use whoopsy::{HttpOptions, WhoopClient};
+//!
+//! let http = HttpOptions::default()
+//!     .with_tcp_keepalive(Duration::from_secs(30))
+//!     .with_pool_idle_timeout(Duration::from_secs(300))
+//!     .build()?;
+//! let client = WhoopClient::new("token".to_string()).with_http_client(http);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! How many requests share an HTTP/2 connection is capped by the server, which
+//! announces its limit when the connection opens; clients can't raise it.
+//!
+//! TLS comes from rustls by default, or from the platform with the
+//! `native-tls` feature, which wins when both are enabled. Behind a proxy that
+//! intercepts TLS, trust its certificate authority with
+//! [`HttpOptions::with_root_certificates_pem`]. Without either feature the
+//! client can only speak plain HTTP, e.g. to a local mock.
+//!
+//! Where the system resolver can't find the API, or traffic must leave through
+//! an internal gateway, [`HttpOptions::with_api_addrs`] pins [`API_HOST`] to
+//! fixed addresses and [`HttpOptions::with_dns_resolver`] replaces resolution
+//! altogether. TLS still checks certificates against the hostname.
+
+use crate::error::Result;
+use reqwest::{Client, Request, Response};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+pub use reqwest::Certificate;
+
+/// The host serving both the API and OAuth.
+pub const API_HOST: &str = "api.prod.whoop.com";
+
+#[derive(Clone)]
+struct Resolver(Arc<dyn Resolve>);
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Resolver")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    resolved: Vec<(String, Vec<SocketAddr>)>,
+    dns_resolver: Option<Resolver>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    root_certificates: Vec<Certificate>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    only_custom_roots: bool,
+    #[cfg(feature = "http2")]
+    http2_only: bool,
+    #[cfg(feature = "http2")]
+    http2_keep_alive: Option<(Duration, Duration)>,
+    #[cfg(feature = "http2")]
+    http2_adaptive_window: bool,
+}
+
+impl HttpOptions {
+    /// Gives up on a whole request after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends TCP keep-alive probes on idle connections every `interval`.
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Closes pooled connections unused for `timeout`. reqwest's default is 90s.
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Connects to `addrs` for `host` instead of looking it up.
+    pub fn with_resolved(mut self, host: &str, addrs: &[SocketAddr]) -> Self {
+        self.resolved.push((host.to_string(), addrs.to_vec()));
+        self
+    }
+
+    /// Connects to `addrs` for [`API_HOST`].
+    pub fn with_api_addrs(self, addrs: &[SocketAddr]) -> Self {
+        self.with_resolved(API_HOST, addrs)
+    }
+
+    /// Looks up every host through `resolver`, except those pinned with
+    /// [`with_resolved`](Self::with_resolved).
+    pub fn with_dns_resolver(mut self, resolver: impl Resolve + 'static) -> Self {
+        self.dns_resolver = Some(Resolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Trusts `certificate` as well as the built-in roots.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Trusts every certificate in a PEM bundle, e.g. a corporate CA file.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_root_certificates_pem(mut self, pem: &[u8]) -> Result<Self> {
+        self.root_certificates
+            .extend(Certificate::from_pem_bundle(pem)?);
+        Ok(self)
+    }
+
+    /// Trusts only the certificates added here, not the built-in roots.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_only_custom_roots(mut self) -> Self {
+        self.only_custom_roots = true;
+        self
+    }
+
+    /// Speaks HTTP/2 from the start instead of negotiating it.
+    #[cfg(feature = "http2")]
+    pub fn with_http2_only(mut self) -> Self {
+        self.http2_only = true;
+        self
+    }
+
+    /// Pings HTTP/2 connections every `interval`, even idle ones, and drops
+    /// those that don't answer within `timeout`.
+    #[cfg(feature = "http2")]
+    pub fn with_http2_keep_alive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.http2_keep_alive = Some((interval, timeout));
+        self
+    }
+
+    /// Grows HTTP/2 flow control windows with the measured bandwidth, which
+    /// helps when many large pages stream over one connection.
+    #[cfg(feature = "http2")]
+    pub fn with_http2_adaptive_window(mut self) -> Self {
+        self.http2_adaptive_window = true;
+        self
+    }
+
+    pub fn build(&self) -> Result<Client> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        for (host, addrs) in &self.resolved {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+        if let Some(Resolver(resolver)) = &self.dns_resolver {
+            builder = builder.dns_resolver2(Arc::clone(resolver));
+        }
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        {
+            for certificate in &self.root_certificates {
+                builder = builder.add_root_certificate(certificate.clone());
+            }
+            builder = builder.tls_built_in_root_certs(!self.only_custom_roots);
+        }
+        #[cfg(feature = "http2")]
+        {
+            if self.http2_only {
+                builder = builder.http2_prior_knowledge();
+            }
+            if let Some((interval, timeout)) = self.http2_keep_alive {
+                builder = builder
+                    .http2_keep_alive_interval(interval)
+                    .http2_keep_alive_timeout(timeout)
+                    .http2_keep_alive_while_idle(true);
+            }
+            builder = builder.http2_adaptive_window(self.http2_adaptive_window);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Sends `request`, inside a span with the `otel` feature.
+#[cfg(feature = "otel")]
+pub(crate) async fn send(client: &Client, request: Request) -> reqwest::Result<Response> {
+    crate::otel::execute(client, request).await
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) async fn send(client: &Client, request: Request) -> reqwest::Result<Response> {
+    client.execute(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_a_tuned_client() {
+        let options = HttpOptions::default()
+            .with_timeout(Duration::from_secs(30))
+            .with_tcp_keepalive(Duration::from_secs(15))
+            .with_pool_max_idle_per_host(8);
+        #[cfg(feature = "http2")]
+        let options = options
+            .with_http2_only()
+            .with_http2_keep_alive(Duration::from_secs(20), Duration::from_secs(5))
+            .with_http2_adaptive_window();
+        assert!(options.build().is_ok());
+    }
+
+    #[cfg(feature = "test-support")]
+    #[tokio::test]
+    async fn test_overrides_host_resolution() {
+        use crate::test_support::MockWhoop;
+
+        struct To(SocketAddr);
+
+        impl Resolve for To {
+            fn resolve(&self, _: Name) -> Resolving {
+                let addr = self.0;
+                Box::pin(async move { Ok(Box::new(std::iter::once(addr)) as Addrs) })
+            }
+        }
+
+        let mock = MockWhoop::start().await;
+        let addr = *mock.server().address();
+        let pinned = HttpOptions::default()
+            .with_resolved("whoop.internal", &[addr])
+            .build()
+            .unwrap();
+        let resolved = HttpOptions::default()
+            .with_dns_resolver(To(addr))
+            .build()
+            .unwrap();
+
+        for http in [pinned, resolved] {
+            let client = mock
+                .client()
+                .with_http_client(http)
+                .with_base_url(format!("http://whoop.internal:{}", addr.port()));
+            client.get_profile_basic().await.unwrap();
+        }
+    }
+
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    #[test]
+    fn test_trusts_custom_roots() {
+        const CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBjDCCATGgAwIBAgIUef2jhhfNcQPEVSr5EpodT7kSz+AwCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPd2hvb3BzeSB0ZXN0IENBMCAXDTI2MTAxNjA5NDE0MloYDzIx
+MjYwOTIyMDk0MTQyWjAaMRgwFgYDVQQDDA93aG9vcHN5IHRlc3QgQ0EwWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAASFru1bUs0j7sPg5fVG56G45UmLwg5U3pl173Ho
+d2kcDeszaT0MRjABVKzyZKQMDSNkcoGu7lMKv1BvUSmdLENzo1MwUTAdBgNVHQ4E
+FgQUGm1FVEb0GVmIT2FOg40/gAU3z3UwHwYDVR0jBBgwFoAUGm1FVEb0GVmIT2FO
+g40/gAU3z3UwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNJADBGAiEAvUhA
+9zTj4ylwoXcf8cRcRIPjDS/XZrOhTXkQwlBDN7oCIQD+11A/EknQlIFPh67UgdrU
+zft6MnBcCWZ2meeqGh+7YA==
+-----END CERTIFICATE-----
+";
+        let options = HttpOptions::default()
+            .with_root_certificates_pem(CA.as_bytes())
+            .unwrap()
+            .with_only_custom_roots();
+        assert_eq!(options.root_certificates.len(), 1);
+        assert!(options.build().is_ok());
+    }
+}
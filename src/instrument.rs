@@ -0,0 +1,63 @@
+//! Log events for requests and token refreshes, through `tracing` with the
+//! `tracing` feature or `log` with the `log` feature. With both, `tracing`
+//! wins; its own `log` feature forwards to loggers. With neither, nothing is
+//! emitted.
+
+/// Emits a formatted event at `$level`, one of `debug`, `info` or `warn`.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::$level!(target: "whoopsy", $($arg)+);
+        #[cfg(all(feature = "log", not(feature = "tracing")))]
+        ::log::$level!(target: "whoopsy", $($arg)+);
+        #[cfg(not(any(feature = "tracing", feature = "log")))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+pub(crate) use event;
+
+#[cfg(all(
+    test,
+    feature = "log",
+    feature = "test-support",
+    not(feature = "tracing")
+))]
+mod tests {
+    use crate::test_support::MockWhoop;
+    use std::sync::Mutex;
+
+    static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            if record.target() == "whoopsy" {
+                let line = format!("{} {}", record.level(), record.args());
+                LINES.lock().unwrap().push(line);
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_logs_requests() {
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+        let mock = MockWhoop::start().await;
+        mock.client().get_profile_basic().await.unwrap();
+
+        let lines = LINES.lock().unwrap();
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.starts_with("DEBUG GET /v2/user/profile/basic -> 200"))
+        );
+    }
+}
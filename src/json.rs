@@ -0,0 +1,54 @@
+//! Parses the JSON of responses, archives and the store.
+//!
+//! With the `simd-json` feature, whole buffers are parsed with simd-json,
+//! which is several times faster on the pages and files bulk exports and sync
+//! jobs churn through. CPUs without the instructions it needs fall back to its
+//! portable parser. Its errors are reported as
+//! [`WhoopError::SerializationError`](crate::WhoopError::SerializationError)
+//! either way, so callers can't tell which backend ran.
+
+use crate::error::Result;
+use serde::de::DeserializeOwned;
+
+/// Parses `bytes`, which simd-json uses as scratch space.
+#[cfg(feature = "simd-json")]
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &mut [u8]) -> Result<T> {
+    simd_json::serde::from_slice(bytes)
+        .map_err(|e| <serde_json::Error as serde::de::Error>::custom(e).into())
+}
+
+/// Parses `bytes`, which simd-json uses as scratch space.
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &mut [u8]) -> Result<T> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+pub(crate) fn from_string<T: DeserializeOwned>(text: String) -> Result<T> {
+    from_slice(&mut text.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WhoopError;
+    use crate::fixtures;
+    use crate::models::{PaginatedSleepResponse, Sleep};
+
+    #[test]
+    fn test_parses_like_serde_json() {
+        let page: PaginatedSleepResponse =
+            from_string(fixtures::SLEEP_COLLECTION.to_string()).unwrap();
+        let expected: PaginatedSleepResponse =
+            serde_json::from_str(fixtures::SLEEP_COLLECTION).unwrap();
+        assert_eq!(
+            serde_json::to_value(page).unwrap(),
+            serde_json::to_value(expected).unwrap()
+        );
+
+        let truncated = &fixtures::SLEEP_SCORED[..fixtures::SLEEP_SCORED.len() / 2];
+        assert!(matches!(
+            from_string::<Sleep>(truncated.to_string()),
+            Err(WhoopError::SerializationError(_))
+        ));
+    }
+}
@@ -1,9 +1,92 @@
+pub mod aggregate;
+#[cfg(feature = "analytics")]
+pub mod alerts;
+pub mod analytics;
+pub mod api;
+#[cfg(feature = "zip")]
+pub mod archive;
+pub mod audit;
 pub mod auth;
+pub mod body;
+pub mod calendar;
+#[cfg(feature = "analytics")]
+pub mod chat;
 pub mod client;
+#[cfg(feature = "openapi")]
+pub mod conformance;
+pub mod diff;
+#[cfg(feature = "email")]
+pub mod email;
 pub mod error;
+pub mod export;
+#[cfg(feature = "fake")]
+pub mod fake;
+pub mod fixtures;
+pub mod format;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod http;
+mod instrument;
+mod json;
+pub mod memory;
+pub mod merge;
+#[cfg(feature = "prometheus")]
+pub mod metrics;
 pub mod models;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod pagination;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+#[cfg(feature = "analytics")]
+pub mod push;
+pub mod query;
+pub mod rate_limit;
+#[cfg(feature = "raw-archive")]
+pub mod raw;
+pub mod replay;
+pub mod report;
+pub mod reporting;
+pub mod sandbox;
+pub mod schedule;
+pub mod scoring;
+#[cfg(any(
+    feature = "prometheus",
+    feature = "ics-server",
+    feature = "graphql",
+    feature = "proxy"
+))]
+mod server;
+#[cfg(feature = "fake")]
+pub mod simulate;
+pub mod sport;
+pub mod store;
+mod stream;
+pub mod sync;
+pub mod tenant;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+#[cfg(feature = "test-support")]
+pub mod vcr;
+pub mod version;
 
+pub use aggregate::{
+    AggregateOptions, DailySummary, MonthlySummary, SportSummary, Summary, WeeklySummary,
+    ZoneDistribution, ZoneShare,
+};
+pub use api::WhoopApi;
 pub use auth::{OAuthConfig, Scope, TokenResponse};
+pub use calendar::{Calendar, CalendarDay, TravelPolicy, TravelReport};
 pub use client::WhoopClient;
-pub use error::{Result, WhoopError};
+pub use error::{ApiErrorBody, ErrorKind, RateLimit, Result, WhoopError};
+pub use http::HttpOptions;
+pub use memory::InMemoryWhoop;
 pub use models::*;
+pub use pagination::Pages;
+pub use rate_limit::RateLimiter;
+pub use report::Report;
+pub use version::{ApiVersion, ResourceFamily};
@@ -1,58 +1,139 @@
-use std::env;
-use whoopsy::{OAuthConfig, Result, Scope, WhoopClient};
+mod cli;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use cli::Context;
+use cli::config::{Config, Units};
+use std::path::PathBuf;
+use whoopsy::Result;
+
+#[derive(Parser)]
+#[command(
+    name = "whoopsy",
+    version,
+    about = "Command line access to the WHOOP API"
+)]
+struct Cli {
+    /// Access token used for API requests.
+    #[arg(
+        long,
+        env = "WHOOP_ACCESS_TOKEN",
+        hide_env_values = true,
+        global = true
+    )]
+    token: Option<String>,
+
+    /// Serves requests from a directory of exported files instead of the API.
+    #[arg(long, env = "WHOOP_SANDBOX", global = true)]
+    sandbox: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the authenticated user's profile and body measurements.
+    Profile {
+        /// Units for height and weight. Defaults to `units` from the config.
+        #[arg(long, value_enum)]
+        units: Option<Units>,
+    },
+    /// Dumps cycles, sleep, recovery and workouts for a date range to files.
+    Export(cli::export::ExportArgs),
+    /// Polls for new or updated records and prints them as they arrive.
+    Watch(cli::watch::WatchArgs),
+    /// Opens a terminal dashboard of recent days.
+    Tui(cli::tui::TuiArgs),
+    /// Backfills and incrementally updates a local SQLite database.
+    Sync(cli::sync::SyncArgs),
+    /// Syncs and mails reports on a schedule until stopped.
+    Daemon(cli::daemon::DaemonArgs),
+    /// Backs the local database up to a zip archive, or restores one.
+    #[cfg(feature = "zip")]
+    #[command(subcommand)]
+    Archive(cli::archive::ArchiveCommand),
+    /// Serves synced data to local tools over HTTP, fetching what's stale.
+    #[cfg(feature = "proxy")]
+    Proxy(cli::proxy::ProxyArgs),
+    /// Compares two periods, e.g. this week against last week.
+    Compare(cli::compare::CompareArgs),
+    /// Prints summary reports.
+    #[command(subcommand)]
+    Report(cli::report::ReportCommand),
+    /// Reads or changes settings in the config file.
+    #[command(subcommand)]
+    Config(cli::config::ConfigCommand),
+    /// Prints a shell completion script to stdout.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Example 1: Using direct access token
-    if let Ok(access_token) = env::var("WHOOP_ACCESS_TOKEN") {
-        let client = WhoopClient::new(access_token);
-
-        // Get user profile
-        match client.get_profile_basic().await {
-            Ok(profile) => {
-                println!("User: {} {}", profile.first_name, profile.last_name);
-                println!("Email: {}", profile.email);
-            }
-            Err(e) => eprintln!("Error fetching profile: {}", e),
-        }
+async fn main() {
+    let cli = Cli::parse();
 
-        // Get recent cycles
-        match client.get_cycle_collection(None).await {
-            Ok(cycles) => {
-                if let Some(records) = cycles.records {
-                    println!("\nRecent cycles:");
-                    for cycle in records.iter().take(5) {
-                        println!("  Cycle {}: {}", cycle.id, cycle.start);
-                        if let Some(score) = &cycle.score {
-                            println!("    Strain: {:.2}", score.strain);
-                        }
-                    }
-                }
-            }
-            Err(e) => eprintln!("Error fetching cycles: {}", e),
-        }
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
+}
 
-    // Example 2: Using OAuth flow (uncomment to use)
-    /*
-    let client_id = env::var("WHOOP_CLIENT_ID").expect("WHOOP_CLIENT_ID not set");
-    let client_secret = env::var("WHOOP_CLIENT_SECRET").expect("WHOOP_CLIENT_SECRET not set");
-    let redirect_uri = env::var("WHOOP_REDIRECT_URI").unwrap_or_else(|_| "http://localhost:8080/callback".to_string());
+async fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Config(command) => return cli::config::run(command),
+        #[cfg(feature = "zip")]
+        Command::Archive(command) => return cli::archive::run(command),
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            clap_complete::generate(shell, &mut command, "whoopsy", &mut std::io::stdout());
+            return Ok(());
+        }
+        _ => {}
+    }
 
-    let config = OAuthConfig::new(client_id, client_secret, redirect_uri)
-        .with_scope(Scope::ReadProfile)
-        .with_scope(Scope::ReadCycles)
-        .with_scope(Scope::ReadSleep)
-        .with_scope(Scope::ReadRecovery)
-        .with_scope(Scope::ReadWorkout);
+    let ctx = Context::new(cli.token, cli.sandbox, Config::load()?).await?;
 
-    // Print authorization URL
-    println!("Visit this URL to authorize: {}", config.get_authorization_url());
+    match cli.command {
+        Command::Profile { units } => print_profile(&ctx, units).await,
+        Command::Export(args) => cli::export::run(&ctx, args).await,
+        Command::Watch(args) => cli::watch::run(&ctx, args).await,
+        Command::Tui(args) => cli::tui::run(&ctx, args).await,
+        Command::Sync(args) => cli::sync::run(&ctx, args).await,
+        Command::Daemon(args) => cli::daemon::run(ctx, args).await,
+        #[cfg(feature = "proxy")]
+        Command::Proxy(args) => cli::proxy::run(&ctx, args).await,
+        Command::Compare(args) => cli::compare::run(&ctx, args).await,
+        Command::Report(command) => cli::report::run(&ctx, command).await,
+        Command::Config(_) | Command::Completions { .. } => unreachable!(),
+        #[cfg(feature = "zip")]
+        Command::Archive(_) => unreachable!(),
+    }
+}
 
-    // After user authorizes, exchange the code for a token
-    // let code = "AUTHORIZATION_CODE_FROM_CALLBACK";
-    // let client = WhoopClient::from_authorization_code(config, code.to_string()).await?;
-    */
+async fn print_profile(ctx: &Context, units: Option<Units>) -> Result<()> {
+    let profile = ctx.client.get_profile_basic().await?;
+    println!("User: {} {}", profile.first_name, profile.last_name);
+    println!("Email: {}", profile.email);
 
+    let body = ctx.client.get_body_measurement().await?;
+    match units.or(ctx.config.units).unwrap_or_default() {
+        Units::Metric => {
+            println!("Height: {:.2} m", body.height_meter);
+            println!("Weight: {:.1} kg", body.weight_kilogram);
+        }
+        Units::Imperial => {
+            let inches = body.height_meter / 0.0254;
+            println!(
+                "Height: {}' {:.0}\"",
+                (inches / 12.0).floor(),
+                inches % 12.0
+            );
+            println!("Weight: {:.1} lb", body.weight_kilogram * 2.204_623);
+        }
+    }
+    println!("Max heart rate: {} bpm", body.max_heart_rate);
     Ok(())
 }
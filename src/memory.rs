@@ -0,0 +1,348 @@
+//! An in-memory [`WhoopApi`] for testing application code offline.
+//!
+//! Collections behave like the real endpoints: newest first, filtered by
+//! `start` (inclusive) and `end` (exclusive), at most 25 records a page with a
+//! `next_token` for the rest. Records can be added, replaced and removed while
+//! the API is in use, e.g. to simulate what a webhook announces.
+
+use crate::api::WhoopApi;
+use crate::error::{Result, WhoopError};
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 25;
+
+#[derive(Default)]
+struct State {
+    cycles: Vec<Cycle>,
+    recoveries: Vec<Recovery>,
+    sleeps: Vec<Sleep>,
+    workouts: Vec<WorkoutV2>,
+    profile: Option<UserBasicProfile>,
+    body: Option<UserBodyMeasurement>,
+}
+
+#[derive(Default)]
+pub struct InMemoryWhoop {
+    state: RwLock<State>,
+}
+
+impl InMemoryWhoop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_cycles(self, cycles: Vec<Cycle>) -> Self {
+        cycles.into_iter().for_each(|c| self.upsert_cycle(c));
+        self
+    }
+
+    pub fn with_recoveries(self, recoveries: Vec<Recovery>) -> Self {
+        recoveries.into_iter().for_each(|r| self.upsert_recovery(r));
+        self
+    }
+
+    pub fn with_sleeps(self, sleeps: Vec<Sleep>) -> Self {
+        sleeps.into_iter().for_each(|s| self.upsert_sleep(s));
+        self
+    }
+
+    pub fn with_workouts(self, workouts: Vec<WorkoutV2>) -> Self {
+        workouts.into_iter().for_each(|w| self.upsert_workout(w));
+        self
+    }
+
+    pub fn with_profile(self, profile: UserBasicProfile) -> Self {
+        self.set_profile(profile);
+        self
+    }
+
+    pub fn with_body_measurement(self, body: UserBodyMeasurement) -> Self {
+        self.set_body_measurement(body);
+        self
+    }
+
+    pub fn set_profile(&self, profile: UserBasicProfile) {
+        self.state.write().unwrap().profile = Some(profile);
+    }
+
+    pub fn set_body_measurement(&self, body: UserBodyMeasurement) {
+        self.state.write().unwrap().body = Some(body);
+    }
+
+    /// Adds the cycle, or replaces the one with the same id.
+    pub fn upsert_cycle(&self, cycle: Cycle) {
+        let cycles = &mut self.state.write().unwrap().cycles;
+        upsert(cycles, cycle, |a, b| a.id == b.id);
+    }
+
+    /// Adds the recovery, or replaces the one for the same cycle.
+    pub fn upsert_recovery(&self, recovery: Recovery) {
+        let recoveries = &mut self.state.write().unwrap().recoveries;
+        upsert(recoveries, recovery, |a, b| a.cycle_id == b.cycle_id);
+    }
+
+    pub fn upsert_sleep(&self, sleep: Sleep) {
+        let sleeps = &mut self.state.write().unwrap().sleeps;
+        upsert(sleeps, sleep, |a, b| a.id == b.id);
+    }
+
+    pub fn upsert_workout(&self, workout: WorkoutV2) {
+        let workouts = &mut self.state.write().unwrap().workouts;
+        upsert(workouts, workout, |a, b| a.id == b.id);
+    }
+
+    pub fn remove_sleep(&self, sleep_id: Uuid) -> Option<Sleep> {
+        let sleeps = &mut self.state.write().unwrap().sleeps;
+        let index = sleeps.iter().position(|s| s.id == sleep_id)?;
+        Some(sleeps.remove(index))
+    }
+
+    pub fn remove_workout(&self, workout_id: Uuid) -> Option<WorkoutV2> {
+        let workouts = &mut self.state.write().unwrap().workouts;
+        let index = workouts.iter().position(|w| w.id == workout_id)?;
+        Some(workouts.remove(index))
+    }
+
+    pub fn remove_recovery(&self, cycle_id: i64) -> Option<Recovery> {
+        let recoveries = &mut self.state.write().unwrap().recoveries;
+        let index = recoveries.iter().position(|r| r.cycle_id == cycle_id)?;
+        Some(recoveries.remove(index))
+    }
+
+    fn find<T: Clone>(&self, records: impl Fn(&State) -> Option<&T>) -> Result<T> {
+        records(&self.state.read().unwrap())
+            .cloned()
+            .ok_or(WhoopError::NotFound(None))
+    }
+}
+
+fn upsert<T>(records: &mut Vec<T>, record: T, same: impl Fn(&T, &T) -> bool) {
+    match records.iter_mut().find(|r| same(r, &record)) {
+        Some(existing) => *existing = record,
+        None => records.push(record),
+    }
+}
+
+/// The query fields every collection endpoint shares.
+struct Query {
+    limit: Option<i32>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    next_token: Option<String>,
+}
+
+macro_rules! impl_query_from {
+    ($($params:ty),*) => {
+        $(impl From<Option<$params>> for Query {
+            fn from(params: Option<$params>) -> Self {
+                match params {
+                    Some(p) => Query {
+                        limit: p.limit,
+                        start: p.start,
+                        end: p.end,
+                        next_token: p.next_token,
+                    },
+                    None => Query {
+                        limit: None,
+                        start: None,
+                        end: None,
+                        next_token: None,
+                    },
+                }
+            }
+        })*
+    };
+}
+
+impl_query_from!(
+    CycleQueryParams,
+    RecoveryQueryParams,
+    SleepQueryParams,
+    WorkoutQueryParams
+);
+
+/// One page of `records`, newest first. The next token is just an offset.
+fn page<T: Clone>(
+    records: &[T],
+    time: impl Fn(&T) -> DateTime<Utc>,
+    query: Query,
+) -> Result<(Option<Vec<T>>, Option<String>)> {
+    let limit = match query.limit {
+        None => DEFAULT_LIMIT,
+        Some(limit @ 1..=25) => limit as usize,
+        Some(limit) => {
+            return Err(WhoopError::BadRequest(
+                format!("limit must be between 1 and {}, got {}", MAX_LIMIT, limit),
+                None,
+            ));
+        }
+    };
+    let offset = match &query.next_token {
+        Some(token) => token
+            .parse::<usize>()
+            .map_err(|_| WhoopError::BadRequest(format!("invalid nextToken {}", token), None))?,
+        None => 0,
+    };
+
+    let mut matching: Vec<&T> = records
+        .iter()
+        .filter(|r| query.start.is_none_or(|start| time(r) >= start))
+        .filter(|r| query.end.is_none_or(|end| time(r) < end))
+        .collect();
+    matching.sort_by_key(|r| std::cmp::Reverse(time(r)));
+
+    let page: Vec<T> = matching
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|r| (*r).clone())
+        .collect();
+    let next_token = (offset + limit < matching.len()).then(|| (offset + limit).to_string());
+    Ok((Some(page), next_token))
+}
+
+impl WhoopApi for InMemoryWhoop {
+    async fn get_cycle_by_id(&self, cycle_id: i64) -> Result<Cycle> {
+        self.find(|s| s.cycles.iter().find(|c| c.id == cycle_id))
+    }
+
+    async fn get_cycle_collection(
+        &self,
+        params: Option<CycleQueryParams>,
+    ) -> Result<PaginatedCycleResponse> {
+        let state = self.state.read().unwrap();
+        let (records, next_token) = page(&state.cycles, |c| c.start, params.into())?;
+        Ok(PaginatedCycleResponse {
+            records,
+            next_token,
+        })
+    }
+
+    async fn get_sleep_for_cycle(&self, cycle_id: i64) -> Result<Sleep> {
+        self.find(|s| s.sleeps.iter().find(|s| s.cycle_id == cycle_id && !s.nap))
+    }
+
+    async fn get_recovery_for_cycle(&self, cycle_id: i64) -> Result<Recovery> {
+        self.find(|s| s.recoveries.iter().find(|r| r.cycle_id == cycle_id))
+    }
+
+    async fn get_recovery_collection(
+        &self,
+        params: Option<RecoveryQueryParams>,
+    ) -> Result<RecoveryCollection> {
+        let state = self.state.read().unwrap();
+        // Recoveries are filtered by their cycle's start, like the API does.
+        let time = |r: &Recovery| {
+            state
+                .cycles
+                .iter()
+                .find(|c| c.id == r.cycle_id)
+                .map_or(r.created_at, |c| c.start)
+        };
+        let (records, next_token) = page(&state.recoveries, time, params.into())?;
+        Ok(RecoveryCollection {
+            records,
+            next_token,
+        })
+    }
+
+    async fn get_sleep_by_id(&self, sleep_id: Uuid) -> Result<Sleep> {
+        self.find(|s| s.sleeps.iter().find(|s| s.id == sleep_id))
+    }
+
+    async fn get_sleep_collection(
+        &self,
+        params: Option<SleepQueryParams>,
+    ) -> Result<PaginatedSleepResponse> {
+        let state = self.state.read().unwrap();
+        let (records, next_token) = page(&state.sleeps, |s| s.start, params.into())?;
+        Ok(PaginatedSleepResponse {
+            records,
+            next_token,
+        })
+    }
+
+    async fn get_body_measurement(&self) -> Result<UserBodyMeasurement> {
+        self.find(|s| s.body.as_ref())
+    }
+
+    async fn get_profile_basic(&self) -> Result<UserBasicProfile> {
+        self.find(|s| s.profile.as_ref())
+    }
+
+    async fn revoke_oauth_access(&self) -> Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "workouts")]
+    async fn get_workout_by_id(&self, workout_id: Uuid) -> Result<WorkoutV2> {
+        self.find(|s| s.workouts.iter().find(|w| w.id == workout_id))
+    }
+
+    #[cfg(feature = "workouts")]
+    async fn get_workout_collection(
+        &self,
+        params: Option<WorkoutQueryParams>,
+    ) -> Result<WorkoutCollection> {
+        let state = self.state.read().unwrap();
+        let (records, next_token) = page(&state.workouts, |w| w.start, params.into())?;
+        Ok(WorkoutCollection {
+            records,
+            next_token,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use chrono::Duration;
+
+    fn cycle(id: i64, start: DateTime<Utc>) -> Cycle {
+        Cycle {
+            id,
+            start,
+            ..fixtures::cycle_pending()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pages_newest_first_and_sees_upserts() {
+        let first: DateTime<Utc> = "2024-03-01T06:00:00Z".parse().unwrap();
+        let api = InMemoryWhoop::new().with_cycles(
+            (0..30)
+                .map(|i| cycle(i, first + Duration::days(i)))
+                .collect(),
+        );
+
+        let params = |next_token| CycleQueryParams {
+            limit: Some(25),
+            start: Some(first + Duration::days(2)),
+            end: None,
+            next_token,
+        };
+        let page = api.get_cycle_collection(Some(params(None))).await.unwrap();
+        assert_eq!(page.records.as_ref().unwrap()[0].id, 29);
+        let rest = api
+            .get_cycle_collection(Some(params(page.next_token)))
+            .await
+            .unwrap();
+        assert_eq!(rest.records.unwrap().len(), 3);
+        assert!(rest.next_token.is_none());
+
+        let mut scored = cycle(29, first + Duration::days(29));
+        scored.score_state = ScoreState::Scored;
+        api.upsert_cycle(scored);
+        let cycle = api.get_cycle_by_id(29).await.unwrap();
+        assert!(matches!(cycle.score_state, ScoreState::Scored));
+        assert!(matches!(
+            api.get_cycle_by_id(99).await,
+            Err(WhoopError::NotFound(_))
+        ));
+    }
+}
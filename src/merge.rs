@@ -0,0 +1,179 @@
+//! How a fetched record is written over the copy already stored.
+//!
+//! The same record reaches a store many ways: pages that overlap between
+//! syncs, a backfill running over a range synced before, a record fetched on
+//! its own after a webhook, an archive imported. All of them go through the
+//! store's upserts, which resolve every record against the stored copy with
+//! the same rule: records are keyed by their id, and the copy with the newer
+//! `updated_at` wins. A copy as new as the stored one replaces it, so writing
+//! the same batch twice changes nothing, and an older copy is dropped instead
+//! of overwriting a newer one.
+
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+/// A record with an identity across fetches and a last-changed time.
+pub trait Versioned: Clone {
+    fn key(&self) -> String;
+    fn updated_at(&self) -> DateTime<Utc>;
+}
+
+impl Versioned for Cycle {
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl Versioned for Sleep {
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl Versioned for Recovery {
+    fn key(&self) -> String {
+        self.cycle_id.to_string()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl Versioned for WorkoutV2 {
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+/// What writing one record did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing was stored under its key.
+    Inserted,
+    /// It replaced an older copy.
+    Updated,
+    /// It replaced a copy with the same `updated_at`.
+    Unchanged,
+    /// The stored copy is newer, so it was dropped.
+    Stale,
+}
+
+impl Outcome {
+    /// Whether the incoming copy gets written.
+    pub fn writes(self) -> bool {
+        self != Outcome::Stale
+    }
+}
+
+/// Resolves an incoming copy against the stored one's `updated_at`.
+pub fn resolve(stored: Option<DateTime<Utc>>, incoming: DateTime<Utc>) -> Outcome {
+    match stored {
+        None => Outcome::Inserted,
+        Some(stored) if incoming > stored => Outcome::Updated,
+        Some(stored) if incoming == stored => Outcome::Unchanged,
+        Some(_) => Outcome::Stale,
+    }
+}
+
+/// How many records of a write came out which way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Merged {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub stale: usize,
+}
+
+impl Merged {
+    pub fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Inserted => self.inserted += 1,
+            Outcome::Updated => self.updated += 1,
+            Outcome::Unchanged => self.unchanged += 1,
+            Outcome::Stale => self.stale += 1,
+        }
+    }
+
+    /// Records that were new, or newer than the stored copy.
+    pub fn changed(&self) -> usize {
+        self.inserted + self.updated
+    }
+}
+
+impl AddAssign for Merged {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+        self.stale += other.stale;
+    }
+}
+
+/// The newest copy of every record in `records`, in the order each first
+/// appears. Of copies with the same `updated_at`, the last one wins, as it
+/// would writing them one by one.
+pub fn newest<T: Versioned>(records: &[T]) -> Vec<&T> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut newest: Vec<&T> = Vec::new();
+    for record in records {
+        match index.get(&record.key()) {
+            Some(&i) => {
+                if record.updated_at() >= newest[i].updated_at() {
+                    newest[i] = record;
+                }
+            }
+            None => {
+                index.insert(record.key(), newest.len());
+                newest.push(record);
+            }
+        }
+    }
+    newest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use chrono::Duration;
+
+    #[test]
+    fn test_newer_copies_win() {
+        let now = Utc::now();
+        assert_eq!(resolve(None, now), Outcome::Inserted);
+        assert_eq!(
+            resolve(Some(now), now + Duration::seconds(1)),
+            Outcome::Updated
+        );
+        assert_eq!(resolve(Some(now), now), Outcome::Unchanged);
+        assert_eq!(
+            resolve(Some(now), now - Duration::seconds(1)),
+            Outcome::Stale
+        );
+        assert!(!Outcome::Stale.writes());
+
+        let old = fixtures::cycle_pending();
+        let mut new = old.clone();
+        new.updated_at += Duration::hours(1);
+        let other = fixtures::cycle_scored();
+        let batch = [old.clone(), other, new.clone(), old];
+        let kept = newest(&batch);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].updated_at, new.updated_at);
+    }
+}
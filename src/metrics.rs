@@ -0,0 +1,272 @@
+//! Prometheus exporter for the latest physiology values.
+//!
+//! [`MetricsExporter`] polls each registered user's newest cycle, recovery and sleep
+//! on an interval and serves them on `/metrics` as gauges labeled by `user`. Values
+//! the API hasn't scored yet are left out rather than reported as zero, and a failed
+//! poll keeps the previous values while `whoop_up` drops to 0.
+
+use crate::client::WhoopClient;
+use crate::error::Result;
+use crate::models::*;
+use crate::server::{self, Body};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The latest values for one user.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub recovery_score: Option<f32>,
+    pub hrv_rmssd_milli: Option<f32>,
+    pub resting_heart_rate: Option<f32>,
+    pub spo2_percentage: Option<f32>,
+    pub skin_temp_celsius: Option<f32>,
+    pub strain: Option<f32>,
+    pub kilojoule: Option<f32>,
+    pub sleep_performance_percentage: Option<f32>,
+    pub sleep_milli: Option<i64>,
+    pub respiratory_rate: Option<f32>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Snapshot {
+    /// Fetches the newest cycle, recovery and (non-nap) sleep for a client.
+    pub async fn fetch(client: &WhoopClient) -> Result<Self> {
+        let cycle = client
+            .get_cycle_collection(Some(CycleQueryParams {
+                limit: Some(1),
+                start: None,
+                end: None,
+                next_token: None,
+            }))
+            .await?
+            .records
+            .and_then(|r| r.into_iter().next());
+        let recovery = client
+            .get_recovery_collection(Some(RecoveryQueryParams {
+                limit: Some(1),
+                start: None,
+                end: None,
+                next_token: None,
+            }))
+            .await?
+            .records
+            .and_then(|r| r.into_iter().next());
+        let sleep = client
+            .get_sleep_collection(Some(SleepQueryParams {
+                limit: Some(5),
+                start: None,
+                end: None,
+                next_token: None,
+            }))
+            .await?
+            .records
+            .and_then(|r| r.into_iter().find(|s| !s.nap));
+
+        let cycle = cycle.and_then(|c| c.score);
+        let recovery = recovery.and_then(|r| r.score);
+        let sleep = sleep.and_then(|s| s.score);
+        Ok(Self {
+            recovery_score: recovery.as_ref().map(|r| r.recovery_score),
+            hrv_rmssd_milli: recovery.as_ref().map(|r| r.hrv_rmssd_milli),
+            resting_heart_rate: recovery.as_ref().map(|r| r.resting_heart_rate),
+            spo2_percentage: recovery.as_ref().and_then(|r| r.spo2_percentage),
+            skin_temp_celsius: recovery.as_ref().and_then(|r| r.skin_temp_celsius),
+            strain: cycle.as_ref().map(|c| c.strain),
+            kilojoule: cycle.as_ref().map(|c| c.kilojoule),
+            sleep_performance_percentage: sleep
+                .as_ref()
+                .and_then(|s| s.sleep_performance_percentage),
+            sleep_milli: sleep
+                .as_ref()
+                .map(|s| s.stage_summary.total_sleep_time_milli()),
+            respiratory_rate: sleep.as_ref().and_then(|s| s.respiratory_rate),
+            updated_at: Some(Utc::now()),
+        })
+    }
+}
+
+#[derive(Default)]
+struct UserState {
+    snapshot: Snapshot,
+    up: bool,
+}
+
+type Gauge = (&'static str, &'static str, fn(&Snapshot) -> Option<f64>);
+
+const GAUGES: &[Gauge] = &[
+    (
+        "whoop_recovery_score",
+        "Latest recovery score (0-100).",
+        |s| s.recovery_score.map(f64::from),
+    ),
+    (
+        "whoop_hrv_rmssd_milliseconds",
+        "Latest HRV (RMSSD) in milliseconds.",
+        |s| s.hrv_rmssd_milli.map(f64::from),
+    ),
+    (
+        "whoop_resting_heart_rate_bpm",
+        "Latest resting heart rate.",
+        |s| s.resting_heart_rate.map(f64::from),
+    ),
+    (
+        "whoop_spo2_percent",
+        "Latest blood oxygen percentage.",
+        |s| s.spo2_percentage.map(f64::from),
+    ),
+    ("whoop_skin_temp_celsius", "Latest skin temperature.", |s| {
+        s.skin_temp_celsius.map(f64::from)
+    }),
+    ("whoop_strain", "Strain of the current cycle so far.", |s| {
+        s.strain.map(f64::from)
+    }),
+    (
+        "whoop_kilojoules",
+        "Energy expended in the current cycle.",
+        |s| s.kilojoule.map(f64::from),
+    ),
+    (
+        "whoop_sleep_performance_percent",
+        "Latest sleep performance.",
+        |s| s.sleep_performance_percentage.map(f64::from),
+    ),
+    (
+        "whoop_sleep_seconds",
+        "Time asleep in the latest sleep.",
+        |s| s.sleep_milli.map(|m| m as f64 / 1000.0),
+    ),
+    (
+        "whoop_respiratory_rate",
+        "Breaths per minute in the latest sleep.",
+        |s| s.respiratory_rate.map(f64::from),
+    ),
+    (
+        "whoop_last_update_timestamp_seconds",
+        "When the values were last fetched.",
+        |s| s.updated_at.map(|t| t.timestamp() as f64),
+    ),
+];
+
+pub struct MetricsExporter {
+    users: Vec<(String, WhoopClient)>,
+    interval: Duration,
+    state: Arc<RwLock<BTreeMap<String, UserState>>>,
+}
+
+impl MetricsExporter {
+    /// Creates an exporter that refreshes every `interval`.
+    /// WHOOP scores land every few minutes at best, so anything under 5 minutes wastes quota.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            users: Vec::new(),
+            interval,
+            state: Arc::default(),
+        }
+    }
+
+    /// Registers a client whose values are exported under `user="<label>"`.
+    pub fn with_user(mut self, label: impl Into<String>, client: WhoopClient) -> Self {
+        self.users.push((label.into(), client));
+        self
+    }
+
+    /// Polls every user once.
+    pub async fn refresh(&self) {
+        for (label, client) in &self.users {
+            let result = Snapshot::fetch(client).await;
+            let mut state = self.state.write().unwrap();
+            let user = state.entry(label.clone()).or_default();
+            user.up = result.is_ok();
+            if let Ok(snapshot) = result {
+                user.snapshot = snapshot;
+            }
+        }
+    }
+
+    /// Renders all gauges in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        render(&self.state.read().unwrap())
+    }
+
+    /// Refreshes in the background and serves `/metrics` on `addr` until the listener fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let state = self.state.clone();
+        let exporter = Arc::new(self);
+        tokio::spawn({
+            let exporter = exporter.clone();
+            async move {
+                let mut ticks = tokio::time::interval(exporter.interval);
+                loop {
+                    ticks.tick().await;
+                    exporter.refresh().await;
+                }
+            }
+        });
+
+        server::serve(addr, move |path| {
+            (path == "/metrics").then(|| Body {
+                content_type: "text/plain; version=0.0.4",
+                content: render(&state.read().unwrap()),
+            })
+        })
+        .await
+    }
+}
+
+fn render(state: &BTreeMap<String, UserState>) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP whoop_up Whether the last poll succeeded.");
+    let _ = writeln!(out, "# TYPE whoop_up gauge");
+    for (user, s) in state {
+        let _ = writeln!(out, "whoop_up{{user=\"{}\"}} {}", escape(user), s.up as u8);
+    }
+
+    for (name, help, value) in GAUGES {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (user, s) in state {
+            if let Some(value) = value(&s.snapshot) {
+                let _ = writeln!(out, "{}{{user=\"{}\"}} {}", name, escape(user), value);
+            }
+        }
+    }
+
+    out
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_skips_missing_values() {
+        let mut state = BTreeMap::new();
+        state.insert(
+            "jo \"j\"".to_string(),
+            UserState {
+                snapshot: Snapshot {
+                    recovery_score: Some(64.0),
+                    ..Snapshot::default()
+                },
+                up: true,
+            },
+        );
+
+        let text = render(&state);
+        assert!(text.contains("whoop_up{user=\"jo \\\"j\\\"\"} 1\n"));
+        assert!(text.contains("whoop_recovery_score{user=\"jo \\\"j\\\"\"} 64\n"));
+        assert!(!text.contains("whoop_strain{"));
+    }
+}
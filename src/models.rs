@@ -1,8 +1,11 @@
+pub mod borrowed;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Cycle {
     pub id: i64,
     pub user_id: i64,
@@ -17,7 +20,8 @@ pub struct Cycle {
     pub score: Option<CycleScore>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ScoreState {
     Scored,
@@ -25,7 +29,19 @@ pub enum ScoreState {
     Unscorable,
 }
 
+impl ScoreState {
+    /// The state as the API spells it, e.g. `PENDING_SCORE`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreState::Scored => "SCORED",
+            ScoreState::PendingScore => "PENDING_SCORE",
+            ScoreState::Unscorable => "UNSCORABLE",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct CycleScore {
     pub strain: f32,
     pub kilojoule: f32,
@@ -42,6 +58,7 @@ pub struct PaginatedCycleResponse {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Sleep {
     pub id: Uuid,
     pub cycle_id: i64,
@@ -60,6 +77,7 @@ pub struct Sleep {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct SleepScore {
     pub stage_summary: SleepStageSummary,
     pub sleep_needed: SleepNeeded,
@@ -74,6 +92,7 @@ pub struct SleepScore {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct SleepStageSummary {
     pub total_in_bed_time_milli: i32,
     pub total_awake_time_milli: i32,
@@ -85,7 +104,17 @@ pub struct SleepStageSummary {
     pub disturbance_count: i32,
 }
 
+impl SleepStageSummary {
+    /// Time actually asleep: light, slow wave and REM sleep combined.
+    pub fn total_sleep_time_milli(&self) -> i64 {
+        self.total_light_sleep_time_milli as i64
+            + self.total_slow_wave_sleep_time_milli as i64
+            + self.total_rem_sleep_time_milli as i64
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct SleepNeeded {
     pub baseline_milli: i64,
     pub need_from_sleep_debt_milli: i64,
@@ -93,6 +122,17 @@ pub struct SleepNeeded {
     pub need_from_recent_nap_milli: i64,
 }
 
+impl SleepNeeded {
+    /// Total sleep needed for the night.
+    /// Recent naps are reported as a negative adjustment, so they're just added in.
+    pub fn total_milli(&self) -> i64 {
+        self.baseline_milli
+            + self.need_from_sleep_debt_milli
+            + self.need_from_recent_strain_milli
+            + self.need_from_recent_nap_milli
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaginatedSleepResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -102,6 +142,7 @@ pub struct PaginatedSleepResponse {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Recovery {
     pub cycle_id: i64,
     pub sleep_id: Uuid,
@@ -114,6 +155,7 @@ pub struct Recovery {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct RecoveryScore {
     pub user_calibrating: bool,
     pub recovery_score: f32,
@@ -133,7 +175,7 @@ pub struct RecoveryCollection {
     pub next_token: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserBodyMeasurement {
     pub height_meter: f32,
     pub weight_kilogram: f32,
@@ -149,6 +191,7 @@ pub struct UserBasicProfile {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct WorkoutV2 {
     pub id: Uuid,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -168,6 +211,7 @@ pub struct WorkoutV2 {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct WorkoutScore {
     pub strain: f32,
     pub average_heart_rate: i32,
@@ -183,7 +227,8 @@ pub struct WorkoutScore {
     pub zone_durations: ZoneDurations,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct ZoneDurations {
     pub zone_zero_milli: i64,
     pub zone_one_milli: i64,
@@ -193,6 +238,34 @@ pub struct ZoneDurations {
     pub zone_five_milli: i64,
 }
 
+impl ZoneDurations {
+    /// Durations per zone, from zone zero up to zone five.
+    pub fn as_array(&self) -> [i64; 6] {
+        [
+            self.zone_zero_milli,
+            self.zone_one_milli,
+            self.zone_two_milli,
+            self.zone_three_milli,
+            self.zone_four_milli,
+            self.zone_five_milli,
+        ]
+    }
+
+    pub fn total_milli(&self) -> i64 {
+        self.as_array().iter().sum()
+    }
+
+    /// Adds another workout's zone durations onto this one.
+    pub fn add(&mut self, other: &ZoneDurations) {
+        self.zone_zero_milli += other.zone_zero_milli;
+        self.zone_one_milli += other.zone_one_milli;
+        self.zone_two_milli += other.zone_two_milli;
+        self.zone_three_milli += other.zone_three_milli;
+        self.zone_four_milli += other.zone_four_milli;
+        self.zone_five_milli += other.zone_five_milli;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkoutCollection {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,216 @@
+//! Records borrowing their strings from the JSON they were parsed from.
+//!
+//! Pipelines that turn records into something else straight away, like a CSV
+//! export, don't need to own them. Parsing a page into [`Page`]`<`[`CycleRef`]`>`
+//! with `serde_json::from_slice` points `timezone_offset` and friends into the
+//! input instead of allocating a `String` per field; only strings containing
+//! escapes are copied. Recoveries hold no strings, so [`Recovery`] serves as is.
+//!
+//! ```
+//! use whoopsy::models::borrowed::{CycleRef, Page};
+//!
+//! let page: Page<CycleRef> = serde_json::from_str(whoopsy::fixtures::CYCLE_COLLECTION)?;
+//! assert_eq!(page.records[0].timezone_offset, "-05:00");
+//! # Ok::<(), serde_json::Error>(())
+//! ```
+
+#[cfg(doc)]
+use super::Recovery;
+use super::{Cycle, CycleScore, ScoreState, Sleep, SleepScore, WorkoutScore, WorkoutV2};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+/// One page of any collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<'a, T> {
+    #[serde(default = "Vec::new")]
+    pub records: Vec<T>,
+    #[serde(borrow, skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CycleRef<'a> {
+    pub id: i64,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+    #[serde(borrow)]
+    pub timezone_offset: Cow<'a, str>,
+    pub score_state: ScoreState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<CycleScore>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SleepRef<'a> {
+    pub id: Uuid,
+    pub cycle_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v1_id: Option<i64>,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(borrow)]
+    pub timezone_offset: Cow<'a, str>,
+    pub nap: bool,
+    pub score_state: ScoreState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<SleepScore>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkoutRef<'a> {
+    pub id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v1_id: Option<i64>,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(borrow)]
+    pub timezone_offset: Cow<'a, str>,
+    #[serde(borrow)]
+    pub sport_name: Cow<'a, str>,
+    pub score_state: ScoreState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<WorkoutScore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sport_id: Option<i32>,
+}
+
+impl CycleRef<'_> {
+    pub fn into_owned(self) -> Cycle {
+        Cycle {
+            id: self.id,
+            user_id: self.user_id,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            start: self.start,
+            end: self.end,
+            timezone_offset: self.timezone_offset.into_owned(),
+            score_state: self.score_state,
+            score: self.score,
+        }
+    }
+}
+
+impl<'a> From<&'a Cycle> for CycleRef<'a> {
+    fn from(cycle: &'a Cycle) -> Self {
+        Self {
+            id: cycle.id,
+            user_id: cycle.user_id,
+            created_at: cycle.created_at,
+            updated_at: cycle.updated_at,
+            start: cycle.start,
+            end: cycle.end,
+            timezone_offset: Cow::Borrowed(&cycle.timezone_offset),
+            score_state: cycle.score_state,
+            score: cycle.score.clone(),
+        }
+    }
+}
+
+impl SleepRef<'_> {
+    pub fn into_owned(self) -> Sleep {
+        Sleep {
+            id: self.id,
+            cycle_id: self.cycle_id,
+            v1_id: self.v1_id,
+            user_id: self.user_id,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            start: self.start,
+            end: self.end,
+            timezone_offset: self.timezone_offset.into_owned(),
+            nap: self.nap,
+            score_state: self.score_state,
+            score: self.score,
+        }
+    }
+}
+
+impl<'a> From<&'a Sleep> for SleepRef<'a> {
+    fn from(sleep: &'a Sleep) -> Self {
+        Self {
+            id: sleep.id,
+            cycle_id: sleep.cycle_id,
+            v1_id: sleep.v1_id,
+            user_id: sleep.user_id,
+            created_at: sleep.created_at,
+            updated_at: sleep.updated_at,
+            start: sleep.start,
+            end: sleep.end,
+            timezone_offset: Cow::Borrowed(&sleep.timezone_offset),
+            nap: sleep.nap,
+            score_state: sleep.score_state,
+            score: sleep.score.clone(),
+        }
+    }
+}
+
+impl WorkoutRef<'_> {
+    pub fn into_owned(self) -> WorkoutV2 {
+        WorkoutV2 {
+            id: self.id,
+            v1_id: self.v1_id,
+            user_id: self.user_id,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            start: self.start,
+            end: self.end,
+            timezone_offset: self.timezone_offset.into_owned(),
+            sport_name: self.sport_name.into_owned(),
+            score_state: self.score_state,
+            score: self.score,
+            sport_id: self.sport_id,
+        }
+    }
+}
+
+impl<'a> From<&'a WorkoutV2> for WorkoutRef<'a> {
+    fn from(workout: &'a WorkoutV2) -> Self {
+        Self {
+            id: workout.id,
+            v1_id: workout.v1_id,
+            user_id: workout.user_id,
+            created_at: workout.created_at,
+            updated_at: workout.updated_at,
+            start: workout.start,
+            end: workout.end,
+            timezone_offset: Cow::Borrowed(&workout.timezone_offset),
+            sport_name: Cow::Borrowed(&workout.sport_name),
+            score_state: workout.score_state,
+            score: workout.score.clone(),
+            sport_id: workout.sport_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_borrows_strings_from_the_input() {
+        let page: Page<WorkoutRef> = serde_json::from_str(fixtures::WORKOUT_COLLECTION).unwrap();
+        let workout = &page.records[0];
+        assert!(matches!(workout.sport_name, Cow::Borrowed(_)));
+        assert!(matches!(workout.timezone_offset, Cow::Borrowed(_)));
+
+        let owned = fixtures::workout_collection().records.unwrap();
+        assert_eq!(
+            serde_json::to_value(page.records[0].clone().into_owned()).unwrap(),
+            serde_json::to_value(&owned[0]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! MQTT publisher for recovery, strain and sleep updates.
+//!
+//! Each update is published as the record's JSON on its own topic. Call the
+//! `publish_*` methods when you learn about a record yourself (e.g. from a webhook),
+//! or let [`MqttPublisher::watch`] poll the API and publish whatever is new or
+//! re-scored since the last poll.
+
+use crate::client::WhoopClient;
+use crate::error::Result;
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use rumqttc::AsyncClient;
+use serde::Serialize;
+use std::time::Duration;
+
+pub use rumqttc::{MqttOptions, QoS};
+
+/// Topics each kind of update is published on.
+#[derive(Debug, Clone)]
+pub struct Topics {
+    pub recovery: String,
+    /// Cycles, which carry the day's strain.
+    pub strain: String,
+    pub sleep: String,
+}
+
+impl Topics {
+    /// `<prefix>/recovery`, `<prefix>/strain` and `<prefix>/sleep`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            recovery: format!("{}/recovery", prefix),
+            strain: format!("{}/strain", prefix),
+            sleep: format!("{}/sleep", prefix),
+        }
+    }
+}
+
+impl Default for Topics {
+    fn default() -> Self {
+        Self::with_prefix("whoop")
+    }
+}
+
+pub struct MqttPublisher {
+    client: AsyncClient,
+    topics: Topics,
+    qos: QoS,
+    retain: bool,
+}
+
+impl MqttPublisher {
+    /// Connects to the broker and drives the connection on a background task.
+    /// Defaults to QoS 1 with retained messages, so new subscribers get the latest value.
+    pub fn connect(options: MqttOptions) -> Self {
+        let (client, mut eventloop) = AsyncClient::new(options, 16);
+        tokio::spawn(async move {
+            loop {
+                if eventloop.poll().await.is_err() {
+                    // The next poll reconnects; back off so a dead broker isn't hammered.
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        });
+
+        Self::with_client(client)
+    }
+
+    fn with_client(client: AsyncClient) -> Self {
+        Self {
+            client,
+            topics: Topics::default(),
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }
+    }
+
+    pub fn with_topics(mut self, topics: Topics) -> Self {
+        self.topics = topics;
+        self
+    }
+
+    pub fn with_qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    pub fn with_retain(mut self, retain: bool) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    pub async fn publish_recovery(&self, recovery: &Recovery) -> Result<()> {
+        self.publish(&self.topics.recovery, recovery).await
+    }
+
+    pub async fn publish_cycle(&self, cycle: &Cycle) -> Result<()> {
+        self.publish(&self.topics.strain, cycle).await
+    }
+
+    pub async fn publish_sleep(&self, sleep: &Sleep) -> Result<()> {
+        self.publish(&self.topics.sleep, sleep).await
+    }
+
+    async fn publish<T: Serialize>(&self, topic: &str, record: &T) -> Result<()> {
+        let payload = serde_json::to_vec(record)?;
+        self.client
+            .publish(topic, self.qos, self.retain, payload)
+            .await?;
+        Ok(())
+    }
+
+    /// Polls the newest cycle, recovery and sleep every `interval` and publishes
+    /// those that changed. API errors skip a poll; publish errors end the loop.
+    pub async fn watch(&self, client: &WhoopClient, interval: Duration) -> Result<()> {
+        let mut seen = Seen::default();
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+
+            if let Ok(Some(cycle)) = latest_cycle(client).await {
+                let key = (cycle.id, cycle.updated_at);
+                if seen.cycle.replace(key) != Some(key) {
+                    self.publish_cycle(&cycle).await?;
+                }
+            }
+            if let Ok(Some(recovery)) = latest_recovery(client).await {
+                let key = (recovery.cycle_id, recovery.updated_at);
+                if seen.recovery.replace(key) != Some(key) {
+                    self.publish_recovery(&recovery).await?;
+                }
+            }
+            if let Ok(Some(sleep)) = latest_sleep(client).await {
+                let key = (sleep.id, sleep.updated_at);
+                if seen.sleep.replace(key) != Some(key) {
+                    self.publish_sleep(&sleep).await?;
+                }
+            }
+        }
+    }
+}
+
+/// The id and update time of the last record published per topic.
+#[derive(Default)]
+struct Seen {
+    cycle: Option<(i64, DateTime<Utc>)>,
+    recovery: Option<(i64, DateTime<Utc>)>,
+    sleep: Option<(uuid::Uuid, DateTime<Utc>)>,
+}
+
+async fn latest_cycle(client: &WhoopClient) -> Result<Option<Cycle>> {
+    let page = client
+        .get_cycle_collection(Some(CycleQueryParams {
+            limit: Some(1),
+            start: None,
+            end: None,
+            next_token: None,
+        }))
+        .await?;
+    Ok(page.records.and_then(|r| r.into_iter().next()))
+}
+
+async fn latest_recovery(client: &WhoopClient) -> Result<Option<Recovery>> {
+    let page = client
+        .get_recovery_collection(Some(RecoveryQueryParams {
+            limit: Some(1),
+            start: None,
+            end: None,
+            next_token: None,
+        }))
+        .await?;
+    Ok(page.records.and_then(|r| r.into_iter().next()))
+}
+
+async fn latest_sleep(client: &WhoopClient) -> Result<Option<Sleep>> {
+    let page = client
+        .get_sleep_collection(Some(SleepQueryParams {
+            limit: Some(1),
+            start: None,
+            end: None,
+            next_token: None,
+        }))
+        .await?;
+    Ok(page.records.and_then(|r| r.into_iter().next()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use rumqttc::Request;
+
+    #[tokio::test]
+    async fn test_publishes_records_as_json_on_their_topics() {
+        let (requests, received) = flume::unbounded();
+        let publisher = MqttPublisher::with_client(AsyncClient::from_senders(requests))
+            .with_topics(Topics::with_prefix("home/whoop"))
+            .with_qos(QoS::AtMostOnce)
+            .with_retain(false);
+        let recovery = fixtures::recovery_scored();
+        publisher.publish_recovery(&recovery).await.unwrap();
+        publisher
+            .publish_cycle(&fixtures::cycle_scored())
+            .await
+            .unwrap();
+        publisher
+            .publish_sleep(&fixtures::sleep_scored())
+            .await
+            .unwrap();
+
+        let published: Vec<_> = received
+            .drain()
+            .map(|request| match request {
+                Request::Publish(publish) => publish,
+                other => panic!("expected a publish, got {:?}", other),
+            })
+            .collect();
+        let topics: Vec<_> = published.iter().map(|p| p.topic.as_str()).collect();
+        assert_eq!(
+            topics,
+            [
+                "home/whoop/recovery",
+                "home/whoop/strain",
+                "home/whoop/sleep"
+            ]
+        );
+        assert!(
+            published
+                .iter()
+                .all(|p| p.qos == QoS::AtMostOnce && !p.retain)
+        );
+        let payload: Recovery = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(
+            (payload.cycle_id, payload.updated_at),
+            (recovery.cycle_id, recovery.updated_at)
+        );
+    }
+}
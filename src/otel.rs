@@ -0,0 +1,174 @@
+//! OpenTelemetry spans for API calls, with the `otel` feature.
+//!
+//! Every request a client sends and every token exchange or refresh gets a
+//! client span from the global tracer provider, named after its method and
+//! carrying the semantic convention HTTP attributes. Spans are children of the
+//! [`Context`] current when the request goes out, and the span's context is
+//! sent along in the headers through the global propagator, so WHOOP calls sit
+//! in the caller's trace:
+//!
+//! ```no_run
+//! # async fn run(client: whoopsy::WhoopClient) -> whoopsy::Result<()> {
+//! use opentelemetry::Context;
+//! use opentelemetry::trace::FutureExt;
+//!
+//! // Inside a span of the service's own, e.g. handling a request.
+//! let cx = Context::current();
+//! let profile = client.get_profile_basic().with_context(cx).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Retries of a rate limited request are marked with `http.request.resend_count`;
+//! wrap a retry in [`resend`] to mark it too.
+
+use opentelemetry::propagation::Injector;
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer, WithContext};
+use opentelemetry::{Context, KeyValue, global};
+use opentelemetry_semantic_conventions::trace as semconv;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Request, Response};
+
+const TRACER: &str = "whoopsy";
+
+/// Which retry of a request the current context is in.
+#[derive(Debug, Clone, Copy)]
+struct ResendCount(u32);
+
+/// Runs `future` as retry number `attempt`, so its requests' spans say they
+/// were resent. Attempt 0 is the first try and isn't marked.
+pub fn resend<F: Future>(future: F, attempt: u32) -> WithContext<F> {
+    future.with_context(Context::current().with_value(ResendCount(attempt)))
+}
+
+/// Sends `request` inside a client span.
+pub(crate) async fn execute(client: &Client, mut request: Request) -> reqwest::Result<Response> {
+    let parent = Context::current();
+    let url = request.url();
+    let mut attributes = vec![
+        KeyValue::new(semconv::HTTP_REQUEST_METHOD, request.method().to_string()),
+        KeyValue::new(semconv::URL_FULL, url.to_string()),
+    ];
+    if let Some(host) = url.host_str() {
+        attributes.push(KeyValue::new(semconv::SERVER_ADDRESS, host.to_string()));
+    }
+    if let Some(port) = url.port_or_known_default() {
+        attributes.push(KeyValue::new(semconv::SERVER_PORT, i64::from(port)));
+    }
+    if let Some(ResendCount(attempt)) = parent.get::<ResendCount>().filter(|c| c.0 > 0) {
+        attributes.push(KeyValue::new(
+            semconv::HTTP_REQUEST_RESEND_COUNT,
+            i64::from(*attempt),
+        ));
+    }
+
+    let tracer = global::tracer(TRACER);
+    let span = tracer
+        .span_builder(request.method().to_string())
+        .with_kind(SpanKind::Client)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent);
+    let cx = parent.with_span(span);
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(request.headers_mut()))
+    });
+
+    let response = client.execute(request).with_context(cx.clone()).await;
+    let span = cx.span();
+    match &response {
+        Ok(response) => {
+            let status = response.status();
+            span.set_attribute(KeyValue::new(
+                semconv::HTTP_RESPONSE_STATUS_CODE,
+                i64::from(status.as_u16()),
+            ));
+            if status.is_client_error() || status.is_server_error() {
+                span.set_attribute(KeyValue::new(
+                    semconv::ERROR_TYPE,
+                    status.as_str().to_string(),
+                ));
+                span.set_status(Status::error(""));
+            }
+        }
+        Err(error) => {
+            let kind = if error.is_timeout() {
+                "timeout"
+            } else if error.is_connect() {
+                "connect"
+            } else {
+                "_OTHER"
+            };
+            span.set_attribute(KeyValue::new(semconv::ERROR_TYPE, kind));
+            span.set_status(Status::error(error.to_string()));
+        }
+    }
+    span.end();
+    response
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::test_support::MockWhoop;
+    use opentelemetry::propagation::{Extractor, TextMapPropagator};
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+    /// Sends the trace id alone, enough to see it arrive.
+    #[derive(Debug)]
+    struct TraceIdPropagator;
+
+    impl TextMapPropagator for TraceIdPropagator {
+        fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+            let trace_id = cx.span().span_context().trace_id();
+            injector.set("x-trace-id", trace_id.to_string());
+        }
+
+        fn extract_with_context(&self, cx: &Context, _: &dyn Extractor) -> Context {
+            cx.clone()
+        }
+
+        fn fields(&self) -> opentelemetry::propagation::text_map_propagator::FieldIter<'_> {
+            opentelemetry::propagation::text_map_propagator::FieldIter::new(&[])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_join_the_callers_trace() {
+        global::set_text_map_propagator(TraceIdPropagator);
+        let trace_id = TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736);
+        let caller = Context::new().with_remote_span_context(SpanContext::new(
+            trace_id,
+            SpanId::from(0x00f067aa0ba902b7),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ));
+
+        let mock = MockWhoop::start().await;
+        let client = mock.client();
+        client
+            .get_profile_basic()
+            .with_context(caller)
+            .await
+            .unwrap();
+
+        let requests = mock.server().received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].headers.get("x-trace-id").unwrap(),
+            trace_id.to_string().as_str()
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Walks a collection endpoint page by page.
+//!
+//! [`Pages`] hands out one page at a time, so callers decide how much to keep:
+//! collecting everything, or writing each page out before fetching the next.
+//! A rate limited request is retried with backoff. Other errors are returned
+//! without moving on, so calling [`Pages::next_page`] again retries the page.
+//!
+//! After processing a page, save [`Pages::checkpoint`]. An interrupted walk
+//! picks up after that page with [`Pages::resume`], in a fresh process if need
+//! be, as long as it asks for the same range.
+
+use crate::api::WhoopApi;
+use crate::error::{Result, WhoopError};
+use crate::instrument::event;
+use crate::models::*;
+use crate::rate_limit::RateLimiter;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// The most records the API returns in one page.
+pub const MAX_PAGE_SIZE: i32 = 25;
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// The query every collection endpoint takes.
+#[derive(Debug, Clone, Default)]
+pub struct PageQuery {
+    pub limit: Option<i32>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub next_token: Option<String>,
+}
+
+/// Where a walk through a collection got to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The token for the page after the last one returned.
+    pub next_token: String,
+    /// The last record returned, to tell where a walk stopped.
+    pub last_id: Option<String>,
+}
+
+/// A record with a collection endpoint.
+pub trait Paged: Sized + Send + 'static {
+    /// The record's id, or its cycle's for recoveries.
+    fn id(&self) -> String;
+
+    fn fetch_page<A: WhoopApi>(
+        api: &A,
+        query: PageQuery,
+    ) -> impl Future<Output = Result<(Vec<Self>, Option<String>)>> + Send;
+}
+
+macro_rules! impl_paged {
+    ($($(#[$attr:meta])* $record:ty => $params:ident, $method:ident, $id:ident;)*) => {
+        $($(#[$attr])* impl Paged for $record {
+            fn id(&self) -> String {
+                self.$id.to_string()
+            }
+
+            async fn fetch_page<A: WhoopApi>(
+                api: &A,
+                query: PageQuery,
+            ) -> Result<(Vec<Self>, Option<String>)> {
+                let params = $params {
+                    limit: query.limit,
+                    start: query.start,
+                    end: query.end,
+                    next_token: query.next_token,
+                };
+                let page = api.$method(Some(params)).await?;
+                Ok((page.records.unwrap_or_default(), page.next_token))
+            }
+        })*
+    };
+}
+
+impl_paged! {
+    Cycle => CycleQueryParams, get_cycle_collection, id;
+    Sleep => SleepQueryParams, get_sleep_collection, id;
+    Recovery => RecoveryQueryParams, get_recovery_collection, cycle_id;
+    #[cfg(feature = "workouts")]
+    WorkoutV2 => WorkoutQueryParams, get_workout_collection, id;
+}
+
+/// The pages of one collection, newest records first.
+pub struct Pages<'a, A, T> {
+    api: &'a A,
+    query: PageQuery,
+    rate_limiter: Option<RateLimiter>,
+    requests: usize,
+    last_id: Option<String>,
+    done: bool,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<'a, A: WhoopApi, T: Paged> Pages<'a, A, T> {
+    /// Every record, in pages as large as the API allows.
+    pub fn new(api: &'a A) -> Self {
+        Self {
+            api,
+            query: PageQuery {
+                limit: Some(MAX_PAGE_SIZE),
+                ..PageQuery::default()
+            },
+            rate_limiter: None,
+            requests: 0,
+            last_id: None,
+            done: false,
+            record: PhantomData,
+        }
+    }
+
+    /// Only records starting at or after `start` and before `end`.
+    pub fn with_range(mut self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        self.query.start = start;
+        self.query.end = end;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: i32) -> Self {
+        self.query.limit = Some(limit);
+        self
+    }
+
+    /// Waits for `rate_limiter` before every request.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Carries on after the page `checkpoint` was taken at.
+    pub fn resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.query.next_token = Some(checkpoint.next_token);
+        self.last_id = checkpoint.last_id;
+        self
+    }
+
+    /// Where to resume after the pages returned so far, or `None` once there
+    /// are no more.
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        if self.done {
+            return None;
+        }
+        self.query.next_token.as_ref().map(|next_token| Checkpoint {
+            next_token: next_token.clone(),
+            last_id: self.last_id.clone(),
+        })
+    }
+
+    /// The id of the last record returned.
+    pub fn last_id(&self) -> Option<&str> {
+        self.last_id.as_deref()
+    }
+
+    /// Requests sent so far, retries included.
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// The next page, or `None` after the last one.
+    pub async fn next_page(&mut self) -> Option<Result<Vec<T>>> {
+        if self.done {
+            return None;
+        }
+
+        let mut attempt = 0;
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            self.requests += 1;
+            let fetch = T::fetch_page(self.api, self.query.clone());
+            #[cfg(feature = "otel")]
+            let fetch = crate::otel::resend(fetch, attempt);
+            match fetch.await {
+                Err(e @ WhoopError::RateLimitExceeded(..)) if attempt < MAX_RATE_LIMIT_RETRIES => {
+                    let backoff = Duration::from_secs(2u64.pow(attempt + 1));
+                    let wait = e.retry_after().unwrap_or(backoff);
+                    event!(warn, "rate limited, retrying in {}s", wait.as_secs());
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                Err(e) => return Some(Err(e)),
+                Ok((records, next_token)) => {
+                    match next_token {
+                        Some(token) if !token.is_empty() => self.query.next_token = Some(token),
+                        _ => self.done = true,
+                    }
+                    if let Some(last) = records.last() {
+                        self.last_id = Some(last.id());
+                    }
+                    return Some(Ok(records));
+                }
+            }
+        }
+    }
+
+    /// Fetches every remaining page.
+    pub async fn collect_all(mut self) -> Result<Vec<T>> {
+        let mut records = Vec::new();
+        while let Some(page) = self.next_page().await {
+            records.extend(page?);
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::memory::InMemoryWhoop;
+
+    #[tokio::test]
+    async fn test_walks_every_page() {
+        let api = InMemoryWhoop::new().with_cycles(fixtures::cycle_collection().records.unwrap());
+        let mut pages = Pages::<_, Cycle>::new(&api).with_limit(1);
+        let mut sizes = Vec::new();
+        while let Some(page) = pages.next_page().await {
+            sizes.push(page.unwrap().len());
+        }
+        assert_eq!(sizes, vec![1, 1]);
+        assert_eq!(pages.requests(), 2);
+        assert!(pages.next_page().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_a_checkpoint() {
+        let cycles = fixtures::cycle_collection().records.unwrap();
+        let api = InMemoryWhoop::new().with_cycles(cycles.clone());
+        let mut pages = Pages::<_, Cycle>::new(&api).with_limit(1);
+        let first = pages.next_page().await.unwrap().unwrap();
+        let checkpoint = pages.checkpoint().unwrap();
+        assert_eq!(checkpoint.last_id, Some(first[0].id.to_string()));
+
+        let mut resumed = Pages::<_, Cycle>::new(&api)
+            .with_limit(1)
+            .resume(checkpoint);
+        let second = resumed.next_page().await.unwrap().unwrap();
+        assert_ne!(first[0].id, second[0].id);
+        assert_eq!(resumed.checkpoint(), None);
+        assert_eq!(resumed.requests(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+//! A local HTTP server in front of the API, answering from a store.
+//!
+//! Tools on the same machine that can't do OAuth, or shouldn't spend the
+//! rate limit, read WHOOP data with plain requests like
+//! `GET /cycles?start=2025-06-01T00:00:00Z`:
+//!
+//! - `/cycles`, `/sleep`, `/recovery` and `/workouts` answer
+//!   `{"records": [...]}` oldest first, for `start` and `end` given as RFC
+//!   3339 timestamps. `end` defaults to now and `start` to a week before it.
+//!   `/workouts`, with the `workouts` feature, also takes a `sport`.
+//! - `/profile` and `/body_measurement` are fetched on every request.
+//!
+//! A range the proxy fetched within the max age, 5 minutes by default, is
+//! answered from the store. Otherwise the range is fetched from the API and
+//! written to the store first, so the store also fills up as tools use it.
+//! Store calls take turns on the one store, each on a blocking thread.
+//! Bad parameters get a 400 and failed fetches a 502, with the error as text.
+
+use crate::api::WhoopApi;
+use crate::error::{Result, WhoopError};
+use crate::merge::Merged;
+use crate::models::*;
+use crate::pagination::{Paged, Pages};
+use crate::server;
+use crate::store::{Store, Stored};
+use chrono::{DateTime, Duration, Utc};
+use hyper::StatusCode;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_MAX_AGE_SECS: i64 = 300;
+const DEFAULT_DAYS: i64 = 7;
+
+pub struct Proxy<A, S> {
+    api: Arc<A>,
+    store: Arc<Mutex<S>>,
+    max_age: Duration,
+    fetches: Mutex<Vec<Fetch>>,
+}
+
+/// A range of one resource fetched into the store.
+struct Fetch {
+    resource: &'static str,
+    start: DateTime<Utc>,
+    /// `None` for up to when it was fetched.
+    end: Option<DateTime<Utc>>,
+    at: DateTime<Utc>,
+}
+
+impl Fetch {
+    fn covers(&self, resource: &str, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> bool {
+        let within = match (self.end, end) {
+            (None, _) => true,
+            (Some(fetched), Some(end)) => end <= fetched,
+            (Some(_), None) => false,
+        };
+        self.resource == resource && self.start <= start && within
+    }
+}
+
+impl<A, S> Proxy<A, S>
+where
+    A: WhoopApi + Send + Sync + 'static,
+    S: Store + Send + 'static,
+{
+    pub fn new(api: Arc<A>, store: S) -> Self {
+        Self {
+            api,
+            store: Arc::new(Mutex::new(store)),
+            max_age: Duration::seconds(DEFAULT_MAX_AGE_SECS),
+            fetches: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// How long a fetched range is served from the store before it's
+    /// fetched again. Defaults to 5 minutes.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Serves requests on `addr` until the listener fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let proxy = Arc::new(self);
+        server::serve_requests(addr, move |request| {
+            let proxy = Arc::clone(&proxy);
+            async move {
+                let uri = request.uri().to_string();
+                match proxy.get(&uri).await {
+                    Ok(json) => server::reply(StatusCode::OK, "application/json", json),
+                    Err(WhoopError::NotFound(_)) => server::respond(None),
+                    Err(WhoopError::BadRequest(message, _)) => {
+                        server::reply(StatusCode::BAD_REQUEST, "text/plain", message)
+                    }
+                    Err(e) => server::reply(StatusCode::BAD_GATEWAY, "text/plain", e.to_string()),
+                }
+            }
+        })
+        .await
+    }
+
+    /// Answers a path and query as the server would, with the JSON body.
+    pub async fn get(&self, uri: &str) -> Result<String> {
+        let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+        let params = Params::parse(query)?;
+        match path {
+            "/cycles" => {
+                let range = self.refresh::<Cycle>(&params).await?;
+                let records = self
+                    .with_store(move |store| store.cycles_between(range))
+                    .await?;
+                json(&PaginatedCycleResponse {
+                    records: Some(records),
+                    next_token: None,
+                })
+            }
+            "/sleep" => {
+                let range = self.refresh::<Sleep>(&params).await?;
+                let records = self
+                    .with_store(move |store| store.sleeps_between(range))
+                    .await?;
+                json(&PaginatedSleepResponse {
+                    records: Some(records),
+                    next_token: None,
+                })
+            }
+            "/recovery" => {
+                let range = self.refresh::<Recovery>(&params).await?;
+                let records = self
+                    .with_store(move |store| store.recoveries_between(range))
+                    .await?;
+                json(&RecoveryCollection {
+                    records: Some(records),
+                    next_token: None,
+                })
+            }
+            #[cfg(feature = "workouts")]
+            "/workouts" => {
+                let range = self.refresh::<WorkoutV2>(&params).await?;
+                let sport = params.sport.clone();
+                let records = self
+                    .with_store(move |store| match &sport {
+                        Some(sport) => store.workouts_by_sport(sport, range),
+                        None => store.workouts_between(range),
+                    })
+                    .await?;
+                json(&WorkoutCollection {
+                    records: Some(records),
+                    next_token: None,
+                })
+            }
+            "/profile" => json(&self.api.get_profile_basic().await?),
+            "/body_measurement" => json(&self.api.get_body_measurement().await?),
+            _ => Err(WhoopError::NotFound(None)),
+        }
+    }
+
+    /// Fetches the requested range of `T` into the store unless a recent
+    /// fetch covered it, returning the range to read.
+    async fn refresh<T: Proxied>(&self, params: &Params) -> Result<Range<DateTime<Utc>>> {
+        let now = Utc::now();
+        let end = params.end;
+        let start = params
+            .start
+            .unwrap_or_else(|| end.unwrap_or(now) - Duration::days(DEFAULT_DAYS));
+        if start >= end.unwrap_or(now) {
+            return Err(WhoopError::BadRequest(
+                "start must be before end".to_string(),
+                None,
+            ));
+        }
+
+        let fresh = {
+            let mut fetches = self.lock_fetches()?;
+            fetches.retain(|f| now - f.at < self.max_age);
+            fetches.iter().any(|f| f.covers(T::RESOURCE, start, end))
+        };
+        if !fresh {
+            let records = Pages::<A, T>::new(&*self.api)
+                .with_range(Some(start), end)
+                .collect_all()
+                .await?;
+            self.with_store(move |store| T::upsert(store, &records))
+                .await?;
+            self.lock_fetches()?.push(Fetch {
+                resource: T::RESOURCE,
+                start,
+                end,
+                at: now,
+            });
+        }
+        Ok(start..end.unwrap_or(now))
+    }
+
+    /// Runs `query` on a blocking thread, since store calls do disk I/O.
+    async fn with_store<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut S) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let store = Arc::clone(&self.store);
+        let task = tokio::task::spawn_blocking(move || {
+            let mut store = store
+                .lock()
+                .map_err(|_| WhoopError::Unknown("proxy store lock poisoned".to_string(), None))?;
+            query(&mut store)
+        });
+        match task.await {
+            Ok(result) => result,
+            Err(e) => Err(WhoopError::Unknown(
+                format!("proxy store task failed: {}", e),
+                None,
+            )),
+        }
+    }
+
+    fn lock_fetches(&self) -> Result<std::sync::MutexGuard<'_, Vec<Fetch>>> {
+        self.fetches
+            .lock()
+            .map_err(|_| WhoopError::Unknown("proxy fetch lock poisoned".to_string(), None))
+    }
+}
+
+/// A record type the proxy fetches into a store.
+trait Proxied: Stored + Paged {
+    fn upsert<S: Store>(store: &mut S, records: &[Self]) -> Result<Merged>;
+}
+
+impl Proxied for Cycle {
+    fn upsert<S: Store>(store: &mut S, records: &[Self]) -> Result<Merged> {
+        store.upsert_cycles(records)
+    }
+}
+
+impl Proxied for Sleep {
+    fn upsert<S: Store>(store: &mut S, records: &[Self]) -> Result<Merged> {
+        store.upsert_sleeps(records)
+    }
+}
+
+impl Proxied for Recovery {
+    fn upsert<S: Store>(store: &mut S, records: &[Self]) -> Result<Merged> {
+        store.upsert_recoveries(records)
+    }
+}
+
+#[cfg(feature = "workouts")]
+impl Proxied for WorkoutV2 {
+    fn upsert<S: Store>(store: &mut S, records: &[Self]) -> Result<Merged> {
+        store.upsert_workouts(records)
+    }
+}
+
+#[derive(Default)]
+struct Params {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    sport: Option<String>,
+}
+
+impl Params {
+    fn parse(query: &str) -> Result<Self> {
+        let mut params = Params::default();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = urlencoding::decode(value)
+                .map_err(|_| WhoopError::BadRequest(format!("invalid {}", key), None))?;
+            match key {
+                "start" => params.start = Some(timestamp(key, &value)?),
+                "end" => params.end = Some(timestamp(key, &value)?),
+                "sport" => params.sport = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+}
+
+fn timestamp(key: &str, value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| {
+            WhoopError::BadRequest(
+                format!("invalid {} '{}', expected RFC 3339", key, value),
+                None,
+            )
+        })
+}
+
+fn json<T: Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::memory::InMemoryWhoop;
+    use crate::store::SqliteStore;
+
+    #[tokio::test]
+    async fn test_serves_fresh_ranges_from_the_store() {
+        let sleep = fixtures::sleep_scored();
+        let api = Arc::new(InMemoryWhoop::new().with_sleeps(vec![sleep.clone()]));
+        let proxy = Proxy::new(Arc::clone(&api), SqliteStore::open_in_memory().unwrap());
+        let day = |days: i64| {
+            (sleep.start + Duration::days(days)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        };
+        let uri = format!("/sleep?start={}&end={}", day(-1), day(1));
+
+        let page: PaginatedSleepResponse =
+            serde_json::from_str(&proxy.get(&uri).await.unwrap()).unwrap();
+        assert_eq!(page.records.unwrap()[0].id, sleep.id);
+
+        // Within the max age, the store answers without asking the API.
+        let mut renapped = sleep.clone();
+        renapped.nap = !sleep.nap;
+        renapped.updated_at += Duration::hours(1);
+        api.upsert_sleep(renapped);
+        let page: PaginatedSleepResponse =
+            serde_json::from_str(&proxy.get(&uri).await.unwrap()).unwrap();
+        assert_eq!(page.records.unwrap()[0].nap, sleep.nap);
+
+        // A range it hasn't fetched is.
+        let wider = format!("/sleep?start={}&end={}", day(-2), day(1));
+        let page: PaginatedSleepResponse =
+            serde_json::from_str(&proxy.get(&wider).await.unwrap()).unwrap();
+        assert_eq!(page.records.unwrap()[0].nap, !sleep.nap);
+
+        assert!(matches!(
+            proxy.get("/sleep?start=yesterday").await,
+            Err(WhoopError::BadRequest(..))
+        ));
+        assert!(matches!(
+            proxy.get("/naps").await,
+            Err(WhoopError::NotFound(_))
+        ));
+    }
+}
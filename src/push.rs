@@ -0,0 +1,238 @@
+//! Phone push notifications for alerts, through ntfy or Pushover.
+//!
+//! [`Ntfy`] needs only a topic, which is also its password on the public
+//! `ntfy.sh` server, so pick one nobody would guess. [`Pushover`] needs an
+//! application token and the user key to deliver to.
+//!
+//! ```no_run
+//! use whoopsy::alerts::{Alerts, Rule};
+//! use whoopsy::push::Ntfy;
+//!
+//! let alerts = Alerts::new()
+//!     .with_rule(Rule::parse("recovery < 34").unwrap())
+//!     .with_notifier(Ntfy::new("whoop-3f9a1c"));
+//! ```
+
+use crate::alerts::{Alert, Notification, Notifier};
+use crate::error::{Result, WhoopError};
+use serde_json::{Value, json};
+
+pub const NTFY_SERVER: &str = "https://ntfy.sh";
+pub const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Posts each alert to an ntfy topic.
+pub struct Ntfy {
+    http: reqwest::Client,
+    server: String,
+    topic: String,
+    token: Option<String>,
+    priority: Option<u8>,
+}
+
+impl Ntfy {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            server: NTFY_SERVER.to_string(),
+            topic: topic.into(),
+            token: None,
+            priority: None,
+        }
+    }
+
+    /// A self-hosted server instead of [`NTFY_SERVER`].
+    pub fn with_server(mut self, server: impl Into<String>) -> Self {
+        self.server = server.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// An access token, for servers or topics that require one.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// From 1, min, to 5, max; the server's default of 3 when not set.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority.clamp(1, 5));
+        self
+    }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    fn payload(&self, alert: &Alert) -> Value {
+        let mut payload = json!({
+            "topic": self.topic,
+            "title": title(alert),
+            "message": alert.message,
+            "tags": ["warning"],
+        });
+        if let Some(priority) = self.priority {
+            payload["priority"] = json!(priority);
+        }
+        payload
+    }
+}
+
+impl Notifier for Ntfy {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> Notification<'a> {
+        Box::pin(async move {
+            // Publishing as JSON goes to the server root, with the topic in the body.
+            let mut request = self.http.post(&self.server).json(&self.payload(alert));
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            check(request.send().await?).await
+        })
+    }
+}
+
+/// Sends each alert through the Pushover API.
+pub struct Pushover {
+    http: reqwest::Client,
+    url: String,
+    token: String,
+    user: String,
+    device: Option<String>,
+    priority: Option<i8>,
+}
+
+impl Pushover {
+    /// `token` is the application's API token, `user` the user or group key.
+    pub fn new(token: impl Into<String>, user: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: PUSHOVER_URL.to_string(),
+            token: token.into(),
+            user: user.into(),
+            device: None,
+            priority: None,
+        }
+    }
+
+    /// Only this device of the user's, instead of all of them.
+    pub fn with_device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// From -2, silent, to 1, high. Emergency priority needs retry settings
+    /// this notifier doesn't send, so it's capped at high.
+    pub fn with_priority(mut self, priority: i8) -> Self {
+        self.priority = Some(priority.clamp(-2, 1));
+        self
+    }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Another endpoint than [`PUSHOVER_URL`], e.g. a mock server.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    fn payload(&self, alert: &Alert) -> Value {
+        let mut payload = json!({
+            "token": self.token,
+            "user": self.user,
+            "title": title(alert),
+            "message": alert.message,
+        });
+        if let Some(device) = &self.device {
+            payload["device"] = json!(device);
+        }
+        if let Some(priority) = self.priority {
+            payload["priority"] = json!(priority);
+        }
+        payload
+    }
+}
+
+impl Notifier for Pushover {
+    fn notify<'a>(&'a self, alert: &'a Alert) -> Notification<'a> {
+        Box::pin(async move {
+            let request = self.http.post(&self.url).json(&self.payload(alert));
+            check(request.send().await?).await
+        })
+    }
+}
+
+fn title(alert: &Alert) -> String {
+    match &alert.user {
+        Some(user) => format!("WHOOP alert for {}: {}", user, alert.rule),
+        None => format!("WHOOP alert: {}", alert.rule),
+    }
+}
+
+async fn check(response: reqwest::Response) -> Result<()> {
+    let status = response.status();
+    if !status.is_success() {
+        return Err(WhoopError::from_status(status, response.text().await.ok()));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn alert() -> Alert {
+        Alert {
+            rule: "recovery < 34".to_string(),
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            value: 28.0,
+            message: "recovery < 34: 2024-03-01 is 28.0".to_string(),
+            user: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_posts_to_ntfy_and_pushover() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(header("authorization", "Bearer tk_secret"))
+            .and(body_partial_json(json!({
+                "topic": "whoop-3f9a1c",
+                "title": "WHOOP alert: recovery < 34",
+                "priority": 4,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/1/messages.json"))
+            .and(body_partial_json(json!({
+                "token": "app",
+                "user": "me",
+                "message": "recovery < 34: 2024-03-01 is 28.0",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let ntfy = Ntfy::new("whoop-3f9a1c")
+            .with_server(format!("{}/", server.uri()))
+            .with_token("tk_secret")
+            .with_priority(4);
+        ntfy.notify(&alert()).await.unwrap();
+        let pushover =
+            Pushover::new("app", "me").with_url(format!("{}/1/messages.json", server.uri()));
+        pushover.notify(&alert()).await.unwrap();
+
+        let rejected = Pushover::new("app", "someone else")
+            .with_url(format!("{}/1/messages.json", server.uri()));
+        assert!(rejected.notify(&alert()).await.is_err());
+    }
+}
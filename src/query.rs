@@ -0,0 +1,107 @@
+//! Collection queries, encoded exactly as WHOOP expects them.
+//!
+//! Every collection endpoint takes `limit`, `start`, `end` and `nextToken`.
+//! Timestamps go out as RFC 3339 in UTC with a `Z`, at a fixed
+//! [`TimestampPrecision`], milliseconds unless the client says otherwise, so
+//! the same query always encodes to the same URL.
+
+use crate::models::*;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// How many fractional digits timestamps are sent with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampPrecision {
+    /// `2024-03-01T08:00:00Z`
+    Seconds,
+    /// `2024-03-01T08:00:00.000Z`
+    #[default]
+    Millis,
+    /// `2024-03-01T08:00:00.000000Z`
+    Micros,
+}
+
+impl TimestampPrecision {
+    pub fn format(self, time: DateTime<Utc>) -> String {
+        let format = match self {
+            TimestampPrecision::Seconds => SecondsFormat::Secs,
+            TimestampPrecision::Millis => SecondsFormat::Millis,
+            TimestampPrecision::Micros => SecondsFormat::Micros,
+        };
+        time.to_rfc3339_opts(format, true)
+    }
+}
+
+/// Query parameters as name and value pairs, ready for the URL.
+pub trait ToQuery {
+    fn to_query(&self, precision: TimestampPrecision) -> Vec<(&'static str, String)>;
+}
+
+macro_rules! impl_to_query {
+    ($($params:ty),*) => {
+        $(impl ToQuery for $params {
+            fn to_query(&self, precision: TimestampPrecision) -> Vec<(&'static str, String)> {
+                let mut query = Vec::new();
+                if let Some(limit) = self.limit {
+                    query.push(("limit", limit.to_string()));
+                }
+                if let Some(start) = self.start {
+                    query.push(("start", precision.format(start)));
+                }
+                if let Some(end) = self.end {
+                    query.push(("end", precision.format(end)));
+                }
+                if let Some(next_token) = &self.next_token {
+                    query.push(("nextToken", next_token.clone()));
+                }
+                query
+            }
+        })*
+    };
+}
+
+impl_to_query!(
+    CycleQueryParams,
+    RecoveryQueryParams,
+    SleepQueryParams,
+    WorkoutQueryParams
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wire(params: &impl ToQuery, precision: TimestampPrecision) -> String {
+        let request = reqwest::Client::new()
+            .get("http://localhost/v2/cycle")
+            .query(&params.to_query(precision))
+            .build()
+            .unwrap();
+        request.url().query().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn test_encodes_the_exact_wire_format() {
+        let params = SleepQueryParams {
+            limit: Some(25),
+            start: Some("2024-03-01T08:00:00.5Z".parse().unwrap()),
+            end: None,
+            next_token: Some("MTIz:a/b".to_string()),
+        };
+        assert_eq!(
+            wire(&params, TimestampPrecision::default()),
+            "limit=25&start=2024-03-01T08%3A00%3A00.500Z&nextToken=MTIz%3Aa%2Fb"
+        );
+        assert_eq!(
+            wire(&params, TimestampPrecision::Seconds),
+            "limit=25&start=2024-03-01T08%3A00%3A00Z&nextToken=MTIz%3Aa%2Fb"
+        );
+
+        let empty = CycleQueryParams {
+            limit: None,
+            start: None,
+            end: None,
+            next_token: None,
+        };
+        assert_eq!(wire(&empty, TimestampPrecision::Micros), "");
+    }
+}
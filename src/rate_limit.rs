@@ -0,0 +1,105 @@
+//! A request budget shared by everything holding a clone of it.
+//!
+//! WHOOP allows 100 requests a minute per app. [`RateLimiter`] is a token
+//! bucket: a full bucket can be spent in a burst, after which requests go out
+//! at the refill rate. Waiting is async, so a throttled task never blocks the
+//! runtime.
+//!
+//! The budget belongs to the app, not the user, so a backend holding clients
+//! for many users gives each a clone of one limiter:
+//!
+//! ```
+//! use whoopsy::{RateLimiter, WhoopClient};
+//!
+//! let limiter = RateLimiter::default();
+//! let clients: Vec<WhoopClient> = ["token-a", "token-b"]
+//!     .into_iter()
+//!     .map(|token| WhoopClient::new(token.to_string()).with_rate_limiter(limiter.clone()))
+//!     .collect();
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Requests per minute WHOOP allows by default.
+pub const WHOOP_REQUESTS_PER_MINUTE: u32 = 100;
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+    capacity: f64,
+    /// Tokens added per second.
+    rate: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Allows `requests` per `period`, all of them at once if the budget is
+    /// unspent.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        let capacity = f64::from(requests.max(1));
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            })),
+            capacity,
+            rate: capacity / period.as_secs_f64().max(f64::EPSILON),
+        }
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Waits until a request may be sent and takes it from the budget.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity);
+                bucket.updated = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    /// WHOOP's default budget.
+    fn default() -> Self {
+        Self::per_minute(WHOOP_REQUESTS_PER_MINUTE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clones_share_one_budget() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(200));
+        let other = limiter.clone();
+        let started = std::time::Instant::now();
+        limiter.acquire().await;
+        other.acquire().await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        // The bucket is empty: the third request waits for a refill.
+        other.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,238 @@
+//! The exact bytes of every API response, kept for debugging and re-parsing.
+//!
+//! A client with a [`RawArchive`] saves the body of each response it gets
+//! from the API before parsing it. When a model turns out to be wrong, the
+//! archive shows what WHOOP actually sent, and once the model is fixed,
+//! [`RawArchive::reparse`] reads history again without a single request.
+//!
+//! Bodies are gzipped and stored by the SHA-256 of their bytes under
+//! `objects/`, so a page fetched again unchanged takes no more space.
+//! `index.jsonl` has one [`RawEntry`] per response, pointing at its body:
+//!
+//! ```no_run
+//! # async fn run() -> whoopsy::Result<()> {
+//! use whoopsy::WhoopClient;
+//! use whoopsy::models::PaginatedCycleResponse;
+//! use whoopsy::raw::RawArchive;
+//!
+//! let archive = RawArchive::open("raw")?;
+//! let client = WhoopClient::new("token".to_string()).with_raw_archive(archive.clone());
+//! client.get_cycle_collection(None).await?;
+//!
+//! for entry in archive.entries()? {
+//!     if entry.endpoint.ends_with("/cycle") {
+//!         let page: PaginatedCycleResponse = archive.reparse(&entry)?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Responses answered by a sandbox or cassette don't come from the API and
+//! aren't archived.
+
+use crate::error::{Result, WhoopError};
+use crate::export::jsonl;
+use crate::json;
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const INDEX: &str = "index.jsonl";
+const OBJECTS: &str = "objects";
+
+/// One archived response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawEntry {
+    pub at: DateTime<Utc>,
+    pub method: String,
+    /// Path and query of the request.
+    pub endpoint: String,
+    pub status: u16,
+    /// Hex SHA-256 of the body, which is where it's stored.
+    pub sha256: String,
+    /// Uncompressed size of the body.
+    pub bytes: usize,
+}
+
+/// A directory of archived responses. Clones share the index file, so one
+/// archive can serve several clients.
+#[derive(Clone)]
+pub struct RawArchive {
+    dir: PathBuf,
+    index: Arc<Mutex<File>>,
+}
+
+impl RawArchive {
+    /// Opens the archive in `dir`, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(dir.join(OBJECTS))?;
+        let index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(INDEX))?;
+        Ok(Self {
+            dir,
+            index: Arc::new(Mutex::new(index)),
+        })
+    }
+
+    /// Stores a response body, returning its index entry. This gzips and
+    /// writes files, so async callers should run it on a blocking thread.
+    pub fn store(
+        &self,
+        method: &str,
+        endpoint: &str,
+        status: u16,
+        body: &[u8],
+    ) -> Result<RawEntry> {
+        let sha256 = hex(&hmac_sha256::Hash::hash(body));
+        let path = self.object_path(&sha256);
+        if !path.exists() {
+            std::fs::create_dir_all(path.parent().unwrap_or(&self.dir))?;
+            // Written aside and renamed, so a crash never leaves half an
+            // object, under a name of its own so concurrent writers don't meet.
+            let partial = path.with_extension(format!("{}.partial", Uuid::new_v4()));
+            let mut gz = GzEncoder::new(File::create(&partial)?, Compression::default());
+            gz.write_all(body)?;
+            gz.finish()?.sync_all()?;
+            std::fs::rename(&partial, &path)?;
+        }
+
+        let entry = RawEntry {
+            at: Utc::now(),
+            method: method.to_string(),
+            endpoint: endpoint.to_string(),
+            status,
+            sha256,
+            bytes: body.len(),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let mut index = self.index.lock().map_err(|_| {
+            WhoopError::Unknown("raw archive index lock poisoned".to_string(), None)
+        })?;
+        index.write_all(&line)?;
+        Ok(entry)
+    }
+
+    /// Every archived response, oldest first.
+    pub fn entries(&self) -> Result<Vec<RawEntry>> {
+        let file = File::open(self.dir.join(INDEX))?;
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.is_empty() {
+                entries.push(json::from_string(line)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The body stored under a hash.
+    pub fn body(&self, sha256: &str) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        GzDecoder::new(File::open(self.object_path(sha256))?).read_to_end(&mut body)?;
+        Ok(body)
+    }
+
+    /// Parses an archived body again, e.g. with a fixed model.
+    pub fn reparse<T: DeserializeOwned>(&self, entry: &RawEntry) -> Result<T> {
+        json::from_slice(&mut self.body(&entry.sha256)?)
+    }
+
+    /// Writes every archived body of a successful response as a JSON Lines
+    /// envelope, for [`crate::replay`] or other tools. `kind` names the
+    /// envelope type of an endpoint, or skips it with `None`.
+    pub fn to_jsonl(
+        &self,
+        out: impl Write,
+        kind: impl Fn(&RawEntry) -> Option<String>,
+    ) -> Result<usize> {
+        let mut out = out;
+        let mut written = 0;
+        for entry in self.entries()? {
+            if !(200..300).contains(&entry.status) {
+                continue;
+            }
+            let Some(kind) = kind(&entry) else {
+                continue;
+            };
+            let payload: serde_json::Value = self.reparse(&entry)?;
+            let envelope = jsonl::Envelope {
+                kind,
+                fetched_at: entry.at,
+                payload,
+            };
+            let mut line = serde_json::to_vec(&envelope)?;
+            line.push(b'\n');
+            out.write_all(&line)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        let (prefix, rest) = sha256.split_at(2.min(sha256.len()));
+        self.dir
+            .join(OBJECTS)
+            .join(prefix)
+            .join(format!("{}.json.gz", rest))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::models::PaginatedCycleResponse;
+
+    #[test]
+    fn test_stores_bodies_by_content() {
+        let dir = std::env::temp_dir().join(format!("whoopsy-{}", uuid::Uuid::new_v4()));
+        let archive = RawArchive::open(&dir).unwrap();
+        let body = fixtures::CYCLE_COLLECTION.as_bytes();
+        let first = archive.store("GET", "/v2/cycle", 200, body).unwrap();
+        archive
+            .clone()
+            .store("GET", "/v2/cycle?limit=25", 200, body)
+            .unwrap();
+        archive
+            .store("GET", "/v2/cycle/1", 404, b"not found")
+            .unwrap();
+
+        let entries = archive.entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], first);
+        assert_eq!(entries[1].sha256, first.sha256);
+        assert_eq!(first.sha256.len(), 64);
+        let objects = std::fs::read_dir(dir.join(OBJECTS)).unwrap().count();
+        assert_eq!(objects, 2);
+
+        assert_eq!(archive.body(&first.sha256).unwrap(), body);
+        let page: PaginatedCycleResponse = archive.reparse(&first).unwrap();
+        assert_eq!(page.records.unwrap().len(), 2);
+
+        let mut lines = Vec::new();
+        let written = archive
+            .to_jsonl(&mut lines, |e| {
+                (!e.endpoint.contains('?')).then(|| "cycle_page".to_string())
+            })
+            .unwrap();
+        assert_eq!(written, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+//! Answers API queries from a previously exported archive.
+//!
+//! [`open`] loads a whole history into an [`InMemoryWhoop`], which implements
+//! [`WhoopApi`](crate::WhoopApi), so analytics and reports can run against
+//! years of data without a single request. Archives can be:
+//!
+//! - a `.jsonl` file of [`Envelope`]s, mixing record types;
+//! - a directory of such files;
+//! - a `.zip` of `.jsonl` files or of `whoopsy export --format json` output,
+//!   with the `zip` feature, which includes [`archive`](crate::archive)s.
+//!
+//! When a record appears more than once, e.g. in files appended across several
+//! runs, the copy fetched last wins.
+
+use crate::error::{Result, WhoopError};
+use crate::export::jsonl::{self, Envelope};
+use crate::memory::InMemoryWhoop;
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Loads the archive at `path`.
+pub fn open(path: impl AsRef<Path>) -> Result<InMemoryWhoop> {
+    let path = path.as_ref();
+    let mut records = Vec::new();
+
+    if path.is_dir() {
+        let mut files: Vec<_> = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        files.sort();
+        for file in files.iter().filter(|f| has_extension(f, "jsonl")) {
+            records.extend(read_envelopes(BufReader::new(std::fs::File::open(file)?))?);
+        }
+    } else if has_extension(path, "zip") {
+        records = read_zip(path)?;
+    } else {
+        records = read_envelopes(BufReader::new(std::fs::File::open(path)?))?;
+    }
+
+    load(records)
+}
+
+/// Loads a `.jsonl` stream of envelopes, e.g. from stdin.
+pub fn from_jsonl(reader: impl BufRead) -> Result<InMemoryWhoop> {
+    load(read_envelopes(reader)?)
+}
+
+fn load(mut records: Vec<Envelope<Value>>) -> Result<InMemoryWhoop> {
+    let api = InMemoryWhoop::new();
+    // Stable, so records fetched together keep their order in the file.
+    records.sort_by_key(|r| r.fetched_at);
+    for record in records {
+        add(&api, &record.kind, record.payload)?;
+    }
+    Ok(api)
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+fn read_envelopes(reader: impl BufRead) -> Result<Vec<Envelope<Value>>> {
+    jsonl::read(reader).collect()
+}
+
+#[cfg(feature = "zip")]
+fn read_zip(path: &Path) -> Result<Vec<Envelope<Value>>> {
+    use crate::json;
+    use chrono::{DateTime, Utc};
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut records = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = Path::new(entry.name()).to_path_buf();
+        if entry.is_dir() {
+            continue;
+        }
+        if has_extension(&name, "jsonl") {
+            records.extend(read_envelopes(BufReader::new(entry))?);
+        } else if has_extension(&name, "json") {
+            let Some(kind) = name
+                .file_stem()
+                .and_then(|s| json_kind(&s.to_string_lossy()))
+            else {
+                continue;
+            };
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            // Plain JSON exports don't say when they were fetched; sort them first.
+            let fetched_at = DateTime::<Utc>::MIN_UTC;
+            match json::from_slice(&mut contents)? {
+                Value::Array(payloads) => {
+                    records.extend(payloads.into_iter().map(|payload| Envelope {
+                        kind: kind.to_string(),
+                        fetched_at,
+                        payload,
+                    }))
+                }
+                payload => records.push(Envelope {
+                    kind: kind.to_string(),
+                    fetched_at,
+                    payload,
+                }),
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(not(feature = "zip"))]
+fn read_zip(path: &Path) -> Result<Vec<Envelope<Value>>> {
+    Err(WhoopError::Unknown(
+        format!("reading {} needs the `zip` feature", path.display()),
+        None,
+    ))
+}
+
+/// The envelope type of a file written by `whoopsy export --format json`.
+#[cfg(feature = "zip")]
+fn json_kind(stem: &str) -> Option<&'static str> {
+    match stem {
+        "cycles" => Some("cycle"),
+        "sleep" => Some("sleep"),
+        "recovery" => Some("recovery"),
+        "workouts" => Some("workout"),
+        "profile" => Some("profile"),
+        "body_measurement" => Some("body_measurement"),
+        _ => None,
+    }
+}
+
+fn add(api: &InMemoryWhoop, kind: &str, payload: Value) -> Result<()> {
+    match kind {
+        "cycle" => api.upsert_cycle(serde_json::from_value(payload)?),
+        "sleep" => api.upsert_sleep(serde_json::from_value(payload)?),
+        "recovery" => api.upsert_recovery(serde_json::from_value(payload)?),
+        "workout" => api.upsert_workout(serde_json::from_value(payload)?),
+        "profile" => api.set_profile(serde_json::from_value(payload)?),
+        "body_measurement" => api.set_body_measurement(serde_json::from_value(payload)?),
+        other => {
+            return Err(WhoopError::Unknown(
+                format!("unknown record type {} in archive", other),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::WhoopApi;
+    use crate::export::jsonl::JsonlWriter;
+    use crate::fixtures;
+    use chrono::{DateTime, Utc};
+
+    #[tokio::test]
+    async fn test_answers_queries_from_a_jsonl_archive() {
+        let mut writer = JsonlWriter::new(Vec::new());
+        let first: DateTime<Utc> = "2024-03-01T00:00:00Z".parse().unwrap();
+        let mut pending = fixtures::cycle_pending();
+        writer.write_fetched_at(&pending, first).unwrap();
+        writer
+            .write_fetched_at(&fixtures::profile(), first)
+            .unwrap();
+        writer
+            .write_fetched_at(&fixtures::workout_scored(), first)
+            .unwrap();
+        // Scored on a later run: the newer copy wins even though it comes first.
+        pending.score_state = crate::models::ScoreState::Scored;
+        let mut later = JsonlWriter::new(Vec::new());
+        later
+            .write_fetched_at(&pending, first + chrono::Duration::days(1))
+            .unwrap();
+        let mut lines = later.into_inner().unwrap();
+        lines.extend(writer.into_inner().unwrap());
+
+        let api = from_jsonl(lines.as_slice()).unwrap();
+        let cycle = api.get_cycle_by_id(pending.id).await.unwrap();
+        assert!(matches!(
+            cycle.score_state,
+            crate::models::ScoreState::Scored
+        ));
+        assert_eq!(api.get_profile_basic().await.unwrap().user_id, 10129);
+        #[cfg(feature = "workouts")]
+        {
+            let workouts = api.get_workout_collection(None).await.unwrap();
+            assert_eq!(workouts.records.unwrap().len(), 1);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! Weekly and monthly reports rendered to Markdown or self-contained HTML.
+//!
+//! A [`Report`] pairs a [`Summary`] with the previous period's for trend arrows and
+//! keeps per-day series for sparklines. Charts are inline SVG, embedded as data URIs
+//! in Markdown and as elements in HTML, so either output stands on its own.
+
+use crate::aggregate::{Summary, ZoneShare};
+use crate::format::duration;
+use crate::models::*;
+use std::fmt::Write;
+
+/// Changes smaller than this fraction of the previous value count as flat.
+const FLAT_THRESHOLD: f64 = 0.02;
+
+pub struct Report {
+    pub title: String,
+    pub summary: Summary,
+    /// The period before, for trend arrows.
+    pub previous: Option<Summary>,
+    pub recovery_series: Vec<f32>,
+    pub hrv_series: Vec<f32>,
+    pub strain_series: Vec<f32>,
+    /// Hours asleep per main sleep.
+    pub sleep_series: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Up,
+    Down,
+    Flat,
+}
+
+impl Trend {
+    pub fn between(current: f64, previous: f64) -> Self {
+        let change = current - previous;
+        if change.abs() <= previous.abs() * FLAT_THRESHOLD {
+            Trend::Flat
+        } else if change > 0.0 {
+            Trend::Up
+        } else {
+            Trend::Down
+        }
+    }
+
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Trend::Up => "↑",
+            Trend::Down => "↓",
+            Trend::Flat => "→",
+        }
+    }
+}
+
+/// One metric row, shared by both renderers.
+struct Row<'a> {
+    label: &'static str,
+    value: String,
+    trend: Option<Trend>,
+    higher_is_better: bool,
+    series: Option<&'a [f32]>,
+}
+
+impl Report {
+    /// Builds a report from records already filtered to the period.
+    pub fn new(
+        title: impl Into<String>,
+        cycles: &[Cycle],
+        recoveries: &[Recovery],
+        sleeps: &[Sleep],
+        workouts: &[WorkoutV2],
+    ) -> Self {
+        let summary = Summary::new(cycles, recoveries, sleeps, workouts);
+        let mut cycles: Vec<&Cycle> = cycles.iter().collect();
+        cycles.sort_by_key(|c| c.start);
+        let mut recoveries: Vec<&Recovery> = recoveries.iter().collect();
+        recoveries.sort_by_key(|r| r.created_at);
+        let mut main_sleeps: Vec<&Sleep> = sleeps.iter().filter(|s| !s.nap).collect();
+        main_sleeps.sort_by_key(|s| s.start);
+
+        let recovery_scores = || recoveries.iter().filter_map(|r| r.score.as_ref());
+        Self {
+            title: title.into(),
+            summary,
+            previous: None,
+            recovery_series: recovery_scores().map(|s| s.recovery_score).collect(),
+            hrv_series: recovery_scores().map(|s| s.hrv_rmssd_milli).collect(),
+            strain_series: cycles
+                .iter()
+                .filter_map(|c| Some(c.score.as_ref()?.strain))
+                .collect(),
+            sleep_series: main_sleeps
+                .iter()
+                .filter_map(|s| {
+                    let milli = s.score.as_ref()?.stage_summary.total_sleep_time_milli();
+                    Some(milli as f32 / 3_600_000.0)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn with_previous(mut self, previous: Summary) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+
+    fn rows(&self) -> Vec<Row<'_>> {
+        let s = &self.summary;
+        let p = self.previous.as_ref();
+        let trend = |current: Option<f64>, previous: Option<f64>| {
+            current.zip(previous).map(|(c, p)| Trend::between(c, p))
+        };
+        let avg =
+            |f: fn(&Summary) -> Option<f32>| (f(s).map(f64::from), p.and_then(f).map(f64::from));
+
+        let (recovery, recovery_before) = avg(|s| s.average_recovery);
+        let (hrv, hrv_before) = avg(|s| s.average_hrv_milli);
+        let (rhr, rhr_before) = avg(|s| s.average_resting_heart_rate);
+        let (strain, strain_before) = avg(|s| s.average_strain);
+        let (performance, performance_before) = avg(|s| s.average_sleep_performance);
+        let total = |f: fn(&Summary) -> f64| (Some(f(s)), p.map(f));
+        let (sleep, sleep_before) = total(|s| s.total_sleep_milli as f64);
+        let (debt, debt_before) = total(|s| s.sleep_debt_milli as f64);
+        let (energy, energy_before) = total(|s| s.total_kilojoule as f64);
+
+        vec![
+            Row {
+                label: "Average recovery",
+                value: percent(s.average_recovery),
+                trend: trend(recovery, recovery_before),
+                higher_is_better: true,
+                series: Some(&self.recovery_series),
+            },
+            Row {
+                label: "Average HRV",
+                value: format!("{} ms", number(s.average_hrv_milli, 1)),
+                trend: trend(hrv, hrv_before),
+                higher_is_better: true,
+                series: Some(&self.hrv_series),
+            },
+            Row {
+                label: "Average RHR",
+                value: format!("{} bpm", number(s.average_resting_heart_rate, 0)),
+                trend: trend(rhr, rhr_before),
+                higher_is_better: false,
+                series: None,
+            },
+            Row {
+                label: "Average strain",
+                value: number(s.average_strain, 1),
+                trend: trend(strain, strain_before),
+                higher_is_better: true,
+                series: Some(&self.strain_series),
+            },
+            Row {
+                label: "Energy",
+                value: format!("{:.0} kJ", s.total_kilojoule),
+                trend: trend(energy, energy_before),
+                higher_is_better: true,
+                series: None,
+            },
+            Row {
+                label: "Total sleep",
+                value: duration(s.total_sleep_milli),
+                trend: trend(sleep, sleep_before),
+                higher_is_better: true,
+                series: Some(&self.sleep_series),
+            },
+            Row {
+                label: "Average sleep performance",
+                value: percent(s.average_sleep_performance),
+                trend: trend(performance, performance_before),
+                higher_is_better: true,
+                series: None,
+            },
+            Row {
+                label: "Sleep debt",
+                value: duration(s.sleep_debt_milli),
+                trend: trend(debt, debt_before),
+                higher_is_better: false,
+                series: None,
+            },
+        ]
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let s = &self.summary;
+        let _ = writeln!(out, "# {}\n", self.title);
+        let _ = writeln!(out, "| Metric | Value | Trend | Chart |");
+        let _ = writeln!(out, "| --- | --- | --- | --- |");
+        for row in self.rows() {
+            let chart = row
+                .series
+                .and_then(sparkline)
+                .map(|svg| {
+                    format!(
+                        "![{}](data:image/svg+xml;utf8,{})",
+                        row.label,
+                        urlencoding::encode(&svg)
+                    )
+                })
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                row.label,
+                row.value,
+                row.trend.map_or("", Trend::arrow),
+                chart
+            );
+        }
+
+        let _ = writeln!(out, "\n## Time in zones\n");
+        let _ = writeln!(out, "| Zone | Time | Share |");
+        let _ = writeln!(out, "| --- | --- | --- |");
+        for ZoneShare {
+            zone,
+            milli,
+            percentage,
+        } in ZoneShare::of(&s.zone_durations)
+        {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {:.0}% |",
+                zone,
+                duration(milli),
+                percentage
+            );
+        }
+
+        let _ = writeln!(out, "\n## Workouts\n");
+        let _ = writeln!(out, "| Sport | Count | Time | Strain |");
+        let _ = writeln!(out, "| --- | --- | --- | --- |");
+        for (sport, w) in &s.workouts_by_sport {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {:.1} |",
+                sport,
+                w.count,
+                duration(w.total_duration_milli),
+                w.total_strain
+            );
+        }
+        out
+    }
+
+    /// A complete HTML document with inline styles and charts.
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let s = &self.summary;
+        let title = escape_html(&self.title);
+        let _ = writeln!(out, "<!DOCTYPE html>");
+        let _ = writeln!(out, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">");
+        let _ = writeln!(out, "<title>{}</title>", title);
+        let _ = writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE);
+        let _ = writeln!(out, "<h1>{}</h1>", title);
+
+        let _ = writeln!(out, "<table>");
+        let _ = writeln!(
+            out,
+            "<tr><th>Metric</th><th>Value</th><th>Trend</th><th>Chart</th></tr>"
+        );
+        for row in self.rows() {
+            let trend = row.trend.map_or(String::new(), |t| {
+                let class = match (t, row.higher_is_better) {
+                    (Trend::Flat, _) => "flat",
+                    (Trend::Up, true) | (Trend::Down, false) => "good",
+                    _ => "bad",
+                };
+                format!("<span class=\"{}\">{}</span>", class, t.arrow())
+            });
+            let chart = row.series.and_then(sparkline).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                row.label, row.value, trend, chart
+            );
+        }
+        let _ = writeln!(out, "</table>");
+
+        let _ = writeln!(out, "<h2>Time in zones</h2>\n<table>");
+        let _ = writeln!(out, "<tr><th>Zone</th><th>Time</th><th>Share</th></tr>");
+        for ZoneShare {
+            zone,
+            milli,
+            percentage,
+        } in ZoneShare::of(&s.zone_durations)
+        {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>",
+                zone,
+                duration(milli),
+                percentage
+            );
+        }
+        let _ = writeln!(out, "</table>");
+
+        let _ = writeln!(out, "<h2>Workouts</h2>\n<table>");
+        let _ = writeln!(
+            out,
+            "<tr><th>Sport</th><th>Count</th><th>Time</th><th>Strain</th></tr>"
+        );
+        for (sport, w) in &s.workouts_by_sport {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td></tr>",
+                escape_html(sport),
+                w.count,
+                duration(w.total_duration_milli),
+                w.total_strain
+            );
+        }
+        let _ = writeln!(out, "</table>\n</body>\n</html>");
+        out
+    }
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:48rem;margin:2rem auto;color:#222}\
+table{border-collapse:collapse;width:100%;margin-bottom:1.5rem}\
+th,td{text-align:left;padding:.35rem .6rem;border-bottom:1px solid #ddd}\
+.good{color:#1a7f37}.bad{color:#cf222e}.flat{color:#777}";
+
+/// A 120x24 line chart of `values`, or `None` with fewer than two points.
+pub fn sparkline(values: &[f32]) -> Option<String> {
+    if values.len() < 2 {
+        return None;
+    }
+    let (width, height) = (120.0, 24.0);
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+    let step = width / (values.len() - 1) as f32;
+
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let x = i as f32 * step;
+            // Keep a pixel of margin so the stroke isn't clipped at the extremes.
+            let y = 1.0 + (height - 2.0) * (1.0 - (v - min) / range);
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+
+    Some(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\
+         <polyline fill=\"none\" stroke=\"#0969da\" stroke-width=\"1.5\" points=\"{}\"/></svg>",
+        width,
+        height,
+        width,
+        height,
+        points.join(" ")
+    ))
+}
+
+fn percent(value: Option<f32>) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.0}%", v))
+}
+
+fn number(value: Option<f32>, precision: usize) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.*}", precision, v))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trend_ignores_small_changes() {
+        assert_eq!(Trend::between(60.5, 60.0), Trend::Flat);
+        assert_eq!(Trend::between(66.0, 60.0), Trend::Up);
+        assert_eq!(Trend::between(50.0, 60.0), Trend::Down);
+        assert!(sparkline(&[1.0]).is_none());
+        assert!(
+            sparkline(&[1.0, 2.0])
+                .unwrap()
+                .contains("0.0,23.0 120.0,1.0")
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Reports failed API requests to an error tracker.
+//!
+//! A client with an [`ErrorReports`] hands every failed request to its
+//! [`ErrorReporter`] as a [`FailedRequest`]: the error, what was requested,
+//! the user the client belongs to and a correlation ID, which also goes to
+//! WHOOP in the [`REQUEST_ID`] header. Any `Fn(&FailedRequest)` is a reporter;
+//! with the `sentry` feature, [`SentryReporter`] captures failures in Sentry:
+//!
+//! ```no_run
+//! # #[cfg(feature = "sentry")]
+//! # fn main() {
+//! use whoopsy::WhoopClient;
+//! use whoopsy::reporting::{ErrorReports, SentryReporter};
+//!
+//! let reports = ErrorReports::new(SentryReporter);
+//! let client = WhoopClient::new("token".to_string()).with_error_reports(reports.for_user("42"));
+//! # }
+//! # #[cfg(not(feature = "sentry"))]
+//! # fn main() {}
+//! ```
+//!
+//! Every failure is reported, expected ones like a missing record included, so
+//! reporters filter on [`WhoopError::kind`] as suits them.
+
+use crate::error::WhoopError;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The header a request's correlation ID is sent in.
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// A request that failed.
+#[derive(Debug)]
+pub struct FailedRequest<'a> {
+    pub error: &'a WhoopError,
+    pub method: &'a str,
+    /// The path, without the query.
+    pub endpoint: &'a str,
+    pub user: Option<&'a str>,
+    pub correlation_id: Uuid,
+}
+
+/// Where failures go. Reporting runs on the request path, so it must not
+/// block for long.
+pub trait ErrorReporter: Send + Sync {
+    fn report(&self, failure: &FailedRequest<'_>);
+}
+
+impl<F: Fn(&FailedRequest<'_>) + Send + Sync> ErrorReporter for F {
+    fn report(&self, failure: &FailedRequest<'_>) {
+        self(failure)
+    }
+}
+
+/// Captures each failure as a Sentry event, through whichever hub is current,
+/// tagged with the request.
+#[cfg(feature = "sentry")]
+pub struct SentryReporter;
+
+#[cfg(feature = "sentry")]
+impl ErrorReporter for SentryReporter {
+    fn report(&self, failure: &FailedRequest<'_>) {
+        sentry_core::with_scope(
+            |scope| {
+                scope.set_tag("whoop.method", failure.method);
+                scope.set_tag("whoop.endpoint", failure.endpoint);
+                scope.set_tag("whoop.error_kind", format!("{:?}", failure.error.kind()));
+                scope.set_tag("correlation_id", failure.correlation_id);
+                if let Some(user) = failure.user {
+                    scope.set_tag("whoop.user", user);
+                }
+            },
+            || sentry_core::capture_error(failure.error),
+        );
+    }
+}
+
+/// An [`ErrorReporter`] shared by clients, with the user they report for.
+#[derive(Clone)]
+pub struct ErrorReports {
+    reporter: Arc<dyn ErrorReporter>,
+    user: Option<String>,
+}
+
+impl ErrorReports {
+    pub fn new(reporter: impl ErrorReporter + 'static) -> Self {
+        Self {
+            reporter: Arc::new(reporter),
+            user: None,
+        }
+    }
+
+    /// The same reporter, attributing failures to `user`.
+    pub fn for_user(&self, user: impl Into<String>) -> Self {
+        Self {
+            reporter: Arc::clone(&self.reporter),
+            user: Some(user.into()),
+        }
+    }
+
+    pub(crate) fn report(
+        &self,
+        error: &WhoopError,
+        method: &str,
+        endpoint: &str,
+        correlation_id: Uuid,
+    ) {
+        self.reporter.report(&FailedRequest {
+            error,
+            method,
+            endpoint,
+            user: self.user.as_deref(),
+            correlation_id,
+        });
+    }
+}
+
+#[cfg(all(test, feature = "sentry"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentry_events_carry_the_request() {
+        let error = WhoopError::NotFound(None);
+        let correlation_id = Uuid::new_v4();
+        let events = sentry_core::test::with_captured_events(|| {
+            ErrorReports::new(SentryReporter).for_user("42").report(
+                &error,
+                "GET",
+                "/v2/cycle/1",
+                correlation_id,
+            );
+        });
+
+        assert_eq!(events.len(), 1);
+        let tags = &events[0].tags;
+        assert_eq!(tags["whoop.method"], "GET");
+        assert_eq!(tags["whoop.endpoint"], "/v2/cycle/1");
+        assert_eq!(tags["whoop.error_kind"], "NotFound");
+        assert_eq!(tags["whoop.user"], "42");
+        assert_eq!(tags["correlation_id"], correlation_id.to_string());
+        assert_eq!(events[0].exception.len(), 1);
+    }
+}
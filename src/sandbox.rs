@@ -0,0 +1,265 @@
+//! Serves the client from exported files instead of the network.
+//!
+//! A sandbox directory holds what `whoopsy export` writes: `cycles`, `sleep`,
+//! `recovery` and `workouts` as `.json` arrays or `.jsonl` envelopes, plus
+//! optional `profile.json` and `body_measurement.json`. Requests still go
+//! through the client's usual request building, parsing and error mapping,
+//! they're just answered by an [`InMemoryWhoop`] holding the files' records, so
+//! demos and CI behave like production without credentials.
+
+use crate::api::WhoopApi;
+use crate::error::{Result, WhoopError};
+use crate::export::jsonl::{self, Envelope};
+use crate::json;
+use crate::memory::InMemoryWhoop;
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use reqwest::{Method, Request, StatusCode};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub struct Sandbox {
+    dir: PathBuf,
+    api: InMemoryWhoop,
+}
+
+impl Sandbox {
+    /// Loads every file in `dir`. Missing files just mean no records of that kind.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        let mut api = InMemoryWhoop::new()
+            .with_cycles(load(&dir, "cycles")?)
+            .with_sleeps(load(&dir, "sleep")?)
+            .with_recoveries(load(&dir, "recovery")?)
+            .with_workouts(load(&dir, "workouts")?);
+        if let Some(profile) = load_one(&dir, "profile")? {
+            api = api.with_profile(profile);
+        }
+        if let Some(body) = load_one(&dir, "body_measurement")? {
+            api = api.with_body_measurement(body);
+        }
+        Ok(Self { dir, api })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The records being served, e.g. to add some mid-test.
+    pub fn api(&self) -> &InMemoryWhoop {
+        &self.api
+    }
+
+    /// Answers a request the way the API would.
+    pub(crate) async fn respond(&self, request: &Request) -> Result<(StatusCode, String)> {
+        let url = request.url();
+        // Whatever the base URL, the API's own paths start at `/v2/`.
+        let path = url.path();
+        let path = path.find("/v2/").map_or(path, |i| &path[i..]);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let query = Query::parse(url.query_pairs());
+
+        // Bad ids and queries land in `result` too, to be answered with 400.
+        let result = async {
+            match (request.method().clone(), segments.as_slice()) {
+                (Method::GET, ["v2", "cycle"]) => {
+                    json(self.api.get_cycle_collection(Some(query?.into())).await)
+                }
+                (Method::GET, ["v2", "cycle", id]) => {
+                    json(self.api.get_cycle_by_id(parse_id(id)?).await)
+                }
+                (Method::GET, ["v2", "cycle", id, "sleep"]) => {
+                    json(self.api.get_sleep_for_cycle(parse_id(id)?).await)
+                }
+                (Method::GET, ["v2", "cycle", id, "recovery"]) => {
+                    json(self.api.get_recovery_for_cycle(parse_id(id)?).await)
+                }
+                (Method::GET, ["v2", "recovery"]) => {
+                    json(self.api.get_recovery_collection(Some(query?.into())).await)
+                }
+                (Method::GET, ["v2", "activity", "sleep"]) => {
+                    json(self.api.get_sleep_collection(Some(query?.into())).await)
+                }
+                (Method::GET, ["v2", "activity", "sleep", id]) => {
+                    json(self.api.get_sleep_by_id(parse_uuid(id)?).await)
+                }
+                #[cfg(feature = "workouts")]
+                (Method::GET, ["v2", "activity", "workout"]) => {
+                    json(self.api.get_workout_collection(Some(query?.into())).await)
+                }
+                #[cfg(feature = "workouts")]
+                (Method::GET, ["v2", "activity", "workout", id]) => {
+                    json(self.api.get_workout_by_id(parse_uuid(id)?).await)
+                }
+                (Method::GET, ["v2", "user", "profile", "basic"]) => {
+                    json(self.api.get_profile_basic().await)
+                }
+                (Method::GET, ["v2", "user", "measurement", "body"]) => {
+                    json(self.api.get_body_measurement().await)
+                }
+                (Method::DELETE, ["v2", "user", "access"]) => self
+                    .api
+                    .revoke_oauth_access()
+                    .await
+                    .map(|()| (StatusCode::NO_CONTENT, String::new())),
+                _ => Err(WhoopError::NotFound(None)),
+            }
+        }
+        .await;
+
+        match result {
+            Err(WhoopError::NotFound(_)) => Ok((StatusCode::NOT_FOUND, String::new())),
+            Err(WhoopError::BadRequest(message, _)) => Ok((StatusCode::BAD_REQUEST, message)),
+            other => other,
+        }
+    }
+}
+
+fn json<T: Serialize>(result: Result<T>) -> Result<(StatusCode, String)> {
+    Ok((StatusCode::OK, serde_json::to_string(&result?)?))
+}
+
+fn parse_id(id: &str) -> Result<i64> {
+    id.parse()
+        .map_err(|_| WhoopError::BadRequest(format!("invalid id {}", id), None))
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid> {
+    id.parse()
+        .map_err(|_| WhoopError::BadRequest(format!("invalid id {}", id), None))
+}
+
+/// Reads `<name>.json` as an array, or else `<name>.jsonl` as envelopes.
+fn load<T: DeserializeOwned>(dir: &Path, name: &str) -> Result<Vec<T>> {
+    let path = dir.join(name).with_extension("json");
+    if path.exists() {
+        return json::from_slice(&mut std::fs::read(path)?);
+    }
+    let path = path.with_extension("jsonl");
+    if path.exists() {
+        let file = BufReader::new(std::fs::File::open(path)?);
+        return jsonl::read(file)
+            .map(|envelope| envelope.map(|e: Envelope<T>| e.payload))
+            .collect();
+    }
+    Ok(Vec::new())
+}
+
+fn load_one<T: DeserializeOwned>(dir: &Path, name: &str) -> Result<Option<T>> {
+    let path = dir.join(name).with_extension("json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    json::from_slice(&mut std::fs::read(path)?).map(Some)
+}
+
+/// The collection query, read back from the URL the client built.
+struct Query {
+    limit: Option<i32>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    next_token: Option<String>,
+}
+
+impl Query {
+    fn parse(pairs: impl Iterator<Item = (impl AsRef<str>, impl AsRef<str>)>) -> Result<Self> {
+        let mut query = Query {
+            limit: None,
+            start: None,
+            end: None,
+            next_token: None,
+        };
+        for (key, value) in pairs {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            let invalid = || WhoopError::BadRequest(format!("invalid {} {}", key, value), None);
+            match key {
+                "limit" => query.limit = Some(value.parse().map_err(|_| invalid())?),
+                "start" => query.start = Some(value.parse().map_err(|_| invalid())?),
+                "end" => query.end = Some(value.parse().map_err(|_| invalid())?),
+                "nextToken" => query.next_token = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(query)
+    }
+}
+
+macro_rules! impl_params_from_query {
+    ($($params:ident),*) => {
+        $(impl From<Query> for $params {
+            fn from(q: Query) -> Self {
+                $params {
+                    limit: q.limit,
+                    start: q.start,
+                    end: q.end,
+                    next_token: q.next_token,
+                }
+            }
+        })*
+    };
+}
+
+impl_params_from_query!(
+    CycleQueryParams,
+    RecoveryQueryParams,
+    SleepQueryParams,
+    WorkoutQueryParams
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::WhoopClient;
+    use crate::export::jsonl::JsonlWriter;
+    use crate::fixtures;
+
+    fn cycle(id: i64, start: DateTime<Utc>) -> Cycle {
+        Cycle {
+            id,
+            start,
+            ..fixtures::cycle_pending()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_reads_exported_files() {
+        let dir = std::env::temp_dir().join(format!("whoopsy-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first: DateTime<Utc> = "2024-03-01T06:00:00Z".parse().unwrap();
+        let cycles: Vec<Cycle> = (0..3)
+            .map(|i| cycle(i, first + chrono::Duration::days(i)))
+            .collect();
+        std::fs::write(
+            dir.join("cycles.json"),
+            serde_json::to_vec(&cycles).unwrap(),
+        )
+        .unwrap();
+        let mut writer = JsonlWriter::new(std::fs::File::create(dir.join("sleep.jsonl")).unwrap());
+        writer.write_all(&Vec::<Sleep>::new()).unwrap();
+        drop(writer);
+
+        let client = WhoopClient::new(String::new()).with_sandbox(Sandbox::open(&dir).unwrap());
+        let page = client
+            .get_cycle_collection(Some(CycleQueryParams {
+                limit: Some(2),
+                start: Some(first + chrono::Duration::days(1)),
+                end: None,
+                next_token: None,
+            }))
+            .await
+            .unwrap();
+        let ids: Vec<i64> = page.records.unwrap().iter().map(|c| c.id).collect();
+        assert_eq!(ids, [2, 1]);
+        assert_eq!(client.get_cycle_by_id(0).await.unwrap().id, 0);
+        assert!(matches!(
+            client.get_profile_basic().await,
+            Err(WhoopError::NotFound(_))
+        ));
+        let sleeps = client.get_sleep_collection(None).await.unwrap();
+        assert!(sleeps.records.unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
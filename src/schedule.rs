@@ -0,0 +1,479 @@
+//! Periodic tasks inside a long-running process: syncs, reports, alert checks.
+//!
+//! A [`Scheduler`] runs each task on its [`Schedule`], a fixed interval or a
+//! cron expression in the machine's local time. Tasks are isolated from each
+//! other: each run is spawned on its own, so one that fails or panics is
+//! logged and counted in its [`TaskReport`], and the task runs again next
+//! time. A run still going at the next due time delays it rather than
+//! overlapping. On shutdown, no new runs start and the ones in flight finish.
+//!
+//! ```no_run
+//! # async fn run() {
+//! use whoopsy::schedule::{Schedule, Scheduler};
+//!
+//! let reports = Scheduler::new()
+//!     .with_task("sync", Schedule::parse("every 1h").unwrap(), || async {
+//!         // Fetch into the store…
+//!         Ok(())
+//!     })
+//!     .with_task("weekly report", Schedule::parse("0 7 * * 1").unwrap(), || async {
+//!         // Mail last week's report…
+//!         Ok(())
+//!     })
+//!     .run(async {
+//!         tokio::signal::ctrl_c().await.ok();
+//!     })
+//!     .await;
+//! # }
+//! ```
+
+use crate::error::Result;
+use crate::instrument::event;
+use chrono::{
+    DateTime, Datelike, Local, Months, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Timelike,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// A cron expression's next time is only looked for this many years ahead;
+/// beyond that, it's taken to never fire (e.g. `0 0 31 2 *`).
+const CRON_HORIZON_YEARS: i32 = 5;
+
+/// When a task runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// At start, then every interval after the previous run started.
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// `every 15m` (with `s`, `m`, `h` or `d`), or a cron expression; see
+    /// [`Cron::parse`].
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        match text.strip_prefix("every ") {
+            Some(interval) => Some(Schedule::Every(interval_from(interval.trim())?)),
+            None => Some(Schedule::Cron(Cron::parse(text)?)),
+        }
+    }
+
+    /// How long from now until the run after one that started at `last`, or
+    /// `None` if there's none.
+    fn wait(&self, last: Option<Instant>) -> Option<Duration> {
+        match self {
+            Schedule::Every(interval) => match last {
+                Some(last) => {
+                    let next = last.checked_add(*interval)?;
+                    Some(next.saturating_duration_since(Instant::now()))
+                }
+                None => Some(Duration::ZERO),
+            },
+            Schedule::Cron(cron) => {
+                let now = Local::now();
+                let next = cron.next_after(&now)?;
+                Some((next - now).to_std().unwrap_or(Duration::ZERO))
+            }
+        }
+    }
+}
+
+fn interval_from(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().ok().filter(|n| *n > 0)?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    number.checked_mul(seconds).map(Duration::from_secs)
+}
+
+/// A five-field cron expression: minute, hour, day of month, month and day
+/// of week (0 or 7 is Sunday). Fields take `*`, numbers, ranges like `1-5`,
+/// lists like `1,15` and steps like `*/15` or `8-18/2`. As in classic cron, a
+/// day matching either the day of month or the day of week matches when both
+/// are restricted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// Also takes `@hourly`, `@daily`, `@weekly` (Sunday) and `@monthly`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = match text.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            text => text,
+        };
+        let [minute, hour, day, month, weekday] = text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .try_into()
+            .ok()?;
+        let weekdays = field(weekday, 0, 7)?;
+        Some(Self {
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            // Sunday is both 0 and 7.
+            weekdays: (weekdays | weekdays >> 7) & 0x7F,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// The first matching minute after `after`, in its time zone. Minutes a
+    /// daylight saving change skips are skipped, and those it repeats run
+    /// once.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let local = after.naive_local();
+        let limit = local.year() + CRON_HORIZON_YEARS;
+        let mut t = local.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        while t.year() <= limit {
+            let date = t.date();
+            if !has(self.months, t.month()) {
+                let first = NaiveDate::from_ymd_opt(t.year(), t.month(), 1)?;
+                t = midnight(first + Months::new(1));
+            } else if !self.day_matches(date) {
+                t = midnight(date.succ_opt()?);
+            } else if !has(self.hours, t.hour()) {
+                t = date.and_hms_opt(t.hour(), 0, 0)? + TimeDelta::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += TimeDelta::minutes(1);
+            } else {
+                // After falling back, `after` may be in the repeated hour.
+                let local = after.timezone().from_local_datetime(&t);
+                match [local.clone().earliest(), local.latest()]
+                    .into_iter()
+                    .flatten()
+                    .find(|at| at > after)
+                {
+                    Some(at) => return Some(at),
+                    None => t += TimeDelta::minutes(1),
+                }
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+/// The values a cron field allows, as bits.
+fn field(text: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (low, high) = if range == "*" {
+            (min, max)
+        } else if let Some((low, high)) = range.split_once('-') {
+            (low.parse().ok()?, high.parse().ok()?)
+        } else {
+            let value = range.parse().ok()?;
+            // `5/10` means from 5 on, every 10.
+            (value, if step > 1 { max } else { value })
+        };
+        if low < min || high > max || low > high {
+            return None;
+        }
+        for value in (low..=high).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap()
+}
+
+/// What a task's job returns.
+pub type Job = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+struct Task {
+    name: String,
+    schedule: Schedule,
+    job: Arc<dyn Fn() -> Job + Send + Sync>,
+}
+
+/// How a task fared until shutdown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskReport {
+    pub name: String,
+    pub runs: u32,
+    /// Runs that returned an error or panicked.
+    pub failures: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_task<F, Fut>(mut self, name: impl Into<String>, schedule: Schedule, job: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.tasks.push(Task {
+            name: name.into(),
+            schedule,
+            job: Arc::new(move || Box::pin(job()) as Job),
+        });
+        self
+    }
+
+    /// Runs every task until `shutdown` completes, then waits for the runs
+    /// in flight. Returns a report per task, in the order they were added.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Vec<TaskReport> {
+        let (stop, stopped) = watch::channel(false);
+        let handles: Vec<_> = self
+            .tasks
+            .into_iter()
+            .map(|task| tokio::spawn(task.run(stopped.clone())))
+            .collect();
+        shutdown.await;
+        // Every task holds a receiver, so this can't fail.
+        let _ = stop.send(true);
+
+        let mut reports = Vec::new();
+        for handle in handles {
+            // Jobs run on tasks of their own, so the loop itself can't panic.
+            if let Ok(report) = handle.await {
+                reports.push(report);
+            }
+        }
+        reports
+    }
+}
+
+impl Task {
+    async fn run(self, mut stopped: watch::Receiver<bool>) -> TaskReport {
+        let mut report = TaskReport {
+            name: self.name,
+            ..Default::default()
+        };
+        let mut last = None;
+        while let Some(wait) = self.schedule.wait(last) {
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = stopped.wait_for(|stopped| *stopped) => break,
+            }
+            last = Some(Instant::now());
+            report.runs += 1;
+            let error = match tokio::spawn((self.job)()).await {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => e.to_string(),
+                Err(e) => e.to_string(),
+            };
+            event!(warn, "scheduled task {} failed: {}", report.name, error);
+            report.failures += 1;
+            report.last_error = Some(error);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WhoopError;
+    use chrono::{FixedOffset, LocalResult, NaiveDateTime, Utc};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// New York around the end of daylight saving time in 2024: UTC-4 until
+    /// 2024-11-03T06:00Z, UTC-5 after.
+    #[derive(Debug, Clone, Copy)]
+    struct NewYork;
+
+    impl TimeZone for NewYork {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            NewYork
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&midnight(*local))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let fits = |offset: FixedOffset| {
+                let utc = *local - offset;
+                (self.offset_from_utc_datetime(&utc) == offset).then_some(offset)
+            };
+            let edt = fits(FixedOffset::west_opt(4 * 3600).unwrap());
+            let est = fits(FixedOffset::west_opt(5 * 3600).unwrap());
+            match (edt, est) {
+                (Some(edt), Some(est)) => LocalResult::Ambiguous(edt, est),
+                (Some(offset), None) | (None, Some(offset)) => LocalResult::Single(offset),
+                (None, None) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&midnight(*utc))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let end = NaiveDate::from_ymd_opt(2024, 11, 3)
+                .unwrap()
+                .and_hms_opt(6, 0, 0)
+                .unwrap();
+            let hours = if *utc < end { 4 } else { 5 };
+            FixedOffset::west_opt(hours * 3600).unwrap()
+        }
+    }
+
+    fn at(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_finds_the_next_cron_time() {
+        let weekdays = Cron::parse("30 7 * * 1-5").unwrap();
+        // A Friday morning, after 7:30.
+        let next = weekdays.next_after(&at("2024-03-01T08:00:00Z")).unwrap();
+        assert_eq!(next, at("2024-03-04T07:30:00Z"));
+
+        let quarterly = Cron::parse("*/15 * * * *").unwrap();
+        let next = quarterly.next_after(&at("2024-03-01T08:00:00Z")).unwrap();
+        assert_eq!(next, at("2024-03-01T08:15:00Z"));
+
+        // The 13th or any Friday.
+        let either = Cron::parse("0 9 13 * 5").unwrap();
+        let next = either.next_after(&at("2024-03-02T00:00:00Z")).unwrap();
+        assert_eq!(next, at("2024-03-08T09:00:00Z"));
+
+        let sunday = Cron::parse("0 0 * * 7").unwrap();
+        assert_eq!(sunday, Cron::parse("@weekly").unwrap());
+
+        assert!(
+            Cron::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(&at("2024-01-01T00:00:00Z"))
+                .is_none()
+        );
+        assert!(Cron::parse("60 * * * *").is_none());
+        assert!(Cron::parse("0 * * *").is_none());
+        assert_eq!(
+            Schedule::parse("every 15m"),
+            Some(Schedule::Every(Duration::from_secs(900)))
+        );
+        assert!(Schedule::parse("every fortnight").is_none());
+
+        // 1:20 the second time round, after clocks fell back from 2:00 EDT.
+        let after = at("2024-11-03T06:20:00Z").with_timezone(&NewYork);
+        let next = quarterly.next_after(&after).unwrap();
+        assert_eq!(next, at("2024-11-03T06:30:00Z"));
+        assert!(Schedule::parse("every 999999999999999999d").is_none());
+        let far = Schedule::parse("every 200000000000000d").unwrap();
+        assert_eq!(far.wait(None), Some(Duration::ZERO));
+        assert_eq!(far.wait(Some(Instant::now())), None);
+    }
+
+    // Printing a panic blocks its thread, so others keep the schedule going.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_failing_tasks_dont_stop_the_others() {
+        let counted = Arc::new(AtomicU32::new(0));
+        let failed = Arc::new(AtomicU32::new(0));
+        let panicked = Arc::new(AtomicU32::new(0));
+        let (count, fail, boom) = (
+            Arc::clone(&counted),
+            Arc::clone(&failed),
+            Arc::clone(&panicked),
+        );
+        let every = Schedule::Every(Duration::from_millis(10));
+        // Stops once every task has run, however slow the machine; panics
+        // print a backtrace, which can take a while on a busy one.
+        let all_ran = {
+            let (counted, failed, panicked) = (counted.clone(), failed.clone(), panicked.clone());
+            async move {
+                while counted.load(Ordering::SeqCst) < 2
+                    || failed.load(Ordering::SeqCst) < 1
+                    || panicked.load(Ordering::SeqCst) < 1
+                {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }
+        };
+        let reports = Scheduler::new()
+            .with_task("count", every.clone(), move || {
+                let count = Arc::clone(&count);
+                async move {
+                    count.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .with_task("fail", every.clone(), move || {
+                let fail = Arc::clone(&fail);
+                async move {
+                    fail.fetch_add(1, Ordering::SeqCst);
+                    Err(WhoopError::Unknown("offline".to_string(), None))
+                }
+            })
+            .with_task("panic", every, move || {
+                let boom = Arc::clone(&boom);
+                async move {
+                    boom.fetch_add(1, Ordering::SeqCst);
+                    panic!("boom")
+                }
+            })
+            .run(async {
+                let _ = tokio::time::timeout(Duration::from_secs(30), all_ran).await;
+            })
+            .await;
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports.iter().all(|r| r.runs >= 1));
+        assert!(reports[0].runs >= 2);
+        assert_eq!(reports[0].runs, counted.load(Ordering::SeqCst));
+        assert_eq!(reports[0].failures, 0);
+        assert_eq!(reports[1].failures, reports[1].runs);
+        assert_eq!(
+            reports[1].last_error.as_deref(),
+            Some("Unknown error: offline")
+        );
+        assert_eq!(reports[2].failures, reports[2].runs);
+    }
+}
@@ -0,0 +1,169 @@
+//! Waiting for WHOOP to score a record.
+//!
+//! A sleep, workout or recovery fetched right after its webhook is usually
+//! still `PENDING_SCORE`. The `wait_for_scored_*` methods on
+//! [`WhoopClient`](crate::WhoopClient) poll it with backoff, as a
+//! [`ScorePolicy`] says, until it's scored:
+//!
+//! ```no_run
+//! # async fn run(client: whoopsy::WhoopClient, id: uuid::Uuid) -> whoopsy::Result<()> {
+//! use whoopsy::scoring::ScorePolicy;
+//!
+//! let sleep = client.wait_for_scored_sleep(id, &ScorePolicy::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A record that turns out unscorable, or is still pending when the policy
+//! times out, fails with [`WhoopError::NotScored`].
+
+use crate::error::{Result, WhoopError};
+use crate::models::ScoreState;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How long to wait between polls, and for how long overall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScorePolicy {
+    /// Before the second poll; each wait after doubles, up to `max_delay`.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// From the first poll until giving up.
+    pub timeout: Duration,
+}
+
+impl Default for ScorePolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(60),
+            timeout: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl ScorePolicy {
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Fetches until `state` of the record is no longer pending.
+pub(crate) async fn wait<T, F>(
+    policy: &ScorePolicy,
+    state: impl Fn(&T) -> &ScoreState,
+    fetch: impl Fn() -> F,
+) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + policy.timeout;
+    let mut delay = policy.initial_delay;
+    loop {
+        let record = fetch().await?;
+        match state(&record) {
+            ScoreState::Scored => return Ok(record),
+            ScoreState::Unscorable => return Err(WhoopError::NotScored(ScoreState::Unscorable)),
+            ScoreState::PendingScore => {}
+        }
+
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(WhoopError::NotScored(ScoreState::PendingScore));
+        }
+        tokio::time::sleep(delay.min(left)).await;
+        delay = (delay * 2).min(policy.max_delay);
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::test_support::MockWhoop;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, ResponseTemplate};
+
+    fn quick() -> ScorePolicy {
+        ScorePolicy::default()
+            .with_initial_delay(Duration::from_millis(10))
+            .with_timeout(Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn test_polls_until_the_sleep_is_scored() {
+        let mock = MockWhoop::start().await;
+        let mut pending = mock.fixtures().sleeps[0].clone();
+        let route = format!("/v2/activity/sleep/{}", pending.id);
+        pending.score_state = ScoreState::PendingScore;
+        pending.score = None;
+        Mock::given(path(&route))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&pending))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+
+        let sleep = mock
+            .client()
+            .wait_for_scored_sleep(pending.id, &quick())
+            .await
+            .unwrap();
+        assert!(sleep.score.is_some());
+        assert_eq!(mock.server().received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_unscorable_and_timed_out_records() {
+        let mock = MockWhoop::start().await;
+        #[cfg(feature = "workouts")]
+        {
+            let mut workout = mock.fixtures().workouts[0].clone();
+            let route = format!("/v2/activity/workout/{}", workout.id);
+            workout.score_state = ScoreState::Unscorable;
+            Mock::given(path(&route))
+                .respond_with(ResponseTemplate::new(200).set_body_json(&workout))
+                .with_priority(1)
+                .mount(mock.server())
+                .await;
+            let error = mock
+                .client()
+                .wait_for_scored_workout(workout.id, &quick())
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error,
+                WhoopError::NotScored(ScoreState::Unscorable)
+            ));
+        }
+
+        let mut recovery = mock.fixtures().recoveries[0].clone();
+        let route = format!("/v2/cycle/{}/recovery", recovery.cycle_id);
+        recovery.score_state = ScoreState::PendingScore;
+        Mock::given(path(&route))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&recovery))
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+        let policy = quick().with_timeout(Duration::from_millis(50));
+        let error = mock
+            .client()
+            .wait_for_scored_recovery(recovery.cycle_id, &policy)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            WhoopError::NotScored(ScoreState::PendingScore)
+        ));
+    }
+}
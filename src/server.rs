@@ -0,0 +1,82 @@
+//! Minimal HTTP/1 server behind the feature-gated endpoints.
+//!
+//! Handlers are plain functions from a request path to a response body, reading
+//! whatever state their owner keeps fresh in the background.
+
+use crate::error::Result;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// A body and its content type. Handlers return `None` for a 404.
+pub(crate) struct Body {
+    pub content_type: &'static str,
+    pub content: String,
+}
+
+/// Serves GET requests on `addr` until the listener fails.
+#[cfg(any(feature = "prometheus", feature = "ics-server"))]
+pub(crate) async fn serve<F>(addr: SocketAddr, handler: F) -> Result<()>
+where
+    F: Fn(&str) -> Option<Body> + Clone + Send + Sync + 'static,
+{
+    serve_requests(addr, move |request| {
+        let body = handler(request.uri().path());
+        async move { respond(body) }
+    })
+    .await
+}
+
+/// Serves whole requests on `addr`, for handlers that need the method, query
+/// or body, until the listener fails.
+pub(crate) async fn serve_requests<F, R>(addr: SocketAddr, handler: F) -> Result<()>
+where
+    F: Fn(Request<Incoming>) -> R + Clone + Send + Sync + 'static,
+    R: Future<Output = Response<Full<Bytes>>> + Send + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request: Request<Incoming>| {
+                let response = handler(request);
+                async move { Ok::<_, Infallible>(response.await) }
+            });
+            // A client hanging up mid-request only ends its own connection.
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+pub(crate) fn respond(body: Option<Body>) -> Response<Full<Bytes>> {
+    match body {
+        Some(body) => reply(StatusCode::OK, body.content_type, body.content),
+        None => reply(
+            StatusCode::NOT_FOUND,
+            "text/plain",
+            "not found\n".to_string(),
+        ),
+    }
+}
+
+pub(crate) fn reply(
+    status: StatusCode,
+    content_type: &'static str,
+    content: String,
+) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Full::new(Bytes::from(content)))
+        .expect("static response parts are valid")
+}
@@ -0,0 +1,423 @@
+//! Multi-month histories driven by simple physiological models, with the ground
+//! truth kept alongside, so analytics can be checked against known answers.
+//!
+//! - Training follows a plan: three build weeks, then an easier week.
+//! - Fitness and fatigue are exponentially weighted daily strain over 42 and 7
+//!   days. The morning's recovery follows form (fitness minus fatigue), the
+//!   previous day's strain and the night's sleep.
+//! - Weekend nights start later and run longer.
+//! - Occasional illnesses raise resting heart rate, respiratory rate and skin
+//!   temperature, suppress HRV and recovery, and replace training with rest.
+//!
+//! Behind the `fake` feature, sharing its record builders.
+
+use crate::fake::{Dataset, Generator, hours, minutes, normal, uuid};
+use crate::models::*;
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, Weekday};
+use fastrand::Rng;
+use serde::Serialize;
+
+const FITNESS_DAYS: f32 = 42.0;
+const FATIGUE_DAYS: f32 = 7.0;
+
+/// What the simulation knew about one day when it generated it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TruthDay {
+    /// The day the cycle started, in local time.
+    pub date: NaiveDate,
+    /// The session strain the plan called for; zero on rest days and while ill.
+    pub planned_strain: f32,
+    /// Long-term load going into the day.
+    pub fitness: f32,
+    /// Short-term load going into the day.
+    pub fatigue: f32,
+    pub ill: bool,
+    /// Whether the night before was a Friday or Saturday night.
+    pub weekend_night: bool,
+}
+
+impl TruthDay {
+    /// Positive when rested relative to the load one is used to.
+    pub fn form(&self) -> f32 {
+        self.fitness - self.fatigue
+    }
+}
+
+/// A run of consecutive sick days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Illness {
+    pub start: NaiveDate,
+    pub days: u32,
+}
+
+/// A simulated history: the records, oldest first, and the truth behind them.
+#[derive(Debug, Clone, Default)]
+pub struct Simulated {
+    pub dataset: Dataset,
+    pub truth: Vec<TruthDay>,
+}
+
+impl Simulated {
+    pub fn illnesses(&self) -> Vec<Illness> {
+        let mut illnesses: Vec<Illness> = Vec::new();
+        let mut previous_ill = false;
+        for day in &self.truth {
+            match (day.ill, previous_ill, illnesses.last_mut()) {
+                (true, true, Some(illness)) => illness.days += 1,
+                (true, _, _) => illnesses.push(Illness {
+                    start: day.date,
+                    days: 1,
+                }),
+                _ => {}
+            }
+            previous_ill = day.ill;
+        }
+        illnesses
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    start: NaiveDate,
+    days: u32,
+    seed: u64,
+    user_id: i64,
+    timezone_offset: FixedOffset,
+    training_days: Vec<Weekday>,
+    illnesses_per_year: f32,
+    weekend_shift_minutes: f64,
+}
+
+impl Simulation {
+    /// `days` days, the first night falling asleep on the evening before `start`.
+    pub fn new(start: NaiveDate, days: u32) -> Self {
+        Self {
+            start,
+            days,
+            seed: 42,
+            user_id: 10129,
+            timezone_offset: FixedOffset::west_opt(5 * 3600).unwrap(),
+            training_days: vec![Weekday::Mon, Weekday::Tue, Weekday::Thu, Weekday::Sat],
+            illnesses_per_year: 3.0,
+            weekend_shift_minutes: 75.0,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: i64) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    pub fn with_timezone_offset(mut self, offset: FixedOffset) -> Self {
+        self.timezone_offset = offset;
+        self
+    }
+
+    /// The weekdays with a planned session. The one falling last in the week is
+    /// the long, hard one.
+    pub fn with_training_days(mut self, days: impl IntoIterator<Item = Weekday>) -> Self {
+        self.training_days = days.into_iter().collect();
+        self
+    }
+
+    pub fn with_illnesses_per_year(mut self, illnesses_per_year: f32) -> Self {
+        self.illnesses_per_year = illnesses_per_year;
+        self
+    }
+
+    /// How much later weekend nights start. They run half as long again past that.
+    pub fn with_weekend_shift_minutes(mut self, minutes: f64) -> Self {
+        self.weekend_shift_minutes = minutes;
+        self
+    }
+
+    pub fn run(&self) -> Simulated {
+        let mut rng = Rng::with_seed(self.seed);
+        let records = Generator::new(self.start, self.days).with_user_id(self.user_id);
+        let offset = self.timezone_offset.to_string();
+        let sick = self.sick_days(&mut rng);
+        let long_day = self
+            .training_days
+            .iter()
+            .max_by_key(|d| d.num_days_from_monday())
+            .copied();
+
+        let baseline_need = hours(7.75);
+        let baseline_hrv = normal(&mut rng, 55.0, 8.0) as f32;
+        let baseline_rhr = normal(&mut rng, 54.0, 3.0) as f32;
+
+        // Warmed up as if the plan had been followed for a while.
+        let (mut fitness, mut fatigue) = (10.5_f32, 10.5_f32);
+        let mut previous_strain = 10.5_f32;
+        let mut debt = 0_i64;
+        let mut onset = self.onset(&mut rng, self.start - Duration::days(1));
+        let mut out = Simulated::default();
+
+        for (day, &ill) in sick.iter().enumerate() {
+            let date = self.start + Duration::days(day as i64);
+            let id = 2_000_000 + day as i64;
+            let weekend_night = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+
+            let mut planned_strain = 0.0;
+            if !ill && self.training_days.contains(&date.weekday()) {
+                let easy_week = (day / 7) % 4 == 3;
+                planned_strain = if Some(date.weekday()) == long_day {
+                    15.0
+                } else {
+                    12.5
+                };
+                if easy_week {
+                    planned_strain *= 0.75;
+                }
+            }
+            out.truth.push(TruthDay {
+                date,
+                planned_strain,
+                fitness,
+                fatigue,
+                ill,
+                weekend_night,
+            });
+
+            // The night.
+            let mut in_bed = normal(&mut rng, 7.7, 0.45);
+            if weekend_night {
+                in_bed += self.weekend_shift_minutes * 1.5 / 60.0;
+            }
+            if ill {
+                in_bed += 0.75;
+            }
+            let start = onset;
+            let wake = start + Duration::milliseconds(hours(in_bed.clamp(4.5, 11.5)));
+            let need = SleepNeeded {
+                baseline_milli: baseline_need,
+                need_from_sleep_debt_milli: debt / 2,
+                need_from_recent_strain_milli: hours(
+                    f64::from(previous_strain - 10.0).max(0.0) * 0.08,
+                ),
+                need_from_recent_nap_milli: 0,
+            };
+            let mut sleep = records.sleep(&mut rng, id, start, wake, &need, &offset);
+            let score = sleep.score.as_mut().unwrap();
+            let slept = score.stage_summary.total_sleep_time_milli();
+            let performance = (slept as f32 / need.total_milli() as f32 * 100.0).min(100.0);
+            debt = (debt + need.total_milli() - slept).clamp(0, hours(4.0));
+            score.respiratory_rate =
+                Some(normal(&mut rng, if ill { 17.0 } else { 15.4 }, 0.3) as f32);
+
+            // The morning.
+            let recovery_score = (62.0 + (fitness - fatigue) * 3.0 + (performance - 85.0) * 0.5
+                - (previous_strain - 11.0) * 1.5
+                - if ill { 25.0 } else { 0.0 }
+                + normal(&mut rng, 0.0, 7.0) as f32)
+                .clamp(1.0, 99.0)
+                .round();
+            let illness = if ill { 1.0 } else { 0.0 };
+            out.dataset.recoveries.push(Recovery {
+                cycle_id: id,
+                sleep_id: sleep.id,
+                user_id: self.user_id,
+                created_at: wake + Duration::minutes(4),
+                updated_at: wake + Duration::minutes(6),
+                score_state: ScoreState::Scored,
+                score: Some(RecoveryScore {
+                    user_calibrating: false,
+                    recovery_score,
+                    resting_heart_rate: (baseline_rhr - (recovery_score - 50.0) * 0.06
+                        + illness * 6.0
+                        + normal(&mut rng, 0.0, 1.0) as f32)
+                        .round(),
+                    hrv_rmssd_milli: baseline_hrv
+                        * (0.7 + recovery_score / 170.0)
+                        * (1.0 - illness * 0.15)
+                        + normal(&mut rng, 0.0, 2.5) as f32,
+                    spo2_percentage: Some(
+                        normal(&mut rng, 96.5 - f64::from(illness) * 1.5, 0.6).min(100.0) as f32,
+                    ),
+                    skin_temp_celsius: Some(
+                        normal(&mut rng, 33.6 + f64::from(illness) * 0.6, 0.15) as f32,
+                    ),
+                }),
+            });
+
+            // The day.
+            let mut strain = normal(&mut rng, if ill { 4.5 } else { 7.0 }, 1.0) as f32;
+            let mut kilojoule = normal(&mut rng, 8_400.0, 500.0) as f32;
+            if planned_strain > 0.0 {
+                // Green days go a little harder than planned, red ones a little easier.
+                let session = (planned_strain
+                    + (recovery_score - 60.0) / 30.0
+                    + normal(&mut rng, 0.0, 0.8) as f32)
+                    .clamp(4.0, 19.5);
+                let workout = self.workout(&mut rng, wake, session, &offset);
+                kilojoule += workout.score.as_ref().unwrap().kilojoule;
+                strain = strain.max(session) + session * 0.15;
+                out.dataset.workouts.push(workout);
+            }
+            let strain = strain.clamp(0.1, 21.0);
+            fitness += (strain - fitness) / FITNESS_DAYS;
+            fatigue += (strain - fatigue) / FATIGUE_DAYS;
+            previous_strain = strain;
+
+            let next_onset = self.onset(&mut rng, date);
+            let end = (day + 1 < self.days as usize).then_some(next_onset);
+            out.dataset.cycles.push(Cycle {
+                id,
+                user_id: self.user_id,
+                created_at: start,
+                updated_at: end.unwrap_or(wake + Duration::hours(12)),
+                start,
+                end,
+                timezone_offset: offset.clone(),
+                score_state: ScoreState::Scored,
+                score: Some(CycleScore {
+                    strain,
+                    kilojoule,
+                    average_heart_rate: (62.0 + strain * 1.2 + illness * 5.0).round() as i32,
+                    max_heart_rate: (120.0 + strain * 3.5).round() as i32,
+                }),
+            });
+            out.dataset.sleeps.push(sleep);
+            onset = next_onset;
+        }
+        out
+    }
+
+    /// Which days are spent ill: episodes of three to seven days.
+    fn sick_days(&self, rng: &mut Rng) -> Vec<bool> {
+        let mut sick = vec![false; self.days as usize];
+        let mut day = 0;
+        while day < sick.len() {
+            if rng.f32() < self.illnesses_per_year / 365.0 {
+                let length = rng.usize(3..=7);
+                let end = (day + length).min(sick.len());
+                sick[day..end].fill(true);
+                // At least a couple of healthy weeks before the next one.
+                day += length + 14;
+            } else {
+                day += 1;
+            }
+        }
+        sick
+    }
+
+    /// When sleep starts on the evening of `date`.
+    fn onset(&self, rng: &mut Rng, date: NaiveDate) -> chrono::DateTime<chrono::Utc> {
+        let weekend = matches!(date.weekday(), Weekday::Fri | Weekday::Sat);
+        let shift = if weekend {
+            self.weekend_shift_minutes
+        } else {
+            0.0
+        };
+        let local = date.and_hms_opt(22, 40, 0).unwrap() + minutes(normal(rng, shift, 20.0));
+        local
+            .and_local_timezone(self.timezone_offset)
+            .unwrap()
+            .to_utc()
+    }
+
+    fn workout(
+        &self,
+        rng: &mut Rng,
+        after: chrono::DateTime<chrono::Utc>,
+        strain: f32,
+        offset: &str,
+    ) -> WorkoutV2 {
+        let start = after + minutes(normal(rng, 540.0, 60.0).max(30.0));
+        let duration = (strain * 5.0 + normal(rng, 0.0, 8.0) as f32).max(20.0) as i64;
+        let milli = duration * 60_000;
+        // Harder sessions spend more time in the upper zones.
+        let hard = ((strain - 8.0) / 10.0).clamp(0.0, 1.0) as f64;
+        let shares = [
+            0.04,
+            0.2 - 0.1 * hard,
+            0.38 - 0.1 * hard,
+            0.26 + 0.06 * hard,
+            0.1 + 0.1 * hard,
+            0.02 + 0.04 * hard,
+        ];
+        let zone = |i: usize| (milli as f64 * shares[i]) as i64;
+
+        WorkoutV2 {
+            id: uuid(rng),
+            v1_id: None,
+            user_id: self.user_id,
+            created_at: start + Duration::minutes(duration + 3),
+            updated_at: start + Duration::minutes(duration + 8),
+            start,
+            end: start + Duration::minutes(duration),
+            timezone_offset: offset.to_string(),
+            sport_name: "running".to_string(),
+            score_state: ScoreState::Scored,
+            score: Some(WorkoutScore {
+                strain,
+                average_heart_rate: (110.0 + strain * 2.5) as i32,
+                max_heart_rate: (140.0 + strain * 2.2) as i32,
+                kilojoule: duration as f32 * normal(rng, 40.0, 4.0) as f32,
+                percent_recorded: 100.0,
+                distance_meter: Some(duration as f32 * normal(rng, 170.0, 15.0) as f32),
+                altitude_gain_meter: None,
+                altitude_change_meter: None,
+                zone_durations: ZoneDurations {
+                    zone_zero_milli: zone(0),
+                    zone_one_milli: zone(1),
+                    zone_two_milli: zone(2),
+                    zone_three_milli: zone(3),
+                    zone_four_milli: zone(4),
+                    zone_five_milli: zone(5),
+                },
+            }),
+            sport_id: Some(0),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "analytics"))]
+mod tests {
+    use super::*;
+    use crate::analytics::correlation::pearson;
+
+    #[test]
+    fn test_records_follow_the_ground_truth() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let simulated = Simulation::new(start, 240)
+            .with_illnesses_per_year(8.0)
+            .run();
+        let illnesses = simulated.illnesses();
+        assert!(!illnesses.is_empty());
+        assert!(illnesses.iter().all(|i| (3..=7).contains(&i.days)));
+
+        let (mut ill_rhr, mut healthy_rhr) = (Vec::new(), Vec::new());
+        let (mut form, mut recovery) = (Vec::new(), Vec::new());
+        for (truth, record) in simulated.truth.iter().zip(&simulated.dataset.recoveries) {
+            let score = record.score.as_ref().unwrap();
+            if truth.ill {
+                ill_rhr.push(score.resting_heart_rate);
+            } else {
+                healthy_rhr.push(score.resting_heart_rate);
+                form.push(truth.form());
+                recovery.push(score.recovery_score);
+            }
+        }
+        let mean = |v: &[f32]| v.iter().sum::<f32>() / v.len() as f32;
+        assert!(mean(&ill_rhr) > mean(&healthy_rhr) + 4.0);
+        assert!(pearson(&form, &recovery).unwrap() > 0.2);
+
+        let sleep_hours = |weekend: bool| {
+            let nights: Vec<f32> = simulated
+                .truth
+                .iter()
+                .zip(&simulated.dataset.sleeps)
+                .filter(|(t, _)| t.weekend_night == weekend && !t.ill)
+                .map(|(_, s)| (s.end - s.start).num_minutes() as f32 / 60.0)
+                .collect();
+            mean(&nights)
+        };
+        assert!(sleep_hours(true) > sleep_hours(false) + 1.0);
+    }
+}
@@ -0,0 +1,200 @@
+//! Canonical sport identifiers.
+//!
+//! `sport_name` comes back as `running` from one endpoint and `Running` or
+//! `Hiking/Rucking` from another. [`normalize`] maps any of those to one
+//! identifier, the name of a [`Sport`] where it's a known one, so workouts
+//! group the same whichever way they were reported. [`Sport::category`] groups
+//! sports further into [`SportCategory`].
+
+use crate::models::WorkoutV2;
+use serde::{Deserialize, Serialize};
+
+/// How a sport trains the body, roughly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SportCategory {
+    Cardio,
+    Strength,
+    Recovery,
+    Other,
+}
+
+macro_rules! sports {
+    ($($sport:ident => $name:literal, $category:ident;)*) => {
+        /// A sport WHOOP reports.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        pub enum Sport {
+            $($sport,)*
+        }
+
+        impl Sport {
+            pub const ALL: &[Sport] = &[$(Sport::$sport,)*];
+
+            /// The canonical identifier, e.g. `hiking-rucking`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Sport::$sport => $name,)*
+                }
+            }
+
+            pub fn category(self) -> SportCategory {
+                match self {
+                    $(Sport::$sport => SportCategory::$category,)*
+                }
+            }
+        }
+    };
+}
+
+sports! {
+    Activity => "activity", Other;
+    Running => "running", Cardio;
+    Cycling => "cycling", Cardio;
+    Baseball => "baseball", Other;
+    Basketball => "basketball", Cardio;
+    Rowing => "rowing", Cardio;
+    Fencing => "fencing", Cardio;
+    FieldHockey => "field-hockey", Cardio;
+    Football => "football", Cardio;
+    Golf => "golf", Other;
+    IceHockey => "ice-hockey", Cardio;
+    Lacrosse => "lacrosse", Cardio;
+    Rugby => "rugby", Cardio;
+    Sailing => "sailing", Other;
+    Skiing => "skiing", Cardio;
+    Soccer => "soccer", Cardio;
+    Softball => "softball", Other;
+    Squash => "squash", Cardio;
+    Swimming => "swimming", Cardio;
+    Tennis => "tennis", Cardio;
+    TrackAndField => "track-and-field", Cardio;
+    Volleyball => "volleyball", Cardio;
+    WaterPolo => "water-polo", Cardio;
+    Wrestling => "wrestling", Strength;
+    Boxing => "boxing", Cardio;
+    Dance => "dance", Cardio;
+    Pilates => "pilates", Strength;
+    Yoga => "yoga", Recovery;
+    Weightlifting => "weightlifting", Strength;
+    CrossCountrySkiing => "cross-country-skiing", Cardio;
+    FunctionalFitness => "functional-fitness", Strength;
+    Duathlon => "duathlon", Cardio;
+    Gymnastics => "gymnastics", Strength;
+    HikingRucking => "hiking-rucking", Cardio;
+    HorsebackRiding => "horseback-riding", Other;
+    Kayaking => "kayaking", Cardio;
+    MartialArts => "martial-arts", Cardio;
+    MountainBiking => "mountain-biking", Cardio;
+    Powerlifting => "powerlifting", Strength;
+    RockClimbing => "rock-climbing", Strength;
+    Paddleboarding => "paddleboarding", Cardio;
+    Triathlon => "triathlon", Cardio;
+    Walking => "walking", Cardio;
+    Surfing => "surfing", Cardio;
+    Elliptical => "elliptical", Cardio;
+    Stairmaster => "stairmaster", Cardio;
+    Meditation => "meditation", Recovery;
+    Other => "other", Other;
+    Spin => "spin", Cardio;
+    JiuJitsu => "jiu-jitsu", Strength;
+    Hiit => "hiit", Cardio;
+    Spinning => "spinning", Cardio;
+    Snowboarding => "snowboarding", Cardio;
+    MotorRacing => "motor-racing", Other;
+    Stretching => "stretching", Recovery;
+    Climber => "climber", Cardio;
+    Pickleball => "pickleball", Cardio;
+    Padel => "padel", Cardio;
+    Sauna => "sauna", Recovery;
+    IceBath => "ice-bath", Recovery;
+}
+
+/// Other spellings of a sport, after slugging.
+const ALIASES: &[(&str, Sport)] = &[
+    ("hiking", Sport::HikingRucking),
+    ("rucking", Sport::HikingRucking),
+    ("weight-lifting", Sport::Weightlifting),
+    ("weight-training", Sport::Weightlifting),
+    ("power-lifting", Sport::Powerlifting),
+    ("paddle-boarding", Sport::Paddleboarding),
+    ("stand-up-paddleboarding", Sport::Paddleboarding),
+    ("stair-master", Sport::Stairmaster),
+    ("stair-climber", Sport::Stairmaster),
+    ("jiu-jitsu-bjj", Sport::JiuJitsu),
+    ("bjj", Sport::JiuJitsu),
+    ("jiujitsu", Sport::JiuJitsu),
+    ("high-intensity-interval-training", Sport::Hiit),
+    ("track-field", Sport::TrackAndField),
+    ("mountain-bike", Sport::MountainBiking),
+    ("cross-country-ski", Sport::CrossCountrySkiing),
+    ("ice-baths", Sport::IceBath),
+    ("cold-plunge", Sport::IceBath),
+];
+
+impl Sport {
+    /// The sport a `sport_name` refers to, however it's written.
+    pub fn from_name(name: &str) -> Option<Sport> {
+        let slug = slug(name);
+        Sport::ALL
+            .iter()
+            .copied()
+            .find(|sport| sport.name() == slug)
+            .or_else(|| {
+                ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == slug)
+                    .map(|(_, sport)| *sport)
+            })
+    }
+
+    pub fn of(workout: &WorkoutV2) -> Option<Sport> {
+        Sport::from_name(&workout.sport_name)
+    }
+}
+
+/// The canonical identifier for a `sport_name`. Sports this crate doesn't
+/// know yet keep their name, lowercased with words joined by `-`.
+pub fn normalize(name: &str) -> String {
+    match Sport::from_name(name) {
+        Some(sport) => sport.name().to_string(),
+        None => slug(name),
+    }
+}
+
+/// The category of a `sport_name`, [`SportCategory::Other`] when unknown.
+pub fn category(name: &str) -> SportCategory {
+    Sport::from_name(name).map_or(SportCategory::Other, Sport::category)
+}
+
+/// Lowercase with `&` spelled out and words joined by single dashes.
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .replace('&', " and ")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_spellings() {
+        assert_eq!(normalize("Hiking/Rucking"), "hiking-rucking");
+        assert_eq!(normalize("Track & Field"), "track-and-field");
+        assert_eq!(normalize("Weight Lifting"), "weightlifting");
+        assert_eq!(normalize("HIIT"), "hiit");
+        assert_eq!(normalize("Underwater Hockey"), "underwater-hockey");
+        assert_eq!(Sport::from_name("Ice_Bath"), Some(Sport::IceBath));
+        assert_eq!(category("Functional Fitness"), SportCategory::Strength);
+        assert_eq!(category("Underwater Hockey"), SportCategory::Other);
+        assert!(
+            Sport::ALL
+                .iter()
+                .all(|s| Sport::from_name(s.name()) == Some(*s))
+        );
+    }
+}
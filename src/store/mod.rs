@@ -0,0 +1,95 @@
+//! Local storage for synced records, so apps can work from a cache instead of the API.
+//!
+//! [`Store`] is what the sync engine and local queries need from a backend.
+//! `SqliteStore` implements it with the default `sqlite` feature, and so does
+//! `SledStore` with the `sled` feature, for where SQLite isn't an option.
+//! [`PostgresStore`] is a sink for services syncing many users, written to
+//! directly rather than through it.
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sled")]
+pub mod sled;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::body::BodySnapshot;
+use crate::error::Result;
+use crate::merge::{Merged, Versioned};
+use crate::models::*;
+use crate::pagination::Checkpoint;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use std::ops::Range;
+
+#[cfg(feature = "sled")]
+pub use self::sled::SledStore;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+/// A record type stores keep, under its resource name.
+pub trait Stored: Versioned + DeserializeOwned {
+    /// As in [`Resource::name`](crate::sync::Resource::name).
+    const RESOURCE: &'static str;
+}
+
+impl Stored for Cycle {
+    const RESOURCE: &'static str = "cycles";
+}
+
+impl Stored for Sleep {
+    const RESOURCE: &'static str = "sleep";
+}
+
+impl Stored for Recovery {
+    const RESOURCE: &'static str = "recovery";
+}
+
+impl Stored for WorkoutV2 {
+    const RESOURCE: &'static str = "workouts";
+}
+
+/// A local store of synced records.
+///
+/// Upserts merge by id and `updated_at`, see [`crate::merge`]. Range queries
+/// go by start time, recoveries by their cycle's start, or their creation
+/// when the cycle isn't stored, and return records oldest first. Resources
+/// are named as in [`Resource::name`](crate::sync::Resource::name).
+pub trait Store {
+    fn upsert_cycles(&mut self, cycles: &[Cycle]) -> Result<Merged>;
+    fn upsert_sleeps(&mut self, sleeps: &[Sleep]) -> Result<Merged>;
+    fn upsert_recoveries(&mut self, recoveries: &[Recovery]) -> Result<Merged>;
+    fn upsert_workouts(&mut self, workouts: &[WorkoutV2]) -> Result<Merged>;
+
+    fn cycles_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Cycle>>;
+    fn sleeps_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Sleep>>;
+    fn recoveries_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Recovery>>;
+    fn workouts_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<WorkoutV2>>;
+
+    /// Sports match by [`sport::normalize`](crate::sport::normalize).
+    fn workouts_by_sport(&self, sport: &str, range: Range<DateTime<Utc>>)
+    -> Result<Vec<WorkoutV2>>;
+    /// Scored recoveries below `score` percent.
+    fn recoveries_below(&self, score: f32) -> Result<Vec<Recovery>>;
+    /// The `n` newest records of a type, oldest first. Recoveries go by
+    /// creation.
+    fn latest<T: Stored>(&self, n: usize) -> Result<Vec<T>>;
+
+    fn insert_body_measurement(&self, snapshot: &BodySnapshot) -> Result<()>;
+    /// Oldest first.
+    fn body_measurements(&self) -> Result<Vec<BodySnapshot>>;
+    fn latest_body_measurement(&self) -> Result<Option<BodySnapshot>>;
+
+    /// Start of the oldest record of `resource` WHOOP may still change: one
+    /// pending a score or, for cycles, one that hasn't ended.
+    fn oldest_unsettled(&self, resource: &str) -> Result<Option<DateTime<Utc>>>;
+    /// How many records of `resource` start at or after `since`.
+    fn count_since(&self, resource: &str, since: DateTime<Utc>) -> Result<usize>;
+    fn watermark(&self, resource: &str) -> Result<Option<DateTime<Utc>>>;
+    fn set_watermark(&self, resource: &str, watermark: DateTime<Utc>) -> Result<()>;
+    fn cursor(&self, key: &str) -> Result<Option<Checkpoint>>;
+    fn set_cursor(&self, key: &str, checkpoint: &Checkpoint) -> Result<()>;
+    fn clear_cursor(&self, key: &str) -> Result<()>;
+}
@@ -0,0 +1,418 @@
+//! Postgres sink for backend services syncing many users into one database.
+//!
+//! Tables mirror the SQLite store but use native types: `TIMESTAMPTZ` timestamps,
+//! `UUID` ids and the full API record as `JSONB` in `raw`. Every table carries
+//! `user_id` with an index on `(user_id, start)`, so per-user range queries stay cheap.
+//! [`PostgresStore::migrate`] applies any migrations not yet recorded in
+//! `whoopsy_migrations` and is safe to call on every startup. Upserts follow
+//! [`crate::merge`]: a copy older than the stored one is skipped.
+
+use crate::error::Result;
+use crate::models::*;
+use tokio_postgres::types::{Json, ToSql};
+use tokio_postgres::{Client, NoTls};
+
+/// Schema migrations, applied in order. Never edit one that has shipped; append instead.
+const MIGRATIONS: &[&str] = &["
+CREATE TABLE whoop_cycles (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    start TIMESTAMPTZ NOT NULL,
+    \"end\" TIMESTAMPTZ,
+    timezone_offset TEXT NOT NULL,
+    score_state TEXT NOT NULL,
+    strain REAL,
+    kilojoule REAL,
+    average_heart_rate INTEGER,
+    max_heart_rate INTEGER,
+    raw JSONB NOT NULL
+);
+CREATE INDEX whoop_cycles_user_start ON whoop_cycles (user_id, start);
+
+CREATE TABLE whoop_sleeps (
+    id UUID PRIMARY KEY,
+    cycle_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    start TIMESTAMPTZ NOT NULL,
+    \"end\" TIMESTAMPTZ NOT NULL,
+    timezone_offset TEXT NOT NULL,
+    nap BOOLEAN NOT NULL,
+    score_state TEXT NOT NULL,
+    total_in_bed_time_milli INTEGER,
+    total_sleep_time_milli BIGINT,
+    sleep_needed_milli BIGINT,
+    respiratory_rate REAL,
+    sleep_performance_percentage REAL,
+    sleep_consistency_percentage REAL,
+    sleep_efficiency_percentage REAL,
+    raw JSONB NOT NULL
+);
+CREATE INDEX whoop_sleeps_user_start ON whoop_sleeps (user_id, start);
+
+CREATE TABLE whoop_recoveries (
+    cycle_id BIGINT PRIMARY KEY,
+    sleep_id UUID NOT NULL,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    score_state TEXT NOT NULL,
+    user_calibrating BOOLEAN,
+    recovery_score REAL,
+    resting_heart_rate REAL,
+    hrv_rmssd_milli REAL,
+    spo2_percentage REAL,
+    skin_temp_celsius REAL,
+    raw JSONB NOT NULL
+);
+CREATE INDEX whoop_recoveries_user_created ON whoop_recoveries (user_id, created_at);
+
+CREATE TABLE whoop_workouts (
+    id UUID PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    start TIMESTAMPTZ NOT NULL,
+    \"end\" TIMESTAMPTZ NOT NULL,
+    timezone_offset TEXT NOT NULL,
+    sport_name TEXT NOT NULL,
+    sport_id INTEGER,
+    score_state TEXT NOT NULL,
+    strain REAL,
+    average_heart_rate INTEGER,
+    max_heart_rate INTEGER,
+    kilojoule REAL,
+    percent_recorded REAL,
+    distance_meter REAL,
+    zone_zero_milli BIGINT,
+    zone_one_milli BIGINT,
+    zone_two_milli BIGINT,
+    zone_three_milli BIGINT,
+    zone_four_milli BIGINT,
+    zone_five_milli BIGINT,
+    raw JSONB NOT NULL
+);
+CREATE INDEX whoop_workouts_user_start ON whoop_workouts (user_id, start);
+"];
+
+const UPSERT_CYCLE: &str = "
+INSERT INTO whoop_cycles VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+ON CONFLICT (id) DO UPDATE SET
+    updated_at = EXCLUDED.updated_at, \"end\" = EXCLUDED.\"end\",
+    score_state = EXCLUDED.score_state, strain = EXCLUDED.strain,
+    kilojoule = EXCLUDED.kilojoule, average_heart_rate = EXCLUDED.average_heart_rate,
+    max_heart_rate = EXCLUDED.max_heart_rate, raw = EXCLUDED.raw
+WHERE EXCLUDED.updated_at >= whoop_cycles.updated_at";
+
+const UPSERT_SLEEP: &str = "
+INSERT INTO whoop_sleeps VALUES
+    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+ON CONFLICT (id) DO UPDATE SET
+    updated_at = EXCLUDED.updated_at, start = EXCLUDED.start, \"end\" = EXCLUDED.\"end\",
+    score_state = EXCLUDED.score_state,
+    total_in_bed_time_milli = EXCLUDED.total_in_bed_time_milli,
+    total_sleep_time_milli = EXCLUDED.total_sleep_time_milli,
+    sleep_needed_milli = EXCLUDED.sleep_needed_milli,
+    respiratory_rate = EXCLUDED.respiratory_rate,
+    sleep_performance_percentage = EXCLUDED.sleep_performance_percentage,
+    sleep_consistency_percentage = EXCLUDED.sleep_consistency_percentage,
+    sleep_efficiency_percentage = EXCLUDED.sleep_efficiency_percentage, raw = EXCLUDED.raw
+WHERE EXCLUDED.updated_at >= whoop_sleeps.updated_at";
+
+const UPSERT_RECOVERY: &str = "
+INSERT INTO whoop_recoveries VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+ON CONFLICT (cycle_id) DO UPDATE SET
+    sleep_id = EXCLUDED.sleep_id, updated_at = EXCLUDED.updated_at,
+    score_state = EXCLUDED.score_state, user_calibrating = EXCLUDED.user_calibrating,
+    recovery_score = EXCLUDED.recovery_score, resting_heart_rate = EXCLUDED.resting_heart_rate,
+    hrv_rmssd_milli = EXCLUDED.hrv_rmssd_milli, spo2_percentage = EXCLUDED.spo2_percentage,
+    skin_temp_celsius = EXCLUDED.skin_temp_celsius, raw = EXCLUDED.raw
+WHERE EXCLUDED.updated_at >= whoop_recoveries.updated_at";
+
+const UPSERT_WORKOUT: &str = "
+INSERT INTO whoop_workouts VALUES
+    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12,
+     $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23)
+ON CONFLICT (id) DO UPDATE SET
+    updated_at = EXCLUDED.updated_at, start = EXCLUDED.start, \"end\" = EXCLUDED.\"end\",
+    sport_name = EXCLUDED.sport_name, sport_id = EXCLUDED.sport_id,
+    score_state = EXCLUDED.score_state, strain = EXCLUDED.strain,
+    average_heart_rate = EXCLUDED.average_heart_rate, max_heart_rate = EXCLUDED.max_heart_rate,
+    kilojoule = EXCLUDED.kilojoule, percent_recorded = EXCLUDED.percent_recorded,
+    distance_meter = EXCLUDED.distance_meter, zone_zero_milli = EXCLUDED.zone_zero_milli,
+    zone_one_milli = EXCLUDED.zone_one_milli, zone_two_milli = EXCLUDED.zone_two_milli,
+    zone_three_milli = EXCLUDED.zone_three_milli, zone_four_milli = EXCLUDED.zone_four_milli,
+    zone_five_milli = EXCLUDED.zone_five_milli, raw = EXCLUDED.raw
+WHERE EXCLUDED.updated_at >= whoop_workouts.updated_at";
+
+/// One record's values, in its table's column order.
+type Params<'a> = Vec<Box<dyn ToSql + Sync + Send + 'a>>;
+
+/// Lands synced records in an existing Postgres database.
+pub struct PostgresStore {
+    client: Client,
+}
+
+impl PostgresStore {
+    /// Wraps a client the application already manages.
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Connects without TLS and drives the connection on a background task.
+    pub async fn connect(config: &str) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(config, NoTls).await?;
+        tokio::spawn(connection);
+        Ok(Self::new(client))
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Applies pending migrations, returning how many ran.
+    pub async fn migrate(&mut self) -> Result<usize> {
+        self.client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS whoopsy_migrations (
+                    version INTEGER PRIMARY KEY,
+                    applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+                )",
+            )
+            .await?;
+
+        let tx = self.client.transaction().await?;
+        // Serializes concurrent service instances migrating at startup.
+        tx.batch_execute("LOCK TABLE whoopsy_migrations IN EXCLUSIVE MODE")
+            .await?;
+        let applied: i32 = tx
+            .query_one(
+                "SELECT COALESCE(MAX(version), 0) FROM whoopsy_migrations",
+                &[],
+            )
+            .await?
+            .get(0);
+
+        let mut count = 0;
+        for (version, sql) in (1..).zip(MIGRATIONS) {
+            if version <= applied {
+                continue;
+            }
+            tx.batch_execute(sql).await?;
+            tx.execute(
+                "INSERT INTO whoopsy_migrations (version) VALUES ($1)",
+                &[&version],
+            )
+            .await?;
+            count += 1;
+        }
+        tx.commit().await?;
+
+        Ok(count)
+    }
+
+    /// Inserts or updates cycles by id in a single transaction.
+    pub async fn upsert_cycles(&mut self, cycles: &[Cycle]) -> Result<()> {
+        self.upsert(UPSERT_CYCLE, cycles, cycle_params).await
+    }
+
+    pub async fn upsert_sleeps(&mut self, sleeps: &[Sleep]) -> Result<()> {
+        self.upsert(UPSERT_SLEEP, sleeps, sleep_params).await
+    }
+
+    /// Inserts or updates recoveries by cycle id.
+    pub async fn upsert_recoveries(&mut self, recoveries: &[Recovery]) -> Result<()> {
+        self.upsert(UPSERT_RECOVERY, recoveries, recovery_params)
+            .await
+    }
+
+    pub async fn upsert_workouts(&mut self, workouts: &[WorkoutV2]) -> Result<()> {
+        self.upsert(UPSERT_WORKOUT, workouts, workout_params).await
+    }
+
+    /// Runs one prepared statement per record inside a transaction.
+    async fn upsert<'a, T>(
+        &mut self,
+        sql: &str,
+        records: &'a [T],
+        params: impl Fn(&'a T) -> Params<'a>,
+    ) -> Result<()> {
+        let tx = self.client.transaction().await?;
+        let statement = tx.prepare(sql).await?;
+        for record in records {
+            let values = params(record);
+            let refs: Vec<&(dyn ToSql + Sync)> = values
+                .iter()
+                .map(|v| v.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            tx.execute(&statement, &refs).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+fn cycle_params(cycle: &Cycle) -> Params<'_> {
+    let score = cycle.score.as_ref();
+    vec![
+        Box::new(cycle.id),
+        Box::new(cycle.user_id),
+        Box::new(cycle.created_at),
+        Box::new(cycle.updated_at),
+        Box::new(cycle.start),
+        Box::new(cycle.end),
+        Box::new(cycle.timezone_offset.clone()),
+        Box::new(cycle.score_state.as_str()),
+        Box::new(score.map(|s| s.strain)),
+        Box::new(score.map(|s| s.kilojoule)),
+        Box::new(score.map(|s| s.average_heart_rate)),
+        Box::new(score.map(|s| s.max_heart_rate)),
+        Box::new(Json(cycle)),
+    ]
+}
+
+fn sleep_params(sleep: &Sleep) -> Params<'_> {
+    let score = sleep.score.as_ref();
+    vec![
+        Box::new(sleep.id),
+        Box::new(sleep.cycle_id),
+        Box::new(sleep.user_id),
+        Box::new(sleep.created_at),
+        Box::new(sleep.updated_at),
+        Box::new(sleep.start),
+        Box::new(sleep.end),
+        Box::new(sleep.timezone_offset.clone()),
+        Box::new(sleep.nap),
+        Box::new(sleep.score_state.as_str()),
+        Box::new(score.map(|s| s.stage_summary.total_in_bed_time_milli)),
+        Box::new(score.map(|s| s.stage_summary.total_sleep_time_milli())),
+        Box::new(score.map(|s| s.sleep_needed.total_milli())),
+        Box::new(score.and_then(|s| s.respiratory_rate)),
+        Box::new(score.and_then(|s| s.sleep_performance_percentage)),
+        Box::new(score.and_then(|s| s.sleep_consistency_percentage)),
+        Box::new(score.and_then(|s| s.sleep_efficiency_percentage)),
+        Box::new(Json(sleep)),
+    ]
+}
+
+fn recovery_params(recovery: &Recovery) -> Params<'_> {
+    let score = recovery.score.as_ref();
+    vec![
+        Box::new(recovery.cycle_id),
+        Box::new(recovery.sleep_id),
+        Box::new(recovery.user_id),
+        Box::new(recovery.created_at),
+        Box::new(recovery.updated_at),
+        Box::new(recovery.score_state.as_str()),
+        Box::new(score.map(|s| s.user_calibrating)),
+        Box::new(score.map(|s| s.recovery_score)),
+        Box::new(score.map(|s| s.resting_heart_rate)),
+        Box::new(score.map(|s| s.hrv_rmssd_milli)),
+        Box::new(score.and_then(|s| s.spo2_percentage)),
+        Box::new(score.and_then(|s| s.skin_temp_celsius)),
+        Box::new(Json(recovery)),
+    ]
+}
+
+fn workout_params(workout: &WorkoutV2) -> Params<'_> {
+    let score = workout.score.as_ref();
+    let zones = score.map(|s| s.zone_durations.as_array());
+    vec![
+        Box::new(workout.id),
+        Box::new(workout.user_id),
+        Box::new(workout.created_at),
+        Box::new(workout.updated_at),
+        Box::new(workout.start),
+        Box::new(workout.end),
+        Box::new(workout.timezone_offset.clone()),
+        Box::new(workout.sport_name.clone()),
+        Box::new(workout.sport_id),
+        Box::new(workout.score_state.as_str()),
+        Box::new(score.map(|s| s.strain)),
+        Box::new(score.map(|s| s.average_heart_rate)),
+        Box::new(score.map(|s| s.max_heart_rate)),
+        Box::new(score.map(|s| s.kilojoule)),
+        Box::new(score.map(|s| s.percent_recorded)),
+        Box::new(score.and_then(|s| s.distance_meter)),
+        Box::new(zones.map(|z| z[0])),
+        Box::new(zones.map(|z| z[1])),
+        Box::new(zones.map(|z| z[2])),
+        Box::new(zones.map(|z| z[3])),
+        Box::new(zones.map(|z| z[4])),
+        Box::new(zones.map(|z| z[5])),
+        Box::new(Json(workout)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use bytes::BytesMut;
+    use tokio_postgres::types::Type;
+
+    /// The column types of `table`, as the migrations create it.
+    fn columns(table: &str) -> Vec<Type> {
+        let sql = MIGRATIONS.concat();
+        let create = &sql[sql.find(&format!("CREATE TABLE {} (", table)).unwrap()..];
+        let body = &create[create.find('(').unwrap() + 1..create.find(");").unwrap()];
+        body.lines()
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .map(|ty| match ty.trim_end_matches(',') {
+                "BIGINT" => Type::INT8,
+                "INTEGER" => Type::INT4,
+                "REAL" => Type::FLOAT4,
+                "BOOLEAN" => Type::BOOL,
+                "TEXT" => Type::TEXT,
+                "TIMESTAMPTZ" => Type::TIMESTAMPTZ,
+                "UUID" => Type::UUID,
+                "JSONB" => Type::JSONB,
+                other => panic!("unexpected column type {}", other),
+            })
+            .collect()
+    }
+
+    fn assert_fits(table: &str, sql: &str, params: Params) {
+        let columns = columns(table);
+        assert_eq!(
+            sql.matches('$').count(),
+            columns.len(),
+            "{} placeholders",
+            table
+        );
+        assert_eq!(params.len(), columns.len(), "{} parameters", table);
+        for (i, (param, ty)) in params.iter().zip(&columns).enumerate() {
+            if let Err(e) = param.to_sql_checked(ty, &mut BytesMut::new()) {
+                panic!(
+                    "{} column {} doesn't take its parameter: {}",
+                    table,
+                    i + 1,
+                    e
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_upserts_match_their_tables() {
+        for cycle in [fixtures::cycle_scored(), fixtures::cycle_pending()] {
+            assert_fits("whoop_cycles", UPSERT_CYCLE, cycle_params(&cycle));
+        }
+        for sleep in [fixtures::sleep_scored(), fixtures::sleep_pending()] {
+            assert_fits("whoop_sleeps", UPSERT_SLEEP, sleep_params(&sleep));
+        }
+        for recovery in [fixtures::recovery_scored(), fixtures::recovery_pending()] {
+            assert_fits(
+                "whoop_recoveries",
+                UPSERT_RECOVERY,
+                recovery_params(&recovery),
+            );
+        }
+        for workout in [fixtures::workout_scored(), fixtures::workout_pending()] {
+            assert_fits("whoop_workouts", UPSERT_WORKOUT, workout_params(&workout));
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! sled-backed store, for where SQLite isn't available or wanted.
+//!
+//! Every resource keeps its records as JSON in a tree keyed by id, next to an
+//! index tree keyed by start time and id that range queries and
+//! [`latest`](SledStore::latest) walk; recoveries are indexed by creation.
+//! Queries by a recovery's cycle start, and by sport or score, scan the
+//! resource's records, which for a person's history is a few thousand at
+//! most. Watermarks, cursors and body measurements get a tree each.
+//!
+//! Writes aren't transactional across records, but each is merged on its own
+//! (see [`crate::merge`]), so a batch interrupted part way is simply written
+//! again by the next sync. sled flushes to disk in the background; call
+//! [`flush`](SledStore::flush) to wait for it.
+
+use crate::body::BodySnapshot;
+use crate::error::{Result, WhoopError};
+use crate::json;
+use crate::merge::{self, Merged, Versioned};
+use crate::models::*;
+use crate::pagination::Checkpoint;
+use crate::sport;
+use crate::store::{Store, Stored};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::ops::Range;
+use std::path::Path;
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_timestamp(time: &[u8]) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(std::str::from_utf8(time).ok()?)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn unknown_resource(resource: &str) -> WhoopError {
+    WhoopError::Unknown(format!("unknown resource {}", resource), None)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    json::from_slice(&mut bytes.to_vec())
+}
+
+/// One resource: records by id, and ids by time.
+struct Table {
+    records: ::sled::Tree,
+    by_time: ::sled::Tree,
+}
+
+impl Table {
+    fn open(db: &::sled::Db, name: &str) -> Result<Self> {
+        Ok(Self {
+            records: db.open_tree(name)?,
+            by_time: db.open_tree(format!("{}_by_time", name))?,
+        })
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.records
+            .get(key)?
+            .map(|bytes| decode(&bytes))
+            .transpose()
+    }
+
+    /// Merges `records` in, indexing each by `time`.
+    fn merge<T: Versioned + Serialize + DeserializeOwned>(
+        &self,
+        records: &[T],
+        time: impl Fn(&T) -> DateTime<Utc>,
+    ) -> Result<Merged> {
+        let mut merged = Merged::default();
+        for record in merge::newest(records) {
+            let key = record.key();
+            let stored: Option<T> = self.get(&key)?;
+            let outcome = merge::resolve(stored.as_ref().map(T::updated_at), record.updated_at());
+            if outcome.writes() {
+                if let Some(stored) = &stored {
+                    self.by_time.remove(index_key(time(stored), &key))?;
+                }
+                self.records.insert(&key, serde_json::to_vec(record)?)?;
+                self.by_time
+                    .insert(index_key(time(record), &key), key.as_bytes())?;
+            }
+            merged.add(outcome);
+        }
+        Ok(merged)
+    }
+
+    fn between<T: DeserializeOwned>(&self, range: Range<DateTime<Utc>>) -> Result<Vec<T>> {
+        // Index keys continue past the timestamp, so they sort after the bare
+        // bound: the start is included and the end left out.
+        let range = timestamp(range.start).into_bytes()..timestamp(range.end).into_bytes();
+        self.resolve(self.by_time.range(range))
+    }
+
+    fn latest<T: DeserializeOwned>(&self, n: usize) -> Result<Vec<T>> {
+        let mut records = self.resolve(self.by_time.iter().rev().take(n))?;
+        records.reverse();
+        Ok(records)
+    }
+
+    fn count_since(&self, since: DateTime<Utc>) -> usize {
+        self.by_time.range(timestamp(since).into_bytes()..).count()
+    }
+
+    fn all<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.records.iter().map(|entry| decode(&entry?.1)).collect()
+    }
+
+    /// The records an index walk points at.
+    fn resolve<T: DeserializeOwned>(
+        &self,
+        index: impl Iterator<Item = ::sled::Result<(::sled::IVec, ::sled::IVec)>>,
+    ) -> Result<Vec<T>> {
+        let mut records = Vec::new();
+        for entry in index {
+            let (_, key) = entry?;
+            if let Some(bytes) = self.records.get(key)? {
+                records.push(decode(&bytes)?);
+            }
+        }
+        Ok(records)
+    }
+}
+
+fn index_key(time: DateTime<Utc>, key: &str) -> Vec<u8> {
+    format!("{}\0{}", timestamp(time), key).into_bytes()
+}
+
+/// A local cache of synced records in a sled database.
+pub struct SledStore {
+    db: ::sled::Db,
+    cycles: Table,
+    sleeps: Table,
+    recoveries: Table,
+    workouts: Table,
+    sync_state: ::sled::Tree,
+    sync_cursors: ::sled::Tree,
+    body_measurements: ::sled::Tree,
+}
+
+impl SledStore {
+    /// Opens (or creates) a database directory.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(::sled::open(path)?)
+    }
+
+    /// Opens a throwaway database, deleted when dropped, handy for tests.
+    pub fn open_temporary() -> Result<Self> {
+        Self::init(::sled::Config::new().temporary(true).open()?)
+    }
+
+    fn init(db: ::sled::Db) -> Result<Self> {
+        Ok(Self {
+            cycles: Table::open(&db, "cycles")?,
+            sleeps: Table::open(&db, "sleeps")?,
+            recoveries: Table::open(&db, "recoveries")?,
+            workouts: Table::open(&db, "workouts")?,
+            sync_state: db.open_tree("sync_state")?,
+            sync_cursors: db.open_tree("sync_cursors")?,
+            body_measurements: db.open_tree("body_measurements")?,
+            db,
+        })
+    }
+
+    /// Waits until everything written so far is on disk.
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn table(&self, resource: &str) -> Result<&Table> {
+        match resource {
+            "cycles" => Ok(&self.cycles),
+            "sleep" => Ok(&self.sleeps),
+            "recovery" => Ok(&self.recoveries),
+            "workouts" => Ok(&self.workouts),
+            other => Err(unknown_resource(other)),
+        }
+    }
+
+    /// Every recovery with its cycle's start, or its creation when the cycle
+    /// isn't stored, oldest first.
+    fn recoveries_by_cycle_start(&self) -> Result<Vec<(DateTime<Utc>, Recovery)>> {
+        let mut recoveries = Vec::new();
+        for recovery in self.recoveries.all::<Recovery>()? {
+            let cycle: Option<Cycle> = self.cycles.get(&recovery.cycle_id.to_string())?;
+            let start = cycle.map_or(recovery.created_at, |c| c.start);
+            recoveries.push((start, recovery));
+        }
+        recoveries.sort_by_key(|(start, _)| *start);
+        Ok(recoveries)
+    }
+}
+
+impl Store for SledStore {
+    fn upsert_cycles(&mut self, cycles: &[Cycle]) -> Result<Merged> {
+        self.cycles.merge(cycles, |c| c.start)
+    }
+
+    fn upsert_sleeps(&mut self, sleeps: &[Sleep]) -> Result<Merged> {
+        self.sleeps.merge(sleeps, |s| s.start)
+    }
+
+    fn upsert_recoveries(&mut self, recoveries: &[Recovery]) -> Result<Merged> {
+        self.recoveries.merge(recoveries, |r| r.created_at)
+    }
+
+    fn upsert_workouts(&mut self, workouts: &[WorkoutV2]) -> Result<Merged> {
+        self.workouts.merge(workouts, |w| w.start)
+    }
+
+    fn cycles_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Cycle>> {
+        self.cycles.between(range)
+    }
+
+    fn sleeps_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Sleep>> {
+        self.sleeps.between(range)
+    }
+
+    fn recoveries_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Recovery>> {
+        Ok(self
+            .recoveries_by_cycle_start()?
+            .into_iter()
+            .filter(|(start, _)| range.contains(start))
+            .map(|(_, recovery)| recovery)
+            .collect())
+    }
+
+    fn workouts_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<WorkoutV2>> {
+        self.workouts.between(range)
+    }
+
+    fn workouts_by_sport(
+        &self,
+        sport: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<WorkoutV2>> {
+        let mut workouts: Vec<WorkoutV2> = self.workouts.between(range)?;
+        let wanted = sport::normalize(sport);
+        workouts.retain(|w| sport::normalize(&w.sport_name) == wanted);
+        Ok(workouts)
+    }
+
+    fn recoveries_below(&self, score: f32) -> Result<Vec<Recovery>> {
+        Ok(self
+            .recoveries_by_cycle_start()?
+            .into_iter()
+            .map(|(_, recovery)| recovery)
+            .filter(|r| r.score.as_ref().is_some_and(|s| s.recovery_score < score))
+            .collect())
+    }
+
+    fn latest<T: Stored>(&self, n: usize) -> Result<Vec<T>> {
+        self.table(T::RESOURCE)?.latest(n)
+    }
+
+    fn insert_body_measurement(&self, snapshot: &BodySnapshot) -> Result<()> {
+        self.body_measurements.insert(
+            timestamp(snapshot.at),
+            serde_json::to_vec(&snapshot.measurement)?,
+        )?;
+        Ok(())
+    }
+
+    fn body_measurements(&self) -> Result<Vec<BodySnapshot>> {
+        self.body_measurements
+            .iter()
+            .map(|entry| body_snapshot(entry?))
+            .collect()
+    }
+
+    fn latest_body_measurement(&self) -> Result<Option<BodySnapshot>> {
+        self.body_measurements
+            .last()?
+            .map(body_snapshot)
+            .transpose()
+    }
+
+    fn oldest_unsettled(&self, resource: &str) -> Result<Option<DateTime<Utc>>> {
+        let pending = |state: &ScoreState| matches!(state, ScoreState::PendingScore);
+        Ok(match resource {
+            "cycles" => self
+                .cycles
+                .all::<Cycle>()?
+                .into_iter()
+                .filter(|c| pending(&c.score_state) || c.end.is_none())
+                .map(|c| c.start)
+                .min(),
+            "sleep" => self
+                .sleeps
+                .all::<Sleep>()?
+                .into_iter()
+                .filter(|s| pending(&s.score_state))
+                .map(|s| s.start)
+                .min(),
+            "recovery" => self
+                .recoveries_by_cycle_start()?
+                .into_iter()
+                .find(|(_, r)| pending(&r.score_state))
+                .map(|(start, _)| start),
+            "workouts" => self
+                .workouts
+                .all::<WorkoutV2>()?
+                .into_iter()
+                .filter(|w| pending(&w.score_state))
+                .map(|w| w.start)
+                .min(),
+            other => return Err(unknown_resource(other)),
+        })
+    }
+
+    fn count_since(&self, resource: &str, since: DateTime<Utc>) -> Result<usize> {
+        match resource {
+            "recovery" => Ok(self
+                .recoveries_by_cycle_start()?
+                .iter()
+                .filter(|(start, _)| *start >= since)
+                .count()),
+            other => Ok(self.table(other)?.count_since(since)),
+        }
+    }
+
+    fn watermark(&self, resource: &str) -> Result<Option<DateTime<Utc>>> {
+        Ok(self
+            .sync_state
+            .get(resource)?
+            .and_then(|bytes| parse_timestamp(&bytes)))
+    }
+
+    fn set_watermark(&self, resource: &str, watermark: DateTime<Utc>) -> Result<()> {
+        self.sync_state
+            .insert(resource, timestamp(watermark).as_bytes())?;
+        Ok(())
+    }
+
+    fn cursor(&self, key: &str) -> Result<Option<Checkpoint>> {
+        self.sync_cursors
+            .get(key)?
+            .map(|bytes| decode(&bytes))
+            .transpose()
+    }
+
+    fn set_cursor(&self, key: &str, checkpoint: &Checkpoint) -> Result<()> {
+        self.sync_cursors
+            .insert(key, serde_json::to_vec(checkpoint)?)?;
+        Ok(())
+    }
+
+    fn clear_cursor(&self, key: &str) -> Result<()> {
+        self.sync_cursors.remove(key)?;
+        Ok(())
+    }
+}
+
+fn body_snapshot((at, measurement): (::sled::IVec, ::sled::IVec)) -> Result<BodySnapshot> {
+    let at = parse_timestamp(&at).ok_or_else(|| {
+        WhoopError::Unknown(
+            format!(
+                "invalid body measurement time {}",
+                String::from_utf8_lossy(&at)
+            ),
+            None,
+        )
+    })?;
+    Ok(BodySnapshot {
+        at,
+        measurement: decode(&measurement)?,
+    })
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::memory::InMemoryWhoop;
+    use crate::rate_limit::RateLimiter;
+    use crate::store::SqliteStore;
+    use crate::sync::Delta;
+    use chrono::Duration;
+
+    /// Runs the same sync and queries against `store`, returning what the queries found.
+    async fn exercise(mut store: impl Store) -> Vec<String> {
+        let api = InMemoryWhoop::new()
+            .with_cycles(fixtures::cycle_collection().records.unwrap())
+            .with_sleeps(fixtures::sleep_collection().records.unwrap())
+            .with_recoveries(fixtures::recovery_collection().records.unwrap())
+            .with_workouts(fixtures::workout_collection().records.unwrap());
+        let delta = Delta::new().with_rate_limiter(RateLimiter::per_minute(10_000));
+        let applied = delta.run(&api, &mut store).await.unwrap();
+        let again = delta.run(&api, &mut store).await.unwrap();
+        assert_eq!(again.changed, 0);
+
+        let all = DateTime::UNIX_EPOCH..Utc::now();
+        let mut stale = fixtures::cycle_scored();
+        stale.updated_at -= Duration::days(1);
+        vec![
+            format!("{:?}", applied),
+            format!("{:?}", store.upsert_cycles(&[stale]).unwrap()),
+            format!("{:?}", store.cycles_between(all.clone()).unwrap()),
+            format!("{:?}", store.sleeps_between(all.clone()).unwrap()),
+            format!("{:?}", store.recoveries_between(all.clone()).unwrap()),
+            format!(
+                "{:?}",
+                store.workouts_by_sport("RUNNING", all.clone()).unwrap()
+            ),
+            format!("{:?}", store.recoveries_below(50.0).unwrap()),
+            format!("{:?}", store.latest::<Sleep>(1).unwrap()),
+            format!("{:?}", store.oldest_unsettled("cycles").unwrap()),
+            format!("{:?}", store.count_since("recovery", all.start).unwrap()),
+            format!("{:?}", store.watermark("workouts").unwrap()),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_behaves_like_the_sqlite_store() {
+        let sled = exercise(SledStore::open_temporary().unwrap()).await;
+        let sqlite = exercise(SqliteStore::open_in_memory().unwrap()).await;
+        for (sled, sqlite) in sled.iter().zip(&sqlite) {
+            assert_eq!(sled, sqlite);
+        }
+
+        let store = SledStore::open_temporary().unwrap();
+        let at: DateTime<Utc> = "2024-03-01T00:00:00Z".parse().unwrap();
+        for (days, weight) in [(0, 80.0), (7, 79.5)] {
+            let mut measurement = fixtures::body_measurement();
+            measurement.weight_kilogram = weight;
+            store
+                .insert_body_measurement(&BodySnapshot {
+                    at: at + Duration::days(days),
+                    measurement,
+                })
+                .unwrap();
+        }
+        let latest = store.latest_body_measurement().unwrap().unwrap();
+        assert_eq!(latest.measurement.weight_kilogram, 79.5);
+        assert_eq!(store.body_measurements().unwrap()[0].at, at);
+
+        let checkpoint = Checkpoint {
+            next_token: "abc".to_string(),
+            last_id: None,
+        };
+        store.set_cursor("cycles:2024", &checkpoint).unwrap();
+        assert_eq!(store.cursor("cycles:2024").unwrap(), Some(checkpoint));
+        store.clear_cursor("cycles:2024").unwrap();
+        assert_eq!(store.cursor("cycles:2024").unwrap(), None);
+    }
+}
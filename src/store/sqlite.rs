@@ -0,0 +1,908 @@
+//! SQLite-backed local store of synced records.
+//!
+//! Every resource gets its own table with the commonly queried score fields pulled
+//! out into columns, plus the full API record as JSON in `raw`:
+//!
+//! - `cycles`: keyed by `id`, strain, kilojoule and heart rate columns
+//! - `sleeps`: keyed by the sleep UUID, stage totals and performance percentages
+//! - `recoveries`: keyed by `cycle_id`, recovery score, RHR, HRV, SpO2 and skin temperature
+//! - `workouts`: keyed by the workout UUID, sport name and its
+//!   [normalized](crate::sport::normalize) form, strain, heart rate and zone durations
+//!
+//! Timestamps are stored as RFC 3339 UTC strings with millisecond precision so they
+//! sort correctly as text, and every table is indexed on the columns its queries
+//! filter by: start times, sport and recovery score. `sync_state` keeps one watermark per resource for
+//! incremental syncs, `body_measurements` every distinct body measurement seen
+//! (see [`crate::body`]), and `sync_cursors` the [`Checkpoint`] of any walk through
+//! pages a sync was interrupted in. Queries decode the `raw` column, so they always return the
+//! same typed models the API client does.
+
+use crate::body::BodySnapshot;
+use crate::error::{Result, WhoopError};
+use crate::json;
+use crate::merge::{self, Merged, Versioned};
+use crate::models::*;
+use crate::pagination::Checkpoint;
+use crate::sport;
+use crate::store::{Store, Stored};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension, Params, params};
+use serde::de::DeserializeOwned;
+use std::ops::Range;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS cycles (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT,
+    timezone_offset TEXT NOT NULL,
+    score_state TEXT NOT NULL,
+    strain REAL,
+    kilojoule REAL,
+    average_heart_rate INTEGER,
+    max_heart_rate INTEGER,
+    raw TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sleeps (
+    id TEXT PRIMARY KEY,
+    cycle_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    timezone_offset TEXT NOT NULL,
+    nap INTEGER NOT NULL,
+    score_state TEXT NOT NULL,
+    total_in_bed_time_milli INTEGER,
+    total_sleep_time_milli INTEGER,
+    sleep_needed_milli INTEGER,
+    respiratory_rate REAL,
+    sleep_performance_percentage REAL,
+    sleep_consistency_percentage REAL,
+    sleep_efficiency_percentage REAL,
+    raw TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS recoveries (
+    cycle_id INTEGER PRIMARY KEY,
+    sleep_id TEXT NOT NULL,
+    user_id INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    score_state TEXT NOT NULL,
+    user_calibrating INTEGER,
+    recovery_score REAL,
+    resting_heart_rate REAL,
+    hrv_rmssd_milli REAL,
+    spo2_percentage REAL,
+    skin_temp_celsius REAL,
+    raw TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS workouts (
+    id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    timezone_offset TEXT NOT NULL,
+    sport_name TEXT NOT NULL,
+    sport_id INTEGER,
+    score_state TEXT NOT NULL,
+    strain REAL,
+    average_heart_rate INTEGER,
+    max_heart_rate INTEGER,
+    kilojoule REAL,
+    percent_recorded REAL,
+    distance_meter REAL,
+    zone_zero_milli INTEGER,
+    zone_one_milli INTEGER,
+    zone_two_milli INTEGER,
+    zone_three_milli INTEGER,
+    zone_four_milli INTEGER,
+    zone_five_milli INTEGER,
+    raw TEXT NOT NULL,
+    sport TEXT
+);
+
+CREATE TABLE IF NOT EXISTS sync_state (
+    resource TEXT PRIMARY KEY,
+    watermark TEXT NOT NULL,
+    synced_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS body_measurements (
+    measured_at TEXT PRIMARY KEY,
+    height_meter REAL NOT NULL,
+    weight_kilogram REAL NOT NULL,
+    max_heart_rate INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS cycles_start ON cycles (start);
+CREATE INDEX IF NOT EXISTS sleeps_start ON sleeps (start);
+CREATE INDEX IF NOT EXISTS recoveries_created_at ON recoveries (created_at);
+CREATE INDEX IF NOT EXISTS recoveries_score ON recoveries (recovery_score);
+CREATE INDEX IF NOT EXISTS workouts_start ON workouts (start);
+
+CREATE TABLE IF NOT EXISTS sync_cursors (
+    key TEXT PRIMARY KEY,
+    next_token TEXT NOT NULL,
+    last_id TEXT,
+    saved_at TEXT NOT NULL
+);
+";
+
+const INSERT_CYCLE: &str = "INSERT OR REPLACE INTO cycles VALUES
+     (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)";
+
+const INSERT_SLEEP: &str = "INSERT OR REPLACE INTO sleeps VALUES
+     (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)";
+
+const INSERT_RECOVERY: &str = "INSERT OR REPLACE INTO recoveries VALUES
+     (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)";
+
+const INSERT_WORKOUT: &str = "INSERT OR REPLACE INTO workouts VALUES
+     (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,
+      ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)";
+
+/// Workouts of a normalized sport within a start range, by the
+/// `workouts_sport` index.
+const WORKOUTS_BY_SPORT: &str = "SELECT raw FROM workouts
+     WHERE sport = ?1 AND start >= ?2 AND start < ?3
+     ORDER BY start";
+
+/// Recoveries below a score, by the `recoveries_score` index.
+const RECOVERIES_BELOW: &str =
+    "SELECT r.raw FROM recoveries r LEFT JOIN cycles c ON c.id = r.cycle_id
+     WHERE r.recovery_score < ?1 ORDER BY COALESCE(c.start, r.created_at)";
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_timestamp(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn unknown_resource(resource: &str) -> WhoopError {
+    WhoopError::Unknown(format!("unknown resource {}", resource), None)
+}
+
+/// A local cache of synced records in a single SQLite database.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) a database file and makes sure the schema exists.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Opens a throwaway in-memory database, handy for tests.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+
+    /// Merges cycles into the store by id; see [`crate::merge`].
+    pub fn upsert_cycles(&mut self, cycles: &[Cycle]) -> Result<Merged> {
+        self.merge(
+            cycles,
+            "SELECT updated_at FROM cycles WHERE id = ?1",
+            insert_cycle,
+        )
+    }
+
+    pub fn upsert_sleeps(&mut self, sleeps: &[Sleep]) -> Result<Merged> {
+        self.merge(
+            sleeps,
+            "SELECT updated_at FROM sleeps WHERE id = ?1",
+            insert_sleep,
+        )
+    }
+
+    /// Merges recoveries by cycle id.
+    pub fn upsert_recoveries(&mut self, recoveries: &[Recovery]) -> Result<Merged> {
+        self.merge(
+            recoveries,
+            "SELECT updated_at FROM recoveries WHERE cycle_id = ?1",
+            insert_recovery,
+        )
+    }
+
+    pub fn upsert_workouts(&mut self, workouts: &[WorkoutV2]) -> Result<Merged> {
+        self.merge(
+            workouts,
+            "SELECT updated_at FROM workouts WHERE id = ?1",
+            insert_workout,
+        )
+    }
+
+    /// Writes the newest copy of each record unless the stored one is newer,
+    /// in one transaction. `stored` looks up a stored copy's `updated_at` by key.
+    fn merge<T: Versioned>(
+        &mut self,
+        records: &[T],
+        stored: &str,
+        insert: fn(&Connection, &T) -> Result<()>,
+    ) -> Result<Merged> {
+        let tx = self.conn.transaction()?;
+        let mut merged = Merged::default();
+        {
+            let mut lookup = tx.prepare(stored)?;
+            for record in merge::newest(records) {
+                // Integer ids compare equal to their text, the column's affinity converts it.
+                let updated_at: Option<String> = lookup
+                    .query_row(params![record.key()], |row| row.get(0))
+                    .optional()?;
+                let outcome = merge::resolve(
+                    updated_at.as_deref().and_then(parse_timestamp),
+                    record.updated_at(),
+                );
+                if outcome.writes() {
+                    insert(&tx, record)?;
+                }
+                merged.add(outcome);
+            }
+        }
+        tx.commit()?;
+        Ok(merged)
+    }
+
+    /// Cycles starting within `range`, oldest first.
+    pub fn cycles_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Cycle>> {
+        self.query(
+            "SELECT raw FROM cycles WHERE start >= ?1 AND start < ?2 ORDER BY start",
+            params![timestamp(range.start), timestamp(range.end)],
+        )
+    }
+
+    /// Sleeps and naps starting within `range`, oldest first.
+    pub fn sleeps_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Sleep>> {
+        self.query(
+            "SELECT raw FROM sleeps WHERE start >= ?1 AND start < ?2 ORDER BY start",
+            params![timestamp(range.start), timestamp(range.end)],
+        )
+    }
+
+    /// Recoveries whose cycle starts within `range`, oldest first.
+    /// Falls back to the recovery's creation time when the cycle isn't stored.
+    pub fn recoveries_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Recovery>> {
+        self.query(
+            "SELECT r.raw FROM recoveries r LEFT JOIN cycles c ON c.id = r.cycle_id
+             WHERE COALESCE(c.start, r.created_at) >= ?1 AND COALESCE(c.start, r.created_at) < ?2
+             ORDER BY COALESCE(c.start, r.created_at)",
+            params![timestamp(range.start), timestamp(range.end)],
+        )
+    }
+
+    /// Workouts starting within `range`, oldest first.
+    pub fn workouts_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<WorkoutV2>> {
+        self.query(
+            "SELECT raw FROM workouts WHERE start >= ?1 AND start < ?2 ORDER BY start",
+            params![timestamp(range.start), timestamp(range.end)],
+        )
+    }
+
+    /// Workouts of one sport starting within `range`, oldest first. Sports
+    /// match by [`sport::normalize`], so aliases and spacing don't matter.
+    pub fn workouts_by_sport(
+        &self,
+        sport: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<WorkoutV2>> {
+        self.query(
+            WORKOUTS_BY_SPORT,
+            params![
+                sport::normalize(sport),
+                timestamp(range.start),
+                timestamp(range.end)
+            ],
+        )
+    }
+
+    /// Scored recoveries below `score` percent, oldest first.
+    pub fn recoveries_below(&self, score: f32) -> Result<Vec<Recovery>> {
+        self.query(RECOVERIES_BELOW, params![score])
+    }
+
+    /// The `n` newest records of a type, oldest first, e.g.
+    /// `store.latest::<Sleep>(7)` for the last week's sleeps and naps.
+    pub fn latest<T: Stored>(&self, n: usize) -> Result<Vec<T>> {
+        let sql = match T::RESOURCE {
+            "cycles" => "SELECT raw FROM cycles ORDER BY start DESC LIMIT ?1",
+            "sleep" => "SELECT raw FROM sleeps ORDER BY start DESC LIMIT ?1",
+            "recovery" => "SELECT raw FROM recoveries ORDER BY created_at DESC LIMIT ?1",
+            "workouts" => "SELECT raw FROM workouts ORDER BY start DESC LIMIT ?1",
+            other => return Err(unknown_resource(other)),
+        };
+        let mut records = self.query(sql, params![n as i64])?;
+        records.reverse();
+        Ok(records)
+    }
+
+    /// The most recently created recovery, if any.
+    pub fn latest_recovery(&self) -> Result<Option<Recovery>> {
+        Ok(self
+            .query(
+                "SELECT raw FROM recoveries ORDER BY created_at DESC LIMIT 1",
+                [],
+            )?
+            .pop())
+    }
+
+    pub fn insert_body_measurement(&self, snapshot: &BodySnapshot) -> Result<()> {
+        let m = &snapshot.measurement;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO body_measurements VALUES (?1, ?2, ?3, ?4)",
+            params![
+                timestamp(snapshot.at),
+                m.height_meter,
+                m.weight_kilogram,
+                m.max_heart_rate
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Body measurements seen, oldest first.
+    pub fn body_measurements(&self) -> Result<Vec<BodySnapshot>> {
+        self.body_snapshots("SELECT * FROM body_measurements ORDER BY measured_at")
+    }
+
+    pub fn latest_body_measurement(&self) -> Result<Option<BodySnapshot>> {
+        Ok(self
+            .body_snapshots("SELECT * FROM body_measurements ORDER BY measured_at DESC LIMIT 1")?
+            .pop())
+    }
+
+    fn body_snapshots(&self, sql: &str) -> Result<Vec<BodySnapshot>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                UserBodyMeasurement {
+                    height_meter: row.get(1)?,
+                    weight_kilogram: row.get(2)?,
+                    max_heart_rate: row.get(3)?,
+                },
+            ))
+        })?;
+
+        let mut snapshots = Vec::new();
+        for row in rows {
+            let (at, measurement) = row?;
+            let at = parse_timestamp(&at).ok_or_else(|| {
+                WhoopError::Unknown(format!("invalid body measurement time {}", at), None)
+            })?;
+            snapshots.push(BodySnapshot { at, measurement });
+        }
+        Ok(snapshots)
+    }
+
+    /// Start of the oldest record of `resource` WHOOP may still change: one
+    /// pending a score or, for cycles, one that hasn't ended. Recoveries go by
+    /// their cycle's start, like [`Self::recoveries_between`].
+    pub fn oldest_unsettled(&self, resource: &str) -> Result<Option<DateTime<Utc>>> {
+        let sql = match resource {
+            "cycles" => {
+                "SELECT MIN(start) FROM cycles WHERE score_state = 'PENDING_SCORE' OR end IS NULL"
+            }
+            "sleep" => "SELECT MIN(start) FROM sleeps WHERE score_state = 'PENDING_SCORE'",
+            "recovery" => {
+                "SELECT MIN(COALESCE(c.start, r.created_at)) FROM recoveries r
+                 LEFT JOIN cycles c ON c.id = r.cycle_id WHERE r.score_state = 'PENDING_SCORE'"
+            }
+            "workouts" => "SELECT MIN(start) FROM workouts WHERE score_state = 'PENDING_SCORE'",
+            other => return Err(unknown_resource(other)),
+        };
+        let oldest: Option<String> = self.conn.query_row(sql, [], |row| row.get(0))?;
+        Ok(oldest.as_deref().and_then(parse_timestamp))
+    }
+
+    /// How many records of `resource` start at or after `since`.
+    pub fn count_since(&self, resource: &str, since: DateTime<Utc>) -> Result<usize> {
+        let sql = match resource {
+            "cycles" => "SELECT COUNT(*) FROM cycles WHERE start >= ?1",
+            "sleep" => "SELECT COUNT(*) FROM sleeps WHERE start >= ?1",
+            "recovery" => {
+                "SELECT COUNT(*) FROM recoveries r LEFT JOIN cycles c ON c.id = r.cycle_id
+                 WHERE COALESCE(c.start, r.created_at) >= ?1"
+            }
+            "workouts" => "SELECT COUNT(*) FROM workouts WHERE start >= ?1",
+            other => return Err(unknown_resource(other)),
+        };
+        let count: i64 = self
+            .conn
+            .query_row(sql, params![timestamp(since)], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// The sync watermark stored for `resource`, if it has been synced before.
+    pub fn watermark(&self, resource: &str) -> Result<Option<DateTime<Utc>>> {
+        let watermark: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT watermark FROM sync_state WHERE resource = ?1",
+                params![resource],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(watermark.as_deref().and_then(parse_timestamp))
+    }
+
+    pub fn set_watermark(&self, resource: &str, watermark: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sync_state (resource, watermark, synced_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (resource) DO UPDATE SET watermark = ?2, synced_at = ?3",
+            params![resource, timestamp(watermark), timestamp(Utc::now())],
+        )?;
+        Ok(())
+    }
+
+    /// The checkpoint saved under `key`, if a walk through pages stopped there.
+    pub fn cursor(&self, key: &str) -> Result<Option<Checkpoint>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT next_token, last_id FROM sync_cursors WHERE key = ?1",
+                params![key],
+                |row| {
+                    Ok(Checkpoint {
+                        next_token: row.get(0)?,
+                        last_id: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn set_cursor(&self, key: &str, checkpoint: &Checkpoint) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sync_cursors (key, next_token, last_id, saved_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (key) DO UPDATE SET next_token = ?2, last_id = ?3, saved_at = ?4",
+            params![
+                key,
+                checkpoint.next_token,
+                checkpoint.last_id,
+                timestamp(Utc::now())
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn clear_cursor(&self, key: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM sync_cursors WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
+    fn query<T: DeserializeOwned>(&self, sql: &str, params: impl Params) -> Result<Vec<T>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| row.get::<_, String>(0))?;
+
+        let mut records = Vec::new();
+        for raw in rows {
+            records.push(json::from_string(raw?)?);
+        }
+        Ok(records)
+    }
+}
+
+impl Store for SqliteStore {
+    fn upsert_cycles(&mut self, cycles: &[Cycle]) -> Result<Merged> {
+        SqliteStore::upsert_cycles(self, cycles)
+    }
+
+    fn upsert_sleeps(&mut self, sleeps: &[Sleep]) -> Result<Merged> {
+        SqliteStore::upsert_sleeps(self, sleeps)
+    }
+
+    fn upsert_recoveries(&mut self, recoveries: &[Recovery]) -> Result<Merged> {
+        SqliteStore::upsert_recoveries(self, recoveries)
+    }
+
+    fn upsert_workouts(&mut self, workouts: &[WorkoutV2]) -> Result<Merged> {
+        SqliteStore::upsert_workouts(self, workouts)
+    }
+
+    fn cycles_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Cycle>> {
+        SqliteStore::cycles_between(self, range)
+    }
+
+    fn sleeps_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Sleep>> {
+        SqliteStore::sleeps_between(self, range)
+    }
+
+    fn recoveries_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<Recovery>> {
+        SqliteStore::recoveries_between(self, range)
+    }
+
+    fn workouts_between(&self, range: Range<DateTime<Utc>>) -> Result<Vec<WorkoutV2>> {
+        SqliteStore::workouts_between(self, range)
+    }
+
+    fn workouts_by_sport(
+        &self,
+        sport: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<WorkoutV2>> {
+        SqliteStore::workouts_by_sport(self, sport, range)
+    }
+
+    fn recoveries_below(&self, score: f32) -> Result<Vec<Recovery>> {
+        SqliteStore::recoveries_below(self, score)
+    }
+
+    fn latest<T: Stored>(&self, n: usize) -> Result<Vec<T>> {
+        SqliteStore::latest(self, n)
+    }
+
+    fn insert_body_measurement(&self, snapshot: &BodySnapshot) -> Result<()> {
+        SqliteStore::insert_body_measurement(self, snapshot)
+    }
+
+    fn body_measurements(&self) -> Result<Vec<BodySnapshot>> {
+        SqliteStore::body_measurements(self)
+    }
+
+    fn latest_body_measurement(&self) -> Result<Option<BodySnapshot>> {
+        SqliteStore::latest_body_measurement(self)
+    }
+
+    fn oldest_unsettled(&self, resource: &str) -> Result<Option<DateTime<Utc>>> {
+        SqliteStore::oldest_unsettled(self, resource)
+    }
+
+    fn count_since(&self, resource: &str, since: DateTime<Utc>) -> Result<usize> {
+        SqliteStore::count_since(self, resource, since)
+    }
+
+    fn watermark(&self, resource: &str) -> Result<Option<DateTime<Utc>>> {
+        SqliteStore::watermark(self, resource)
+    }
+
+    fn set_watermark(&self, resource: &str, watermark: DateTime<Utc>) -> Result<()> {
+        SqliteStore::set_watermark(self, resource, watermark)
+    }
+
+    fn cursor(&self, key: &str) -> Result<Option<Checkpoint>> {
+        SqliteStore::cursor(self, key)
+    }
+
+    fn set_cursor(&self, key: &str, checkpoint: &Checkpoint) -> Result<()> {
+        SqliteStore::set_cursor(self, key, checkpoint)
+    }
+
+    fn clear_cursor(&self, key: &str) -> Result<()> {
+        SqliteStore::clear_cursor(self, key)
+    }
+}
+
+/// Brings a database an older version created up to date. `sport`, the
+/// normalized `sport_name`, is the workouts table's last column so it can be
+/// added to one.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    let has_sport = tx
+        .prepare("SELECT 1 FROM pragma_table_info('workouts') WHERE name = 'sport'")?
+        .exists([])?;
+    if !has_sport {
+        // The index was on sport_name, compared case-insensitively.
+        tx.execute_batch(
+            "ALTER TABLE workouts ADD COLUMN sport TEXT;
+             DROP INDEX IF EXISTS workouts_sport;",
+        )?;
+        let names: Vec<String> = tx
+            .prepare("SELECT DISTINCT sport_name FROM workouts")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for name in names {
+            tx.execute(
+                "UPDATE workouts SET sport = ?1 WHERE sport_name = ?2",
+                params![sport::normalize(&name), name],
+            )?;
+        }
+    }
+    tx.execute_batch("CREATE INDEX IF NOT EXISTS workouts_sport ON workouts (sport, start);")?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn insert_cycle(conn: &Connection, cycle: &Cycle) -> Result<()> {
+    let score = cycle.score.as_ref();
+    conn.execute(
+        INSERT_CYCLE,
+        params![
+            cycle.id,
+            cycle.user_id,
+            timestamp(cycle.created_at),
+            timestamp(cycle.updated_at),
+            timestamp(cycle.start),
+            cycle.end.map(timestamp),
+            cycle.timezone_offset,
+            cycle.score_state.as_str(),
+            score.map(|s| s.strain),
+            score.map(|s| s.kilojoule),
+            score.map(|s| s.average_heart_rate),
+            score.map(|s| s.max_heart_rate),
+            serde_json::to_string(cycle)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_sleep(conn: &Connection, sleep: &Sleep) -> Result<()> {
+    let score = sleep.score.as_ref();
+    conn.execute(
+        INSERT_SLEEP,
+        params![
+            sleep.id.to_string(),
+            sleep.cycle_id,
+            sleep.user_id,
+            timestamp(sleep.created_at),
+            timestamp(sleep.updated_at),
+            timestamp(sleep.start),
+            timestamp(sleep.end),
+            sleep.timezone_offset,
+            sleep.nap,
+            sleep.score_state.as_str(),
+            score.map(|s| s.stage_summary.total_in_bed_time_milli),
+            score.map(|s| s.stage_summary.total_sleep_time_milli()),
+            score.map(|s| s.sleep_needed.total_milli()),
+            score.and_then(|s| s.respiratory_rate),
+            score.and_then(|s| s.sleep_performance_percentage),
+            score.and_then(|s| s.sleep_consistency_percentage),
+            score.and_then(|s| s.sleep_efficiency_percentage),
+            serde_json::to_string(sleep)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_recovery(conn: &Connection, recovery: &Recovery) -> Result<()> {
+    let score = recovery.score.as_ref();
+    conn.execute(
+        INSERT_RECOVERY,
+        params![
+            recovery.cycle_id,
+            recovery.sleep_id.to_string(),
+            recovery.user_id,
+            timestamp(recovery.created_at),
+            timestamp(recovery.updated_at),
+            recovery.score_state.as_str(),
+            score.map(|s| s.user_calibrating),
+            score.map(|s| s.recovery_score),
+            score.map(|s| s.resting_heart_rate),
+            score.map(|s| s.hrv_rmssd_milli),
+            score.and_then(|s| s.spo2_percentage),
+            score.and_then(|s| s.skin_temp_celsius),
+            serde_json::to_string(recovery)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_workout(conn: &Connection, workout: &WorkoutV2) -> Result<()> {
+    let score = workout.score.as_ref();
+    let zones = score.map(|s| s.zone_durations.as_array());
+    conn.execute(
+        INSERT_WORKOUT,
+        params![
+            workout.id.to_string(),
+            workout.user_id,
+            timestamp(workout.created_at),
+            timestamp(workout.updated_at),
+            timestamp(workout.start),
+            timestamp(workout.end),
+            workout.timezone_offset,
+            workout.sport_name,
+            workout.sport_id,
+            workout.score_state.as_str(),
+            score.map(|s| s.strain),
+            score.map(|s| s.average_heart_rate),
+            score.map(|s| s.max_heart_rate),
+            score.map(|s| s.kilojoule),
+            score.map(|s| s.percent_recorded),
+            score.and_then(|s| s.distance_meter),
+            zones.map(|z| z[0]),
+            zones.map(|z| z[1]),
+            zones.map(|z| z[2]),
+            zones.map(|z| z[3]),
+            zones.map(|z| z[4]),
+            zones.map(|z| z[5]),
+            serde_json::to_string(workout)?,
+            sport::normalize(&workout.sport_name),
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upsert_replaces_and_queries_by_range() {
+        let time: DateTime<Utc> = "2024-03-01T06:30:00Z".parse().unwrap();
+        let mut cycle = Cycle {
+            id: 93845,
+            user_id: 10129,
+            created_at: time,
+            updated_at: time,
+            start: time,
+            end: None,
+            timezone_offset: "-05:00".to_string(),
+            score_state: ScoreState::PendingScore,
+            score: None,
+        };
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.upsert_cycles(std::slice::from_ref(&cycle)).unwrap();
+        cycle.score_state = ScoreState::Scored;
+        store.upsert_cycles(&[cycle]).unwrap();
+
+        let day = chrono::Duration::days(1);
+        let cycles = store.cycles_between(time - day..time + day).unwrap();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].score_state.as_str(), "SCORED");
+        assert!(
+            store
+                .cycles_between(time + day..time + day * 2)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_upsert_keeps_the_newest_copy() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let recovery = crate::fixtures::recovery_scored();
+        let mut newer = recovery.clone();
+        newer.updated_at += chrono::Duration::hours(1);
+        newer.score.as_mut().unwrap().recovery_score = 12.0;
+        let sleep = crate::fixtures::sleep_scored();
+
+        let merged = store
+            .upsert_recoveries(&[recovery.clone(), newer.clone()])
+            .unwrap();
+        assert_eq!((merged.inserted, merged.changed()), (1, 1));
+        assert_eq!(
+            store
+                .upsert_sleeps(std::slice::from_ref(&sleep))
+                .unwrap()
+                .inserted,
+            1
+        );
+        assert_eq!(store.upsert_sleeps(&[sleep]).unwrap().unchanged, 1);
+
+        // A page fetched before the rescore arrives late.
+        let merged = store.upsert_recoveries(&[recovery]).unwrap();
+        assert_eq!(merged.stale, 1);
+        let stored = store.latest_recovery().unwrap().unwrap();
+        assert_eq!(stored.score.unwrap().recovery_score, 12.0);
+        assert_eq!(store.upsert_recoveries(&[newer]).unwrap().unchanged, 1);
+    }
+
+    #[test]
+    fn test_queries_use_indexes() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store
+            .upsert_recoveries(&[
+                crate::fixtures::recovery_scored(),
+                crate::fixtures::recovery_calibrating(),
+            ])
+            .unwrap();
+        store
+            .upsert_workouts(&[crate::fixtures::workout_scored()])
+            .unwrap();
+        store
+            .upsert_sleeps(&[
+                crate::fixtures::sleep_scored(),
+                crate::fixtures::sleep_nap(),
+            ])
+            .unwrap();
+
+        let low: Vec<f32> = store
+            .recoveries_below(50.0)
+            .unwrap()
+            .iter()
+            .map(|r| r.score.as_ref().unwrap().recovery_score)
+            .collect();
+        assert_eq!(low, [44.0]);
+        let sleeps = store.latest::<Sleep>(1).unwrap();
+        assert_eq!(sleeps.len(), 1);
+        let newest = [
+            crate::fixtures::sleep_scored(),
+            crate::fixtures::sleep_nap(),
+        ]
+        .into_iter()
+        .max_by_key(|s| s.start)
+        .unwrap();
+        assert_eq!(sleeps[0].id, newest.id);
+
+        let workout = crate::fixtures::workout_scored();
+        let day = chrono::Duration::days(1);
+        let range = workout.start - day..workout.start + day;
+        let sport = workout.sport_name.to_uppercase();
+        assert_eq!(
+            store
+                .workouts_by_sport(&sport, range.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(
+            store
+                .workouts_by_sport("rowing", range.clone())
+                .unwrap()
+                .is_empty()
+        );
+
+        let plan = |sql: &str, params: &[&dyn rusqlite::ToSql]| {
+            let mut plan = store
+                .conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+                .unwrap();
+            let details: Vec<String> = plan
+                .query_map(params, |row| row.get(3))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            details.join("\n")
+        };
+        let detail = plan(WORKOUTS_BY_SPORT, &[&"running", &"a", &"b"]);
+        assert!(detail.contains("workouts_sport"), "{}", detail);
+        let detail = plan(RECOVERIES_BELOW, &[&50.0]);
+        assert!(detail.contains("recoveries_score"), "{}", detail);
+    }
+
+    #[test]
+    fn test_matches_sports_by_their_normalized_name() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let workout = WorkoutV2 {
+            sport_name: "Weight Lifting".to_string(),
+            ..crate::fixtures::workout_scored()
+        };
+        store
+            .upsert_workouts(std::slice::from_ref(&workout))
+            .unwrap();
+        let day = chrono::Duration::days(1);
+        let range = workout.start - day..workout.start + day;
+        let found = |store: &SqliteStore| {
+            store
+                .workouts_by_sport("weight_training", range.clone())
+                .unwrap()
+                .len()
+        };
+        assert_eq!(found(&store), 1);
+
+        // A database from before the normalized column gains it on open.
+        store
+            .conn
+            .execute_batch(
+                "DROP INDEX workouts_sport;
+                 ALTER TABLE workouts DROP COLUMN sport;
+                 CREATE INDEX workouts_sport ON workouts (sport_name COLLATE NOCASE, start);",
+            )
+            .unwrap();
+        let store = SqliteStore::init(store.conn).unwrap();
+        assert_eq!(found(&store), 1);
+    }
+}
@@ -0,0 +1,126 @@
+//! Deserializes large responses while they download.
+//!
+//! Small bodies are buffered and parsed in one go, with simd-json when the
+//! `simd-json` feature is on. Anything bigger, or of unknown length, is handed
+//! chunk by chunk to a blocking task parsing with [`serde_json::from_reader`],
+//! so a multi-megabyte page is never held in memory as raw text next to the
+//! records parsed from it.
+
+use crate::error::{Result, WhoopError};
+use crate::json;
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::io::{BufReader, Read};
+use tokio::sync::mpsc;
+
+/// Bodies up to this size are buffered rather than streamed.
+const BUFFER_LIMIT: u64 = 256 * 1024;
+/// Chunks waiting for the parser before downloading pauses.
+const CHANNEL_CHUNKS: usize = 16;
+
+pub(crate) async fn deserialize<T>(mut response: Response) -> Result<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    if response
+        .content_length()
+        .is_some_and(|len| len <= BUFFER_LIMIT)
+    {
+        return json::from_slice(&mut Vec::from(response.bytes().await?));
+    }
+
+    let (sender, parsed) = parser();
+    while let Some(chunk) = response.chunk().await? {
+        // The parser only hangs up early when it has already failed.
+        if sender.send(chunk).await.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+    parsed.await
+}
+
+/// A sender for body chunks and the value they parse into once it's dropped.
+fn parser<T, C>() -> (mpsc::Sender<C>, impl Future<Output = Result<T>>)
+where
+    T: DeserializeOwned + Send + 'static,
+    C: AsRef<[u8]> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(CHANNEL_CHUNKS);
+    let task = tokio::task::spawn_blocking(move || {
+        let reader = ChunkReader {
+            receiver,
+            chunk: None,
+            position: 0,
+        };
+        serde_json::from_reader::<_, T>(BufReader::new(reader))
+    });
+    let parsed = async move {
+        match task.await {
+            Ok(result) => Ok(result?),
+            Err(e) => Err(WhoopError::Unknown(
+                format!("parser task failed: {}", e),
+                None,
+            )),
+        }
+    };
+    (sender, parsed)
+}
+
+/// Reads chunks off a channel until the sender is dropped.
+struct ChunkReader<C> {
+    receiver: mpsc::Receiver<C>,
+    chunk: Option<C>,
+    position: usize,
+}
+
+impl<C: AsRef<[u8]>> Read for ChunkReader<C> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let rest = &chunk.as_ref()[self.position..];
+                if !rest.is_empty() {
+                    let n = rest.len().min(buf.len());
+                    buf[..n].copy_from_slice(&rest[..n]);
+                    self.position += n;
+                    return Ok(n);
+                }
+            }
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk);
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::models::PaginatedCycleResponse;
+
+    #[tokio::test]
+    async fn test_parses_chunks_split_anywhere() {
+        let body = fixtures::CYCLE_COLLECTION.as_bytes();
+        let (sender, parsed) = parser::<PaginatedCycleResponse, Vec<u8>>();
+        for chunk in body.chunks(7) {
+            sender.send(chunk.to_vec()).await.unwrap();
+        }
+        drop(sender);
+        let page = parsed.await.unwrap();
+        assert_eq!(page.records.unwrap().len(), 2);
+
+        let (sender, parsed) = parser::<PaginatedCycleResponse, Vec<u8>>();
+        sender.send(body[..body.len() / 2].to_vec()).await.unwrap();
+        drop(sender);
+        assert!(matches!(
+            parsed.await,
+            Err(WhoopError::SerializationError(_))
+        ));
+    }
+}
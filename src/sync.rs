@@ -0,0 +1,606 @@
+//! Backfills a [`Store`] by fetching in parallel.
+//!
+//! [`Engine`] splits a date range into windows and fetches the windows of
+//! every resource concurrently, up to a limit, under one [`RateLimiter`].
+//! Pages within a window are still fetched in order, as each needs the
+//! previous page's `next_token`.
+//!
+//! Records are written page by page as they arrive. For each resource, the end
+//! of the windows finished without gaps is saved as a checkpoint, and so is
+//! the [`Checkpoint`] of every window part way through, so running the same
+//! backfill again after a crash or running out of retries resumes at the page
+//! it stopped at. The store's regular watermarks
+//! move as well, so `whoopsy sync` can carry on incrementally afterwards.
+//!
+//! [`Engine::backfill`] imports an account's whole history: it finds the year
+//! of the first cycle, saves it so later runs resume the same backfill, and
+//! fetches from there to now. [`Engine::with_progress`] hears each time a
+//! resource's checkpoint moves.
+//!
+//! Keeping a store up to date after that is [`Delta`]'s job.
+
+use crate::api::WhoopApi;
+use crate::error::{Result, WhoopError};
+use crate::merge::Merged;
+use crate::models::*;
+use crate::pagination::{Checkpoint, Paged, Pages};
+use crate::rate_limit::RateLimiter;
+use crate::store::Store;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+pub mod delta;
+
+pub use delta::Delta;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+
+/// Where [`Engine::backfill`] keeps the start of the account's history.
+const BACKFILL_START: &str = "backfill:start";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Cycles,
+    Sleep,
+    Recovery,
+    #[cfg(feature = "workouts")]
+    Workouts,
+}
+
+impl Resource {
+    pub const ALL: &[Resource] = &[
+        Resource::Cycles,
+        Resource::Sleep,
+        Resource::Recovery,
+        #[cfg(feature = "workouts")]
+        Resource::Workouts,
+    ];
+
+    /// The resource's watermark key in the store.
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Cycles => "cycles",
+            Resource::Sleep => "sleep",
+            Resource::Recovery => "recovery",
+            #[cfg(feature = "workouts")]
+            Resource::Workouts => "workouts",
+        }
+    }
+}
+
+/// Records fetched by a run, per resource, and the requests it took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Synced {
+    pub cycles: usize,
+    pub sleeps: usize,
+    pub recoveries: usize,
+    pub workouts: usize,
+    pub requests: usize,
+    /// How the fetched records merged with the stored ones.
+    pub merged: Merged,
+}
+
+impl Synced {
+    /// Records fetched, of every resource.
+    pub fn total(&self) -> usize {
+        self.cycles + self.sleeps + self.recoveries + self.workouts
+    }
+}
+
+/// How far a run has got with one resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackfillProgress {
+    pub resource: Resource,
+    /// Everything of the resource before this is in the store.
+    pub through: DateTime<Utc>,
+    /// Windows of the resource this run has finished, and has in all.
+    pub windows_done: usize,
+    pub windows: usize,
+    /// Written so far by this run, of every resource.
+    pub synced: Synced,
+}
+
+pub struct Engine<A> {
+    api: Arc<A>,
+    resources: Vec<Resource>,
+    concurrency: usize,
+    window: Duration,
+    rate_limiter: RateLimiter,
+    progress: Option<OnProgress>,
+}
+
+type OnProgress = Box<dyn Fn(&BackfillProgress) + Send + Sync>;
+
+impl<A: WhoopApi + 'static> Engine<A> {
+    pub fn new(api: Arc<A>) -> Self {
+        Self {
+            api,
+            resources: Resource::ALL.to_vec(),
+            concurrency: DEFAULT_CONCURRENCY,
+            window: Duration::days(DEFAULT_WINDOW_DAYS),
+            rate_limiter: RateLimiter::default(),
+            progress: None,
+        }
+    }
+
+    /// Windows fetched at the same time. Defaults to 4.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How much of the range each window covers. Defaults to 30 days.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window.max(Duration::seconds(1));
+        self
+    }
+
+    /// Shares a budget with whatever else holds a clone of `rate_limiter`.
+    /// Defaults to a budget of its own at WHOOP's 100 requests a minute.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Only syncs these resources.
+    pub fn with_resources(mut self, resources: &[Resource]) -> Self {
+        self.resources = resources.to_vec();
+        self
+    }
+
+    /// Called whenever a resource's checkpoint moves, i.e. after every
+    /// window finished in order.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&BackfillProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Fetches everything from the start of the year of the account's first
+    /// cycle up to now. The start is saved in `store`, so running this again
+    /// after an interruption resumes where it stopped, and once finished
+    /// only fetches what's new. An account without cycles fetches nothing.
+    pub async fn backfill<S: Store>(&self, store: &mut S) -> Result<Synced> {
+        let start = match store.watermark(BACKFILL_START)? {
+            Some(start) => start,
+            None => match self.account_start().await? {
+                Some(start) => {
+                    store.set_watermark(BACKFILL_START, start)?;
+                    start
+                }
+                None => return Ok(Synced::default()),
+            },
+        };
+        self.run(store, start..Utc::now()).await
+    }
+
+    /// January 1st of the year of the first cycle. Each request asks for the
+    /// newest cycle before the year of the last one found, so it takes one
+    /// request per year with data, and one more.
+    async fn account_start(&self) -> Result<Option<DateTime<Utc>>> {
+        let mut start = None;
+        let mut before = None;
+        loop {
+            let mut pages = Pages::<A, Cycle>::new(&*self.api)
+                .with_range(None, before)
+                .with_limit(1)
+                .with_rate_limiter(self.rate_limiter.clone());
+            let page = pages.next_page().await.transpose()?.unwrap_or_default();
+            let Some(cycle) = page.first() else {
+                return Ok(start);
+            };
+            let year = Utc
+                .with_ymd_and_hms(cycle.start.year(), 1, 1, 0, 0, 0)
+                .single();
+            if year.is_none() || year == start {
+                return Ok(start);
+            }
+            start = year;
+            before = year;
+        }
+    }
+
+    /// Fetches every record starting in `range` into `store`.
+    pub async fn run<S: Store>(
+        &self,
+        store: &mut S,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Synced> {
+        let mut progress = Vec::new();
+        for &resource in &self.resources {
+            let key = checkpoint_key(resource, range.start);
+            let from = store
+                .watermark(&key)?
+                .map_or(range.start, |through| through.max(range.start));
+            progress.push(Progress::new(
+                resource,
+                key,
+                windows(from..range.end, self.window),
+            ));
+        }
+
+        // Window by window, so every resource moves forward together.
+        let longest = progress.iter().map(|p| p.windows.len()).max().unwrap_or(0);
+        let mut pending = Vec::new();
+        for w in 0..longest {
+            for (r, p) in progress.iter().enumerate() {
+                if w < p.windows.len() {
+                    let cursor = store.cursor(&p.window_key(w))?;
+                    pending.push((r, w, cursor));
+                }
+            }
+        }
+        pending.reverse();
+
+        let mut tasks = JoinSet::new();
+        let mut synced = Synced::default();
+        loop {
+            while tasks.len() < self.concurrency {
+                let Some((r, w, cursor)) = pending.pop() else {
+                    break;
+                };
+                let api = Arc::clone(&self.api);
+                let rate_limiter = self.rate_limiter.clone();
+                let resource = progress[r].resource;
+                let window = progress[r].windows[w].clone();
+                tasks.spawn(async move {
+                    let page = fetch_page(&*api, &rate_limiter, resource, window, cursor).await;
+                    (r, w, page)
+                });
+            }
+
+            // Returning early drops `tasks`, which aborts the fetches in flight.
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            let (r, w, page) = joined
+                .map_err(|e| WhoopError::Unknown(format!("sync task failed: {}", e), None))?;
+            let (batch, next, requests) = page?;
+            synced.requests += requests;
+            let newest = write(store, batch, &mut synced)?;
+
+            let name = progress[r].resource.name();
+            if let Some(newest) = newest.into_iter().chain(store.watermark(name)?).max() {
+                store.set_watermark(name, newest)?;
+            }
+            let key = progress[r].window_key(w);
+            match next {
+                // The window's next page goes first, so windows finish in order.
+                Some(checkpoint) => {
+                    store.set_cursor(&key, &checkpoint)?;
+                    pending.push((r, w, Some(checkpoint)));
+                }
+                None => {
+                    store.clear_cursor(&key)?;
+                    if let Some(through) = progress[r].finish(w) {
+                        store.set_watermark(&progress[r].key, through)?;
+                        if let Some(report) = &self.progress {
+                            report(&BackfillProgress {
+                                resource: progress[r].resource,
+                                through,
+                                windows_done: progress[r].finished,
+                                windows: progress[r].windows.len(),
+                                synced,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(synced)
+    }
+}
+
+/// Tracks which windows of one resource are in the store.
+struct Progress {
+    resource: Resource,
+    key: String,
+    windows: Vec<Range<DateTime<Utc>>>,
+    done: Vec<bool>,
+    /// Windows finished without gaps from the start.
+    finished: usize,
+}
+
+impl Progress {
+    fn new(resource: Resource, key: String, windows: Vec<Range<DateTime<Utc>>>) -> Self {
+        Self {
+            resource,
+            key,
+            done: vec![false; windows.len()],
+            windows,
+            finished: 0,
+        }
+    }
+
+    /// Where a window's pages stopped is saved under this key.
+    fn window_key(&self, w: usize) -> String {
+        format!("{}:{}", self.key, self.windows[w].start.timestamp())
+    }
+
+    /// Marks window `w` done, returning the new checkpoint if it moved.
+    fn finish(&mut self, w: usize) -> Option<DateTime<Utc>> {
+        self.done[w] = true;
+        let before = self.finished;
+        while self.done.get(self.finished).copied().unwrap_or(false) {
+            self.finished += 1;
+        }
+        (self.finished > before).then(|| self.windows[self.finished - 1].end)
+    }
+}
+
+/// Checkpoints belong to a backfill's start, so different backfills don't
+/// skip each other's ranges.
+fn checkpoint_key(resource: Resource, start: DateTime<Utc>) -> String {
+    format!("{}:backfill:{}", resource.name(), start.timestamp())
+}
+
+fn windows(range: Range<DateTime<Utc>>, size: Duration) -> Vec<Range<DateTime<Utc>>> {
+    let mut windows = Vec::new();
+    let mut start = range.start;
+    while start < range.end {
+        let end = (start + size).min(range.end);
+        windows.push(start..end);
+        start = end;
+    }
+    windows
+}
+
+enum Batch {
+    Cycles(Vec<Cycle>),
+    Sleep(Vec<Sleep>),
+    Recovery(Vec<Recovery>),
+    #[cfg(feature = "workouts")]
+    Workouts(Vec<WorkoutV2>),
+}
+
+/// Writes a batch, returning the newest watermark among its records.
+fn write<S: Store>(
+    store: &mut S,
+    batch: Batch,
+    synced: &mut Synced,
+) -> Result<Option<DateTime<Utc>>> {
+    Ok(match batch {
+        Batch::Cycles(records) => {
+            synced.merged += store.upsert_cycles(&records)?;
+            synced.cycles += records.len();
+            records.iter().map(|r| r.start).max()
+        }
+        Batch::Sleep(records) => {
+            synced.merged += store.upsert_sleeps(&records)?;
+            synced.sleeps += records.len();
+            records.iter().map(|r| r.start).max()
+        }
+        Batch::Recovery(records) => {
+            synced.merged += store.upsert_recoveries(&records)?;
+            synced.recoveries += records.len();
+            // Recoveries carry no start time, so the watermark is when they were created.
+            records.iter().map(|r| r.created_at).max()
+        }
+        #[cfg(feature = "workouts")]
+        Batch::Workouts(records) => {
+            synced.merged += store.upsert_workouts(&records)?;
+            synced.workouts += records.len();
+            records.iter().map(|r| r.start).max()
+        }
+    })
+}
+
+/// Fetches every record of `resource` starting between `start` and `end`,
+/// counting the requests it sends in `requests`.
+async fn fetch<A: WhoopApi>(
+    api: &A,
+    rate_limiter: &RateLimiter,
+    resource: Resource,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    requests: &mut usize,
+) -> Result<Batch> {
+    Ok(match resource {
+        Resource::Cycles => Batch::Cycles(collect(api, rate_limiter, start, end, requests).await?),
+        Resource::Sleep => Batch::Sleep(collect(api, rate_limiter, start, end, requests).await?),
+        Resource::Recovery => {
+            Batch::Recovery(collect(api, rate_limiter, start, end, requests).await?)
+        }
+        #[cfg(feature = "workouts")]
+        Resource::Workouts => {
+            Batch::Workouts(collect(api, rate_limiter, start, end, requests).await?)
+        }
+    })
+}
+
+/// Fetches the page of `resource` in `window` after `cursor`, returning it
+/// with the checkpoint for the next page, if any, and the requests it took.
+async fn fetch_page<A: WhoopApi>(
+    api: &A,
+    rate_limiter: &RateLimiter,
+    resource: Resource,
+    window: Range<DateTime<Utc>>,
+    cursor: Option<Checkpoint>,
+) -> Result<(Batch, Option<Checkpoint>, usize)> {
+    Ok(match resource {
+        Resource::Cycles => {
+            let (records, next, requests) = page(api, rate_limiter, window, cursor).await?;
+            (Batch::Cycles(records), next, requests)
+        }
+        Resource::Sleep => {
+            let (records, next, requests) = page(api, rate_limiter, window, cursor).await?;
+            (Batch::Sleep(records), next, requests)
+        }
+        Resource::Recovery => {
+            let (records, next, requests) = page(api, rate_limiter, window, cursor).await?;
+            (Batch::Recovery(records), next, requests)
+        }
+        #[cfg(feature = "workouts")]
+        Resource::Workouts => {
+            let (records, next, requests) = page(api, rate_limiter, window, cursor).await?;
+            (Batch::Workouts(records), next, requests)
+        }
+    })
+}
+
+async fn page<A: WhoopApi, T: Paged>(
+    api: &A,
+    rate_limiter: &RateLimiter,
+    window: Range<DateTime<Utc>>,
+    cursor: Option<Checkpoint>,
+) -> Result<(Vec<T>, Option<Checkpoint>, usize)> {
+    let mut pages = Pages::new(api)
+        .with_range(Some(window.start), Some(window.end))
+        .with_rate_limiter(rate_limiter.clone());
+    if let Some(cursor) = cursor {
+        pages = pages.resume(cursor);
+    }
+    let records = pages.next_page().await.transpose()?.unwrap_or_default();
+    Ok((records, pages.checkpoint(), pages.requests()))
+}
+
+async fn collect<A: WhoopApi, T: Paged>(
+    api: &A,
+    rate_limiter: &RateLimiter,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    requests: &mut usize,
+) -> Result<Vec<T>> {
+    let mut pages = Pages::new(api)
+        .with_range(start, end)
+        .with_rate_limiter(rate_limiter.clone());
+    let mut records = Vec::new();
+    while let Some(page) = pages.next_page().await {
+        records.extend(page?);
+    }
+    *requests += pages.requests();
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "sqlite")]
+    use crate::fixtures;
+    #[cfg(feature = "sqlite")]
+    use crate::memory::InMemoryWhoop;
+    #[cfg(feature = "sqlite")]
+    use crate::store::SqliteStore;
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_backfills_every_resource_and_checkpoints() {
+        let api = InMemoryWhoop::new()
+            .with_cycles(fixtures::cycle_collection().records.unwrap())
+            .with_sleeps(fixtures::sleep_collection().records.unwrap())
+            .with_recoveries(fixtures::recovery_collection().records.unwrap())
+            .with_workouts(fixtures::workout_collection().records.unwrap());
+        let engine = Engine::new(Arc::new(api))
+            .with_concurrency(3)
+            .with_window(Duration::days(1))
+            .with_rate_limiter(RateLimiter::per_minute(10_000));
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let start: DateTime<Utc> = "2022-04-01T00:00:00Z".parse().unwrap();
+        let end: DateTime<Utc> = "2022-05-01T00:00:00Z".parse().unwrap();
+
+        let synced = engine.run(&mut store, start..end).await.unwrap();
+        assert_eq!(synced.cycles, 2);
+        assert_eq!(store.cycles_between(start..end).unwrap().len(), 2);
+        assert_eq!(
+            synced.total(),
+            store.cycles_between(start..end).unwrap().len()
+                + store.sleeps_between(start..end).unwrap().len()
+                + store.recoveries_between(start..end).unwrap().len()
+                + store.workouts_between(start..end).unwrap().len()
+        );
+        assert_eq!(
+            store.watermark("cycles").unwrap(),
+            Some("2022-04-25T02:25:44.774Z".parse().unwrap())
+        );
+
+        // Finished: running the same backfill again fetches nothing.
+        let key = checkpoint_key(Resource::Cycles, start);
+        assert_eq!(store.watermark(&key).unwrap(), Some(end));
+        let again = engine.run(&mut store, start..end).await.unwrap();
+        assert_eq!(again, Synced::default());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_resumes_a_window_at_its_cursor() {
+        let api = Arc::new(
+            InMemoryWhoop::new().with_cycles(fixtures::cycle_collection().records.unwrap()),
+        );
+        let start: DateTime<Utc> = "2022-04-01T00:00:00Z".parse().unwrap();
+        let end: DateTime<Utc> = "2022-05-01T00:00:00Z".parse().unwrap();
+
+        // A previous run stopped after the window's first page.
+        let mut pages = Pages::<_, Cycle>::new(&*api)
+            .with_range(Some(start), Some(end))
+            .with_limit(1);
+        pages.next_page().await.unwrap().unwrap();
+        let key = format!(
+            "{}:{}",
+            checkpoint_key(Resource::Cycles, start),
+            start.timestamp()
+        );
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store
+            .set_cursor(&key, &pages.checkpoint().unwrap())
+            .unwrap();
+
+        let engine = Engine::new(api)
+            .with_resources(&[Resource::Cycles])
+            .with_window(end - start)
+            .with_rate_limiter(RateLimiter::per_minute(10_000));
+        let synced = engine.run(&mut store, start..end).await.unwrap();
+        assert_eq!((synced.cycles, synced.requests), (1, 1));
+        assert_eq!(store.cursor(&key).unwrap(), None);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_backfills_from_the_first_year_with_data() {
+        let mut cycles = fixtures::cycle_collection().records.unwrap();
+        let mut old = cycles[0].clone();
+        old.id = 1;
+        old.start = "2019-06-01T22:00:00Z".parse().unwrap();
+        old.end = Some("2019-06-02T07:00:00Z".parse().unwrap());
+        cycles.push(old);
+        let api = InMemoryWhoop::new().with_cycles(cycles);
+        let heard = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&heard);
+        let engine = Engine::new(Arc::new(api))
+            .with_resources(&[Resource::Cycles])
+            .with_window(Duration::days(365))
+            .with_rate_limiter(RateLimiter::per_minute(10_000))
+            .with_progress(move |p| log.lock().unwrap().push(*p));
+        let mut store = SqliteStore::open_in_memory().unwrap();
+
+        let synced = engine.backfill(&mut store).await.unwrap();
+        assert_eq!(synced.cycles, 3);
+        let start: DateTime<Utc> = "2019-01-01T00:00:00Z".parse().unwrap();
+        assert_eq!(store.watermark(BACKFILL_START).unwrap(), Some(start));
+
+        let heard = heard.lock().unwrap().clone();
+        let last = heard.last().unwrap();
+        assert_eq!(last.windows_done, last.windows);
+        assert_eq!(last.synced.cycles, 3);
+        assert!(heard.windows(2).all(|p| p[0].through < p[1].through));
+
+        // Done: another run only looks at what's new.
+        let again = engine.backfill(&mut store).await.unwrap();
+        assert_eq!(again.cycles, 0);
+        assert!(again.requests <= 1);
+    }
+
+    #[test]
+    fn test_checkpoint_waits_for_earlier_windows() {
+        let start: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let all = windows(start..start + Duration::days(3), Duration::days(1));
+        let mut progress = Progress::new(Resource::Sleep, String::new(), all.clone());
+        assert_eq!(progress.finish(1), None);
+        assert_eq!(progress.finish(0), Some(all[1].end));
+        assert_eq!(progress.finish(2), Some(all[2].end));
+    }
+}
@@ -0,0 +1,288 @@
+//! Brings a store up to date with as few requests as the API allows.
+//!
+//! WHOOP's collections can only be filtered by start time and don't answer
+//! conditional requests, so what has to be fetched is worked out from the
+//! store. Per resource, [`Delta`] fetches every record starting from `since`:
+//!
+//! - the start of the oldest stored record WHOOP may still change, i.e. one
+//!   pending a score or a cycle that hasn't ended;
+//! - else the watermark, the start of the newest record synced;
+//! - else everything, for a resource never synced.
+//!
+//! Everything before `since` is settled and already stored, so fetching it
+//! could only return what the store holds. Everything after it is either new
+//! or may have changed, and the API can't leave any of it out of a page
+//! without also leaving out records the store is missing. Pages are always as
+//! large as the API allows, so no smaller set of requests brings the store up
+//! to date. Merging them by `updated_at`, see [`crate::merge`], then tells which
+//! fetched records actually changed.
+//!
+//! [`Delta::plan`] works out the same thing without a request, as a dry run.
+//! Records WHOOP changes after they've settled, e.g. a sleep edited in the
+//! app, can only be caught by re-checking a window before `since`; see
+//! [`Delta::with_overlap`].
+//!
+//! With [`Delta::with_alerts`], the alert rules are checked against the store
+//! after every run.
+
+use super::{Resource, Synced, fetch, write};
+#[cfg(feature = "analytics")]
+use crate::alerts::Alerts;
+use crate::api::WhoopApi;
+use crate::error::Result;
+#[cfg(feature = "analytics")]
+use crate::instrument::event;
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::rate_limit::RateLimiter;
+use crate::store::Store;
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+#[cfg(feature = "analytics")]
+use std::sync::Arc;
+
+pub struct Delta {
+    resources: Vec<Resource>,
+    overlap: Duration,
+    rate_limiter: RateLimiter,
+    #[cfg(feature = "analytics")]
+    alerts: Option<Arc<Alerts>>,
+}
+
+impl Default for Delta {
+    fn default() -> Self {
+        Self {
+            resources: Resource::ALL.to_vec(),
+            overlap: Duration::zero(),
+            rate_limiter: RateLimiter::default(),
+            #[cfg(feature = "analytics")]
+            alerts: None,
+        }
+    }
+}
+
+impl Delta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only syncs these resources.
+    pub fn with_resources(mut self, resources: &[Resource]) -> Self {
+        self.resources = resources.to_vec();
+        self
+    }
+
+    /// Also re-checks records starting up to `overlap` before `since`, to
+    /// catch changes to settled records at the cost of the extra requests.
+    pub fn with_overlap(mut self, overlap: Duration) -> Self {
+        self.overlap = overlap.max(Duration::zero());
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Checks `alerts` after every run. A notifier failing doesn't fail the
+    /// run; it's logged.
+    #[cfg(feature = "analytics")]
+    pub fn with_alerts(mut self, alerts: Arc<Alerts>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// What a run would fetch, without sending a request.
+    pub fn plan<S: Store>(&self, store: &S) -> Result<Plan> {
+        let mut steps = Vec::new();
+        for &resource in &self.resources {
+            let mut bounds = vec![
+                (store.oldest_unsettled(resource.name())?, Reason::Unsettled),
+                (store.watermark(resource.name())?, Reason::Watermark),
+            ];
+            // A recovery can still arrive or change while its cycle is open.
+            if resource == Resource::Recovery {
+                bounds.push((store.oldest_unsettled("cycles")?, Reason::Unsettled));
+            }
+            let since = bounds
+                .into_iter()
+                .filter_map(|(since, reason)| since.map(|s| (s - self.overlap, reason)))
+                .min_by_key(|(since, _)| *since);
+            steps.push(match since {
+                Some((since, reason)) => Step {
+                    resource,
+                    since: Some(since),
+                    reason,
+                    stored: store.count_since(resource.name(), since)?,
+                },
+                None => Step {
+                    resource,
+                    since: None,
+                    reason: Reason::Backfill,
+                    stored: 0,
+                },
+            });
+        }
+        Ok(Plan { steps })
+    }
+
+    /// Fetches what [`plan`](Self::plan) says into `store`.
+    pub async fn run<A: WhoopApi, S: Store>(&self, api: &A, store: &mut S) -> Result<Applied> {
+        let plan = self.plan(store)?;
+        let mut applied = Applied::default();
+        for step in &plan.steps {
+            let batch = fetch(
+                api,
+                &self.rate_limiter,
+                step.resource,
+                step.since,
+                None,
+                &mut applied.synced.requests,
+            )
+            .await?;
+
+            let name = step.resource.name();
+            let newest = write(store, batch, &mut applied.synced)?;
+            applied.changed = applied.synced.merged.changed();
+            if let Some(newest) = newest.into_iter().chain(store.watermark(name)?).max() {
+                store.set_watermark(name, newest)?;
+            }
+        }
+
+        #[cfg(feature = "analytics")]
+        if let Some(alerts) = &self.alerts {
+            let days = alerts.recent_days(store)?;
+            if let Err(error) = alerts.process(&days).await {
+                event!(warn, "alert notification failed: {}", error);
+            }
+        }
+        Ok(applied)
+    }
+}
+
+/// What a [`Delta`] run would fetch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    /// The fewest requests the run can take.
+    pub fn min_requests(&self) -> usize {
+        self.steps.iter().map(Step::min_requests).sum()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        write!(f, "at least {} requests", self.min_requests())
+    }
+}
+
+/// Fetching one resource from `since` on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub resource: Resource,
+    /// `None` fetches everything.
+    pub since: Option<DateTime<Utc>>,
+    pub reason: Reason,
+    /// Stored records from `since` on, fetched again to check for changes.
+    pub stored: usize,
+}
+
+impl Step {
+    /// One request per page of stored records, or one to find out there's
+    /// nothing new. New records may take more.
+    pub fn min_requests(&self) -> usize {
+        self.stored.div_ceil(MAX_PAGE_SIZE as usize).max(1)
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.since {
+            Some(since) => write!(
+                f,
+                "{}: since {} ({}), {} stored records to re-check",
+                self.resource.name(),
+                since.format("%Y-%m-%d %H:%M UTC"),
+                self.reason,
+                self.stored
+            ),
+            None => write!(f, "{}: everything ({})", self.resource.name(), self.reason),
+        }
+    }
+}
+
+/// Why a step starts where it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Nothing synced yet.
+    Backfill,
+    /// The oldest record WHOOP may still change.
+    Unsettled,
+    /// The newest record synced.
+    Watermark,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Reason::Backfill => "never synced",
+            Reason::Unsettled => "oldest record WHOOP may still change",
+            Reason::Watermark => "newest record synced",
+        })
+    }
+}
+
+/// What a [`Delta`] run did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Applied {
+    pub synced: Synced,
+    /// Fetched records that were new or had a newer `updated_at` than the
+    /// stored copy; see [`Merged::changed`](crate::merge::Merged::changed).
+    pub changed: usize,
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::memory::InMemoryWhoop;
+    use crate::models::ScoreState;
+    use crate::store::SqliteStore;
+
+    #[tokio::test]
+    async fn test_fetches_from_the_oldest_unsettled_record() {
+        let mut cycles = fixtures::cycle_collection().records.unwrap();
+        let api = InMemoryWhoop::new().with_cycles(cycles.clone());
+        let delta = Delta::new()
+            .with_resources(&[Resource::Cycles])
+            .with_rate_limiter(RateLimiter::per_minute(10_000));
+        let mut store = SqliteStore::open_in_memory().unwrap();
+
+        let plan = delta.plan(&store).unwrap();
+        assert_eq!(plan.steps[0].reason, Reason::Backfill);
+        let applied = delta.run(&api, &mut store).await.unwrap();
+        assert_eq!((applied.synced.cycles, applied.changed), (2, 2));
+
+        // The older cycle is still pending, so the next run starts there and
+        // sees it scored; the newer one comes back unchanged.
+        cycles.sort_by_key(|c| c.start);
+        cycles[0].score_state = ScoreState::PendingScore;
+        store.upsert_cycles(&cycles[..1]).unwrap();
+        let plan = delta.plan(&store).unwrap();
+        assert_eq!(plan.steps[0].since, Some(cycles[0].start));
+        assert_eq!(plan.steps[0].reason, Reason::Unsettled);
+        assert_eq!(plan.min_requests(), 1);
+
+        cycles[0].score_state = ScoreState::Scored;
+        cycles[0].updated_at += Duration::hours(1);
+        api.upsert_cycle(cycles[0].clone());
+        let applied = delta.run(&api, &mut store).await.unwrap();
+        assert_eq!((applied.synced.requests, applied.changed), (1, 1));
+        assert!(plan.to_string().starts_with("cycles: since"));
+    }
+}
@@ -0,0 +1,437 @@
+//! Clients for many users of one app.
+//!
+//! [`Tenants`] keeps every user's [`TokenResponse`] in a [`TokenStore`] and
+//! builds their [`WhoopClient`] the first time it's asked for. When WHOOP
+//! rejects an access token, [`Tenants::call`] refreshes it and tries again.
+//! Concurrent requests failing for the same user share one refresh, since
+//! WHOOP's refresh tokens only work once.
+//!
+//! A refresh token can stop working for good, e.g. when the user revokes
+//! access in the app. Subscribers then get [`TenantEvent::RefreshTokenInvalid`]
+//! and the user needs to authorize again; the stored token is left for the
+//! app to deal with.
+//!
+//! [`Tenants::disconnect_user`] undoes all of it when a user leaves: it
+//! revokes the app's access and the refresh token, deletes the stored tokens
+//! and runs the app's own cleanup, reporting whichever steps failed.
+//!
+//! ```no_run
+//! # async fn run(config: whoopsy::OAuthConfig) -> whoopsy::Result<()> {
+//! use whoopsy::tenant::{MemoryTokenStore, Tenants};
+//!
+//! let tenants = Tenants::new(config, MemoryTokenStore::default());
+//! let profile = tenants
+//!     .call("user-42", |client| async move { client.get_profile_basic().await })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::audit::AuditLog;
+use crate::auth::{OAuthConfig, TokenResponse};
+use crate::client::WhoopClient;
+use crate::error::{Result, WhoopError};
+use crate::rate_limit::RateLimiter;
+use crate::reporting::ErrorReports;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+const EVENT_CAPACITY: usize = 64;
+
+/// Where users' tokens are kept between runs.
+pub trait TokenStore: Send + Sync {
+    fn load(&self, user: &str) -> impl Future<Output = Result<Option<TokenResponse>>> + Send;
+
+    fn save(&self, user: &str, token: &TokenResponse) -> impl Future<Output = Result<()>> + Send;
+
+    fn remove(&self, user: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Keeps tokens for as long as the process runs.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<HashMap<String, TokenResponse>>,
+}
+
+impl TokenStore for MemoryTokenStore {
+    async fn load(&self, user: &str) -> Result<Option<TokenResponse>> {
+        Ok(self.tokens.lock().unwrap().get(user).cloned())
+    }
+
+    async fn save(&self, user: &str, token: &TokenResponse) -> Result<()> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(user.to_string(), token.clone());
+        Ok(())
+    }
+
+    async fn remove(&self, user: &str) -> Result<()> {
+        self.tokens.lock().unwrap().remove(user);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantEvent {
+    /// A user's tokens were refreshed and saved.
+    Refreshed { user: String },
+    /// WHOOP rejected a user's refresh token, or there was none.
+    RefreshTokenInvalid { user: String },
+}
+
+/// A step of [`Tenants::disconnect_user`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectStep {
+    /// Revoking the app's access to the user's data.
+    RevokeAccess,
+    /// Revoking the refresh token at the OAuth server.
+    RevokeRefreshToken,
+    /// Deleting the tokens from the [`TokenStore`].
+    DeleteTokens,
+    /// The app's own cleanup.
+    Cleanup,
+}
+
+/// What [`Tenants::disconnect_user`] couldn't do.
+#[derive(Debug, Default)]
+pub struct Disconnect {
+    pub failed: Vec<(DisconnectStep, WhoopError)>,
+}
+
+impl Disconnect {
+    /// Whether every step succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    fn record(&mut self, step: DisconnectStep, result: Result<()>) {
+        if let Err(e) = result {
+            self.failed.push((step, e));
+        }
+    }
+}
+
+/// A user's client, locked while it's built or refreshed.
+type Slot = Arc<tokio::sync::Mutex<Option<Arc<WhoopClient>>>>;
+
+pub struct Tenants<S> {
+    config: OAuthConfig,
+    store: S,
+    rate_limiter: Option<RateLimiter>,
+    base_url: Option<String>,
+    audit_log: Option<AuditLog>,
+    error_reports: Option<ErrorReports>,
+    clients: Mutex<HashMap<String, Slot>>,
+    events: broadcast::Sender<TenantEvent>,
+}
+
+impl<S: TokenStore> Tenants<S> {
+    pub fn new(config: OAuthConfig, store: S) -> Self {
+        Self {
+            config,
+            store,
+            rate_limiter: None,
+            base_url: None,
+            audit_log: None,
+            error_reports: None,
+            clients: Mutex::new(HashMap::new()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Gives every client a clone of `rate_limiter`, so all users share the
+    /// app's budget.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Points every client at another server. See [`WhoopClient::with_base_url`].
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Records every client's requests in `audit_log`, under their user.
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Reports every client's failed requests to `error_reports`, under their
+    /// user.
+    pub fn with_error_reports(mut self, error_reports: ErrorReports) -> Self {
+        self.error_reports = Some(error_reports);
+        self
+    }
+
+    /// Events from here on.
+    pub fn subscribe(&self) -> broadcast::Receiver<TenantEvent> {
+        self.events.subscribe()
+    }
+
+    /// Saves a user's tokens, e.g. after they authorize, replacing their client.
+    pub async fn insert(&self, user: &str, token: TokenResponse) -> Result<()> {
+        let slot = self.slot(user);
+        let mut client = slot.lock().await;
+        self.store.save(user, &token).await?;
+        *client = None;
+        Ok(())
+    }
+
+    /// Forgets a user's tokens and client.
+    pub async fn remove(&self, user: &str) -> Result<()> {
+        let slot = self.slot(user);
+        let mut client = slot.lock().await;
+        self.store.remove(user).await?;
+        *client = None;
+        Ok(())
+    }
+
+    /// The user's client, built from their stored tokens if there's none yet.
+    pub async fn client(&self, user: &str) -> Result<Arc<WhoopClient>> {
+        let slot = self.slot(user);
+        let mut client = slot.lock().await;
+        if let Some(client) = &*client {
+            return Ok(Arc::clone(client));
+        }
+        let token = self.load(user).await?;
+        let built = Arc::new(self.build(user, token));
+        *client = Some(Arc::clone(&built));
+        Ok(built)
+    }
+
+    /// Refreshes the user's tokens after `stale` was rejected, returning the
+    /// new client. If another caller refreshed them meanwhile, returns that
+    /// client without refreshing again.
+    pub async fn refresh(&self, user: &str, stale: &Arc<WhoopClient>) -> Result<Arc<WhoopClient>> {
+        let slot = self.slot(user);
+        let mut client = slot.lock().await;
+        if let Some(current) = client.as_ref().filter(|c| !Arc::ptr_eq(c, stale)) {
+            return Ok(Arc::clone(current));
+        }
+
+        let token = self.load(user).await?;
+        let Some(refresh_token) = token.refresh_token.clone() else {
+            *client = None;
+            let msg = "No refresh token available".to_string();
+            return Err(self.invalid(user, WhoopError::AuthenticationError(msg, None)));
+        };
+        let mut refreshed = match self.config.refresh_token(refresh_token).await {
+            Ok(refreshed) => refreshed,
+            Err(e @ WhoopError::AuthenticationError(..)) => {
+                *client = None;
+                return Err(self.invalid(user, e));
+            }
+            Err(e) => return Err(e),
+        };
+        if refreshed.refresh_token.is_none() {
+            refreshed.refresh_token = token.refresh_token;
+        }
+        self.store.save(user, &refreshed).await?;
+        // Nobody may be listening, which is fine.
+        let _ = self.events.send(TenantEvent::Refreshed {
+            user: user.to_string(),
+        });
+
+        let built = Arc::new(self.build(user, refreshed));
+        *client = Some(Arc::clone(&built));
+        Ok(built)
+    }
+
+    /// Runs `f` with the user's client, refreshing their tokens and running it
+    /// once more if WHOOP rejects the access token.
+    pub async fn call<T, F, Fut>(&self, user: &str, f: F) -> Result<T>
+    where
+        F: Fn(Arc<WhoopClient>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let client = self.client(user).await?;
+        match f(Arc::clone(&client)).await {
+            Err(WhoopError::AuthenticationError(..)) => f(self.refresh(user, &client).await?).await,
+            result => result,
+        }
+    }
+
+    /// Disconnects a user for good: revokes the app's access and their
+    /// refresh token, deletes their tokens and then runs `cleanup`, e.g. to
+    /// delete their synced data. Every step runs even if an earlier one fails.
+    pub async fn disconnect_user<F, Fut>(&self, user: &str, cleanup: F) -> Disconnect
+    where
+        F: FnOnce(&str) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut disconnect = Disconnect::default();
+        let revoked = self
+            .call(
+                user,
+                |client| async move { client.revoke_oauth_access().await },
+            )
+            .await;
+        disconnect.record(DisconnectStep::RevokeAccess, revoked);
+
+        // Loaded after revoking access, which may have refreshed the tokens.
+        let revoked = match self.load(user).await {
+            Ok(TokenResponse {
+                refresh_token: Some(refresh_token),
+                ..
+            }) => self.config.revoke_token(&refresh_token).await,
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        disconnect.record(DisconnectStep::RevokeRefreshToken, revoked);
+        disconnect.record(DisconnectStep::DeleteTokens, self.remove(user).await);
+        disconnect.record(DisconnectStep::Cleanup, cleanup(user).await);
+        disconnect
+    }
+
+    fn slot(&self, user: &str) -> Slot {
+        let mut clients = self.clients.lock().unwrap();
+        Arc::clone(clients.entry(user.to_string()).or_default())
+    }
+
+    async fn load(&self, user: &str) -> Result<TokenResponse> {
+        self.store.load(user).await?.ok_or_else(|| {
+            WhoopError::AuthenticationError(format!("No tokens stored for user {}", user), None)
+        })
+    }
+
+    fn build(&self, user: &str, token: TokenResponse) -> WhoopClient {
+        let mut client = WhoopClient::new_with_oauth(self.config.clone(), token);
+        if let Some(base_url) = &self.base_url {
+            client = client.with_base_url(base_url.clone());
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            client = client.with_rate_limiter(rate_limiter.clone());
+        }
+        if let Some(audit_log) = &self.audit_log {
+            client = client.with_audit_log(audit_log.for_user(user));
+        }
+        if let Some(error_reports) = &self.error_reports {
+            client = client.with_error_reports(error_reports.for_user(user));
+        }
+        client
+    }
+
+    fn invalid(&self, user: &str, error: WhoopError) -> WhoopError {
+        let _ = self.events.send(TenantEvent::RefreshTokenInvalid {
+            user: user.to_string(),
+        });
+        error
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::test_support::{ACCESS_TOKEN, EXPIRED_TOKEN, MockWhoop, REFRESH_TOKEN};
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, ResponseTemplate};
+
+    fn token(access_token: &str, refresh_token: &str) -> TokenResponse {
+        TokenResponse {
+            access_token: access_token.to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: Some(0),
+            refresh_token: Some(refresh_token.to_string()),
+            scope: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_refresh() {
+        let mock = MockWhoop::start().await;
+        let tenants = Tenants::new(mock.oauth_config(), MemoryTokenStore::default())
+            .with_base_url(mock.uri());
+        tenants
+            .insert("alice", token(EXPIRED_TOKEN, REFRESH_TOKEN))
+            .await
+            .unwrap();
+        let mut events = tenants.subscribe();
+
+        let profile = |client: Arc<WhoopClient>| async move { client.get_profile_basic().await };
+        let (a, b) = tokio::join!(
+            tenants.call("alice", profile),
+            tenants.call("alice", profile)
+        );
+        assert!(a.is_ok() && b.is_ok());
+
+        let refreshes = mock
+            .server()
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.method.as_str() == "POST")
+            .count();
+        assert_eq!(refreshes, 1);
+        assert_eq!(
+            events.recv().await.unwrap(),
+            TenantEvent::Refreshed {
+                user: "alice".to_string()
+            }
+        );
+        let saved = tenants.store.load("alice").await.unwrap().unwrap();
+        assert_eq!(saved.access_token, ACCESS_TOKEN);
+    }
+
+    #[tokio::test]
+    async fn test_disconnects_a_user() {
+        let mock = MockWhoop::start().await;
+        let tenants = Tenants::new(mock.oauth_config(), MemoryTokenStore::default())
+            .with_base_url(mock.uri());
+        tenants
+            .insert("carol", token(ACCESS_TOKEN, REFRESH_TOKEN))
+            .await
+            .unwrap();
+
+        let disconnect = tenants
+            .disconnect_user("carol", |_| async {
+                Err(WhoopError::Unknown("disk full".to_string(), None))
+            })
+            .await;
+        let failed: Vec<_> = disconnect.failed.iter().map(|(step, _)| *step).collect();
+        assert_eq!(failed, vec![DisconnectStep::Cleanup]);
+        assert!(tenants.store.load("carol").await.unwrap().is_none());
+
+        let paths: Vec<_> = mock
+            .server()
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.url.path().to_string())
+            .collect();
+        assert_eq!(paths, vec!["/v2/user/access", "/oauth/oauth2/revoke"]);
+    }
+
+    #[tokio::test]
+    async fn test_reports_a_revoked_refresh_token() {
+        let mock = MockWhoop::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("revoked"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_grant"))
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+        let tenants = Tenants::new(mock.oauth_config(), MemoryTokenStore::default())
+            .with_base_url(mock.uri());
+        tenants
+            .insert("bob", token(EXPIRED_TOKEN, "revoked"))
+            .await
+            .unwrap();
+        let mut events = tenants.subscribe();
+
+        let stale = tenants.client("bob").await.unwrap();
+        assert!(tenants.refresh("bob", &stale).await.is_err());
+        assert_eq!(
+            events.recv().await.unwrap(),
+            TenantEvent::RefreshTokenInvalid {
+                user: "bob".to_string()
+            }
+        );
+    }
+}
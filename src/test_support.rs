@@ -0,0 +1,403 @@
+//! A mock WHOOP API for integration tests, behind the `test-support` feature.
+//!
+//! [`MockWhoop::start`] serves every endpoint from a small, consistent set of
+//! [`Fixtures`]: collections come back in two pages, unknown tokens get a 401 and
+//! the OAuth token endpoint hands out [`ACCESS_TOKEN`]. Use [`MockWhoop::server`]
+//! to mount extra responses on top.
+
+use crate::auth::{OAuthConfig, TokenResponse};
+use crate::client::WhoopClient;
+use crate::models::*;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+use wiremock::matchers::{
+    bearer_token, method, path, path_regex, query_param, query_param_is_missing,
+};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// The token the mock accepts.
+pub const ACCESS_TOKEN: &str = "test-access-token";
+/// A token the mock rejects with 401, until the client refreshes it.
+pub const EXPIRED_TOKEN: &str = "test-expired-token";
+pub const REFRESH_TOKEN: &str = "test-refresh-token";
+pub const USER_ID: i64 = 10129;
+
+const TOKEN_PATH: &str = "/oauth/oauth2/token";
+const REVOKE_PATH: &str = "/oauth/oauth2/revoke";
+const SECOND_PAGE: &str = "page-2";
+
+/// The records the mock serves, newest first like the API returns them.
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    pub cycles: Vec<Cycle>,
+    pub recoveries: Vec<Recovery>,
+    pub sleeps: Vec<Sleep>,
+    pub workouts: Vec<WorkoutV2>,
+    pub profile: UserBasicProfile,
+    pub body: UserBodyMeasurement,
+}
+
+impl Default for Fixtures {
+    /// Four days from 2024-03-01 in UTC-5, each with a cycle, a night's sleep,
+    /// a recovery and a workout.
+    fn default() -> Self {
+        let mut fixtures = Self {
+            cycles: Vec::new(),
+            recoveries: Vec::new(),
+            sleeps: Vec::new(),
+            workouts: Vec::new(),
+            profile: UserBasicProfile {
+                user_id: USER_ID,
+                email: "jane@example.com".to_string(),
+                first_name: "Jane".to_string(),
+                last_name: "Doe".to_string(),
+            },
+            body: UserBodyMeasurement {
+                height_meter: 1.72,
+                weight_kilogram: 64.5,
+                max_heart_rate: 194,
+            },
+        };
+        let first: DateTime<Utc> = "2024-03-01T04:00:00Z".parse().unwrap();
+        for day in 0..4_i64 {
+            let (cycle, sleep, recovery, workout) = day_records(day, first + Duration::days(day));
+            fixtures.cycles.push(cycle);
+            fixtures.sleeps.push(sleep);
+            fixtures.recoveries.push(recovery);
+            fixtures.workouts.push(workout);
+        }
+        fixtures.cycles.reverse();
+        fixtures.sleeps.reverse();
+        fixtures.recoveries.reverse();
+        fixtures.workouts.reverse();
+        fixtures
+    }
+}
+
+fn day_records(day: i64, start: DateTime<Utc>) -> (Cycle, Sleep, Recovery, WorkoutV2) {
+    let hour = 3_600_000;
+    let id = 93_845 + day;
+    let sleep_id = Uuid::from_u128(0xecfc6a15_4661_442f_a9a4_f160dd7afae8 + day as u128);
+    let wake = start + Duration::hours(3);
+    let offset = "-05:00".to_string();
+
+    let cycle = Cycle {
+        id,
+        user_id: USER_ID,
+        created_at: wake,
+        updated_at: wake,
+        start,
+        end: Some(start + Duration::days(1)),
+        timezone_offset: offset.clone(),
+        score_state: ScoreState::Scored,
+        score: Some(CycleScore {
+            strain: 9.5 + day as f32 * 1.7,
+            kilojoule: 8288.3 + day as f32 * 410.0,
+            average_heart_rate: 68,
+            max_heart_rate: 141 + day as i32 * 6,
+        }),
+    };
+    let sleep = Sleep {
+        id: sleep_id,
+        cycle_id: id,
+        v1_id: None,
+        user_id: USER_ID,
+        created_at: wake,
+        updated_at: wake,
+        start: wake - Duration::minutes(470),
+        end: wake,
+        timezone_offset: offset.clone(),
+        nap: false,
+        score_state: ScoreState::Scored,
+        score: Some(SleepScore {
+            stage_summary: SleepStageSummary {
+                total_in_bed_time_milli: 470 * 60_000,
+                total_awake_time_milli: 32 * 60_000,
+                total_no_data_time_milli: 0,
+                total_light_sleep_time_milli: 3 * hour + 55 * 60_000,
+                total_slow_wave_sleep_time_milli: hour + 38 * 60_000,
+                total_rem_sleep_time_milli: hour + 45 * 60_000,
+                sleep_cycle_count: 4,
+                disturbance_count: 11 + day as i32,
+            },
+            sleep_needed: SleepNeeded {
+                baseline_milli: 27_395_716,
+                need_from_sleep_debt_milli: 352_230,
+                need_from_recent_strain_milli: 208_595,
+                need_from_recent_nap_milli: -12_312,
+            },
+            respiratory_rate: Some(16.1 + day as f32 * 0.1),
+            sleep_performance_percentage: Some(86.0 + day as f32 * 2.0),
+            sleep_consistency_percentage: Some(90.0),
+            sleep_efficiency_percentage: Some(93.2),
+        }),
+    };
+    let recovery = Recovery {
+        cycle_id: id,
+        sleep_id,
+        user_id: USER_ID,
+        created_at: wake,
+        updated_at: wake,
+        score_state: ScoreState::Scored,
+        score: Some(RecoveryScore {
+            user_calibrating: false,
+            recovery_score: [44.0, 71.0, 63.0, 88.0][day as usize],
+            resting_heart_rate: 54.0 - day as f32,
+            hrv_rmssd_milli: 31.8 + day as f32 * 4.2,
+            spo2_percentage: Some(95.7),
+            skin_temp_celsius: Some(33.7),
+        }),
+    };
+    let workout_start = start + Duration::hours(13);
+    let workout = WorkoutV2 {
+        id: Uuid::from_u128(0x7bfc6a15_5521_612f_b9a4_e160dd7ae000 + day as u128),
+        v1_id: None,
+        user_id: USER_ID,
+        created_at: workout_start + Duration::hours(1),
+        updated_at: workout_start + Duration::hours(1),
+        start: workout_start,
+        end: workout_start + Duration::minutes(48),
+        timezone_offset: offset,
+        sport_name: if day % 2 == 0 { "running" } else { "cycling" }.to_string(),
+        score_state: ScoreState::Scored,
+        score: Some(WorkoutScore {
+            strain: 8.2 + day as f32,
+            average_heart_rate: 142,
+            max_heart_rate: 171,
+            kilojoule: 1569.3,
+            percent_recorded: 100.0,
+            distance_meter: Some(if day % 2 == 0 { 8_410.0 } else { 21_300.0 }),
+            altitude_gain_meter: Some(46.1),
+            altitude_change_meter: Some(-0.8),
+            zone_durations: ZoneDurations {
+                zone_zero_milli: 60_000,
+                zone_one_milli: 420_000,
+                zone_two_milli: 1_080_000,
+                zone_three_milli: 960_000,
+                zone_four_milli: 300_000,
+                zone_five_milli: 60_000,
+            },
+        }),
+        sport_id: Some(if day % 2 == 0 { 0 } else { 1 }),
+    };
+    (cycle, sleep, recovery, workout)
+}
+
+/// A running mock of the WHOOP API. Dropping it shuts the server down.
+pub struct MockWhoop {
+    server: MockServer,
+    fixtures: Fixtures,
+}
+
+impl MockWhoop {
+    /// Starts a mock serving [`Fixtures::default`].
+    pub async fn start() -> Self {
+        Self::with_fixtures(Fixtures::default()).await
+    }
+
+    pub async fn with_fixtures(fixtures: Fixtures) -> Self {
+        let mock = Self {
+            server: MockServer::start().await,
+            fixtures,
+        };
+        mock.mount_defaults().await;
+        mock
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// The underlying server, for mounting extra responses.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    pub fn fixtures(&self) -> &Fixtures {
+        &self.fixtures
+    }
+
+    /// A client with a valid static token.
+    pub fn client(&self) -> WhoopClient {
+        WhoopClient::new(ACCESS_TOKEN.to_string()).with_base_url(self.uri())
+    }
+
+    /// An OAuth config whose token exchange goes to the mock.
+    pub fn oauth_config(&self) -> OAuthConfig {
+        OAuthConfig::new(
+            "test-client".to_string(),
+            "test-secret".to_string(),
+            "http://localhost/callback".to_string(),
+        )
+        .with_all_scopes()
+        .with_token_url(format!("{}{}", self.uri(), TOKEN_PATH))
+        .with_revoke_url(format!("{}{}", self.uri(), REVOKE_PATH))
+    }
+
+    /// An OAuth client holding [`EXPIRED_TOKEN`]: requests fail with 401 until
+    /// [`WhoopClient::refresh_token`] is called.
+    pub fn expired_oauth_client(&self) -> WhoopClient {
+        let token = TokenResponse {
+            access_token: EXPIRED_TOKEN.to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: Some(0),
+            refresh_token: Some(REFRESH_TOKEN.to_string()),
+            scope: None,
+        };
+        WhoopClient::new_with_oauth(self.oauth_config(), token).with_base_url(self.uri())
+    }
+
+    /// Answers the next `times` requests to `path` with 429 and a `Retry-After` header.
+    pub async fn rate_limit(&self, path: &str, times: u64) {
+        Mock::given(wiremock::matchers::path(path))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    async fn mount_defaults(&self) {
+        let f = &self.fixtures;
+        self.collection("/v2/cycle", &f.cycles).await;
+        self.collection("/v2/recovery", &f.recoveries).await;
+        self.collection("/v2/activity/sleep", &f.sleeps).await;
+        self.collection("/v2/activity/workout", &f.workouts).await;
+
+        for cycle in &f.cycles {
+            self.get(&format!("/v2/cycle/{}", cycle.id), cycle).await;
+        }
+        for recovery in &f.recoveries {
+            self.get(
+                &format!("/v2/cycle/{}/recovery", recovery.cycle_id),
+                recovery,
+            )
+            .await;
+        }
+        for sleep in &f.sleeps {
+            self.get(&format!("/v2/activity/sleep/{}", sleep.id), sleep)
+                .await;
+            if !sleep.nap {
+                self.get(&format!("/v2/cycle/{}/sleep", sleep.cycle_id), sleep)
+                    .await;
+            }
+        }
+        for workout in &f.workouts {
+            self.get(&format!("/v2/activity/workout/{}", workout.id), workout)
+                .await;
+        }
+        self.get("/v2/user/profile/basic", &f.profile).await;
+        self.get("/v2/user/measurement/body", &f.body).await;
+
+        Mock::given(method("DELETE"))
+            .and(path("/v2/user/access"))
+            .and(bearer_token(ACCESS_TOKEN))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": ACCESS_TOKEN,
+                "token_type": "bearer",
+                "expires_in": 3600,
+                "refresh_token": REFRESH_TOKEN,
+                "scope": "offline read:recovery read:cycles read:workout read:sleep read:profile read:body_measurement",
+            })))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(REVOKE_PATH))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.server)
+            .await;
+
+        // Anything under /v2 without the right token, after every other mock failed to match.
+        Mock::given(path_regex("^/v2/"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": "invalid_token",
+            })))
+            .with_priority(10)
+            .mount(&self.server)
+            .await;
+    }
+
+    async fn get<T: Serialize>(&self, route: &str, body: &T) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(bearer_token(ACCESS_TOKEN))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves `records` as two pages linked by `next_token`.
+    async fn collection<T: Serialize>(&self, route: &str, records: &[T]) {
+        let (first, second) = records.split_at(records.len().div_ceil(2));
+        let first_page = if second.is_empty() {
+            json!({ "records": first })
+        } else {
+            json!({ "records": first, "next_token": SECOND_PAGE })
+        };
+
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(bearer_token(ACCESS_TOKEN))
+            .and(query_param_is_missing("nextToken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first_page))
+            .mount(&self.server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(route))
+            .and(bearer_token(ACCESS_TOKEN))
+            .and(query_param("nextToken", SECOND_PAGE))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "records": second })))
+            .mount(&self.server)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WhoopError;
+
+    #[tokio::test]
+    async fn test_pages_rate_limits_and_refresh() {
+        let mock = MockWhoop::start().await;
+        let client = mock.client();
+
+        let page = client.get_cycle_collection(None).await.unwrap();
+        assert_eq!(page.records.unwrap().len(), 2);
+        let next = client
+            .get_cycle_collection(Some(CycleQueryParams {
+                limit: None,
+                start: None,
+                end: None,
+                next_token: page.next_token,
+            }))
+            .await
+            .unwrap();
+        assert!(next.next_token.is_none());
+
+        mock.rate_limit("/v2/user/profile/basic", 1).await;
+        let limited = client.get_profile_basic().await.unwrap_err();
+        assert_eq!(
+            limited.retry_after(),
+            Some(std::time::Duration::from_secs(1))
+        );
+        assert_eq!(client.get_profile_basic().await.unwrap().user_id, USER_ID);
+
+        let mut expired = mock.expired_oauth_client();
+        assert!(matches!(
+            expired.get_body_measurement().await,
+            Err(WhoopError::AuthenticationError(..))
+        ));
+        expired.refresh_token().await.unwrap();
+        assert!(expired.get_body_measurement().await.is_ok());
+    }
+}
@@ -0,0 +1,202 @@
+//! Record and replay API traffic, behind the `test-support` feature.
+//!
+//! Record once against the real API, commit the cassette, and tests replay it
+//! without a network or credentials:
+//!
+//! ```no_run
+//! # async fn run() -> whoopsy::Result<()> {
+//! use whoopsy::{WhoopClient, vcr::Cassette};
+//!
+//! let token = std::env::var("WHOOP_TOKEN").unwrap_or_default();
+//! let cassette = if token.is_empty() {
+//!     Cassette::replay("tests/cassettes/profile.json")?
+//! } else {
+//!     Cassette::record("tests/cassettes/profile.json")
+//! };
+//! let client = WhoopClient::new(token).with_cassette(cassette);
+//! let profile = client.get_profile_basic().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Cassettes store the method, path, status and body of each exchange, never the
+//! request headers, so bearer tokens stay out of them. Token fields that show up
+//! in response bodies are redacted too.
+
+use crate::error::{Result, WhoopError};
+use reqwest::{Client, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const REDACTED_FIELDS: [&str; 3] = ["access_token", "refresh_token", "id_token"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    /// Path and query, without the host, so cassettes work against any base URL.
+    pub path: String,
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Cassette {
+    /// Sends requests for real and records them. The cassette is written when
+    /// it's dropped, or earlier with [`save`](Self::save).
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: Mode::Record,
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answers requests from a recorded cassette. Each interaction is used once,
+    /// in order, so repeated requests replay whatever came back each time.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let interactions = serde_json::from_slice(&std::fs::read(&path)?)?;
+        Ok(Self {
+            path,
+            mode: Mode::Replay,
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes what's been recorded so far. Does nothing when replaying.
+    pub fn save(&self) -> Result<()> {
+        if self.mode == Mode::Replay {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let interactions = self.interactions.lock().unwrap();
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&*interactions)?)?;
+        Ok(())
+    }
+
+    pub(crate) async fn send(
+        &self,
+        client: &Client,
+        request: Request,
+    ) -> Result<(StatusCode, String)> {
+        let method = request.method().to_string();
+        let url = request.url();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        match self.mode {
+            Mode::Replay => {
+                let mut interactions = self.interactions.lock().unwrap();
+                let index = interactions
+                    .iter()
+                    .position(|i| i.method == method && i.path == path)
+                    .ok_or_else(|| {
+                        WhoopError::Unknown(
+                            format!("no recorded response for {} {}", method, path),
+                            None,
+                        )
+                    })?;
+                let interaction = interactions.remove(index);
+                let status = StatusCode::from_u16(interaction.status)
+                    .map_err(|e| WhoopError::Unknown(e.to_string(), None))?;
+                Ok((status, interaction.body))
+            }
+            Mode::Record => {
+                let response = client.execute(request).await?;
+                let status = response.status();
+                let body = response.text().await?;
+                self.interactions.lock().unwrap().push(Interaction {
+                    method,
+                    path,
+                    status: status.as_u16(),
+                    body: redact(&body),
+                });
+                Ok((status, body))
+            }
+        }
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        // Best effort; call `save` to see the error.
+        let _ = self.save();
+    }
+}
+
+/// Blanks token fields anywhere in a JSON body. Non-JSON bodies are kept as they are.
+fn redact(body: &str) -> String {
+    fn walk(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if REDACTED_FIELDS.contains(&key.as_str()) {
+                        *value = Value::String("REDACTED".to_string());
+                    } else {
+                        walk(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(walk),
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str::<Value>(body) {
+        Ok(mut value) => {
+            walk(&mut value);
+            value.to_string()
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::WhoopClient;
+    use crate::test_support::{ACCESS_TOKEN, MockWhoop};
+
+    #[tokio::test]
+    async fn test_recorded_cassette_replays_without_a_server() {
+        let path = std::env::temp_dir().join(format!("whoopsy-{}.json", uuid::Uuid::new_v4()));
+        let mock = MockWhoop::start().await;
+
+        let client = mock.client().with_cassette(Cassette::record(&path));
+        let recorded = client.get_cycle_collection(None).await.unwrap();
+        drop(client);
+        drop(mock);
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains(ACCESS_TOKEN));
+
+        let client = WhoopClient::new(String::new())
+            .with_base_url("http://127.0.0.1:9")
+            .with_cassette(Cassette::replay(&path).unwrap());
+        let replayed = client.get_cycle_collection(None).await.unwrap();
+        assert_eq!(replayed.next_token, recorded.next_token);
+        assert!(client.get_cycle_collection(None).await.is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+//! Which version of the API each resource family is requested from.
+//!
+//! WHOOP serves some endpoints under both `/v1` and `/v2`. A client defaults
+//! to v2 throughout; [`WhoopClient::with_api_version`] moves one family back,
+//! so an app can migrate endpoint by endpoint:
+//!
+//! ```
+//! use whoopsy::{ApiVersion, ResourceFamily, WhoopClient};
+//!
+//! let client = WhoopClient::new("token".to_string())
+//!     .with_api_version(ResourceFamily::Cycle, ApiVersion::V1);
+//! ```
+//!
+//! Only families whose v1 responses parse into this crate's models can be
+//! moved. v1 recoveries, sleeps and workouts refer to sleeps and workouts by
+//! integer ids the models don't hold, so those always use v2, as do endpoints
+//! only v2 has.
+//!
+//! [`WhoopClient::with_api_version`]: crate::WhoopClient::with_api_version
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    #[default]
+    V2,
+}
+
+impl ApiVersion {
+    /// The path prefix, e.g. `/v2`.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/v1",
+            ApiVersion::V2 => "/v2",
+        }
+    }
+}
+
+/// Endpoints that move between versions together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceFamily {
+    /// `/cycle` and `/cycle/{id}`.
+    Cycle,
+    /// `/user/profile/basic` and `/user/measurement/body`.
+    User,
+}
+
+/// The version of each family.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ApiVersions {
+    cycle: ApiVersion,
+    user: ApiVersion,
+}
+
+impl ApiVersions {
+    pub(crate) fn set(&mut self, family: ResourceFamily, version: ApiVersion) {
+        match family {
+            ResourceFamily::Cycle => self.cycle = version,
+            ResourceFamily::User => self.user = version,
+        }
+    }
+
+    /// `path` under the family's version, e.g. `/v1/cycle` for `/cycle`.
+    pub(crate) fn path(&self, family: ResourceFamily, path: &str) -> String {
+        let version = match family {
+            ResourceFamily::Cycle => self.cycle,
+            ResourceFamily::User => self.user,
+        };
+        format!("{}{}", version.prefix(), path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moves_one_family_at_a_time() {
+        let mut versions = ApiVersions::default();
+        versions.set(ResourceFamily::Cycle, ApiVersion::V1);
+        assert_eq!(versions.path(ResourceFamily::Cycle, "/cycle"), "/v1/cycle");
+        assert_eq!(
+            versions.path(ResourceFamily::User, "/user/profile/basic"),
+            "/v2/user/profile/basic"
+        );
+    }
+}